@@ -0,0 +1,41 @@
+//! Decides which TLS implementation and memory allocator is compiled in, from the enabled features and the target,
+//! so that the same feature set can be used for all release targets. The code only checks the resulting cfgs:
+//! * `tls_backend = "rustls" | "native" | "none"`
+//! * `jemalloc_allocator`
+
+use std::env;
+
+fn main() {
+	println!("cargo::rustc-check-cfg=cfg(tls_backend, values(\"rustls\", \"native\", \"none\"))");
+	println!("cargo::rustc-check-cfg=cfg(jemalloc_allocator)");
+	println!("cargo::rerun-if-env-changed=PG2PARQUET_ALLOCATOR");
+
+	let feature = |name: &str| env::var_os(format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"))).is_some();
+	let target = |name: &str| env::var(format!("CARGO_CFG_TARGET_{}", name.to_uppercase())).unwrap_or_default();
+	let (os, family, arch, target_env) = (target("os"), target("family"), target("arch"), target("env"));
+
+	// native-tls links OpenSSL on Linux, which is only available for the common glibc targets
+	let native_tls_supported = os == "macos" || os == "windows" || (os == "linux" && target_env != "musl" && (arch == "x86_64" || arch == "aarch64"));
+	// rustls is only the fallback, so that the release builds can enable both features and keep the system TLS library where it is available
+	let tls_backend = if feature("native-tls") && native_tls_supported {
+		"native"
+	} else if feature("rustls") {
+		"rustls"
+	} else {
+		"none"
+	};
+	println!("cargo::rustc-cfg=tls_backend=\"{}\"", tls_backend);
+
+	// the jemallocator dependency is not compiled on Windows and riscv64, see Cargo.toml
+	let jemalloc_supported = family != "windows" && arch != "riscv64";
+	let jemalloc = match env::var("PG2PARQUET_ALLOCATOR").as_deref() {
+		Ok("system") => false,
+		Ok("jemalloc") if !feature("jemalloc") => panic!("PG2PARQUET_ALLOCATOR=jemalloc requires the jemalloc feature"),
+		Ok("jemalloc") if !jemalloc_supported => panic!("PG2PARQUET_ALLOCATOR=jemalloc is not supported on this target"),
+		Ok("jemalloc" | "") | Err(_) => feature("jemalloc") && jemalloc_supported,
+		Ok(other) => panic!("Invalid PG2PARQUET_ALLOCATOR={}, expected system or jemalloc", other),
+	};
+	if jemalloc {
+		println!("cargo::rustc-cfg=jemalloc_allocator");
+	}
+}
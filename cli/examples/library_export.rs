@@ -0,0 +1,39 @@
+//! Exports a query into a Parquet file using the library API, with a custom mapper for the pg_lsn type which pg2parquet does not support.
+//! The connection is taken from the PGHOST, PGPORT, PGUSER, PGDATABASE and PGPASSWORD environment variables.
+//!
+//! Usage: `cargo run --example library_export -- <query> <output_file>`
+use parquet::basic::Type;
+use pg2parquet::{CustomTypeMapper, CustomValue, ExportJob, PostgresConnArgs};
+
+/// pg_lsn is a 64-bit position in the write-ahead log, exported as INT64
+struct PgLsnMapper;
+
+impl CustomTypeMapper for PgLsnMapper {
+	fn type_name(&self) -> &str { "pg_lsn" }
+	fn physical_type(&self) -> Type { Type::INT64 }
+	fn convert(&self, value: &[u8]) -> Result<Option<CustomValue>, String> {
+		let value: [u8; 8] = value.try_into().map_err(|_| format!("pg_lsn value has {} bytes, expected 8", value.len()))?;
+		Ok(Some(CustomValue::Int64(i64::from_be_bytes(value))))
+	}
+}
+
+fn main() {
+	let args: Vec<String> = std::env::args().collect();
+	let [_, query, output_file] = &args[..] else {
+		eprintln!("Usage: library_export <query> <output_file>");
+		std::process::exit(2);
+	};
+	let connection = PostgresConnArgs { no_password: true, ..Default::default() };
+	let result = ExportJob::new(connection, output_file)
+		.query(query)
+		.column_stats(true)
+		.custom_type_mapper(PgLsnMapper)
+		.run();
+	match result {
+		Ok(stats) => println!("Exported {} rows, {} columns", stats.rows, stats.columns.len()),
+		Err(e) => {
+			eprintln!("Error: {}", e);
+			std::process::exit(1);
+		}
+	}
+}
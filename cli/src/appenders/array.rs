@@ -11,8 +11,8 @@ pub struct ArrayColumnAppender<TPg: Clone, TInner>
 	rl: i16,
 	allow_null: bool,
 	allow_element_null: bool,
-	_dummy: PhantomData<TPg>,
-	// dummy2: PhantomData<TPg>,
+	_dummy: PhantomData<fn(TPg)>,
+	// dummy2: PhantomData<fn(TPg)>,
 }
 
 impl<TPg: Clone, TInner> ArrayColumnAppender<TPg, TInner>
@@ -50,36 +50,55 @@ impl<TPg: Clone, TInner> ColumnAppenderBase for ArrayColumnAppender<TPg, TInner>
 		self.inner.max_rl() - 1
 	}
 
-	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
 		self.inner.write_columns(column_i, next_col)
-	}	
+	}
+
+	fn begin_row(&mut self) { self.inner.begin_row() }
+
+	fn rollback_row(&mut self) { self.inner.rollback_row() }
 }
 
-impl<'a, TPg: Clone, TInner, TArray: Clone> ColumnAppender<TArray> for ArrayColumnAppender<TPg, TInner>
+impl<TPg: Clone, TInner> ArrayColumnAppender<TPg, TInner>
+	where TInner: ColumnAppender<TPg> {
+	fn copy_element(&mut self, nested_ri: &mut LevelIndexList, value: Option<Cow<TPg>>) -> Result<usize, String> {
+		match value {
+			Some(value) => {
+				let bytes = self.inner.copy_value(nested_ri, value)?;
+				nested_ri.inc();
+				Ok(bytes)
+			},
+			None if self.allow_element_null => {
+				debug_assert_eq!(self.dl + 1, self.inner.max_dl() - 1);
+				let bytes = self.inner.write_null(nested_ri, self.dl + 1)?;
+				nested_ri.inc();
+				Ok(bytes)
+			},
+			None => Err("Array contains a NULL element, but the elements are REQUIRED in the Parquet schema (see --array-element-nullability)".to_string()),
+		}
+	}
+}
+
+impl<TPg: Clone, TInner, TArray: Clone> ColumnAppender<TArray> for ArrayColumnAppender<TPg, TInner>
 	where TInner: ColumnAppender<TPg>,
-		  TArray: IntoIterator<Item = Option<TPg>> + Clone {
+		  TArray: IntoIterator<Item = Option<TPg>> + Clone,
+		  for<'x> &'x TArray: IntoIterator<Item = &'x Option<TPg>> {
 
 	fn copy_value(&mut self, repetition_index: &LevelIndexList, array: Cow<TArray>) -> Result<usize, String> {
 		let mut bytes_written = 0;
 
 		let mut nested_ri = repetition_index.new_child();
 
-		for (_index, value) in array.into_owned().into_iter().enumerate() {
-			match value {
-				Some(value) => {
-					bytes_written += self.inner.copy_value(&nested_ri, Cow::Owned(value))?;
-					nested_ri.inc();
+		// borrowed arrays are not cloned, the elements are passed to the inner appender by reference
+		match array {
+			Cow::Owned(array) =>
+				for value in array {
+					bytes_written += self.copy_element(&mut nested_ri, value.map(Cow::Owned))?;
+				},
+			Cow::Borrowed(array) =>
+				for value in array {
+					bytes_written += self.copy_element(&mut nested_ri, value.as_ref().map(Cow::Borrowed))?;
 				},
-				None => {
-					if self.allow_element_null {
-						debug_assert_eq!(self.dl + 1, self.inner.max_dl() - 1);
-						bytes_written += self.inner.write_null(&nested_ri, self.dl + 1)?;
-						nested_ri.inc();
-					} else {
-						// skip
-					}
-				}
-			}
 		}
 
 		if nested_ri.index == 0 {
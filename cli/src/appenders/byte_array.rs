@@ -2,14 +2,14 @@ use std::{marker::PhantomData, sync::Arc, borrow::Cow};
 
 use byteorder::{ReadBytesExt, ByteOrder, BigEndian};
 use bytes::{Bytes, BufMut};
-use parquet::{data_type::{DataType, ByteArray, FixedLenByteArray, ByteArrayType}, file::writer::SerializedColumnWriter, errors::ParquetError};
+use parquet::{data_type::{DataType, ByteArray, FixedLenByteArray, ByteArrayType, FixedLenByteArrayType}, file::writer::SerializedColumnWriter, errors::ParquetError};
 
 use crate::{level_index::{LevelIndexState, LevelIndexList}, myfrom::MyFrom, pg_custom_types::{PgAnyRef, PgAbstractRow}};
 
-use super::{real_memory_size::RealMemorySize, ColumnAppenderBase, ColumnAppender, DynamicSerializedWriter};
+use super::{real_memory_size::RealMemorySize, ColumnAppenderBase, ColumnAppender, DynamicSerializedWriter, FlatColumnValues, FlatValues};
 
 
-pub struct ByteArrayColumnAppender<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> {
+pub struct ByteArrayColumnAppender<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Result<Option<usize>, String>> {
 	max_dl: i16,
 	max_rl: i16,
 	byte_buffer: Vec<u8>,
@@ -18,10 +18,13 @@ pub struct ByteArrayColumnAppender<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Optio
 	rls: Vec<i16>,
 	repetition_index: LevelIndexState,
 	conversion: FCopyTo,
-	_dummy: PhantomData<TPg>,
+	_dummy: PhantomData<fn(TPg)>,
+	/// Lengths of the buffers and the repetition index at the start of the current row, for `rollback_row`
+	row_start: (usize, usize, usize, usize),
+	row_start_index: LevelIndexState,
 }
 
-impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ByteArrayColumnAppender<TPg, FCopyTo> {
+impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Result<Option<usize>, String>> ByteArrayColumnAppender<TPg, FCopyTo> {
 	pub fn new(max_dl: i16, max_rl: i16, f_copy: FCopyTo) -> Self {
 		if max_dl < 0 || max_rl < 0 {
 			panic!("Cannot create {} with max_dl={}, max_rl={}", std::any::type_name::<Self>(), max_dl, max_rl);
@@ -35,12 +38,14 @@ impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ByteArrayColumnAppen
 			rls: Vec::new(),
 			repetition_index: LevelIndexState::new(max_rl),
 			conversion: f_copy,
+			row_start: (0, 0, 0, 0),
+			row_start_index: LevelIndexState::new(max_rl),
 		}
 	}
 
-	pub fn append(&mut self, repetition_index: &LevelIndexList, value: &TPg) -> usize {
+	pub fn append(&mut self, repetition_index: &LevelIndexList, value: &TPg) -> Result<usize, String> {
 		let index = self.byte_buffer.len();
-		if let Some(len) = (self.conversion)(value, &mut self.byte_buffer) {
+		if let Some(len) = (self.conversion)(value, &mut self.byte_buffer)? {
 			debug_assert_eq!(index + len, self.byte_buffer.len());
 			self.offsets.push(index);
 
@@ -54,9 +59,9 @@ impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ByteArrayColumnAppen
 				self.rls.push(rl);
 			}
 
-			len + 2 * (self.max_dl > 0) as usize + 2 * (self.max_rl > 0) as usize
+			Ok(len + 2 * (self.max_dl > 0) as usize + 2 * (self.max_rl > 0) as usize)
 		} else {
-			self.write_null(repetition_index, self.max_dl - 1)
+			Ok(self.write_null(repetition_index, self.max_dl - 1))
 		}
 	}
 
@@ -74,24 +79,26 @@ impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ByteArrayColumnAppen
 	}
 
 	fn write_column(&mut self, writer: &mut SerializedColumnWriter) -> Result<(), ParquetError> {
+		let column = self.take_byte_arrays();
 		let dls = if self.max_dl > 0 { Some(self.dls.as_slice()) } else { None };
 		let rls = if self.max_rl > 0 { Some(self.rls.as_slice()) } else { None };
 
 		let writer_t = writer.typed::<ByteArrayType>();
+		let _num_written = writer_t.write_batch(&column, dls, rls)?;
+		std::mem::drop(column);
+
+		self.dls.clear();
+		self.rls.clear();
+
+		Ok(())
+	}
 
-		if self.offsets.len() == 0 {
+	/// Splits the buffered bytes into the values, without copying them
+	fn take_byte_arrays(&mut self) -> Vec<ByteArray> {
+		if self.offsets.is_empty() {
 			assert_eq!(0, self.byte_buffer.len());
-			writer_t.write_batch(&[], dls, rls)?;
-			self.dls.clear();
-			self.rls.clear();
-			return Ok(());
+			return Vec::new();
 		}
-
-		// if self.max_rl > 0 {
-		// 	println!("Writing values: {:?}", self.column);
-		// 	println!("           RLS: {:?}", self.rls);
-		// 	println!("           DLS: {:?}", self.dls);
-		// }
 		let mut byte_array = Vec::new();
 		std::mem::swap(&mut self.byte_buffer, &mut byte_array);
 		let byte_array = Bytes::from(byte_array);
@@ -104,22 +111,16 @@ impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ByteArrayColumnAppen
 		}
 		column[self.offsets.len()-1] = ByteArray::from(byte_array.slice(*self.offsets.last().unwrap()..));
 
-		let _num_written = writer_t.write_batch(&column, dls, rls)?;
-		std::mem::drop(column);
-
 		self.offsets.clear();
 		self.byte_buffer.reserve(byte_array.len());
 		assert_eq!(0, self.byte_buffer.len());
-		self.dls.clear();
-		self.rls.clear();
-
-		Ok(())
+		column
 	}
 }
 
-impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ColumnAppenderBase for ByteArrayColumnAppender<TPg, FCopyTo> {
+impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Result<Option<usize>, String>> ColumnAppenderBase for ByteArrayColumnAppender<TPg, FCopyTo> {
 
-	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
 		let mut error = None;
 		let c = next_col.next_column(&mut |mut column| {
 			let result = self.write_column(&mut column);
@@ -130,8 +131,8 @@ impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ColumnAppende
 			
 		}).map_err(|e| format!("Could not create column[{}]: {}", column_i, e))?;
 
-		if error.is_some() {
-			return Err(format!("Couldn't write data of column[{}]: {}", column_i, error.unwrap()));
+		if let Some(error) = error {
+			return Err(format!("Couldn't write data of column[{}]: {}", column_i, error));
 		}
 
 		if !c {
@@ -147,13 +148,178 @@ impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ColumnAppende
 
 	fn max_dl(&self) -> i16 { self.max_dl }
 	fn max_rl(&self) -> i16 { self.max_rl }
+
+	fn begin_row(&mut self) {
+		self.row_start = (self.offsets.len(), self.byte_buffer.len(), self.dls.len(), self.rls.len());
+		self.row_start_index.copy_from(&self.repetition_index);
+	}
+
+	fn rollback_row(&mut self) {
+		let (offsets, bytes, dls, rls) = self.row_start;
+		self.offsets.truncate(offsets);
+		self.byte_buffer.truncate(bytes);
+		self.dls.truncate(dls);
+		self.rls.truncate(rls);
+		self.repetition_index.copy_from(&self.row_start_index);
+	}
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+		if self.max_rl > 0 {
+			return None;
+		}
+		let values = FlatValues::ByteArray(self.take_byte_arrays());
+		Some(FlatColumnValues { values, dls: std::mem::take(&mut self.dls), max_dl: self.max_dl })
+	}
 }
 
-impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ColumnAppender<TPg> for ByteArrayColumnAppender<TPg, FCopyTo> {
+impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Result<Option<usize>, String>> ColumnAppender<TPg> for ByteArrayColumnAppender<TPg, FCopyTo> {
 	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<TPg>) -> Result<usize, String> {
-		let byte_size = self.append(repetition_index, value.as_ref());
-		
-		Ok(byte_size)
+		self.append(repetition_index, value.as_ref())
+	}
+}
+
+/// Appends values of a fixed length into one buffer, which is split into the FIXED_LEN_BYTE_ARRAY values when the column is written.
+/// Unlike `GenericColumnAppender` with `FixedLenByteArray` values, it does not allocate each value separately
+pub struct FixedLenByteArrayColumnAppender<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>)> {
+	max_dl: i16,
+	max_rl: i16,
+	/// Number of bytes which `conversion` appends for each value
+	length: usize,
+	byte_buffer: Vec<u8>,
+	dls: Vec<i16>,
+	rls: Vec<i16>,
+	repetition_index: LevelIndexState,
+	conversion: FCopyTo,
+	_dummy: PhantomData<fn(TPg)>,
+	/// Lengths of the buffers and the repetition index at the start of the current row, for `rollback_row`
+	row_start: (usize, usize, usize),
+	row_start_index: LevelIndexState,
+}
+
+impl<TPg, FCopyTo: Fn(&TPg, &mut Vec<u8>)> FixedLenByteArrayColumnAppender<TPg, FCopyTo> {
+	pub fn new(max_dl: i16, max_rl: i16, length: usize, f_copy: FCopyTo) -> Self {
+		if max_dl < 0 || max_rl < 0 || length == 0 {
+			panic!("Cannot create {} with max_dl={}, max_rl={}, length={}", std::any::type_name::<Self>(), max_dl, max_rl, length);
+		}
+		FixedLenByteArrayColumnAppender {
+			max_dl, max_rl, length,
+			byte_buffer: Vec::new(),
+			dls: Vec::new(),
+			rls: Vec::new(),
+			repetition_index: LevelIndexState::new(max_rl),
+			conversion: f_copy,
+			_dummy: PhantomData,
+			row_start: (0, 0, 0),
+			row_start_index: LevelIndexState::new(max_rl),
+		}
+	}
+
+	pub fn append(&mut self, repetition_index: &LevelIndexList, value: &TPg) -> usize {
+		let index = self.byte_buffer.len();
+		(self.conversion)(value, &mut self.byte_buffer);
+		debug_assert_eq!(index + self.length, self.byte_buffer.len());
+
+		if self.max_dl > 0 {
+			self.dls.push(self.max_dl);
+		}
+		if self.max_rl > 0 {
+			let rl = self.repetition_index.copy_and_diff(repetition_index);
+			self.rls.push(rl);
+		}
+		self.length + 2 * (self.max_dl > 0) as usize + 2 * (self.max_rl > 0) as usize
+	}
+
+	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> usize {
+		debug_assert!(level < self.max_dl);
+
+		self.dls.push(level);
+		if self.max_rl > 0 {
+			let rl = self.repetition_index.copy_and_diff(repetition_index);
+			self.rls.push(rl);
+			4
+		} else {
+			2
+		}
+	}
+
+	fn write_column(&mut self, writer: &mut SerializedColumnWriter) -> Result<(), ParquetError> {
+		let column = self.take_fixed_len_byte_arrays();
+		let dls = if self.max_dl > 0 { Some(self.dls.as_slice()) } else { None };
+		let rls = if self.max_rl > 0 { Some(self.rls.as_slice()) } else { None };
+
+		let writer_t = writer.typed::<FixedLenByteArrayType>();
+		let _num_written = writer_t.write_batch(&column, dls, rls)?;
+		std::mem::drop(column);
+
+		self.dls.clear();
+		self.rls.clear();
+
+		Ok(())
+	}
+
+	/// Splits the buffered bytes into the values, without copying them
+	fn take_fixed_len_byte_arrays(&mut self) -> Vec<FixedLenByteArray> {
+		let byte_array = Bytes::from(std::mem::take(&mut self.byte_buffer));
+		let column = (0..byte_array.len()).step_by(self.length)
+			.map(|offset| FixedLenByteArray::from(ByteArray::from(byte_array.slice(offset..offset + self.length))))
+			.collect();
+		self.byte_buffer.reserve(byte_array.len());
+		column
+	}
+}
+
+impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>)> ColumnAppenderBase for FixedLenByteArrayColumnAppender<TPg, FCopyTo> {
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+		let mut error = None;
+		let c = next_col.next_column(&mut |mut column| {
+			let result = self.write_column(&mut column);
+			let result2 = column.close();
+			error = result.err().or(result2.err());
+		}).map_err(|e| format!("Could not create column[{}]: {}", column_i, e))?;
+
+		if let Some(error) = error {
+			return Err(format!("Couldn't write data of column[{}]: {}", column_i, error));
+		}
+
+		if !c {
+			return Err("Not enough columns".to_string());
+		}
+
+		Ok(())
+	}
+
+	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+		Ok(self.write_null(repetition_index, level))
+	}
+
+	fn max_dl(&self) -> i16 { self.max_dl }
+	fn max_rl(&self) -> i16 { self.max_rl }
+
+	fn begin_row(&mut self) {
+		self.row_start = (self.byte_buffer.len(), self.dls.len(), self.rls.len());
+		self.row_start_index.copy_from(&self.repetition_index);
+	}
+
+	fn rollback_row(&mut self) {
+		let (bytes, dls, rls) = self.row_start;
+		self.byte_buffer.truncate(bytes);
+		self.dls.truncate(dls);
+		self.rls.truncate(rls);
+		self.repetition_index.copy_from(&self.row_start_index);
+	}
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+		if self.max_rl > 0 {
+			return None;
+		}
+		let values = FlatValues::FixedLenByteArray(self.take_fixed_len_byte_arrays());
+		Some(FlatColumnValues { values, dls: std::mem::take(&mut self.dls), max_dl: self.max_dl })
+	}
+}
+
+impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>)> ColumnAppender<TPg> for FixedLenByteArrayColumnAppender<TPg, FCopyTo> {
+	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<TPg>) -> Result<usize, String> {
+		Ok(self.append(repetition_index, value.as_ref()))
 	}
 }
 
@@ -163,35 +329,79 @@ impl<TPg: Clone, FCopyTo: Fn(&TPg, &mut Vec<u8>) -> Option<usize>> ColumnAppende
 
 // impl Col
 
-/// Directly appends the bytes of the Postgres wire representation
+/// Directly appends the bytes of the Postgres wire representation, without allocating a String or validating UTF-8
 /// Works for TEXT (and similar), BYTES, JSON (not JSONB!!)
 pub fn create_pg_raw_appender<TRow: PgAbstractRow + Clone>(max_dl: i16, max_rl: i16, column_index: usize) -> impl ColumnAppender<TRow> {
-	let a = ByteArrayColumnAppender::new(max_dl, max_rl, move |row: &TRow, buffer: &mut Vec<u8>| {
-		if let Some(value) = row.ab_get::<Option<PgAnyRef>>(column_index) {
-			buffer.extend_from_slice(value.value);
-			Some(value.value.len())
-		} else {
-			None
+	ByteArrayColumnAppender::new(max_dl, max_rl, move |row: &TRow, buffer: &mut Vec<u8>| {
+		match row.ab_get::<Option<PgAnyRef>>(column_index)? {
+			Some(value) => {
+				buffer.extend_from_slice(value.value);
+				Ok(Some(value.value.len()))
+			},
+			None => Ok(None),
+		}
+	})
+}
+
+/// Check of the UTF-8 validity of text values, see `--invalid-utf8`
+#[derive(Debug, Clone)]
+pub struct Utf8Check {
+	/// Column named in the error message
+	pub column_name: String,
+	/// Invalid sequences are replaced by U+FFFD instead of failing
+	pub replace_invalid: bool,
+}
+
+impl Utf8Check {
+	fn append(&self, text: &[u8], buffer: &mut Vec<u8>) -> Result<usize, String> {
+		match std::str::from_utf8(text) {
+			Ok(_) => {
+				buffer.extend_from_slice(text);
+				Ok(text.len())
+			},
+			Err(_) if self.replace_invalid => {
+				let text = String::from_utf8_lossy(text);
+				buffer.extend_from_slice(text.as_bytes());
+				Ok(text.len())
+			},
+			Err(e) => Err(format!("Column {} contains text which is not valid UTF-8 ({}), use --invalid-utf8=replace or --invalid-utf8=bytes to export it", self.column_name, e)),
 		}
-	});
-	a
+	}
 }
 
-pub fn create_jsonb_appender<TRow: PgAbstractRow + Clone>(max_dl: i16, max_rl: i16, column_index: usize) -> impl ColumnAppender<TRow> {
-	let a = ByteArrayColumnAppender::new(max_dl, max_rl, move |row: &TRow, buffer: &mut Vec<u8>| {
-		if let Some(value) = row.ab_get::<Option<PgAnyRef>>(column_index) {
+/// Appends the bytes of a text value after checking that it is valid UTF-8.
+/// Invalid sequences are either replaced by U+FFFD or reported as an error naming the column
+pub fn create_pg_text_appender<TRow: PgAbstractRow + Clone>(max_dl: i16, max_rl: i16, column_index: usize, utf8_check: Utf8Check) -> impl ColumnAppender<TRow> {
+	ByteArrayColumnAppender::new(max_dl, max_rl, move |row: &TRow, buffer: &mut Vec<u8>| {
+		match row.ab_get::<Option<PgAnyRef>>(column_index)? {
+			Some(value) => Ok(Some(utf8_check.append(value.value, buffer)?)),
+			None => Ok(None),
+		}
+	})
+}
 
-			debug_assert_eq!(value.ty, postgres::types::Type::JSONB);
-			let mut data = value.value;
-			let version = data.read_i32::<BigEndian>().unwrap();
-			assert_eq!(version, 1);
-			buffer.extend_from_slice(data);
-			Some(value.value.len())
-		} else {
-			None
+/// Appends the JSON text of a JSONB value, skipping its version byte. The text is copied unchanged without `utf8_check`
+pub fn create_jsonb_appender<TRow: PgAbstractRow + Clone>(max_dl: i16, max_rl: i16, column_index: usize, utf8_check: Option<Utf8Check>) -> impl ColumnAppender<TRow> {
+	ByteArrayColumnAppender::new(max_dl, max_rl, move |row: &TRow, buffer: &mut Vec<u8>| {
+		match row.ab_get::<Option<PgAnyRef>>(column_index)? {
+			Some(value) => {
+				debug_assert_eq!(value.ty, postgres::types::Type::JSONB);
+				let mut data = value.value;
+				let version = data.read_u8().map_err(|_| "Empty jsonb value".to_string())?;
+				if version != 1 {
+					return Err(format!("Unknown jsonb version {}", version));
+				}
+				match &utf8_check {
+					Some(check) => Ok(Some(check.append(data, buffer)?)),
+					None => {
+						buffer.extend_from_slice(data);
+						Ok(Some(data.len()))
+					},
+				}
+			},
+			None => Ok(None),
 		}
-	});
-	a
+	})
 }
 // pub fn create_string_appender<TRow: PgAbstractRow>(max_dl: i16, max_rl: i16, column_index: usize) -> impl ColumnAppender<Arc<TRow>> {
 // 	create_pg_raw_appender::<TRow>(max_dl, max_rl, column_index)
@@ -4,7 +4,7 @@ use parquet::{column::writer::ColumnWriter, data_type::DataType, errors::Parquet
 
 use crate::{level_index::{LevelIndexState, LevelIndexList}, myfrom::MyFrom};
 
-use super::{real_memory_size::RealMemorySize, ColumnAppenderBase, ColumnAppender, DynamicSerializedWriter};
+use super::{real_memory_size::RealMemorySize, ColumnAppenderBase, ColumnAppender, DynamicSerializedWriter, FlatColumnValues, FlatValues};
 
 
 pub struct GenericColumnAppender<TPg, TPq, FConversion>
@@ -14,10 +14,13 @@ pub struct GenericColumnAppender<TPg, TPq, FConversion>
 	column: Vec<TPq::T>,
 	dls: Vec<i16>,
 	rls: Vec<i16>,
-	dummy: PhantomData<TPg>,
-	dummy2: PhantomData<TPq>,
+	dummy: PhantomData<fn(TPg)>,
+	dummy2: PhantomData<fn(TPq)>,
 	repetition_index: LevelIndexState,
 	conversion: FConversion,
+	/// Lengths of the buffers and the repetition index at the start of the current row, for `rollback_row`
+	row_start: (usize, usize, usize),
+	row_start_index: LevelIndexState,
 }
 
 pub fn new_autoconv_generic_appender<TPg, TPq: DataType>(
@@ -43,6 +46,8 @@ impl<TPg, TPq, FConversion> GenericColumnAppender<TPg, TPq, FConversion>
 			rls: Vec::new(),
 			repetition_index: LevelIndexState::new(max_rl),
 			conversion,
+			row_start: (0, 0, 0),
+			row_start_index: LevelIndexState::new(max_rl),
 		}
 	}
 
@@ -71,9 +76,9 @@ impl<TPg, TPq, FConversion> GenericColumnAppender<TPg, TPq, FConversion>
 }
 
 impl<TPg, TPq, FConversion> ColumnAppenderBase for GenericColumnAppender<TPg, TPq, FConversion>
-	where TPq::T: Clone + RealMemorySize, TPq: DataType, FConversion: Fn(TPg) -> TPq::T {
+	where TPq::T: Clone + RealMemorySize + 'static, TPq: DataType, FConversion: Fn(TPg) -> TPq::T {
 
-	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
 		let mut error = None;
 		let mut col_descriptor: Option<(Arc<ColumnDescriptor>, u64, u64)> = None;
 		let c = next_col.next_column(&mut |mut column| {
@@ -90,9 +95,9 @@ impl<TPg, TPq, FConversion> ColumnAppenderBase for GenericColumnAppender<TPg, TP
 		debug_assert_eq!(col_descriptor.as_ref().unwrap().0.max_def_level(), self.max_dl);
 		debug_assert_eq!(col_descriptor.as_ref().unwrap().0.max_rep_level(), self.max_rl);
 
-		if error.is_some() {
+		if let Some(error) = error {
 			let col_name = col_descriptor.map(|(desc, _, _)| desc.path().string()).unwrap_or_else(|| format!("column[{}]", column_i));
-			return Err(format!("Couldn't write data of {}: {}", col_name, error.unwrap()));
+			return Err(format!("Couldn't write data of {}: {}", col_name, error));
 		}
 
 		if !c {
@@ -121,6 +126,27 @@ impl<TPg, TPq, FConversion> ColumnAppenderBase for GenericColumnAppender<TPg, TP
 
 	fn max_dl(&self) -> i16 { self.max_dl }
 	fn max_rl(&self) -> i16 { self.max_rl }
+
+	fn begin_row(&mut self) {
+		self.row_start = (self.column.len(), self.dls.len(), self.rls.len());
+		self.row_start_index.copy_from(&self.repetition_index);
+	}
+
+	fn rollback_row(&mut self) {
+		let (column, dls, rls) = self.row_start;
+		self.column.truncate(column);
+		self.dls.truncate(dls);
+		self.rls.truncate(rls);
+		self.repetition_index.copy_from(&self.row_start_index);
+	}
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+		if self.max_rl > 0 {
+			return None;
+		}
+		let values = FlatValues::from_vec(std::mem::take(&mut self.column))?;
+		Some(FlatColumnValues { values, dls: std::mem::take(&mut self.dls), max_dl: self.max_dl })
+	}
 }
 
 fn get_column_descriptor(column: &mut SerializedColumnWriter) -> (Arc<ColumnDescriptor>, u64, u64) {
@@ -137,7 +163,7 @@ fn get_column_descriptor(column: &mut SerializedColumnWriter) -> (Arc<ColumnDesc
 }
 
 impl<TPg: Clone, TPq, FConversion> ColumnAppender<TPg> for GenericColumnAppender<TPg, TPq, FConversion>
-	where TPq::T: Clone + RealMemorySize, TPq: DataType, FConversion: Fn(TPg) -> TPq::T {
+	where TPq::T: Clone + RealMemorySize + 'static, TPq: DataType, FConversion: Fn(TPg) -> TPq::T {
 	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<TPg>) -> Result<usize, String> {
 		let pq_value = self.convert(value.into_owned());
 		let byte_size = pq_value.real_memory_size();
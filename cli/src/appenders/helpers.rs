@@ -1,12 +1,12 @@
-use std::{borrow::Cow, marker::PhantomData, sync::Arc};
+use std::{borrow::Cow, marker::PhantomData};
 
 use crate::level_index::LevelIndexList;
 
-use super::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter};
+use super::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, FlatColumnValues};
 
 pub struct UnwrapOptionAppender<T: Clone, Appender2: ColumnAppender<T>> {
     appender: Appender2,
-    _dummy: PhantomData<T>
+    _dummy: PhantomData<fn(T)>
 }
 impl<T: Clone, Appender2: ColumnAppender<T>> UnwrapOptionAppender<T, Appender2> {
     pub fn new(appender: Appender2) -> Self {
@@ -18,7 +18,7 @@ impl<T: Clone, Appender2: ColumnAppender<T>> ColumnAppenderBase for UnwrapOption
         self.appender.write_null(repetition_index, level)
     }
 
-    fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+    fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
         self.appender.write_columns(column_i, next_col)
     }
 
@@ -29,6 +29,18 @@ impl<T: Clone, Appender2: ColumnAppender<T>> ColumnAppenderBase for UnwrapOption
     fn max_rl(&self) -> i16 {
         self.appender.max_rl()
     }
+
+    fn begin_row(&mut self) {
+        self.appender.begin_row()
+    }
+
+    fn rollback_row(&mut self) {
+        self.appender.rollback_row()
+    }
+
+    fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+        self.appender.take_flat_values()
+    }
 }
 impl<T: Clone, Appender2: ColumnAppender<T>> ColumnAppender<Option<T>> for UnwrapOptionAppender<T, Appender2> {
     fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<Option<T>>) -> Result<usize, String> {
@@ -36,10 +48,55 @@ impl<T: Clone, Appender2: ColumnAppender<T>> ColumnAppender<Option<T>> for Unwra
     }
 }
 
+/// Writes NULL instead of all values, the inner appender must be a top-level column
+pub struct AlwaysNullAppender<T: Clone, Appender2: ColumnAppender<T>> {
+    appender: Appender2,
+    _dummy: PhantomData<fn(T)>
+}
+impl<T: Clone, Appender2: ColumnAppender<T>> AlwaysNullAppender<T, Appender2> {
+    pub fn new(appender: Appender2) -> Self {
+        AlwaysNullAppender { appender, _dummy: PhantomData }
+    }
+}
+impl<T: Clone, Appender2: ColumnAppender<T>> ColumnAppenderBase for AlwaysNullAppender<T, Appender2> {
+    fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+        self.appender.write_null(repetition_index, level)
+    }
+
+    fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+        self.appender.write_columns(column_i, next_col)
+    }
+
+    fn max_dl(&self) -> i16 {
+        self.appender.max_dl()
+    }
+
+    fn max_rl(&self) -> i16 {
+        self.appender.max_rl()
+    }
+
+    fn begin_row(&mut self) {
+        self.appender.begin_row()
+    }
+
+    fn rollback_row(&mut self) {
+        self.appender.rollback_row()
+    }
+
+    fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+        self.appender.take_flat_values()
+    }
+}
+impl<T: Clone, Appender2: ColumnAppender<T>> ColumnAppender<T> for AlwaysNullAppender<T, Appender2> {
+    fn copy_value(&mut self, repetition_index: &LevelIndexList, _value: Cow<T>) -> Result<usize, String> {
+        self.appender.write_null(repetition_index, 0)
+    }
+}
+
 pub struct PreprocessAppender<T1: Clone, T2: Clone, Appender2: ColumnAppender<T2>, F: Fn(Cow<T1>) -> Cow<T2>> {
     appender: Appender2,
     f: F,
-    _dummy: PhantomData<(T1, T2)>
+    _dummy: PhantomData<fn((T1, T2))>
 }
 impl<T1: Clone, T2: Clone, Appender2: ColumnAppender<T2>, F: Fn(Cow<T1>) -> Cow<T2>> PreprocessAppender<T1, T2, Appender2, F> {
     pub fn new(appender: Appender2, f: F) -> Self {
@@ -51,7 +108,7 @@ impl<T1: Clone, T2: Clone, Appender2: ColumnAppender<T2>, F: Fn(Cow<T1>) -> Cow<
         self.appender.write_null(repetition_index, level)
     }
 
-    fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+    fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
         self.appender.write_columns(column_i, next_col)
     }
 
@@ -62,57 +119,75 @@ impl<T1: Clone, T2: Clone, Appender2: ColumnAppender<T2>, F: Fn(Cow<T1>) -> Cow<
     fn max_rl(&self) -> i16 {
         self.appender.max_rl()
     }
+
+    fn begin_row(&mut self) {
+        self.appender.begin_row()
+    }
+
+    fn rollback_row(&mut self) {
+        self.appender.rollback_row()
+    }
+
+    fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+        self.appender.take_flat_values()
+    }
 }
 impl<T1: Clone, T2: Clone, Appender2: ColumnAppender<T2>, F: Fn(Cow<T1>) -> Cow<T2>> ColumnAppender<T1> for PreprocessAppender<T1, T2, Appender2, F> {
     fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<T1>) -> Result<usize, String> {
         self.appender.copy_value(repetition_index, (self.f)(value))
     }
 }
-pub trait PreprocessExt<T2: Clone, Appender2: ColumnAppender<T2>> {
-    fn preprocess<T1: Clone, F: Fn(Cow<T1>) -> Cow<T2>>(self, f: F) -> PreprocessAppender<T1, T2, Appender2, F>;
+/// Ignores the row and writes a value computed from its index in the file instead, used for the synthetic `--add-meta-columns`
+pub struct RowIndexAppender<TRow: Clone, T: Clone, Appender2: ColumnAppender<T>, F: Fn(usize) -> T> {
+    appender: Appender2,
+    f: F,
+    _dummy: PhantomData<fn((TRow, T))>
 }
-impl<T2: Clone, Appender2: ColumnAppender<T2>> PreprocessExt<T2, Appender2> for Appender2 {
-    fn preprocess<T1: Clone, F: Fn(Cow<T1>) -> Cow<T2>>(self, f: F) -> PreprocessAppender<T1, T2, Appender2, F> {
-        PreprocessAppender::new(self, f)
+impl<TRow: Clone, T: Clone, Appender2: ColumnAppender<T>, F: Fn(usize) -> T> RowIndexAppender<TRow, T, Appender2, F> {
+    pub fn new(appender: Appender2, f: F) -> Self {
+        RowIndexAppender { appender, f, _dummy: PhantomData }
     }
 }
+impl<TRow: Clone, T: Clone, Appender2: ColumnAppender<T>, F: Fn(usize) -> T> ColumnAppenderBase for RowIndexAppender<TRow, T, Appender2, F> {
+    fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+        self.appender.write_null(repetition_index, level)
+    }
 
-pub struct RcWrapperAppender<T, TInner: ColumnAppender<Arc<T>>> {
-	pub inner: TInner,
-	pub dummy: PhantomData<T>
-}
-impl<T, TInner: ColumnAppender<Arc<T>>> RcWrapperAppender<T, TInner> {
-	pub fn new(inner: TInner) -> Self {
-		Self { inner, dummy: PhantomData }
-	}
-}
-impl<T, TInner: ColumnAppender<Arc<T>>> ColumnAppenderBase for RcWrapperAppender<T, TInner> {
-	fn write_null(&mut self, repetition_index: &crate::level_index::LevelIndexList, level: i16) -> Result<usize, String> {
-		self.inner.write_null(repetition_index, level)
-	}
+    fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+        self.appender.write_columns(column_i, next_col)
+    }
 
-	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn crate::appenders::DynamicSerializedWriter) -> Result<(), String> {
-		self.inner.write_columns(column_i, next_col)
-	}
+    fn max_dl(&self) -> i16 {
+        self.appender.max_dl()
+    }
+
+    fn max_rl(&self) -> i16 {
+        self.appender.max_rl()
+    }
+
+    fn begin_row(&mut self) {
+        self.appender.begin_row()
+    }
 
-	fn max_dl(&self) -> i16 { self.inner.max_dl() }
+    fn rollback_row(&mut self) {
+        self.appender.rollback_row()
+    }
 
-	fn max_rl(&self) -> i16 { self.inner.max_rl() }
+    fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+        self.appender.take_flat_values()
+    }
 }
-impl<T: Clone, TInner: ColumnAppender<Arc<T>>> ColumnAppender<T> for RcWrapperAppender<T, TInner> {
-	fn copy_value(&mut self, repetition_index: &crate::level_index::LevelIndexList, value: Cow<T>) -> Result<usize, String> {
-		let arc = Arc::new(value.into_owned());
-		let cow = Cow::Owned(arc);
-		self.inner.copy_value(repetition_index, cow)
-	}
+impl<TRow: Clone, T: Clone, Appender2: ColumnAppender<T>, F: Fn(usize) -> T> ColumnAppender<TRow> for RowIndexAppender<TRow, T, Appender2, F> {
+    fn copy_value(&mut self, repetition_index: &LevelIndexList, _value: Cow<TRow>) -> Result<usize, String> {
+        self.appender.copy_value(repetition_index, Cow::Owned((self.f)(repetition_index.index)))
+    }
 }
 
-
-pub trait RcWrapperExt<TInner: ColumnAppender<Arc<T>>, T> {
-    fn rc_wrapper(self) -> RcWrapperAppender<T, TInner>;
+pub trait PreprocessExt<T2: Clone, Appender2: ColumnAppender<T2>> {
+    fn preprocess<T1: Clone, F: Fn(Cow<T1>) -> Cow<T2>>(self, f: F) -> PreprocessAppender<T1, T2, Appender2, F>;
 }
-impl<TInner: ColumnAppender<Arc<T>>, T> RcWrapperExt<TInner, T> for TInner {
-    fn rc_wrapper(self) -> RcWrapperAppender<T, TInner> {
-        RcWrapperAppender::new(self)
+impl<T2: Clone, Appender2: ColumnAppender<T2>> PreprocessExt<T2, Appender2> for Appender2 {
+    fn preprocess<T1: Clone, F: Fn(Cow<T1>) -> Cow<T2>>(self, f: F) -> PreprocessAppender<T1, T2, Appender2, F> {
+        PreprocessAppender::new(self, f)
     }
 }
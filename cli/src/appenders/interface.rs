@@ -1,19 +1,72 @@
-use std::{borrow::Cow, sync::Arc, cell::RefCell, io::Write};
+use std::{any::Any, borrow::Cow, sync::Arc, cell::RefCell, io::Write};
 
-use parquet::file::writer::{SerializedColumnWriter, SerializedRowGroupWriter};
+use bytes::Bytes;
+use parquet::column::page::{CompressedPage, Page, PageWriteSpec, PageWriter};
+use parquet::column::writer::{get_column_writer, ColumnCloseResult};
+use parquet::data_type::{ByteArray, FixedLenByteArray};
+use parquet::file::properties::WriterPropertiesPtr;
+use parquet::file::writer::{OnCloseColumnChunk, SerializedColumnWriter, SerializedPageWriter, SerializedRowGroupWriter, TrackedWrite};
+use parquet::format::{DataPageHeader, DataPageHeaderV2, DictionaryPageHeader, PageHeader};
+use parquet::schema::types::ColumnDescPtr;
+use parquet::thrift::{TCompactOutputProtocol, TSerializable};
 
 use crate::level_index::LevelIndexList;
 
 pub trait ColumnAppenderBase {
 	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String>;
 
-	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String>;
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String>;
 
 	fn max_dl(&self) -> i16;
 	fn max_rl(&self) -> i16;
+
+	/// Remembers the current state of the appender, so that the values of the next row can be removed by `rollback_row`
+	fn begin_row(&mut self);
+	/// Removes all values written since the last `begin_row` call, used when the row could not be read completely
+	fn rollback_row(&mut self);
+
+	/// Removes the buffered values of a column without repetition and returns them instead of writing them into a column writer,
+	/// used by the Arrow engine. None if the appender writes nested columns
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> { None }
+}
+
+/// Non-null values of a flat column, see `ColumnAppenderBase::take_flat_values`
+#[derive(Debug)]
+pub enum FlatValues {
+	Boolean(Vec<bool>),
+	Int32(Vec<i32>),
+	Int64(Vec<i64>),
+	Float(Vec<f32>),
+	Double(Vec<f64>),
+	ByteArray(Vec<ByteArray>),
+	FixedLenByteArray(Vec<FixedLenByteArray>),
+}
+
+impl FlatValues {
+	/// Wraps the values of a Parquet data type, None for INT96
+	pub fn from_vec<T: 'static>(values: Vec<T>) -> Option<FlatValues> {
+		let values: Box<dyn Any> = Box::new(values);
+		let values = match values.downcast::<Vec<bool>>() { Ok(v) => return Some(FlatValues::Boolean(*v)), Err(v) => v };
+		let values = match values.downcast::<Vec<i32>>() { Ok(v) => return Some(FlatValues::Int32(*v)), Err(v) => v };
+		let values = match values.downcast::<Vec<i64>>() { Ok(v) => return Some(FlatValues::Int64(*v)), Err(v) => v };
+		let values = match values.downcast::<Vec<f32>>() { Ok(v) => return Some(FlatValues::Float(*v)), Err(v) => v };
+		let values = match values.downcast::<Vec<f64>>() { Ok(v) => return Some(FlatValues::Double(*v)), Err(v) => v };
+		let values = match values.downcast::<Vec<ByteArray>>() { Ok(v) => return Some(FlatValues::ByteArray(*v)), Err(v) => v };
+		match values.downcast::<Vec<FixedLenByteArray>>() { Ok(v) => Some(FlatValues::FixedLenByteArray(*v)), Err(_) => None }
+	}
+}
+
+#[derive(Debug)]
+pub struct FlatColumnValues {
+	pub values: FlatValues,
+	/// Definition level of each row, empty if `max_dl` is 0. The value is NULL if it is lower than `max_dl`
+	pub dls: Vec<i16>,
+	pub max_dl: i16,
 }
 
 pub trait ColumnAppender<TPg: Clone>: ColumnAppenderBase {
+	/// Appends the value. Nested appenders receive borrowed values (e.g. the fields of a composite value are all read from one `Cow::Borrowed` record),
+	/// so the appender should only take ownership of the value when it needs to store it
 	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<TPg>) -> Result<usize, String>;
 	fn copy_value_opt(&mut self, repetition_index: &LevelIndexList, value: Cow<Option<TPg>>) -> Result<usize, String> {
 		match value {
@@ -27,14 +80,14 @@ pub trait ColumnAppender<TPg: Clone>: ColumnAppenderBase {
 	}
 }
 
-pub type DynColumnAppender<T> = Box<dyn ColumnAppender<T>>;
+pub type DynColumnAppender<T> = Box<dyn ColumnAppender<T> + Send>;
 
 impl<T> ColumnAppenderBase for DynColumnAppender<T> {
     fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
         self.as_mut().write_null(repetition_index, level)
     }
 
-    fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+    fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
         self.as_mut().write_columns(column_i, next_col)
     }
 
@@ -45,6 +98,18 @@ impl<T> ColumnAppenderBase for DynColumnAppender<T> {
     fn max_rl(&self) -> i16 {
         self.as_ref().max_rl()
     }
+
+    fn begin_row(&mut self) {
+        self.as_mut().begin_row()
+    }
+
+    fn rollback_row(&mut self) {
+        self.as_mut().rollback_row()
+    }
+
+    fn take_flat_values(&mut self) -> Option<FlatColumnValues> {
+        self.as_mut().take_flat_values()
+    }
 }
 
 impl<T: Clone> ColumnAppender<T> for DynColumnAppender<T> {
@@ -57,13 +122,13 @@ pub type Arcell<T> = Arc<RefCell<T>>;
 
 /// Helper trait for ColumnAppender to allow dynamic dispatch of creating new columns
 pub trait DynamicSerializedWriter {
-	fn next_column(&mut self, callback: &mut dyn FnMut(SerializedColumnWriter<'_>) -> ()) -> parquet::errors::Result<bool>;
+	fn next_column(&mut self, callback: &mut dyn FnMut(SerializedColumnWriter<'_>)) -> parquet::errors::Result<bool>;
 }
 struct DynamicSerializedWriterImpl<'a, W: Write + Send> {
 	writer: Arcell<Option<SerializedRowGroupWriter<'a, W>>>
 }
-impl<'a, 'b, W: Write + Send> DynamicSerializedWriter for DynamicSerializedWriterImpl<'a, W> {
-	fn next_column(&mut self, callback: &mut dyn FnMut(SerializedColumnWriter<'_>) -> ()) -> parquet::errors::Result<bool> {
+impl<'a, W: Write + Send> DynamicSerializedWriter for DynamicSerializedWriterImpl<'a, W> {
+	fn next_column(&mut self, callback: &mut dyn FnMut(SerializedColumnWriter<'_>)) -> parquet::errors::Result<bool> {
 		let mut writer = self.writer.borrow_mut();
 		let writer2 = writer.as_mut().unwrap();
 		let col = writer2.next_column()?;
@@ -80,3 +145,125 @@ impl<'a, 'b, W: Write + Send> DynamicSerializedWriter for DynamicSerializedWrite
 pub fn new_dynamic_serialized_writer<'a, W: Write + Send>(writer: Arcell<Option<SerializedRowGroupWriter<'a, W>>>) -> Box<dyn DynamicSerializedWriter + 'a> {
 	Box::new(DynamicSerializedWriterImpl::<'a, W> { writer })
 }
+
+/// Writes the columns into separate in-memory buffers, which can be appended to a row group later
+/// using `SerializedRowGroupWriter::append_column`
+pub struct InMemoryColumnWriter {
+	columns: std::vec::IntoIter<ColumnDescPtr>,
+	props: WriterPropertiesPtr,
+	/// Write the CRC32 checksum of each page into its header
+	page_checksums: bool,
+	chunks: Vec<(Bytes, ColumnCloseResult)>,
+}
+impl InMemoryColumnWriter {
+	pub fn new(columns: Vec<ColumnDescPtr>, props: WriterPropertiesPtr, page_checksums: bool) -> Self {
+		InMemoryColumnWriter { columns: columns.into_iter(), props, page_checksums, chunks: Vec::new() }
+	}
+
+	pub fn into_chunks(self) -> Vec<(Bytes, ColumnCloseResult)> {
+		self.chunks
+	}
+}
+impl DynamicSerializedWriter for InMemoryColumnWriter {
+	fn next_column(&mut self, callback: &mut dyn FnMut(SerializedColumnWriter<'_>)) -> parquet::errors::Result<bool> {
+		let Some(descr) = self.columns.next() else {
+			return Ok(false);
+		};
+		let mut buffer = TrackedWrite::new(Vec::new());
+		let mut close_result = None;
+		{
+			let page_writer: Box<dyn PageWriter> = if self.page_checksums {
+				Box::new(ChecksumPageWriter { sink: &mut buffer })
+			} else {
+				Box::new(SerializedPageWriter::new(&mut buffer))
+			};
+			let on_close: OnCloseColumnChunk = Box::new(|r| {
+				close_result = Some(r);
+				Ok(())
+			});
+			callback(SerializedColumnWriter::new(get_column_writer(descr, self.props.clone(), page_writer), Some(on_close)));
+		}
+		// if the column was not closed, the appender returns the error
+		if let Some(close_result) = close_result {
+			self.chunks.push((Bytes::from(buffer.into_inner()?), close_result));
+		}
+		Ok(true)
+	}
+}
+
+/// Page writer which fills in the crc field of the page headers, otherwise the same as `SerializedPageWriter`.
+/// The checksum covers the page data after the header, as it is written into the file
+struct ChecksumPageWriter<'a, W: Write> {
+	sink: &'a mut TrackedWrite<W>,
+}
+
+impl<W: Write + Send> PageWriter for ChecksumPageWriter<'_, W> {
+	fn write_page(&mut self, page: CompressedPage) -> parquet::errors::Result<PageWriteSpec> {
+		let start_pos = self.sink.bytes_written();
+		let header = page_header(&page);
+		{
+			let mut protocol = TCompactOutputProtocol::new(&mut *self.sink);
+			header.write_to_out_protocol(&mut protocol)?;
+		}
+		let header_size = self.sink.bytes_written() - start_pos;
+		self.sink.write_all(page.data())?;
+
+		let mut spec = PageWriteSpec::new();
+		spec.page_type = page.page_type();
+		spec.uncompressed_size = page.uncompressed_size() + header_size;
+		spec.compressed_size = page.compressed_size() + header_size;
+		spec.offset = start_pos as u64;
+		spec.bytes_written = (self.sink.bytes_written() - start_pos) as u64;
+		spec.num_values = page.num_values();
+		Ok(spec)
+	}
+
+	fn close(&mut self) -> parquet::errors::Result<()> {
+		self.sink.flush()?;
+		Ok(())
+	}
+}
+
+fn page_header(page: &CompressedPage) -> PageHeader {
+	let mut header = PageHeader {
+		type_: page.page_type().into(),
+		uncompressed_page_size: page.uncompressed_size() as i32,
+		compressed_page_size: page.compressed_size() as i32,
+		crc: Some(crc32fast::hash(page.data()) as i32),
+		data_page_header: None,
+		index_page_header: None,
+		dictionary_page_header: None,
+		data_page_header_v2: None,
+	};
+	match page.compressed_page() {
+		Page::DataPage { num_values, encoding, def_level_encoding, rep_level_encoding, statistics, .. } => {
+			header.data_page_header = Some(DataPageHeader {
+				num_values: *num_values as i32,
+				encoding: (*encoding).into(),
+				definition_level_encoding: (*def_level_encoding).into(),
+				repetition_level_encoding: (*rep_level_encoding).into(),
+				statistics: parquet::file::statistics::to_thrift(statistics.as_ref()),
+			});
+		},
+		Page::DataPageV2 { num_values, num_nulls, num_rows, encoding, def_levels_byte_len, rep_levels_byte_len, is_compressed, statistics, .. } => {
+			header.data_page_header_v2 = Some(DataPageHeaderV2 {
+				num_values: *num_values as i32,
+				num_nulls: *num_nulls as i32,
+				num_rows: *num_rows as i32,
+				encoding: (*encoding).into(),
+				definition_levels_byte_length: *def_levels_byte_len as i32,
+				repetition_levels_byte_length: *rep_levels_byte_len as i32,
+				is_compressed: Some(*is_compressed),
+				statistics: parquet::file::statistics::to_thrift(statistics.as_ref()),
+			});
+		},
+		Page::DictionaryPage { num_values, encoding, is_sorted, .. } => {
+			header.dictionary_page_header = Some(DictionaryPageHeader {
+				num_values: *num_values as i32,
+				encoding: (*encoding).into(),
+				is_sorted: Some(*is_sorted),
+			});
+		},
+	}
+	header
+}
@@ -14,6 +14,10 @@ impl<T> DynamicMergedAppender<T> {
 	pub fn new(columns: Vec<DynColumnAppender<T>>, max_dl: i16, max_rl: i16) -> Self {
 		DynamicMergedAppender { columns, max_dl, max_rl }
 	}
+
+	pub fn columns_mut(&mut self) -> &mut [DynColumnAppender<T>] {
+		&mut self.columns
+	}
 }
 
 impl<T> ColumnAppenderBase for DynamicMergedAppender<T> {
@@ -25,7 +29,7 @@ impl<T> ColumnAppenderBase for DynamicMergedAppender<T> {
 		Ok(total)
 	}
 
-	fn write_columns<'b>(&mut self, _column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+	fn write_columns(&mut self, _column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
 		for (i, c) in self.columns.iter_mut().enumerate() {
 			c.write_columns(i, next_col)?;
 		}
@@ -39,6 +43,18 @@ impl<T> ColumnAppenderBase for DynamicMergedAppender<T> {
 	fn max_rl(&self) -> i16 {
 		self.max_rl
 	}
+
+	fn begin_row(&mut self) {
+		for c in self.columns.iter_mut() {
+			c.begin_row();
+		}
+	}
+
+	fn rollback_row(&mut self) {
+		for c in self.columns.iter_mut() {
+			c.rollback_row();
+		}
+	}
 }
 
 impl<T: Clone> ColumnAppender<T> for DynamicMergedAppender<T> {
@@ -77,7 +93,7 @@ pub trait StaticMergedAppender<T: Clone>: ColumnAppender<T> {
 pub struct StaticMergedAppenderImpl<T: Clone, TAppender: ColumnAppender<T>, Next: ColumnAppender<T>> {
     pub appender: TAppender,
     pub next: Next,
-    pub _dummy: PhantomData<T>
+    pub _dummy: PhantomData<fn(T)>
 }
 
 impl<T: Clone, TAppender: ColumnAppender<T>, Next: ColumnAppender<T>> ColumnAppenderBase for StaticMergedAppenderImpl<T, TAppender, Next> {
@@ -87,7 +103,7 @@ impl<T: Clone, TAppender: ColumnAppender<T>, Next: ColumnAppender<T>> ColumnAppe
         Ok(x + y)
     }
 
-    fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+    fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
         self.next.write_columns(column_i, next_col)?;
         self.appender.write_columns(column_i, next_col)?;
         Ok(())
@@ -100,6 +116,16 @@ impl<T: Clone, TAppender: ColumnAppender<T>, Next: ColumnAppender<T>> ColumnAppe
     fn max_rl(&self) -> i16 {
         self.next.max_rl()
     }
+
+    fn begin_row(&mut self) {
+        self.next.begin_row();
+        self.appender.begin_row();
+    }
+
+    fn rollback_row(&mut self) {
+        self.next.rollback_row();
+        self.appender.rollback_row();
+    }
 }
 
 impl<T: Clone, TAppender: ColumnAppender<T>, Next: ColumnAppender<T>> ColumnAppender<T> for StaticMergedAppenderImpl<T, TAppender, Next> {
@@ -122,13 +148,17 @@ impl ColumnAppenderBase for StaticMergedAppenderNil {
         Ok(0)
     }
 
-    fn write_columns<'b>(&mut self, _column_i: usize, _next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+    fn write_columns(&mut self, _column_i: usize, _next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
         Ok(())
     }
 
     fn max_dl(&self) -> i16 { self.max_dl }
 
     fn max_rl(&self) -> i16 { self.max_rl }
+
+    fn begin_row(&mut self) {}
+
+    fn rollback_row(&mut self) {}
 }
 
 impl<T: Clone> ColumnAppender<T> for StaticMergedAppenderNil {
@@ -2,9 +2,9 @@ pub use interface::*;
 pub use generic::{GenericColumnAppender, new_autoconv_generic_appender};
 pub use array::ArrayColumnAppender;
 pub use real_memory_size::RealMemorySize;
-pub use pg_column::BasicPgRowColumnAppender;
+pub use pg_column::{BasicPgRowColumnAppender, PgRowPairColumnAppender};
 pub use merged::{DynamicMergedAppender, StaticMergedAppender, new_static_merged_appender};
-pub use helpers::{UnwrapOptionAppender, PreprocessAppender, PreprocessExt, RcWrapperAppender};
+pub use helpers::{AlwaysNullAppender, UnwrapOptionAppender, PreprocessAppender, PreprocessExt, RowIndexAppender};
 
 mod interface;
 mod generic;
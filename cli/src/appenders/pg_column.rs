@@ -4,14 +4,14 @@ use postgres::types::FromSql;
 
 use crate::{pg_custom_types::PgAbstractRow, level_index::LevelIndexList};
 
-use super::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter};
+use super::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, FlatColumnValues};
 
 
 pub struct BasicPgRowColumnAppender<TPg, TInner>
 	where TPg: Clone, TInner: ColumnAppender<TPg> {
 	column_i: usize,
 	appender: TInner,
-	_dummy: PhantomData<TPg>
+	_dummy: PhantomData<fn(TPg)>
 }
 
 impl<TPg, TInner> BasicPgRowColumnAppender<TPg, TInner>
@@ -41,6 +41,12 @@ impl<TPg, TInner> ColumnAppenderBase for BasicPgRowColumnAppender<TPg, TInner>
 	fn max_dl(&self) -> i16 { self.appender.max_dl() }
 
 	fn max_rl(&self) -> i16 { self.appender.max_rl() }
+
+	fn begin_row(&mut self) { self.appender.begin_row() }
+
+	fn rollback_row(&mut self) { self.appender.rollback_row() }
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> { self.appender.take_flat_values() }
 }
 
 impl<TPg, TAppender, TRow: PgAbstractRow + Clone> ColumnAppender<TRow> for BasicPgRowColumnAppender<TPg, TAppender>
@@ -49,8 +55,59 @@ impl<TPg, TAppender, TRow: PgAbstractRow + Clone> ColumnAppender<TRow> for Basic
 	fn copy_value(&mut self, repetition_index: &LevelIndexList, reader: Cow<TRow>) -> Result<usize, String> {
 		debug_assert_eq!(repetition_index.level, self.appender.max_rl());
 
-		let v = reader.ab_get::<Option<TPg>>(self.column_i);
+		let v = reader.ab_get::<Option<TPg>>(self.column_i)?;
 
 		self.appender.copy_value_opt(repetition_index, Cow::Owned(v))
 	}
 }
+
+/// Reads two columns of the row and passes them to the appender as a pair, the value is NULL when the first column is NULL
+pub struct PgRowPairColumnAppender<TPg1, TPg2, TInner>
+	where TPg1: Clone, TPg2: Clone, TInner: ColumnAppender<(TPg1, Option<TPg2>)> {
+	column_i: usize,
+	second_column_i: usize,
+	appender: TInner,
+	_dummy: PhantomData<fn(TPg1, TPg2)>
+}
+
+impl<TPg1, TPg2, TInner> PgRowPairColumnAppender<TPg1, TPg2, TInner>
+	where TPg1: Clone, TPg2: Clone, TInner: ColumnAppender<(TPg1, Option<TPg2>)> {
+	pub fn new(column_i: usize, second_column_i: usize, appender: TInner) -> Self {
+		PgRowPairColumnAppender {
+			column_i,
+			second_column_i,
+			appender,
+			_dummy: PhantomData
+		}
+	}
+}
+
+impl<TPg1, TPg2, TInner> ColumnAppenderBase for PgRowPairColumnAppender<TPg1, TPg2, TInner>
+	where TPg1: Clone, TPg2: Clone, TInner: ColumnAppender<(TPg1, Option<TPg2>)> {
+	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+		self.appender.write_null(repetition_index, level)
+	}
+
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+		self.appender.write_columns(column_i, next_col)
+	}
+
+	fn max_dl(&self) -> i16 { self.appender.max_dl() }
+
+	fn max_rl(&self) -> i16 { self.appender.max_rl() }
+
+	fn begin_row(&mut self) { self.appender.begin_row() }
+
+	fn rollback_row(&mut self) { self.appender.rollback_row() }
+}
+
+impl<TPg1, TPg2, TAppender, TRow: PgAbstractRow + Clone> ColumnAppender<TRow> for PgRowPairColumnAppender<TPg1, TPg2, TAppender>
+	where TPg1: for<'a> FromSql<'a> + Clone, TPg2: for<'a> FromSql<'a> + Clone, TAppender: ColumnAppender<(TPg1, Option<TPg2>)> {
+
+	fn copy_value(&mut self, repetition_index: &LevelIndexList, reader: Cow<TRow>) -> Result<usize, String> {
+		let first = reader.ab_get::<Option<TPg1>>(self.column_i)?;
+		let second = reader.ab_get::<Option<TPg2>>(self.second_column_i)?;
+
+		self.appender.copy_value_opt(repetition_index, Cow::Owned(first.map(|first| (first, second))))
+	}
+}
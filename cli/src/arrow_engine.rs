@@ -0,0 +1,224 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::types::{ArrowPrimitiveType, Date32Type, Decimal128Type, Decimal256Type, Float16Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type, Time64MicrosecondType, Time64NanosecondType, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type};
+use arrow_array::{ArrayRef, BinaryArray, BooleanArray, FixedSizeBinaryArray, PrimitiveArray, RecordBatch, StringArray};
+use arrow_buffer::{i256, BooleanBuffer, Buffer, NullBuffer, OffsetBuffer};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::{parquet_to_arrow_schema, ArrowSchemaConverter, ArrowWriter, ARROW_SCHEMA_META_KEY};
+use parquet::basic::{Repetition, Type as PhysicalType};
+use parquet::file::metadata::{KeyValue, RowGroupMetaDataPtr};
+use parquet::schema::types::{ColumnDescriptor, SchemaDescriptor, Type as ParquetType};
+
+use crate::appenders::{ColumnAppenderBase, FlatColumnValues, FlatValues};
+use crate::parquet_writer::RowAppender;
+
+/// Arrow schema used by `--engine arrow` to write the Parquet schema.
+/// Returns an error when the schema cannot be written exactly by the Arrow engine, i.e. it has nested columns
+/// or a column type would be changed by the conversion to Arrow and back (for example JSON or UUID columns).
+/// The field metadata is taken from the ARROW:schema entry of the `metadata`, if there is one
+pub fn arrow_schema(schema: &ParquetType, metadata: &[KeyValue]) -> Result<SchemaRef, String> {
+	for field in schema.get_fields() {
+		if !field.is_primitive() || field.get_basic_info().repetition() == Repetition::REPEATED {
+			return Err(format!("column {} is nested", field.name()));
+		}
+	}
+	let descriptor = SchemaDescriptor::new(Arc::new(schema.clone()));
+	let schema_hint: Vec<KeyValue> = metadata.iter().filter(|kv| kv.key == ARROW_SCHEMA_META_KEY).cloned().collect();
+	let arrow_schema = parquet_to_arrow_schema(&descriptor, Some(&schema_hint))
+		.map_err(|e| format!("the schema cannot be converted to Arrow: {}", e))?;
+	let roundtrip = ArrowSchemaConverter::new().schema_root(schema.name()).convert(&arrow_schema)
+		.map_err(|e| format!("the Arrow schema cannot be converted back to Parquet: {}", e))?;
+	for (i, field) in arrow_schema.fields().iter().enumerate() {
+		let column = descriptor.column(i);
+		if column.self_type() != roundtrip.column(i).self_type() {
+			return Err(format!("column {} would be written with a different type (Arrow {})", field.name(), field.data_type()));
+		}
+		check_conversion(&column, field)?;
+	}
+	Ok(Arc::new(arrow_schema))
+}
+
+/// Whether `take_batch` can convert the columns of the `schema` into the `arrow_schema`, i.e. the schema has no nested columns
+pub fn is_flat(schema: &ParquetType, arrow_schema: &Schema) -> bool {
+	let descriptor = SchemaDescriptor::new(Arc::new(schema.clone()));
+	schema.get_fields().iter().all(|f| f.is_primitive() && f.get_basic_info().repetition() != Repetition::REPEATED)
+		&& arrow_schema.fields().iter().enumerate().all(|(i, field)| check_conversion(&descriptor.column(i), field).is_ok())
+}
+
+/// Checks that `to_arrow_array` converts the values of the column into the Arrow type of the field
+fn check_conversion(column: &ColumnDescriptor, field: &Field) -> Result<(), String> {
+	let empty = FlatColumnValues { values: empty_values(column.physical_type())?, dls: Vec::new(), max_dl: column.max_def_level() };
+	to_arrow_array(empty, field.data_type()).map_err(|e| format!("column {}: {}", field.name(), e))?;
+	Ok(())
+}
+
+/// Creates the `ArrowWriter` for a schema returned by `arrow_schema`, the Parquet schema root keeps the name of `schema`
+pub fn create_writer<W: Write + Send>(output: W, schema: &ParquetType, arrow_schema: SchemaRef, props: parquet::file::properties::WriterProperties) -> Result<ArrowWriter<W>, String> {
+	let options = parquet::arrow::arrow_writer::ArrowWriterOptions::new()
+		.with_properties(props)
+		.with_schema_root(schema.name().to_string());
+	ArrowWriter::try_new_with_options(output, arrow_schema, options)
+		.map_err(|e| format!("Failed to create Arrow writer: {}", e))
+}
+
+/// Converts the values buffered in the appenders into a RecordBatch, the columns must not be nested, see `is_flat`
+pub fn take_batch(schema: &SchemaRef, appender: &mut RowAppender) -> Result<RecordBatch, String> {
+	let columns = appender.columns_mut().iter_mut().zip(schema.fields()).map(|(column, field)| {
+		let values = column.take_flat_values()
+			.ok_or_else(|| format!("Column {} is not supported by the Arrow engine", field.name()))?;
+		to_arrow_array(values, field.data_type()).map_err(|e| format!("Could not convert column {} to Arrow: {}", field.name(), e))
+	}).collect::<Result<Vec<_>, String>>()?;
+	RecordBatch::try_new(schema.clone(), columns).map_err(|e| format!("Could not create Arrow RecordBatch: {}", e))
+}
+
+/// Converts the values buffered in the appenders into a RecordBatch and writes it as one row group.
+/// Returns the compressed size of the row group
+pub fn write_group<W: Write + Send>(writer: &mut ArrowWriter<W>, schema: &SchemaRef, appender: &mut RowAppender) -> Result<Option<RowGroupMetaDataPtr>, String> {
+	let batch = take_batch(schema, appender)?;
+
+	let flushed = writer.flushed_row_groups().len();
+	writer.write(&batch).map_err(|e| format!("Error writing row group: {}", e))?;
+	writer.flush().map_err(|e| format!("Error closing row group: {}", e))?;
+	// nothing is written if the batch is empty
+	Ok(writer.flushed_row_groups().get(flushed).map(|rg| Arc::new(rg.clone())))
+}
+
+fn empty_values(physical_type: PhysicalType) -> Result<FlatValues, String> {
+	Ok(match physical_type {
+		PhysicalType::BOOLEAN => FlatValues::Boolean(Vec::new()),
+		PhysicalType::INT32 => FlatValues::Int32(Vec::new()),
+		PhysicalType::INT64 => FlatValues::Int64(Vec::new()),
+		PhysicalType::FLOAT => FlatValues::Float(Vec::new()),
+		PhysicalType::DOUBLE => FlatValues::Double(Vec::new()),
+		PhysicalType::BYTE_ARRAY => FlatValues::ByteArray(Vec::new()),
+		PhysicalType::FIXED_LEN_BYTE_ARRAY => FlatValues::FixedLenByteArray(Vec::new()),
+		PhysicalType::INT96 => return Err("INT96 is not supported".to_string()),
+	})
+}
+
+/// Places the non-null values at the positions of the valid rows, NULL rows get the default value
+fn spread<T: Default>(values: Vec<T>, nulls: &Option<NullBuffer>) -> Vec<T> {
+	match nulls {
+		Some(nulls) if nulls.null_count() > 0 => {
+			let mut values = values.into_iter();
+			nulls.iter().map(|valid| if valid { values.next().unwrap() } else { T::default() }).collect()
+		},
+		_ => values,
+	}
+}
+
+fn primitive<T: ArrowPrimitiveType>(values: Vec<T::Native>, nulls: Option<NullBuffer>) -> PrimitiveArray<T> {
+	PrimitiveArray::new(values.into(), nulls)
+}
+
+fn convert_primitive<TIn: Default, T: ArrowPrimitiveType>(values: Vec<TIn>, nulls: Option<NullBuffer>, f: impl Fn(TIn) -> T::Native) -> PrimitiveArray<T> {
+	primitive(spread(values, &nulls).into_iter().map(f).collect(), nulls)
+}
+
+/// Big-endian two's complement integer of at most 16 bytes
+fn decimal128_from_bytes(bytes: &[u8]) -> i128 {
+	let negative = bytes.first().is_some_and(|b| b & 0x80 != 0);
+	let mut buffer = if negative { [0xffu8; 16] } else { [0u8; 16] };
+	buffer[16 - bytes.len()..].copy_from_slice(bytes);
+	i128::from_be_bytes(buffer)
+}
+
+fn decimal256_from_bytes(bytes: &[u8]) -> i256 {
+	let negative = bytes.first().is_some_and(|b| b & 0x80 != 0);
+	let mut buffer = if negative { [0xffu8; 32] } else { [0u8; 32] };
+	buffer[32 - bytes.len()..].copy_from_slice(bytes);
+	i256::from_be_bytes(buffer)
+}
+
+/// Like `spread`, the data of NULL rows is empty. (The byte arrays themselves have no default value)
+fn spread_bytes<'a, T: AsRef<[u8]>>(values: &'a [T], nulls: &Option<NullBuffer>) -> Vec<&'a [u8]> {
+	spread(values.iter().map(|v| v.as_ref()).collect(), nulls)
+}
+
+/// Concatenated bytes and offsets of the values
+fn byte_buffers(values: &[&[u8]]) -> (OffsetBuffer<i32>, Buffer) {
+	let offsets = OffsetBuffer::from_lengths(values.iter().map(|v| v.len()));
+	let data: Vec<u8> = values.concat();
+	(offsets, Buffer::from(data))
+}
+
+fn to_arrow_array(column: FlatColumnValues, data_type: &DataType) -> Result<ArrayRef, String> {
+	let nulls = (column.max_dl > 0).then(|| NullBuffer::from(column.dls.iter().map(|&dl| dl == column.max_dl).collect::<Vec<bool>>()));
+	let unsupported = |physical_type: &str| format!("{} values cannot be converted to {}", physical_type, data_type);
+	let array: ArrayRef = match column.values {
+		FlatValues::Boolean(v) => match data_type {
+			DataType::Boolean => Arc::new(BooleanArray::new(BooleanBuffer::from(spread(v, &nulls)), nulls)),
+			_ => return Err(unsupported("BOOLEAN")),
+		},
+		FlatValues::Int32(v) => match data_type {
+			DataType::Int32 => Arc::new(convert_primitive::<_, Int32Type>(v, nulls, |x| x)),
+			DataType::Int16 => Arc::new(convert_primitive::<_, Int16Type>(v, nulls, |x| x as i16)),
+			DataType::Int8 => Arc::new(convert_primitive::<_, Int8Type>(v, nulls, |x| x as i8)),
+			DataType::UInt32 => Arc::new(convert_primitive::<_, UInt32Type>(v, nulls, |x| x as u32)),
+			DataType::UInt16 => Arc::new(convert_primitive::<_, UInt16Type>(v, nulls, |x| x as u16)),
+			DataType::UInt8 => Arc::new(convert_primitive::<_, UInt8Type>(v, nulls, |x| x as u8)),
+			DataType::Date32 => Arc::new(convert_primitive::<_, Date32Type>(v, nulls, |x| x)),
+			DataType::Decimal128(precision, scale) => Arc::new(convert_primitive::<_, Decimal128Type>(v, nulls, |x| x as i128)
+				.with_precision_and_scale(*precision, *scale).map_err(|e| e.to_string())?),
+			_ => return Err(unsupported("INT32")),
+		},
+		FlatValues::Int64(v) => match data_type {
+			DataType::Int64 => Arc::new(convert_primitive::<_, Int64Type>(v, nulls, |x| x)),
+			DataType::UInt64 => Arc::new(convert_primitive::<_, UInt64Type>(v, nulls, |x| x as u64)),
+			DataType::Time64(TimeUnit::Microsecond) => Arc::new(convert_primitive::<_, Time64MicrosecondType>(v, nulls, |x| x)),
+			DataType::Time64(TimeUnit::Nanosecond) => Arc::new(convert_primitive::<_, Time64NanosecondType>(v, nulls, |x| x)),
+			DataType::Timestamp(TimeUnit::Millisecond, tz) => Arc::new(convert_primitive::<_, TimestampMillisecondType>(v, nulls, |x| x).with_timezone_opt(tz.clone())),
+			DataType::Timestamp(TimeUnit::Microsecond, tz) => Arc::new(convert_primitive::<_, TimestampMicrosecondType>(v, nulls, |x| x).with_timezone_opt(tz.clone())),
+			DataType::Timestamp(TimeUnit::Nanosecond, tz) => Arc::new(convert_primitive::<_, TimestampNanosecondType>(v, nulls, |x| x).with_timezone_opt(tz.clone())),
+			DataType::Decimal128(precision, scale) => Arc::new(convert_primitive::<_, Decimal128Type>(v, nulls, |x| x as i128)
+				.with_precision_and_scale(*precision, *scale).map_err(|e| e.to_string())?),
+			_ => return Err(unsupported("INT64")),
+		},
+		FlatValues::Float(v) => match data_type {
+			DataType::Float32 => Arc::new(convert_primitive::<_, Float32Type>(v, nulls, |x| x)),
+			_ => return Err(unsupported("FLOAT")),
+		},
+		FlatValues::Double(v) => match data_type {
+			DataType::Float64 => Arc::new(convert_primitive::<_, Float64Type>(v, nulls, |x| x)),
+			_ => return Err(unsupported("DOUBLE")),
+		},
+		FlatValues::ByteArray(v) => {
+			let v = spread_bytes(&v, &nulls);
+			match data_type {
+				DataType::Utf8 => {
+					let (offsets, data) = byte_buffers(&v);
+					Arc::new(StringArray::try_new(offsets, data, nulls).map_err(|e| e.to_string())?)
+				},
+				DataType::Binary => {
+					let (offsets, data) = byte_buffers(&v);
+					Arc::new(BinaryArray::try_new(offsets, data, nulls).map_err(|e| e.to_string())?)
+				},
+				_ => return Err(unsupported("BYTE_ARRAY")),
+			}
+		},
+		FlatValues::FixedLenByteArray(v) => {
+			let v = spread_bytes(&v, &nulls);
+			match data_type {
+				DataType::FixedSizeBinary(size) => {
+					// NULL rows must also have the fixed size
+					let data: Vec<u8> = v.iter().flat_map(|x| if x.is_empty() { vec![0u8; *size as usize] } else { x.to_vec() }).collect();
+					Arc::new(FixedSizeBinaryArray::try_new(*size, Buffer::from(data), nulls).map_err(|e| e.to_string())?)
+				},
+				DataType::Float16 =>
+					Arc::new(primitive::<Float16Type>(v.iter().map(|x| match x {
+						[a, b] => half::f16::from_le_bytes([*a, *b]),
+						_ => half::f16::ZERO,
+					}).collect(), nulls)),
+				DataType::Decimal128(precision, scale) if v.iter().all(|x| x.len() <= 16) =>
+					Arc::new(primitive::<Decimal128Type>(v.iter().map(|x| decimal128_from_bytes(x)).collect(), nulls)
+						.with_precision_and_scale(*precision, *scale).map_err(|e| e.to_string())?),
+				DataType::Decimal256(precision, scale) if v.iter().all(|x| x.len() <= 32) =>
+					Arc::new(primitive::<Decimal256Type>(v.iter().map(|x| decimal256_from_bytes(x)).collect(), nulls)
+						.with_precision_and_scale(*precision, *scale).map_err(|e| e.to_string())?),
+				_ => return Err(unsupported("FIXED_LEN_BYTE_ARRAY")),
+			}
+		},
+	};
+	Ok(array)
+}
@@ -0,0 +1,86 @@
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+
+use arrow_array::RecordBatch;
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::SchemaRef;
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::{ArrowReaderOptions, ParquetRecordBatchReaderBuilder};
+use parquet::arrow::parquet_to_arrow_schema;
+use parquet::basic::Compression;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{WriterProperties, WriterPropertiesPtr};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::{SchemaDescriptor, TypePtr};
+
+use crate::arrow_engine;
+use crate::parquet_writer::{self, RowAppender};
+
+/// Writes the row groups into an Arrow IPC file (also known as Feather v2), one record batch per row group.
+/// The Arrow schema is converted from the Parquet schema, so the type mapping and all options are the same as for Parquet export
+pub struct IpcWriter<W: Write> {
+	writer: FileWriter<BufWriter<W>>,
+	schema: SchemaRef,
+	/// Parquet schema and properties of the in-memory row group which the nested columns are encoded into and read back by the arrow-rs reader.
+	/// None if the columns are flat, they are converted the same way as by the Arrow engine
+	nested: Option<(TypePtr, WriterPropertiesPtr)>,
+}
+
+impl<W: Write> IpcWriter<W> {
+	/// The `metadata` is written into the Arrow schema, its ARROW:schema entry determines the Arrow types of the columns
+	pub fn new(output: W, schema: TypePtr, metadata: &[KeyValue]) -> Result<Self, String> {
+		let descriptor = SchemaDescriptor::new(schema.clone());
+		let arrow_schema = parquet_to_arrow_schema(&descriptor, Some(&metadata.to_vec()))
+			.map_err(|e| format!("The schema cannot be converted to Arrow: {}", e))?;
+		let arrow_schema = Arc::new(arrow_schema);
+		let writer = FileWriter::try_new_buffered(output, &arrow_schema)
+			.map_err(|e| format!("Failed to create Arrow IPC writer: {}", e))?;
+		// the in-memory row group is read right away, compressing it would be wasted work
+		let nested = (!arrow_engine::is_flat(&schema, &arrow_schema))
+			.then(|| (schema, Arc::new(WriterProperties::builder().set_compression(Compression::UNCOMPRESSED).set_dictionary_enabled(false).build())));
+		Ok(IpcWriter { writer, schema: arrow_schema, nested })
+	}
+
+	/// Metadata added after the schema was written (such as the watermark) is stored in the file footer
+	pub fn append_key_value_metadata(&mut self, kv: KeyValue) {
+		if self.schema.metadata.get(&kv.key) != kv.value.as_ref() {
+			self.writer.write_metadata(kv.key, kv.value.unwrap_or_default());
+		}
+	}
+
+	/// Converts the values buffered in the appenders into record batches and writes them
+	pub fn write_group(&mut self, appender: &mut RowAppender) -> Result<(), String> {
+		let batches = match &self.nested {
+			None => vec![arrow_engine::take_batch(&self.schema, appender)?],
+			Some((schema, props)) => self.read_nested(schema.clone(), props.clone(), appender)?,
+		};
+		for batch in batches.iter().filter(|b| b.num_rows() > 0) {
+			self.writer.write(batch).map_err(|e| format!("Error writing record batch: {}", e))?;
+		}
+		Ok(())
+	}
+
+	fn read_nested(&self, schema: TypePtr, mut props: WriterPropertiesPtr, appender: &mut RowAppender) -> Result<Vec<RecordBatch>, String> {
+		let mut writer = SerializedFileWriter::new(Vec::new(), schema, props.clone())
+			.map_err(|e| format!("Failed to create parquet writer: {}", e))?;
+		let row_group = parquet_writer::write_group(&mut writer, &mut props, false, &mut false, appender)?;
+		let buffer = writer.into_inner().map_err(|e| format!("Error closing row group: {}", e))?;
+
+		let options = ArrowReaderOptions::new().with_schema(self.schema.clone());
+		let reader = ParquetRecordBatchReaderBuilder::try_new_with_options(Bytes::from(buffer), options)
+			.and_then(|builder| builder.with_batch_size(row_group.num_rows().max(1) as usize).build())
+			.map_err(|e| format!("Could not convert the row group to Arrow: {}", e))?;
+		reader
+			.map(|batch| {
+				let batch = batch.map_err(|e| format!("Could not convert the row group to Arrow: {}", e))?;
+				// the batch has the schema without the metadata
+				RecordBatch::try_new(self.schema.clone(), batch.columns().to_vec()).map_err(|e| format!("Could not create Arrow RecordBatch: {}", e))
+			})
+			.collect()
+	}
+
+	pub fn close(mut self) -> Result<(), String> {
+		self.writer.finish().map_err(|e| format!("Failed to write the Arrow IPC footer: {}", e))?;
+		self.writer.into_inner().and_then(|mut w| w.flush().map_err(Into::into)).map_err(|e| format!("Failed to write the Arrow IPC file: {}", e))
+	}
+}
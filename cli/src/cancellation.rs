@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::ThreadId;
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+static CANCELLED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+static FINALIZE: AtomicBool = AtomicBool::new(false);
+/// Cancels the query running on the connection last opened by each thread, otherwise closing the connection waits until the query finishes.
+/// The parallel exports of --jobs each read from their own connection in their own thread
+static CANCEL_QUERY: Mutex<Option<HashMap<ThreadId, QueryCanceller>>> = Mutex::new(None);
+
+type QueryCanceller = Box<dyn FnOnce() + Send>;
+
+/// Registers the canceller of the connection opened by the current thread, replacing the one of its previous connection
+pub fn set_query_canceller(cancel: impl FnOnce() + Send + 'static) {
+	CANCEL_QUERY.lock().unwrap().get_or_insert_with(HashMap::new).insert(std::thread::current().id(), Box::new(cancel));
+}
+
+fn cancel_queries() {
+	let cancellers = CANCEL_QUERY.lock().unwrap().take().unwrap_or_default();
+	for cancel in cancellers.into_values() {
+		cancel();
+	}
+}
+
+/// Installs SIGINT and SIGTERM handler which makes the export stop after the current row.
+/// The running queries are cancelled, so that the export does not wait for the next row of a slow query.
+/// With `finalize`, the exporters close the output file as if all rows were written, otherwise they fail.
+/// Second signal terminates the process immediately
+pub fn install_handler(finalize: bool) -> Result<(), String> {
+	FINALIZE.store(finalize, Ordering::Relaxed);
+	let flag = CANCELLED.get_or_init(|| Arc::new(AtomicBool::new(false)));
+	for signal in [SIGINT, SIGTERM] {
+		signal_hook::flag::register_conditional_shutdown(signal, 130, flag.clone())
+			.and_then(|_| signal_hook::flag::register(signal, flag.clone()))
+			.map_err(|e| format!("Failed to register signal handler: {}", e))?;
+	}
+	// cancelling connects to the server, which cannot be done in the signal handler itself
+	let mut signals = Signals::new([SIGINT, SIGTERM]).map_err(|e| format!("Failed to register signal handler: {}", e))?;
+	std::thread::spawn(move || {
+		for _ in signals.forever() {
+			cancel_queries();
+		}
+	});
+	Ok(())
+}
+
+pub fn was_cancelled() -> bool {
+	CANCELLED.get().is_some_and(|f| f.load(Ordering::Relaxed))
+}
+
+/// Returns an error if the export was cancelled and should be aborted. With `finalize`, the export instead continues with the rows
+/// which the server sent before the query was cancelled, and stops using `should_stop` when reading the next one fails
+pub fn check_aborted() -> Result<(), String> {
+	if was_cancelled() && !FINALIZE.load(Ordering::Relaxed) {
+		return Err("The export was cancelled".to_string());
+	}
+	Ok(())
+}
+
+/// Returns true if the export should stop writing and close the output file, or an error if it should be aborted.
+/// Also used when reading the query result fails, which is expected after the query was cancelled
+pub fn should_stop() -> Result<bool, String> {
+	if !was_cancelled() {
+		return Ok(false);
+	}
+	cancel_queries();
+	if FINALIZE.load(Ordering::Relaxed) {
+		Ok(true)
+	} else {
+		Err("The export was cancelled".to_string())
+	}
+}
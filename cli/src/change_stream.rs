@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, Int64Type};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{WriterPropertiesBuilder, WriterPropertiesPtr};
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::schema::types::Type as ParquetType;
+use postgres::{Client, Row};
+
+use crate::cancellation;
+use crate::output_template::{OutputTemplate, TemplateValue};
+use crate::parquet_writer::WriterStats;
+use crate::postgres_cloner::pg_connect;
+use crate::postgresutils::{format_pg_error, quote_identifier};
+use crate::PostgresConnArgs;
+
+/// Logical decoding output plugin of the replication slot
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputPlugin {
+	/// The built-in plugin used by logical replication, changes of the tables in the --publication are exported
+	Pgoutput,
+	/// The wal2json extension, used with format-version 2
+	Wal2json,
+}
+
+impl OutputPlugin {
+	fn name(&self) -> &'static str {
+		match self {
+			OutputPlugin::Pgoutput => "pgoutput",
+			OutputPlugin::Wal2json => "wal2json",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamSettings {
+	pub slot: String,
+	pub plugin: OutputPlugin,
+	/// Publications consumed by pgoutput
+	pub publications: Vec<String>,
+	/// Create the slot if it does not exist
+	pub create_slot: bool,
+	/// Path of the written files, see `--output-template`
+	pub output_template: OutputTemplate,
+	/// A new file is started when the current one has at least this many changes
+	pub rows_per_file: usize,
+	/// A new file is started when the first change of the current one is older than this
+	pub file_interval: Duration,
+	/// Delay between the polls when there are no new changes
+	pub poll_interval: Duration,
+	/// Stop when all changes are exported, instead of waiting for new ones
+	pub exit_when_idle: bool,
+	pub quiet: bool,
+}
+
+/// Maximum number of messages decoded by one poll, complete transactions are always returned
+const POLL_BATCH: usize = 10_000;
+/// Microseconds between the Unix epoch and the PostgreSQL epoch (2000-01-01)
+const PG_EPOCH_OFFSET_MICROS: i64 = 946_684_800_000_000;
+
+/// One row of the output files
+struct ChangeRecord {
+	lsn: String,
+	xid: i64,
+	/// Microseconds since the Unix epoch
+	commit_time: Option<i64>,
+	op: &'static str,
+	schema: String,
+	table: String,
+	/// JSON object with the old values. Only the replica identity columns are included, unless the table has REPLICA IDENTITY FULL
+	before: Option<String>,
+	/// JSON object with the new values. pgoutput leaves out unchanged TOASTed values
+	after: Option<String>,
+}
+
+/// Columns of a table, as described by the pgoutput Relation message
+struct Relation {
+	schema: String,
+	table: String,
+	columns: Vec<String>,
+	/// Which columns are part of the replica identity
+	key_columns: Vec<bool>,
+}
+
+/// Converts the messages of the output plugin into change records. Relations and commit timestamps are tracked
+/// from the preceding messages, so all messages returned by a poll must be decoded, even those which were already exported
+struct Decoder {
+	plugin: OutputPlugin,
+	relations: HashMap<u32, Relation>,
+	commit_time: Option<i64>,
+	/// The last decoded message was a commit, so a file can end here
+	transaction_end: bool,
+}
+
+impl Decoder {
+	fn new(plugin: OutputPlugin) -> Self {
+		Decoder { plugin, relations: HashMap::new(), commit_time: None, transaction_end: false }
+	}
+
+	fn decode(&mut self, lsn: &str, xid: i64, data: &[u8]) -> Result<Vec<ChangeRecord>, String> {
+		self.transaction_end = false;
+		match self.plugin {
+			OutputPlugin::Pgoutput => self.decode_pgoutput(lsn, xid, data).map_err(|e| format!("Invalid pgoutput message at {}: {}", lsn, e)),
+			OutputPlugin::Wal2json => self.decode_wal2json(lsn, xid, data).map_err(|e| format!("Invalid wal2json message at {}: {}", lsn, e)),
+		}
+	}
+
+	fn decode_pgoutput(&mut self, lsn: &str, xid: i64, mut data: &[u8]) -> io::Result<Vec<ChangeRecord>> {
+		let (op, relation_id, before, after) = match data.read_u8()? {
+			b'B' => {
+				let _final_lsn = data.read_u64::<BigEndian>()?;
+				self.commit_time = Some(data.read_i64::<BigEndian>()? + PG_EPOCH_OFFSET_MICROS);
+				return Ok(vec![]);
+			},
+			b'R' => {
+				let id = data.read_u32::<BigEndian>()?;
+				let schema = read_cstring(&mut data)?;
+				let table = read_cstring(&mut data)?;
+				let _replica_identity = data.read_u8()?;
+				let column_count = data.read_i16::<BigEndian>()?;
+				let mut columns = Vec::new();
+				let mut key_columns = Vec::new();
+				for _ in 0..column_count {
+					key_columns.push(data.read_u8()? & 1 != 0);
+					columns.push(read_cstring(&mut data)?);
+					let _type_oid = data.read_u32::<BigEndian>()?;
+					let _type_modifier = data.read_i32::<BigEndian>()?;
+				}
+				self.relations.insert(id, Relation { schema, table, columns, key_columns });
+				return Ok(vec![]);
+			},
+			b'I' => {
+				let id = data.read_u32::<BigEndian>()?;
+				expect_tag(&mut data, b"N")?;
+				("insert", id, None, Some(self.read_tuple(id, &mut data, false)?))
+			},
+			b'U' => {
+				let id = data.read_u32::<BigEndian>()?;
+				let mut tag = data.read_u8()?;
+				// the old tuple is only sent when the replica identity changed or the table has REPLICA IDENTITY FULL
+				let before = if tag == b'K' || tag == b'O' {
+					let before = self.read_tuple(id, &mut data, tag == b'K')?;
+					tag = data.read_u8()?;
+					Some(before)
+				} else {
+					None
+				};
+				if tag != b'N' {
+					return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected tuple type {:?}", tag as char)));
+				}
+				("update", id, before, Some(self.read_tuple(id, &mut data, false)?))
+			},
+			b'D' => {
+				let id = data.read_u32::<BigEndian>()?;
+				let tag = expect_tag(&mut data, b"KO")?;
+				("delete", id, Some(self.read_tuple(id, &mut data, tag == b'K')?), None)
+			},
+			b'C' => {
+				self.transaction_end = true;
+				return Ok(vec![]);
+			},
+			b'T' => {
+				let relation_count = data.read_u32::<BigEndian>()?;
+				let _options = data.read_u8()?;
+				let mut records = Vec::new();
+				for _ in 0..relation_count {
+					let id = data.read_u32::<BigEndian>()?;
+					records.push(self.record(lsn, xid, "truncate", id, None, None)?);
+				}
+				return Ok(records);
+			},
+			// origin, type and logical messages do not contain any changes
+			_ => return Ok(vec![]),
+		};
+		Ok(vec![self.record(lsn, xid, op, relation_id, before, after)?])
+	}
+
+	fn record(&self, lsn: &str, xid: i64, op: &'static str, relation_id: u32, before: Option<String>, after: Option<String>) -> io::Result<ChangeRecord> {
+		let relation = self.relation(relation_id)?;
+		Ok(ChangeRecord { lsn: lsn.to_string(), xid, commit_time: self.commit_time, op, schema: relation.schema.clone(), table: relation.table.clone(), before, after })
+	}
+
+	fn relation(&self, id: u32) -> io::Result<&Relation> {
+		self.relations.get(&id).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("unknown relation {}", id)))
+	}
+
+	/// Reads TupleData into a JSON object with the text representation of the values. Key tuples contain NULLs instead of the columns outside of the replica identity, these are left out
+	fn read_tuple(&self, relation_id: u32, data: &mut &[u8], key_only: bool) -> io::Result<String> {
+		let relation = self.relation(relation_id)?;
+		let column_count = data.read_i16::<BigEndian>()?;
+		let mut values = serde_json::Map::new();
+		for i in 0..column_count as usize {
+			let name = relation.columns.get(i).cloned().unwrap_or_else(|| format!("column{}", i + 1));
+			match data.read_u8()? {
+				b'n' if key_only && !relation.key_columns.get(i).copied().unwrap_or(true) => {},
+				b'n' => { values.insert(name, serde_json::Value::Null); },
+				// unchanged TOASTed value, it is not sent by PostgreSQL
+				b'u' => {},
+				b't' => {
+					let len = data.read_i32::<BigEndian>()?;
+					let mut value = vec![0; len.max(0) as usize];
+					data.read_exact(&mut value)?;
+					values.insert(name, String::from_utf8_lossy(&value).into_owned().into());
+				},
+				kind => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported value kind {:?}", kind as char))),
+			}
+		}
+		Ok(serde_json::Value::Object(values).to_string())
+	}
+
+	fn decode_wal2json(&mut self, lsn: &str, xid: i64, data: &[u8]) -> Result<Vec<ChangeRecord>, String> {
+		let message: serde_json::Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+		let op = match message["action"].as_str() {
+			Some("B") => {
+				self.commit_time = message["timestamp"].as_str()
+					.and_then(|t| chrono::DateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S%.f%#z").ok())
+					.map(|t| t.timestamp_micros());
+				return Ok(vec![]);
+			},
+			Some("I") => "insert",
+			Some("U") => "update",
+			Some("D") => "delete",
+			Some("T") => "truncate",
+			Some("C") => {
+				self.transaction_end = true;
+				return Ok(vec![]);
+			},
+			// logical messages
+			_ => return Ok(vec![]),
+		};
+		let values = |key: &str| message[key].as_array().map(|columns| {
+			let values: serde_json::Map<String, serde_json::Value> = columns.iter()
+				.map(|c| (c["name"].as_str().unwrap_or_default().to_string(), c["value"].clone()))
+				.collect();
+			serde_json::Value::Object(values).to_string()
+		});
+		Ok(vec![ChangeRecord {
+			lsn: lsn.to_string(),
+			xid,
+			commit_time: self.commit_time,
+			op,
+			schema: message["schema"].as_str().unwrap_or_default().to_string(),
+			table: message["table"].as_str().unwrap_or_default().to_string(),
+			before: values("identity"),
+			after: values("columns"),
+		}])
+	}
+}
+
+fn read_cstring(data: &mut &[u8]) -> io::Result<String> {
+	let mut bytes = Vec::new();
+	data.read_until(0, &mut bytes)?;
+	if bytes.pop() != Some(0) {
+		return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "unterminated string"));
+	}
+	Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn expect_tag(data: &mut &[u8], expected: &[u8]) -> io::Result<u8> {
+	let tag = data.read_u8()?;
+	if !expected.contains(&tag) {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected tuple type {:?}", tag as char)));
+	}
+	Ok(tag)
+}
+
+/// Parses the text representation of pg_lsn (for example `16/B374D848`)
+fn parse_lsn(lsn: &str) -> Result<u64, String> {
+	let (high, low) = lsn.split_once('/').ok_or_else(|| format!("Invalid LSN {}", lsn))?;
+	let high = u64::from_str_radix(high, 16).map_err(|_| format!("Invalid LSN {}", lsn))?;
+	let low = u64::from_str_radix(low, 16).map_err(|_| format!("Invalid LSN {}", lsn))?;
+	Ok(high << 32 | low)
+}
+
+fn check_slot(client: &mut Client, settings: &StreamSettings) -> Result<(), String> {
+	let slot = client.query_opt("SELECT plugin::text FROM pg_replication_slots WHERE slot_name = $1", &[&settings.slot])
+		.map_err(|e| format!("Failed to query replication slots: {}", format_pg_error(&e)))?;
+	match slot {
+		Some(slot) if slot.get::<_, String>(0) != settings.plugin.name() =>
+			Err(format!("Replication slot {} uses the {} plugin, not {}", settings.slot, slot.get::<_, String>(0), settings.plugin.name())),
+		Some(_) => Ok(()),
+		None if settings.create_slot => {
+			client.execute("SELECT pg_create_logical_replication_slot($1, $2)", &[&settings.slot, &settings.plugin.name()])
+				.map_err(|e| format!("Failed to create replication slot {}: {}", settings.slot, format_pg_error(&e)))?;
+			if !settings.quiet {
+				eprintln!("Created replication slot {}", settings.slot);
+			}
+			Ok(())
+		},
+		None => Err(format!("Replication slot {} does not exist, use --create-slot to create it", settings.slot)),
+	}
+}
+
+/// Reads the messages from the slot without consuming them, the slot is only advanced after the changes are written into a file
+fn peek_changes(client: &mut Client, settings: &StreamSettings, limit: usize) -> Result<Vec<Row>, String> {
+	let limit = limit as i32;
+	let result = match settings.plugin {
+		OutputPlugin::Pgoutput => {
+			let publications = settings.publications.iter().map(|p| quote_identifier(p)).collect::<Vec<_>>().join(",");
+			client.query("SELECT lsn::text, xid::text::bigint, data FROM pg_logical_slot_peek_binary_changes($1, NULL, $2, 'proto_version', '1', 'publication_names', $3)", &[&settings.slot, &limit, &publications])
+		},
+		OutputPlugin::Wal2json =>
+			client.query("SELECT lsn::text, xid::text::bigint, convert_to(data, 'UTF8') FROM pg_logical_slot_peek_changes($1, NULL, $2, 'format-version', '2', 'include-timestamp', '1')", &[&settings.slot, &limit]),
+	};
+	result.map_err(|e| format!("Failed to read changes from replication slot {}: {}", settings.slot, format_pg_error(&e)))
+}
+
+fn advance_slot(client: &mut Client, slot: &str, lsn: &str) -> Result<(), String> {
+	client.execute("SELECT pg_replication_slot_advance($1, $2::text::pg_lsn)", &[&slot, &lsn])
+		.map_err(|e| format!("Failed to advance replication slot {}: {}", slot, format_pg_error(&e)))?;
+	Ok(())
+}
+
+fn change_schema() -> Arc<ParquetType> {
+	let string = |name: &str, repetition: Repetition| Arc::new(ParquetType::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+		.with_repetition(repetition)
+		.with_logical_type(Some(LogicalType::String))
+		.build().unwrap());
+	let fields = vec![
+		string("lsn", Repetition::REQUIRED),
+		Arc::new(ParquetType::primitive_type_builder("xid", PhysicalType::INT64).with_repetition(Repetition::REQUIRED).build().unwrap()),
+		Arc::new(ParquetType::primitive_type_builder("commit_time", PhysicalType::INT64)
+			.with_repetition(Repetition::OPTIONAL)
+			.with_logical_type(Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: parquet::format::TimeUnit::MICROS(parquet::format::MicroSeconds {  }) }))
+			.build().unwrap()),
+		string("op", Repetition::REQUIRED),
+		string("schema", Repetition::REQUIRED),
+		string("table", Repetition::REQUIRED),
+		string("before", Repetition::OPTIONAL),
+		string("after", Repetition::OPTIONAL),
+	];
+	Arc::new(ParquetType::group_type_builder("root").with_fields(fields).build().unwrap())
+}
+
+fn write_column<T: DataType>(row_group: &mut SerializedRowGroupWriter<File>, values: Vec<Option<T::T>>, optional: bool) -> Result<(), parquet::errors::ParquetError> {
+	let mut column = row_group.next_column()?.unwrap();
+	let def_levels: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+	let values: Vec<T::T> = values.into_iter().flatten().collect();
+	column.typed::<T>().write_batch(&values, optional.then_some(&def_levels[..]), None)?;
+	column.close()
+}
+
+/// Writes the records into a single row group, the file is renamed into its final path when complete
+fn write_changes(path: &Path, records: &[ChangeRecord], props: &WriterPropertiesPtr, metadata: Vec<KeyValue>) -> Result<usize, String> {
+	let string = |s: &str| Some(ByteArray::from(s));
+	let json = |s: &Option<String>| s.as_deref().map(ByteArray::from);
+	let mut temp_file = path.as_os_str().to_owned();
+	temp_file.push(".tmp");
+	let temp_file = PathBuf::from(temp_file);
+
+	let write = || -> Result<(), parquet::errors::ParquetError> {
+		let file = File::create(&temp_file)?;
+		let mut writer = SerializedFileWriter::new(file, change_schema(), props.clone())?;
+		for kv in metadata.iter().cloned() {
+			writer.append_key_value_metadata(kv);
+		}
+		let mut row_group = writer.next_row_group()?;
+		write_column::<ByteArrayType>(&mut row_group, records.iter().map(|r| string(&r.lsn)).collect(), false)?;
+		write_column::<Int64Type>(&mut row_group, records.iter().map(|r| Some(r.xid)).collect(), false)?;
+		write_column::<Int64Type>(&mut row_group, records.iter().map(|r| r.commit_time).collect(), true)?;
+		write_column::<ByteArrayType>(&mut row_group, records.iter().map(|r| string(r.op)).collect(), false)?;
+		write_column::<ByteArrayType>(&mut row_group, records.iter().map(|r| string(&r.schema)).collect(), false)?;
+		write_column::<ByteArrayType>(&mut row_group, records.iter().map(|r| string(&r.table)).collect(), false)?;
+		write_column::<ByteArrayType>(&mut row_group, records.iter().map(|r| json(&r.before)).collect(), true)?;
+		write_column::<ByteArrayType>(&mut row_group, records.iter().map(|r| json(&r.after)).collect(), true)?;
+		row_group.close()?;
+		writer.close()?;
+		Ok(())
+	};
+	write().map_err(|e| format!("Failed to write {:?}: {}", temp_file, e))?;
+	// the slot is advanced after the file is written, so it must be on the disk before that
+	File::open(&temp_file).and_then(|f| f.sync_all()).map_err(|e| format!("Failed to sync {:?}: {}", temp_file, e))?;
+	std::fs::rename(&temp_file, path).map_err(|e| format!("Failed to rename {:?} to {:?}: {}", temp_file, path, e))?;
+	sync_parent_dir(path)?;
+	Ok(std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0))
+}
+
+/// Makes the rename of a file durable. Directories cannot be opened on Windows, where the rename is durable without it
+fn sync_parent_dir(path: &Path) -> Result<(), String> {
+	if cfg!(windows) {
+		return Ok(());
+	}
+	let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+	File::open(dir).and_then(|d| d.sync_all()).map_err(|e| format!("Failed to sync {:?}: {}", dir, e))
+}
+
+/// Current end of the WAL, the slot cannot have any new changes if it did not move since the last poll
+fn wal_position(client: &mut Client) -> Result<String, String> {
+	let row = client.query_one("SELECT (CASE WHEN pg_is_in_recovery() THEN pg_last_wal_replay_lsn() ELSE pg_current_wal_insert_lsn() END)::text", &[])
+		.map_err(|e| format!("Failed to query the WAL position: {}", format_pg_error(&e)))?;
+	Ok(row.get::<_, Option<String>>(0).unwrap_or_default())
+}
+
+/// Continuously exports the changes decoded by a logical replication slot into Parquet files named by `settings.output_template`.
+/// Each file contains complete transactions and is named by the slot and the LSN the slot was advanced to after writing it.
+/// When pg2parquet is stopped, the changes not yet written into a file are exported again on the next start
+pub fn stream_changes(pg_args: &PostgresConnArgs, settings: &StreamSettings, props: WriterPropertiesBuilder) -> Result<WriterStats, String> {
+	if settings.plugin == OutputPlugin::Pgoutput && settings.publications.is_empty() {
+		return Err("--publication must be specified with the pgoutput plugin".to_string());
+	}
+	let props = Arc::new(props.build());
+	let mut client = pg_connect(pg_args)?;
+	check_slot(&mut client, settings)?;
+
+	let mut stats = WriterStats::default();
+	let mut records = Vec::new();
+	let mut file_started = Instant::now();
+	// peeking always starts at the slot position, which is advanced after each written file.
+	// The messages decoded since then are skipped, they are only peeked again when there are new ones
+	let mut consumed = 0;
+	let mut last_lsn: Option<String> = None;
+	let mut idle_wal_position = None;
+	loop {
+		let stop = cancellation::was_cancelled();
+		let wal_position = if stop { None } else { Some(wal_position(&mut client)?) };
+		let unchanged = idle_wal_position.is_some() && idle_wal_position == wal_position;
+		let messages = if stop || unchanged { Vec::new() } else { peek_changes(&mut client, settings, consumed + POLL_BATCH)? };
+		let idle = unchanged || messages.len() <= consumed;
+		idle_wal_position = wal_position.filter(|_| idle);
+		let mut decoder = Decoder::new(settings.plugin);
+		let mut advanced_at = 0;
+		for (i, message) in messages.iter().enumerate() {
+			let lsn: &str = message.get(0);
+			let changes = decoder.decode(lsn, message.get(1), message.get(2))?;
+			if i < consumed {
+				continue;
+			}
+			if records.is_empty() && !changes.is_empty() {
+				file_started = Instant::now();
+			}
+			records.extend(changes);
+			last_lsn = Some(lsn.to_string());
+			if decoder.transaction_end && records.len() >= settings.rows_per_file {
+				write_file(&mut client, settings, &props, &mut records, lsn, &mut stats)?;
+				advanced_at = i + 1;
+			}
+		}
+		consumed = messages.len().max(consumed) - advanced_at;
+
+		let finish = stop || (idle && settings.exit_when_idle);
+		if let Some(lsn) = last_lsn.as_deref().filter(|_| consumed > 0) {
+			if records.is_empty() {
+				// transactions without any exported changes (e.g. of tables outside of the publication)
+				advance_slot(&mut client, &settings.slot, lsn)?;
+				consumed = 0;
+			} else if finish || file_started.elapsed() >= settings.file_interval {
+				write_file(&mut client, settings, &props, &mut records, lsn, &mut stats)?;
+				consumed = 0;
+			}
+		}
+
+		if finish {
+			break;
+		}
+		if idle {
+			let wait_start = Instant::now();
+			while wait_start.elapsed() < settings.poll_interval && !cancellation::was_cancelled() {
+				std::thread::sleep(settings.poll_interval.min(Duration::from_millis(100)));
+			}
+		}
+	}
+	Ok(stats)
+}
+
+/// Writes the buffered changes into a file and advances the slot to the end of the last written transaction
+fn write_file(client: &mut Client, settings: &StreamSettings, props: &WriterPropertiesPtr, records: &mut Vec<ChangeRecord>, lsn: &str, stats: &mut WriterStats) -> Result<(), String> {
+	let path = settings.output_template.render(&[
+		("slot", TemplateValue::Text(&settings.slot)),
+		("lsn", TemplateValue::Text(&format!("{:016X}", parse_lsn(lsn)?))),
+		("date", TemplateValue::Text(&chrono::Utc::now().format("%Y-%m-%d").to_string())),
+		("seq", TemplateValue::Number(stats.groups as u64)),
+	]);
+	if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+		std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+	}
+	let metadata = vec![
+		KeyValue::new("pg2parquet.replication_slot".to_string(), settings.slot.clone()),
+		KeyValue::new("pg2parquet.end_lsn".to_string(), lsn.to_string()),
+	];
+	stats.bytes_out += write_changes(&path, records, props, metadata)?;
+	stats.rows += records.len();
+	stats.groups += 1;
+	if !settings.quiet {
+		eprintln!("Wrote {} changes to {:?}", records.len(), path);
+	}
+	records.clear();
+	advance_slot(client, &settings.slot, lsn)
+}
@@ -0,0 +1,62 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+/// Digest of the output file, computed while it is being written. The handle is shared with the `ChecksumWriter`
+#[derive(Clone, Default)]
+pub struct Checksum {
+	hasher: Arc<Mutex<Sha256>>,
+}
+
+impl Checksum {
+	/// Wraps the file into a writer which updates this checksum. Previously hashed data is discarded, since the file is written from the beginning
+	pub fn wrap<W: Write>(&self, inner: W) -> ChecksumWriter<W> {
+		*self.hasher.lock().unwrap() = Sha256::new();
+		ChecksumWriter { inner, hasher: Some(self.hasher.clone()) }
+	}
+
+	pub fn hex(&self) -> String {
+		format!("{:x}", self.hasher.lock().unwrap().clone().finalize())
+	}
+
+	/// Writes the `file.sha256` sidecar file, in the format of `sha256sum` utility
+	pub fn write_sidecar(&self, output_file: &Path) -> Result<PathBuf, String> {
+		let mut sidecar = output_file.as_os_str().to_owned();
+		sidecar.push(".sha256");
+		let sidecar = PathBuf::from(sidecar);
+		let file_name = output_file.file_name().unwrap_or_default().to_string_lossy();
+		std::fs::write(&sidecar, format!("{}  {}\n", self.hex(), file_name)).map_err(|e| format!("Failed to write {:?}: {}", sidecar, e))?;
+		Ok(sidecar)
+	}
+}
+
+/// Passes the data to the inner writer, and to the checksum if there is one
+pub struct ChecksumWriter<W: Write> {
+	inner: W,
+	hasher: Option<Arc<Mutex<Sha256>>>,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+	pub fn new(inner: W, checksum: Option<&Checksum>) -> Self {
+		match checksum {
+			Some(c) => c.wrap(inner),
+			None => ChecksumWriter { inner, hasher: None },
+		}
+	}
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		let written = self.inner.write(buf)?;
+		if let Some(hasher) = &self.hasher {
+			hasher.lock().unwrap().update(&buf[..written]);
+		}
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.inner.flush()
+	}
+}
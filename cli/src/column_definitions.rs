@@ -0,0 +1,167 @@
+//! Explicit column definition list of a query result, given by `--columns 'a int, b text'`
+use postgres::error::{ErrorPosition, SqlState};
+use postgres::Client;
+
+use crate::postgresutils::format_pg_error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefinitions {
+	/// Column name (as written, possibly quoted) and its type
+	columns: Vec<(String, String)>,
+}
+
+impl ColumnDefinitions {
+	/// Parses a comma separated list of `name type` pairs, the types may contain commas in parentheses (`numeric(10, 2)`)
+	pub fn parse(definitions: &str) -> Result<ColumnDefinitions, String> {
+		let mut columns = Vec::new();
+		for definition in split_top_level(definitions) {
+			let definition = definition.trim();
+			let name_end = if let Some(quoted) = definition.strip_prefix('"') {
+				match quoted.find('"') {
+					Some(end) => end + 2,
+					None => return Err(format!("Unclosed quoted column name in '{}'", definition)),
+				}
+			} else {
+				definition.find(char::is_whitespace).unwrap_or(definition.len())
+			};
+			let (name, data_type) = definition.split_at(name_end);
+			if name.is_empty() || data_type.trim().is_empty() {
+				return Err(format!("Invalid column definition '{}', expected a column name and type, for example 'id int'", definition));
+			}
+			columns.push((name.to_string(), data_type.trim().to_string()));
+		}
+		Ok(ColumnDefinitions { columns })
+	}
+
+	fn definition_list(&self) -> String {
+		self.columns.iter().map(|(name, data_type)| format!("{} {}", name, data_type)).collect::<Vec<_>>().join(", ")
+	}
+
+	/// Rewrites the query so that its result has the defined columns. If PostgreSQL requires a column definition list
+	/// for a function returning `record` (`SELECT * FROM my_report_fn(...)`), the list is added after the function call.
+	/// Otherwise, the result columns are renamed and cast to the defined types
+	pub fn apply(&self, client: &mut Client, query: &str) -> Result<String, String> {
+		let error = match client.prepare(query) {
+			Ok(statement) if statement.columns().len() < self.columns.len() =>
+				return Err(format!("The query returns {} columns, but --columns defines {}", statement.columns().len(), self.columns.len())),
+			Ok(_) => return Ok(self.cast_columns(query)),
+			Err(e) => e,
+		};
+		let function_start = match error.as_db_error() {
+			Some(db_error) if *db_error.code() == SqlState::SYNTAX_ERROR => match db_error.position() {
+				Some(ErrorPosition::Original(position)) => query.char_indices().nth((*position as usize).saturating_sub(1)).map(|(i, _)| i),
+				_ => None,
+			},
+			_ => None,
+		};
+		let rewritten = function_start.and_then(|start| self.add_definition_list(query, start))
+			.ok_or_else(|| format!("Failed to prepare the query: {}", format_pg_error(&error)))?;
+		client.prepare(&rewritten).map_err(|e| format!("Failed to prepare the query with the --columns definition list: {}", format_pg_error(&e)))?;
+		Ok(rewritten)
+	}
+
+	fn cast_columns(&self, query: &str) -> String {
+		let select_list = self.columns.iter()
+			.map(|(name, data_type)| format!("q.{}::{} AS {}", name, data_type, name))
+			.collect::<Vec<_>>().join(", ");
+		let aliases = self.columns.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(", ");
+		format!("SELECT {} FROM ({}) AS q({})", select_list, query, aliases)
+	}
+
+	/// Adds the column definition list after the function call starting at byte `start`, or after its `AS alias`. None if there is no function call
+	fn add_definition_list(&self, query: &str, start: usize) -> Option<String> {
+		let arguments_start = start + skip_qualified_name(&query[start..])?;
+		let arguments_end = arguments_start + matching_parenthesis(&query[arguments_start..])?;
+		// an explicit alias gets the definition list, otherwise a new alias is added
+		Some(match skip_as_alias(&query[arguments_end..]) {
+			Some(alias_len) => {
+				let alias_end = arguments_end + alias_len;
+				format!("{}({}){}", &query[..alias_end], self.definition_list(), &query[alias_end..])
+			},
+			None => format!("{} AS pg2parquet_columns({}){}", &query[..arguments_end], self.definition_list(), &query[arguments_end..]),
+		})
+	}
+}
+
+/// Splits on commas which are not in parentheses or double quotes
+fn split_top_level(s: &str) -> Vec<&str> {
+	let mut parts = Vec::new();
+	let (mut depth, mut quoted, mut start) = (0, false, 0);
+	for (i, c) in s.char_indices() {
+		match c {
+			'"' => quoted = !quoted,
+			'(' if !quoted => depth += 1,
+			')' if !quoted => depth -= 1,
+			',' if !quoted && depth == 0 => {
+				parts.push(&s[start..i]);
+				start = i + 1;
+			},
+			_ => {},
+		}
+	}
+	parts.push(&s[start..]);
+	parts
+}
+
+/// Length of the identifier at the start of `s`, quoted or not
+fn skip_identifier(s: &str) -> Option<usize> {
+	if let Some(quoted) = s.strip_prefix('"') {
+		// "" is an escaped quote inside the identifier
+		let mut chars = quoted.char_indices().peekable();
+		while let Some((i, c)) = chars.next() {
+			if c == '"' {
+				if chars.peek().map(|(_, c)| *c) == Some('"') {
+					chars.next();
+				} else {
+					return Some(i + 2);
+				}
+			}
+		}
+		return None;
+	}
+	let len = s.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).unwrap_or(s.len());
+	(len > 0).then_some(len)
+}
+
+/// Length of `AS alias` at the start of `s`, including the whitespace before it
+fn skip_as_alias(s: &str) -> Option<usize> {
+	let trimmed = s.trim_start();
+	let after_keyword = trimmed.get(2..)?;
+	if !trimmed[..2].eq_ignore_ascii_case("as") || !after_keyword.starts_with(char::is_whitespace) {
+		return None;
+	}
+	let alias = after_keyword.trim_start();
+	Some(s.len() - alias.len() + skip_identifier(alias)?)
+}
+
+/// Length of the possibly schema-qualified function name and the whitespace before its opening parenthesis
+fn skip_qualified_name(s: &str) -> Option<usize> {
+	let mut len = skip_identifier(s)?;
+	while s[len..].starts_with('.') {
+		len += 1 + skip_identifier(&s[len + 1..])?;
+	}
+	len += s[len..].len() - s[len..].trim_start().len();
+	s[len..].starts_with('(').then_some(len)
+}
+
+/// Length up to and including the parenthesis which closes the one at the start of `s`, skipping string literals and quoted identifiers
+fn matching_parenthesis(s: &str) -> Option<usize> {
+	let mut depth = 0;
+	let mut quote = None;
+	for (i, c) in s.char_indices() {
+		match (quote, c) {
+			(Some(q), c) if c == q => quote = None,
+			(Some(_), _) => {},
+			(None, '\'' | '"') => quote = Some(c),
+			(None, '(') => depth += 1,
+			(None, ')') => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(i + 1);
+				}
+			},
+			_ => {},
+		}
+	}
+	None
+}
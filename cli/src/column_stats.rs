@@ -0,0 +1,156 @@
+use std::hash::{DefaultHasher, Hasher};
+
+use parquet::file::metadata::KeyValue;
+use postgres::types::{FromSql, Type as PgType};
+
+use crate::pg_custom_types::{strip_domains, PgAnyRef};
+
+/// Null count and approximate number of distinct values of a top-level column
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+	pub name: String,
+	pub null_count: usize,
+	pub approx_distinct_count: u64,
+}
+
+/// Number of HyperLogLog registers is 2^HLL_PRECISION, the standard error of the estimate is 1.04 / sqrt(2^HLL_PRECISION), about 1.6%
+const HLL_PRECISION: u32 = 12;
+
+/// HyperLogLog estimate of the number of distinct values, the values are compared using their binary representation
+#[derive(Clone)]
+struct DistinctCounter {
+	registers: Vec<u8>,
+}
+
+impl DistinctCounter {
+	fn new() -> Self {
+		DistinctCounter { registers: vec![0; 1 << HLL_PRECISION] }
+	}
+
+	fn add(&mut self, value: &[u8]) {
+		let mut hasher = DefaultHasher::new();
+		hasher.write(value);
+		let hash = hasher.finish();
+		let index = (hash >> (64 - HLL_PRECISION)) as usize;
+		// position of the first 1 bit in the remaining bits, the extra bit limits it when all of them are zero
+		let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() as u8 + 1;
+		self.registers[index] = self.registers[index].max(rank);
+	}
+
+	fn estimate(&self) -> u64 {
+		let m = self.registers.len() as f64;
+		let alpha = 0.7213 / (1.0 + 1.079 / m);
+		let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+		let estimate = alpha * m * m / sum;
+		let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+		if estimate <= 2.5 * m && zeros > 0 {
+			// linear counting is more accurate for small cardinalities
+			(m * (m / zeros as f64).ln()).round() as u64
+		} else {
+			estimate.round() as u64
+		}
+	}
+}
+
+/// Collects the null counts and distinct value estimates of all top-level columns from the raw PostgreSQL values
+pub struct ColumnStatsCollector {
+	names: Vec<String>,
+	null_counts: Vec<usize>,
+	distinct: Vec<DistinctCounter>,
+}
+
+impl ColumnStatsCollector {
+	pub fn new(names: Vec<String>) -> Self {
+		ColumnStatsCollector {
+			null_counts: vec![0; names.len()],
+			distinct: vec![DistinctCounter::new(); names.len()],
+			names,
+		}
+	}
+
+	pub fn add_row(&mut self, row: &postgres::Row) -> Result<(), String> {
+		for i in 0..self.names.len() {
+			let value = row.try_get::<_, Option<PgAnyRef>>(i).map_err(|e| format!("Could not read column {}: {}", self.names[i], e))?;
+			match value {
+				Some(value) => self.distinct[i].add(value.value),
+				None => self.null_counts[i] += 1,
+			}
+		}
+		Ok(())
+	}
+
+	pub fn stats(&self) -> Vec<ColumnStats> {
+		self.names.iter().zip(&self.null_counts).zip(&self.distinct)
+			.map(|((name, &null_count), distinct)| ColumnStats { name: name.clone(), null_count, approx_distinct_count: distinct.estimate() })
+			.collect()
+	}
+}
+
+/// Minimum and maximum of the watermark column (`--watermark-column`) in the exported rows, written into the file metadata,
+/// so that the range of the next incremental export can be derived from the previously written files
+pub struct WatermarkCollector {
+	column: usize,
+	name: String,
+	/// Sort key and the raw value of the smallest and largest value, see `watermark_key`
+	min: Option<(i64, Vec<u8>)>,
+	max: Option<(i64, Vec<u8>)>,
+	ty: Option<PgType>,
+}
+
+/// Whether the column type can be used as a watermark
+pub fn is_watermark_type(t: &PgType) -> bool {
+	matches!(*strip_domains(t), PgType::INT2 | PgType::INT4 | PgType::INT8 | PgType::DATE | PgType::TIMESTAMP | PgType::TIMESTAMPTZ)
+}
+
+/// The binary representation of all the supported types is a big-endian signed integer
+fn watermark_key(value: &[u8]) -> i64 {
+	match value.len() {
+		2 => i16::from_be_bytes(value.try_into().unwrap()) as i64,
+		4 => i32::from_be_bytes(value.try_into().unwrap()) as i64,
+		8 => i64::from_be_bytes(value.try_into().unwrap()),
+		_ => panic!("Unexpected length {} of the watermark value", value.len()),
+	}
+}
+
+/// Formats the value as a PostgreSQL literal of the type, which can be used in the condition of the next export
+fn format_watermark(t: &PgType, value: &[u8]) -> String {
+	let formatted = match *strip_domains(t) {
+		PgType::DATE => chrono::NaiveDate::from_sql(&PgType::DATE, value).map(|d| d.to_string()).ok(),
+		PgType::TIMESTAMP => chrono::NaiveDateTime::from_sql(&PgType::TIMESTAMP, value).map(|t| t.format("%Y-%m-%d %H:%M:%S%.f").to_string()).ok(),
+		PgType::TIMESTAMPTZ => chrono::DateTime::<chrono::Utc>::from_sql(&PgType::TIMESTAMPTZ, value).map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)).ok(),
+		_ => Some(watermark_key(value).to_string()),
+	};
+	// dates and timestamps out of the chrono range are only the infinities
+	formatted.unwrap_or_else(|| if watermark_key(value) > 0 { "infinity".to_string() } else { "-infinity".to_string() })
+}
+
+impl WatermarkCollector {
+	pub fn new(column: usize, name: String) -> Self {
+		WatermarkCollector { column, name, min: None, max: None, ty: None }
+	}
+
+	pub fn add_row(&mut self, row: &postgres::Row) -> Result<(), String> {
+		let Some(value) = row.try_get::<_, Option<PgAnyRef>>(self.column).map_err(|e| format!("Could not read column {}: {}", self.name, e))? else {
+			return Ok(());
+		};
+		let key = watermark_key(value.value);
+		if self.min.as_ref().is_none_or(|(min, _)| key < *min) {
+			self.min = Some((key, value.value.to_vec()));
+		}
+		if self.max.as_ref().is_none_or(|(max, _)| key > *max) {
+			self.max = Some((key, value.value.to_vec()));
+		}
+		self.ty.get_or_insert(value.ty);
+		Ok(())
+	}
+
+	/// `pg2parquet.watermark.column`, and `pg2parquet.watermark.min` and `.max` unless all the values were NULL
+	pub fn metadata(&self) -> Vec<KeyValue> {
+		let mut metadata = vec![KeyValue::new("pg2parquet.watermark.column".to_string(), self.name.clone())];
+		if let (Some(ty), Some((_, min)), Some((_, max))) = (&self.ty, &self.min, &self.max) {
+			metadata.push(KeyValue::new("pg2parquet.watermark.min".to_string(), format_watermark(ty, min)));
+			metadata.push(KeyValue::new("pg2parquet.watermark.max".to_string(), format_watermark(ty, max)));
+		}
+		metadata
+	}
+}
@@ -0,0 +1,113 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use parquet::basic::{LogicalType, Type as PhysicalType};
+
+use crate::appenders::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, FlatColumnValues};
+use crate::level_index::LevelIndexList;
+use crate::pg_custom_types::PgAny;
+
+/// Value returned by a [`CustomTypeMapper`], the variant must match the physical type of the column
+#[derive(Debug, Clone, PartialEq)]
+pub enum CustomValue {
+	Bool(bool),
+	Int32(i32),
+	Int64(i64),
+	Float(f32),
+	Double(f64),
+	/// Value of a BYTE_ARRAY or FIXED_LEN_BYTE_ARRAY column
+	Bytes(Vec<u8>),
+}
+
+/// Exports the values of a PostgreSQL type which pg2parquet does not support, for example a type of a proprietary extension.
+/// The mapper is used for all values of the type named `type_name`, including array elements and fields of composite types,
+/// before the built-in mappings. Register it using [`crate::ExportJob::custom_type_mapper`] or `SchemaSettings::custom_type_mappers`.
+///
+/// ```no_run
+/// use pg2parquet::{CustomTypeMapper, CustomValue};
+/// use parquet::basic::{LogicalType, Type};
+///
+/// /// Exports the binary value of `mytype` as a hex string
+/// struct HexMapper;
+/// impl CustomTypeMapper for HexMapper {
+///     fn type_name(&self) -> &str { "mytype" }
+///     fn physical_type(&self) -> Type { Type::BYTE_ARRAY }
+///     fn logical_type(&self) -> Option<LogicalType> { Some(LogicalType::String) }
+///     fn convert(&self, value: &[u8]) -> Result<Option<CustomValue>, String> {
+///         Ok(Some(CustomValue::Bytes(value.iter().map(|b| format!("{:02x}", b)).collect::<String>().into_bytes())))
+///     }
+/// }
+///
+/// let connection = pg2parquet::PostgresConnArgs { host: vec!["localhost".to_string()], ..Default::default() };
+/// pg2parquet::ExportJob::new(connection, "my_table.parquet")
+///     .table("my_table")
+///     .custom_type_mapper(HexMapper)
+///     .run()?;
+/// # Ok::<(), String>(())
+/// ```
+pub trait CustomTypeMapper: Send + Sync {
+	/// Name of the PostgreSQL type (pg_type.typname)
+	fn type_name(&self) -> &str;
+	/// Physical type of the Parquet column. INT96 is not supported
+	fn physical_type(&self) -> PhysicalType;
+	/// Logical type of the Parquet column, such as String or Json
+	fn logical_type(&self) -> Option<LogicalType> { None }
+	/// Length of the FIXED_LEN_BYTE_ARRAY values
+	fn type_length(&self) -> i32 { -1 }
+	/// Converts a value in the PostgreSQL binary format. Returning None writes NULL,
+	/// returning an error fails the row (which is then handled according to `--on-row-error`)
+	fn convert(&self, value: &[u8]) -> Result<Option<CustomValue>, String>;
+}
+
+impl std::fmt::Debug for dyn CustomTypeMapper {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "CustomTypeMapper({})", self.type_name())
+	}
+}
+
+/// Converts the raw PostgreSQL values using the `CustomTypeMapper` and writes the result into the inner appender
+#[derive(Clone)]
+pub(crate) struct CustomTypeAppender<T: Clone, TInner: ColumnAppender<T>> {
+	inner: TInner,
+	mapper: Arc<dyn CustomTypeMapper>,
+	column: String,
+	extract: fn(CustomValue) -> Result<T, CustomValue>,
+	_dummy: PhantomData<fn(T)>,
+}
+
+impl<T: Clone, TInner: ColumnAppender<T>> CustomTypeAppender<T, TInner> {
+	pub fn new(inner: TInner, mapper: Arc<dyn CustomTypeMapper>, column: String, extract: fn(CustomValue) -> Result<T, CustomValue>) -> Self {
+		CustomTypeAppender { inner, mapper, column, extract, _dummy: PhantomData }
+	}
+}
+
+impl<T: Clone, TInner: ColumnAppender<T>> ColumnAppenderBase for CustomTypeAppender<T, TInner> {
+	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+		self.inner.write_null(repetition_index, level)
+	}
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+		self.inner.write_columns(column_i, next_col)
+	}
+	fn max_dl(&self) -> i16 { self.inner.max_dl() }
+	fn max_rl(&self) -> i16 { self.inner.max_rl() }
+	fn begin_row(&mut self) { self.inner.begin_row() }
+	fn rollback_row(&mut self) { self.inner.rollback_row() }
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> { self.inner.take_flat_values() }
+}
+
+impl<T: Clone, TInner: ColumnAppender<T>> ColumnAppender<PgAny> for CustomTypeAppender<T, TInner> {
+	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<PgAny>) -> Result<usize, String> {
+		let converted = self.mapper.convert(&value.value)
+			.map_err(|e| format!("Could not convert value of column {} using the custom mapper of {}: {}", self.column, self.mapper.type_name(), e))?;
+		match converted {
+			None => self.inner.write_null(repetition_index, self.inner.max_dl() - 1),
+			Some(v) => {
+				let v = (self.extract)(v)
+					.map_err(|v| format!("The custom mapper of {} returned {:?} for column {}, which does not match its physical type {}", self.mapper.type_name(), v, self.column, self.mapper.physical_type()))?;
+				self.inner.copy_value(repetition_index, Cow::Owned(v))
+			},
+		}
+	}
+}
@@ -0,0 +1,73 @@
+use std::fmt::Display;
+
+use byteorder::{ReadBytesExt, BigEndian};
+use postgres::types::FromSql;
+
+/// Flag of the cube header marking a point, which only stores one corner
+const POINT_BIT: u32 = 0x8000_0000;
+
+/// Value of the `cube` extension type, an n-dimensional box given by two opposite corners.
+/// A point has the same lower and upper corner
+#[derive(Debug, Clone)]
+pub struct PgCube {
+	pub lower: Vec<f64>,
+	pub upper: Vec<f64>,
+}
+
+impl PgCube {
+	pub fn is_point(&self) -> bool {
+		self.lower == self.upper
+	}
+}
+
+impl<'a> FromSql<'a> for PgCube {
+	fn from_sql(_ty: &postgres::types::Type, mut raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		let header = raw.read_u32::<BigEndian>()?;
+		let dim = (header & !POINT_BIT) as usize;
+		if raw.len() < dim * 8 {
+			return Err(format!("Invalid cube, {} bytes are not enough for {} dimensions", raw.len(), dim).into());
+		}
+		let lower = (0..dim).map(|_| raw.read_f64::<BigEndian>()).collect::<Result<Vec<_>, _>>()?;
+		let upper = if header & POINT_BIT != 0 {
+			lower.clone()
+		} else {
+			(0..dim).map(|_| raw.read_f64::<BigEndian>()).collect::<Result<Vec<_>, _>>()?
+		};
+		Ok(PgCube { lower, upper })
+	}
+
+	fn accepts(ty: &postgres::types::Type) -> bool {
+		ty.name() == "cube"
+	}
+}
+
+/// Same format as the cube output function: `(1, 2),(3, 4)`, or `(1, 2)` for a point
+impl Display for PgCube {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let corner = |c: &[f64]| c.iter().map(|x| format_float8(*x)).collect::<Vec<_>>().join(", ");
+		write!(f, "({})", corner(&self.lower))?;
+		if !self.is_point() {
+			write!(f, ",({})", corner(&self.upper))?;
+		}
+		Ok(())
+	}
+}
+
+/// Formats the number like the PostgreSQL float8 output: the shortest exact representation,
+/// with an exponent if it is below -4 or at least 15 (`1e+20`, `1.5e-05`)
+fn format_float8(x: f64) -> String {
+	if x.is_nan() {
+		return "NaN".to_string();
+	}
+	if x.is_infinite() {
+		return if x > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+	}
+	let scientific = format!("{:e}", x);
+	let (mantissa, exponent) = scientific.split_once('e').unwrap();
+	let exponent: i32 = exponent.parse().unwrap();
+	if x != 0.0 && !(-4..15).contains(&exponent) {
+		format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+	} else {
+		format!("{}", x)
+	}
+}
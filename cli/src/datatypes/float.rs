@@ -0,0 +1,69 @@
+use std::{borrow::Cow, marker::PhantomData, sync::Arc};
+
+use pg_bigdecimal::PgNumeric;
+
+use crate::appenders::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, FlatColumnValues};
+use crate::level_index::LevelIndexList;
+use crate::postgres_cloner::{SchemaSettingsFloatSpecialValues, ValueCounters};
+
+/// Values which can be NaN or Infinity
+pub trait SpecialFloatValue {
+	fn is_special(&self) -> bool;
+}
+
+impl SpecialFloatValue for f32 {
+	fn is_special(&self) -> bool { !self.is_finite() }
+}
+impl SpecialFloatValue for f64 {
+	fn is_special(&self) -> bool { !self.is_finite() }
+}
+impl SpecialFloatValue for PgNumeric {
+	/// numeric NaN (Infinity is not supported by PgNumeric and fails when reading the value)
+	fn is_special(&self) -> bool { self.n.is_none() }
+}
+
+/// Counts NaN and Infinity values and replaces them by NULL or fails, according to `--float-special-values`
+#[derive(Clone)]
+pub struct FloatSpecialValuesAppender<T: SpecialFloatValue + Clone, TInner: ColumnAppender<T>> {
+	inner: TInner,
+	policy: SchemaSettingsFloatSpecialValues,
+	column: String,
+	counters: Arc<ValueCounters>,
+	_dummy: PhantomData<fn(T)>,
+}
+
+impl<T: SpecialFloatValue + Clone, TInner: ColumnAppender<T>> FloatSpecialValuesAppender<T, TInner> {
+	pub fn new(inner: TInner, policy: SchemaSettingsFloatSpecialValues, column: String, counters: Arc<ValueCounters>) -> Self {
+		FloatSpecialValuesAppender { inner, policy, column, counters, _dummy: PhantomData }
+	}
+}
+
+impl<T: SpecialFloatValue + Clone, TInner: ColumnAppender<T>> ColumnAppenderBase for FloatSpecialValuesAppender<T, TInner> {
+	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+		self.inner.write_null(repetition_index, level)
+	}
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+		self.inner.write_columns(column_i, next_col)
+	}
+	fn max_dl(&self) -> i16 { self.inner.max_dl() }
+	fn max_rl(&self) -> i16 { self.inner.max_rl() }
+	fn begin_row(&mut self) { self.inner.begin_row() }
+	fn rollback_row(&mut self) { self.inner.rollback_row() }
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> { self.inner.take_flat_values() }
+}
+
+impl<T: SpecialFloatValue + Clone, TInner: ColumnAppender<T>> ColumnAppender<T> for FloatSpecialValuesAppender<T, TInner> {
+	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<T>) -> Result<usize, String> {
+		if !value.is_special() {
+			return self.inner.copy_value(repetition_index, value);
+		}
+		self.counters.count_special_float_value(&self.column);
+		match self.policy {
+			SchemaSettingsFloatSpecialValues::Keep => self.inner.copy_value(repetition_index, value),
+			SchemaSettingsFloatSpecialValues::Null => self.inner.write_null(repetition_index, self.inner.max_dl() - 1),
+			SchemaSettingsFloatSpecialValues::Error =>
+				Err(format!("Column {} contains a NaN or Infinity value, which is not allowed with --float-special-values=error", self.column)),
+		}
+	}
+}
@@ -0,0 +1,53 @@
+use parquet::data_type::FixedLenByteArray;
+use postgres::types::FromSql;
+
+use crate::myfrom::MyFrom;
+
+/// inet value in the binary format, including the network prefix length (which is dropped by the IpAddr conversion)
+#[derive(Debug, Clone)]
+pub struct PgInet {
+	/// 4 for IPv4, 6 for IPv6
+	pub family: u8,
+	pub prefix: u8,
+	/// 4 or 16 bytes of the address
+	pub addr: Vec<u8>
+}
+
+impl<'a> FromSql<'a> for PgInet {
+	fn from_sql(_ty: &postgres::types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		// family (PGSQL_AF_INET = 2, PGSQL_AF_INET6 = 3), prefix bits, is_cidr, address length, address
+		let [family, prefix, _is_cidr, len, addr @ ..] = raw else {
+			return Err("Invalid inet value".into());
+		};
+		let family = match family {
+			2 => 4,
+			3 => 6,
+			_ => return Err(format!("Unknown inet address family {}", family).into()),
+		};
+		if addr.len() != *len as usize || addr.len() != if family == 4 { 4 } else { 16 } {
+			return Err("Invalid inet address length".into());
+		}
+		Ok(PgInet { family, prefix: *prefix, addr: addr.to_vec() })
+	}
+
+	fn accepts(ty: &postgres::types::Type) -> bool {
+		ty == &postgres::types::Type::INET
+	}
+}
+
+impl MyFrom<PgInet> for FixedLenByteArray {
+	/// 16 bytes of the address followed by the prefix length. IPv4 addresses are mapped into IPv6 (`::ffff:a.b.c.d`),
+	/// so that the values are ordered by the address and all have the same length
+	fn my_from(t: PgInet) -> Self {
+		let mut bytes = Vec::with_capacity(17);
+		if t.family == 4 {
+			bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff]);
+			bytes.extend_from_slice(&t.addr);
+			bytes.push(t.prefix + 96);
+		} else {
+			bytes.extend_from_slice(&t.addr);
+			bytes.push(t.prefix);
+		}
+		FixedLenByteArray::from(bytes)
+	}
+}
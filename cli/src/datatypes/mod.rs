@@ -3,3 +3,9 @@ pub mod money;
 pub mod jsonb;
 pub mod interval;
 pub mod array;
+pub mod inet;
+pub mod snapshot;
+pub mod float;
+pub mod text;
+pub mod vector;
+pub mod cube;
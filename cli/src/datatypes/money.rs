@@ -8,9 +8,22 @@ pub struct PgMoney {
 	pub amount: i64
 }
 
+impl PgMoney {
+	/// Formats the amount with `scale` fractional digits, without the currency symbol and thousands separators
+	pub fn to_decimal_string(&self, scale: i32) -> String {
+		if scale <= 0 {
+			return self.amount.to_string();
+		}
+		let digits = self.amount.unsigned_abs().to_string();
+		let digits = format!("{:0>width$}", digits, width = scale as usize + 1);
+		let (int_part, frac_part) = digits.split_at(digits.len() - scale as usize);
+		format!("{}{}.{}", if self.amount < 0 { "-" } else { "" }, int_part, frac_part)
+	}
+}
+
 impl<'a> FromSql<'a> for PgMoney {
 	fn from_sql(_ty: &postgres::types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
-		let amount = BigEndian::read_i64(&raw);
+		let amount = BigEndian::read_i64(raw);
 		Ok(PgMoney { amount })
 	}
 
@@ -1,80 +1,119 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
-use parquet::data_type::{ByteArray, ByteArrayType};
+use parquet::data_type::{ByteArray, ByteArrayType, Int32Type};
 use pg_bigdecimal::{PgNumeric, BigDecimal, BigInt};
-use bigdecimal::ToPrimitive;
+use bigdecimal::{Signed, Zero};
 
-use crate::appenders::{GenericColumnAppender, ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, new_autoconv_generic_appender, PreprocessExt, PreprocessAppender, UnwrapOptionAppender};
+use crate::appenders::{GenericColumnAppender, ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, FlatColumnValues, new_autoconv_generic_appender, new_static_merged_appender, PreprocessExt, StaticMergedAppender, UnwrapOptionAppender};
 use crate::level_index::LevelIndexList;
 use crate::myfrom::MyFrom;
+use crate::postgres_cloner::{SchemaSettingsDecimalRounding, ValueCounters};
 
 
-fn convert_decimal_to_bytes(d: &BigDecimal, scale: i32, precision: u32) -> Vec<u8> {
-	let dd = d.with_prec(precision as u64).with_scale(scale as i64);
-	let (int, exp) = dd.into_bigint_and_exponent();
-	debug_assert_eq!(exp, scale as i64);
-	int.to_signed_bytes_be()
-}
-
-pub fn convert_decimal_to_int<Int: TryFrom<BigInt>>(d: &BigDecimal, scale: i32, precision: u32) -> Option<Int>
-	where Int::Error: std::fmt::Display {
-	debug_assert!(precision <= 18);
-	let dd = d.with_prec(precision as u64).with_scale(scale as i64);
-	let (int, exp) = dd.into_bigint_and_exponent();
-	debug_assert_eq!(exp, scale as i64);
-	int.try_into().map_err(|err| {
-		eprintln!("Error converting decimal number {}, the value is replaced by NULL: {}", d, err)
-	}).ok()
+/// Rescales the number to exactly `scale` fractional digits, returns the unscaled integer and whether the number had more digits and was rounded
+fn rescale_decimal(d: &BigDecimal, scale: i32, rounding: SchemaSettingsDecimalRounding) -> Result<(BigInt, bool), String> {
+	let (int, exp) = d.as_bigint_and_exponent();
+	let scale = scale as i64;
+	if exp <= scale {
+		return Ok((int * BigInt::from(10).pow((scale - exp) as u32), false));
+	}
+	let factor = BigInt::from(10).pow((exp - scale) as u32);
+	let quotient = &int / &factor;
+	let remainder = &int % &factor;
+	if remainder.is_zero() {
+		return Ok((quotient, false));
+	}
+	let round_away_from_zero = match rounding {
+		SchemaSettingsDecimalRounding::Truncate => false,
+		SchemaSettingsDecimalRounding::HalfUp => remainder.abs() * 2 >= factor,
+		SchemaSettingsDecimalRounding::HalfEven => {
+			let twice_remainder = remainder.abs() * 2;
+			twice_remainder > factor || (twice_remainder == factor && !(&quotient % BigInt::from(2)).is_zero())
+		},
+		SchemaSettingsDecimalRounding::Error =>
+			return Err(format!("value {} has more than {} fractional digits (rounding is disabled by --decimal-rounding=error)", d, scale)),
+	};
+	if round_away_from_zero {
+		Ok((quotient + int.signum(), true))
+	} else {
+		Ok((quotient, true))
+	}
 }
 
-pub fn new_decimal_bytes_appender(max_dl: i16, max_rl: i16, precision: u32, scale: i32) -> impl ColumnAppender<PgNumeric> {
+pub fn new_decimal_bytes_appender(max_dl: i16, max_rl: i16, precision: u32, scale: i32, rounding: SchemaSettingsDecimalRounding, column: String, counters: Arc<ValueCounters>) -> impl ColumnAppender<PgNumeric> {
 	let inner: GenericColumnAppender<Vec<u8>, ByteArrayType, _> = new_autoconv_generic_appender(max_dl, max_rl);
-	DecimalBytesAppender {
-		inner,
-		precision,
-		scale,
-	}
+	DecimalAppender::new(inner, precision, scale, rounding, column, counters, |int| Some(int.to_signed_bytes_be()))
 }
 
-pub fn new_decimal_int_appender<Int: TryFrom<BigInt> + Clone, TPq: parquet::data_type::DataType>(max_dl: i16, max_rl: i16, precision: u32, scale: i32) -> impl ColumnAppender<PgNumeric>
-	where Int::Error: std::fmt::Display,
-		TPq::T: Clone + crate::appenders::RealMemorySize,
+pub fn new_decimal_int_appender<Int: TryFrom<BigInt> + Clone, TPq: parquet::data_type::DataType>(max_dl: i16, max_rl: i16, precision: u32, scale: i32, rounding: SchemaSettingsDecimalRounding, column: String, counters: Arc<ValueCounters>) -> impl ColumnAppender<PgNumeric>
+	where TPq::T: Clone + crate::appenders::RealMemorySize,
 		TPq::T: MyFrom<Int>{
-	let inner = UnwrapOptionAppender::new(new_autoconv_generic_appender::<Int, TPq>(max_dl, max_rl));
-	PreprocessAppender::new(inner, move |value: Cow<PgNumeric>| {
-		match &value.n {
-			Some(n) => Cow::Owned(convert_decimal_to_int(n, scale, precision)),
-			None => Cow::Owned(None),
-		}
-	})
+	debug_assert!(precision <= 18);
+	let inner = new_autoconv_generic_appender::<Int, TPq>(max_dl, max_rl);
+	DecimalAppender::new(inner, precision, scale, rounding, column, counters, |int| int.try_into().ok())
+}
+
+/// Writes numeric as struct { unscaled: BYTE_ARRAY, scale: i32 } without any rounding, the value is `unscaled * 10^-scale`.
+/// The unscaled integer is in the same big-endian two's complement format as in the DECIMAL type. NaN is written as NULL
+pub fn new_decimal_struct_appender(max_dl: i16, max_rl: i16) -> impl ColumnAppender<PgNumeric> {
+	let inner = new_static_merged_appender::<BigDecimal>(max_dl, max_rl)
+		.add_appender_map(new_autoconv_generic_appender::<Vec<u8>, ByteArrayType>(max_dl + 1, max_rl), |n| Cow::Owned(n.as_bigint_and_exponent().0.to_signed_bytes_be()))
+		.add_appender_map(new_autoconv_generic_appender::<i32, Int32Type>(max_dl + 1, max_rl), |n| Cow::Owned(n.as_bigint_and_exponent().1 as i32));
+	UnwrapOptionAppender::new(inner).preprocess(|v: Cow<PgNumeric>| Cow::Owned(v.into_owned().n))
 }
 
+/// Writes numeric as Decimal(precision, scale), with the unscaled integer converted to T. NaN is written as NULL,
+/// numbers which do not fit into the precision are replaced by NULL (with a message on stderr)
 #[derive(Clone)]
-struct DecimalBytesAppender<TInner: ColumnAppender<Vec<u8>>> {
+struct DecimalAppender<T: Clone, TInner: ColumnAppender<T>> {
 	inner: TInner,
 	precision: u32,
 	scale: i32,
+	rounding: SchemaSettingsDecimalRounding,
+	column: String,
+	counters: Arc<ValueCounters>,
+	/// 10^precision, the smallest number which does not fit
+	limit: BigInt,
+	convert: fn(BigInt) -> Option<T>,
 }
 
-impl<TInner: ColumnAppender<Vec<u8>>> ColumnAppenderBase for DecimalBytesAppender<TInner> {
+impl<T: Clone, TInner: ColumnAppender<T>> DecimalAppender<T, TInner> {
+	fn new(inner: TInner, precision: u32, scale: i32, rounding: SchemaSettingsDecimalRounding, column: String, counters: Arc<ValueCounters>, convert: fn(BigInt) -> Option<T>) -> Self {
+		DecimalAppender { inner, precision, scale, rounding, column, counters, limit: BigInt::from(10).pow(precision), convert }
+	}
+}
+
+impl<T: Clone, TInner: ColumnAppender<T>> ColumnAppenderBase for DecimalAppender<T, TInner> {
 	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
 		self.inner.write_null(repetition_index, level)
 	}
-	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
 		self.inner.write_columns(column_i, next_col)
 	}
 	fn max_dl(&self) -> i16 { self.inner.max_dl() }
 	fn max_rl(&self) -> i16 { self.inner.max_rl() }
+	fn begin_row(&mut self) { self.inner.begin_row() }
+	fn rollback_row(&mut self) { self.inner.rollback_row() }
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> { self.inner.take_flat_values() }
 }
 
-impl<TInner: ColumnAppender<Vec<u8>>> ColumnAppender<PgNumeric> for DecimalBytesAppender<TInner> {
+impl<T: Clone, TInner: ColumnAppender<T>> ColumnAppender<PgNumeric> for DecimalAppender<T, TInner> {
 	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<PgNumeric>) -> Result<usize, String> {
-		let value = value.as_ref();
-		let bytes = match &value.n {
-			Some(n) => Some(convert_decimal_to_bytes(n, self.scale, self.precision)),
-			None => None,
+		let Some(n) = &value.n else {
+			return self.inner.copy_value_opt(repetition_index, Cow::Owned(None));
 		};
-		self.inner.copy_value_opt(repetition_index, Cow::Owned(bytes))
+		let (int, rounded) = rescale_decimal(n, self.scale, self.rounding)
+			.map_err(|e| format!("Could not convert column {} to Decimal({}, {}): {}", self.column, self.precision, self.scale, e))?;
+		if rounded {
+			self.counters.count_rounded_decimal_value(&self.column);
+		}
+		let converted = if int.abs() < self.limit { (self.convert)(int) } else { None };
+		if converted.is_none() {
+			eprintln!("Error converting decimal number {}, the value is replaced by NULL: it does not fit into Decimal({}, {})", n, self.precision, self.scale);
+		}
+		self.inner.copy_value_opt(repetition_index, Cow::Owned(converted))
 	}
 }
 
@@ -96,33 +135,3 @@ impl MyFrom<PgNumeric> for f32 {
 		}
     }
 }
-
-// #[derive(Clone)]
-// struct DecimalIntAppender<TInt: TryFrom<BigInt>, TInner: ColumnAppender<i64>>
-// 	where TInt::Error: std::fmt::Display {
-// 	inner: TInner,
-// 	precision: u32,
-// 	scale: i32,
-// }
-
-// impl<TInner: ColumnAppender<i64>> ColumnAppenderBase for DecimalIntAppender<TInner> {
-// 	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
-// 		self.inner.write_null(repetition_index, level)
-// 	}
-// 	fn write_columns<'b>(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
-// 		self.inner.write_columns(column_i, next_col)
-// 	}
-// 	fn max_dl(&self) -> i16 { self.inner.max_dl() }
-// 	fn max_rl(&self) -> i16 { self.inner.max_rl() }
-// }
-
-// impl<TInner: ColumnAppender<i64>> ColumnAppender<PgNumeric> for DecimalIntAppender<TInner> {
-// 	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<PgNumeric>) -> Result<usize, String> {
-// 		let value = value.as_ref();
-// 		let int = match &value.n {
-// 			Some(n) => convert_decimal_to_int(n, self.scale, self.precision),
-// 			None => None,
-// 		};
-// 		self.inner.copy_value_opt(repetition_index, Cow::Owned(int))
-// 	}
-// }
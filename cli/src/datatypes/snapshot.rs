@@ -0,0 +1,28 @@
+use byteorder::{ReadBytesExt, BigEndian};
+use postgres::types::FromSql;
+
+/// pg_snapshot or txid_snapshot value, the transaction IDs are 64-bit (including the epoch)
+#[derive(Debug, Clone)]
+pub struct PgSnapshot {
+	pub xmin: u64,
+	pub xmax: u64,
+	/// Transactions in progress at the time of the snapshot
+	pub xip: Vec<u64>
+}
+
+impl<'a> FromSql<'a> for PgSnapshot {
+	fn from_sql(_ty: &postgres::types::Type, mut raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		let nxip = raw.read_i32::<BigEndian>()?;
+		if nxip < 0 {
+			return Err(format!("Invalid snapshot, number of in-progress transactions is {}", nxip).into());
+		}
+		let xmin = raw.read_u64::<BigEndian>()?;
+		let xmax = raw.read_u64::<BigEndian>()?;
+		let xip = (0..nxip).map(|_| raw.read_u64::<BigEndian>()).collect::<Result<Vec<_>, _>>()?;
+		Ok(PgSnapshot { xmin, xmax, xip })
+	}
+
+	fn accepts(ty: &postgres::types::Type) -> bool {
+		ty.name() == "txid_snapshot" || ty.name() == "pg_snapshot"
+	}
+}
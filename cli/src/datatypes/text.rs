@@ -0,0 +1,48 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use crate::appenders::{ColumnAppender, ColumnAppenderBase, DynamicSerializedWriter, FlatColumnValues};
+use crate::level_index::LevelIndexList;
+
+/// Writes NULL values of a text column as a placeholder string and optionally treats empty strings as NULL, see `--null-string` and `--empty-string-as-null`
+#[derive(Clone)]
+pub struct TextNullHandlingAppender<TInner: ColumnAppender<String>> {
+	inner: TInner,
+	null_string: Option<String>,
+	empty_string_as_null: bool,
+	_dummy: PhantomData<fn(String)>,
+}
+
+impl<TInner: ColumnAppender<String>> TextNullHandlingAppender<TInner> {
+	pub fn new(inner: TInner, null_string: Option<String>, empty_string_as_null: bool) -> Self {
+		TextNullHandlingAppender { inner, null_string, empty_string_as_null, _dummy: PhantomData }
+	}
+}
+
+impl<TInner: ColumnAppender<String>> ColumnAppenderBase for TextNullHandlingAppender<TInner> {
+	fn write_null(&mut self, repetition_index: &LevelIndexList, level: i16) -> Result<usize, String> {
+		// only NULL values of the column itself are replaced, not NULL parent structs or arrays
+		match &self.null_string {
+			Some(null_string) if level == self.inner.max_dl() - 1 => self.inner.copy_value(repetition_index, Cow::Owned(null_string.clone())),
+			_ => self.inner.write_null(repetition_index, level),
+		}
+	}
+	fn write_columns(&mut self, column_i: usize, next_col: &mut dyn DynamicSerializedWriter) -> Result<(), String> {
+		self.inner.write_columns(column_i, next_col)
+	}
+	fn max_dl(&self) -> i16 { self.inner.max_dl() }
+	fn max_rl(&self) -> i16 { self.inner.max_rl() }
+	fn begin_row(&mut self) { self.inner.begin_row() }
+	fn rollback_row(&mut self) { self.inner.rollback_row() }
+
+	fn take_flat_values(&mut self) -> Option<FlatColumnValues> { self.inner.take_flat_values() }
+}
+
+impl<TInner: ColumnAppender<String>> ColumnAppender<String> for TextNullHandlingAppender<TInner> {
+	fn copy_value(&mut self, repetition_index: &LevelIndexList, value: Cow<String>) -> Result<usize, String> {
+		if self.empty_string_as_null && value.is_empty() {
+			return self.write_null(repetition_index, self.inner.max_dl() - 1);
+		}
+		self.inner.copy_value(repetition_index, value)
+	}
+}
@@ -0,0 +1,69 @@
+use byteorder::{ReadBytesExt, BigEndian};
+use half::f16;
+use postgres::types::FromSql;
+
+/// Value of the pgvector `vector` or `halfvec` type, the halfvec elements are converted to f32.
+/// `sparsevec` values are converted to the dense representation
+#[derive(Debug, Clone)]
+pub struct PgVector {
+	pub values: Vec<f32>,
+}
+
+impl<'a> FromSql<'a> for PgVector {
+	fn from_sql(ty: &postgres::types::Type, mut raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		if ty.name() == "sparsevec" {
+			return Ok(PgSparseVec::from_sql(ty, raw)?.to_dense());
+		}
+		let dim = raw.read_u16::<BigEndian>()?;
+		let _unused = raw.read_u16::<BigEndian>()?;
+		let values = if ty.name() == "halfvec" {
+			(0..dim).map(|_| raw.read_u16::<BigEndian>().map(|bits| f16::from_bits(bits).to_f32())).collect::<Result<Vec<_>, _>>()?
+		} else {
+			(0..dim).map(|_| raw.read_f32::<BigEndian>()).collect::<Result<Vec<_>, _>>()?
+		};
+		Ok(PgVector { values })
+	}
+
+	fn accepts(ty: &postgres::types::Type) -> bool {
+		ty.name() == "vector" || ty.name() == "halfvec" || ty.name() == "sparsevec"
+	}
+}
+
+/// Value of the pgvector `sparsevec` type, the indices are zero-based
+#[derive(Debug, Clone)]
+pub struct PgSparseVec {
+	pub dim: i32,
+	pub indices: Vec<i32>,
+	pub values: Vec<f32>,
+}
+
+impl PgSparseVec {
+	pub fn to_dense(&self) -> PgVector {
+		let mut values = vec![0.0; self.dim.max(0) as usize];
+		for (i, v) in self.indices.iter().zip(&self.values) {
+			values[*i as usize] = *v;
+		}
+		PgVector { values }
+	}
+}
+
+impl<'a> FromSql<'a> for PgSparseVec {
+	fn from_sql(_ty: &postgres::types::Type, mut raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		let dim = raw.read_i32::<BigEndian>()?;
+		let nnz = raw.read_i32::<BigEndian>()?;
+		let _unused = raw.read_i32::<BigEndian>()?;
+		if nnz < 0 || nnz > dim {
+			return Err(format!("Invalid sparsevec, {} non-zero elements of dimension {}", nnz, dim).into());
+		}
+		let indices = (0..nnz).map(|_| raw.read_i32::<BigEndian>()).collect::<Result<Vec<_>, _>>()?;
+		if let Some(i) = indices.iter().find(|i| **i < 0 || **i >= dim) {
+			return Err(format!("Invalid sparsevec, index {} is out of range of dimension {}", i, dim).into());
+		}
+		let values = (0..nnz).map(|_| raw.read_f32::<BigEndian>()).collect::<Result<Vec<_>, _>>()?;
+		Ok(PgSparseVec { dim, indices, values })
+	}
+
+	fn accepts(ty: &postgres::types::Type) -> bool {
+		ty.name() == "sparsevec"
+	}
+}
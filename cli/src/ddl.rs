@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use postgres::{Client, Column};
+use postgres::types::{Kind, Type as PgType};
+
+use crate::postgresutils::{format_pg_error, quote_identifier};
+
+/// Where the CREATE TABLE statement is written, see `--emit-ddl`
+#[derive(Clone, Debug)]
+pub struct DdlOutput {
+	/// The SQL file
+	pub file: PathBuf,
+	/// Exported data file, the table created by the DDL is named after it when `table` is not specified
+	pub data_file: PathBuf,
+	/// Exported table, the DDL creates a table of the same name
+	pub table: Option<String>,
+}
+
+/// Writes the CREATE TABLE statement describing the exported columns into the `output` file, preceded by
+/// definitions of the used enums, composite types, domains and ranges. The columns read directly from a table
+/// include the type modifiers such as varchar length, and the NOT NULL constraints of the exported table
+pub fn write_ddl(client: &mut Client, columns: &[Column], output: &DdlOutput) -> Result<(), String> {
+	let mut type_definitions = Vec::new();
+	let mut defined_types = Vec::new();
+	for column in columns {
+		collect_type_definitions(client, column.type_(), &mut defined_types, &mut type_definitions)?;
+	}
+
+	let table_oids: Vec<u32> = columns.iter().map(|c| c.table_oid().unwrap_or(0)).collect();
+	let column_ids: Vec<i16> = columns.iter().map(|c| c.column_id().unwrap_or(0)).collect();
+	let type_oids: Vec<u32> = columns.iter().map(|c| c.type_().oid()).collect();
+	let column_types = client.query(
+			"SELECT format_type(c.type_oid, CASE WHEN a.atttypid = c.type_oid THEN a.atttypmod END), coalesce(a.attnotnull AND a.attrelid = $4::text::regclass, false)
+			 FROM unnest($1::oid[], $2::int2[], $3::oid[]) WITH ORDINALITY c(table_oid, column_id, type_oid, i)
+			 LEFT JOIN pg_attribute a ON a.attrelid = c.table_oid AND a.attnum = c.column_id AND NOT a.attisdropped
+			 ORDER BY c.i",
+			&[&table_oids, &column_ids, &type_oids, &output.table]
+		).map_err(|e| format!("Failed to query the column types: {}", format_pg_error(&e)))?;
+	let columns: Vec<String> = columns.iter().zip(column_types)
+		.map(|(c, r)| format!("{} {}{}", quote_identifier(c.name()), r.get::<_, String>(0), if r.get(1) { " NOT NULL" } else { "" }))
+		.collect();
+	let table_name = match &output.table {
+		Some(table) => table.to_string(),
+		None => quote_identifier(&output.data_file.file_stem().unwrap_or_default().to_string_lossy()),
+	};
+
+	let mut ddl = String::new();
+	ddl.push_str(&format!("-- Schema of {:?} exported by pg2parquet\n", output.data_file));
+	for definition in type_definitions {
+		ddl.push_str(&definition);
+		ddl.push_str(";\n\n");
+	}
+	ddl.push_str(&format!("CREATE TABLE {} (\n\t{}\n);\n", table_name, columns.join(",\n\t")));
+
+	std::fs::write(&output.file, ddl).map_err(|e| format!("Failed to write {:?}: {}", output.file, e))
+}
+
+fn format_type(client: &mut Client, t: &PgType) -> Result<String, String> {
+	client.query_one("SELECT format_type($1, NULL)", &[&t.oid()])
+		.map(|r| r.get(0))
+		.map_err(|e| format!("Failed to format type {}: {}", t, format_pg_error(&e)))
+}
+
+/// Adds CREATE TYPE / CREATE DOMAIN statements of all user-defined types `t` depends on, dependencies go first
+fn collect_type_definitions(client: &mut Client, t: &PgType, defined: &mut Vec<u32>, definitions: &mut Vec<String>) -> Result<(), String> {
+	if defined.contains(&t.oid()) || matches!(t.schema(), "pg_catalog" | "information_schema") {
+		return Ok(());
+	}
+	defined.push(t.oid());
+	let definition = match t.kind() {
+		Kind::Array(element) => return collect_type_definitions(client, element, defined, definitions),
+		Kind::Enum(variants) => {
+			let variants = variants.iter().map(|v| format!("'{}'", v.replace('\'', "''"))).collect::<Vec<_>>();
+			format!("CREATE TYPE {} AS ENUM ({})", format_type(client, t)?, variants.join(", "))
+		},
+		Kind::Composite(fields) => {
+			for f in fields {
+				collect_type_definitions(client, f.type_(), defined, definitions)?;
+			}
+			let attributes = client.query(
+					"SELECT a.attname::text, format_type(a.atttypid, a.atttypmod) FROM pg_attribute a JOIN pg_type t ON a.attrelid = t.typrelid
+					 WHERE t.oid = $1 AND a.attnum > 0 AND NOT a.attisdropped ORDER BY a.attnum",
+					&[&t.oid()]
+				).map_err(|e| format!("Failed to query attributes of {}: {}", t, format_pg_error(&e)))?
+				.iter()
+				.map(|r| format!("{} {}", quote_identifier(r.get(0)), r.get::<_, String>(1)))
+				.collect::<Vec<_>>();
+			format!("CREATE TYPE {} AS (\n\t{}\n)", format_type(client, t)?, attributes.join(",\n\t"))
+		},
+		Kind::Domain(base) => {
+			collect_type_definitions(client, base, defined, definitions)?;
+			let row = client.query_one("SELECT format_type(typbasetype, typtypmod), typnotnull FROM pg_type WHERE oid = $1", &[&t.oid()])
+				.map_err(|e| format!("Failed to query domain {}: {}", t, format_pg_error(&e)))?;
+			let constraints = client.query("SELECT pg_get_constraintdef(oid) FROM pg_constraint WHERE contypid = $1 AND contype = 'c' ORDER BY oid", &[&t.oid()])
+				.map_err(|e| format!("Failed to query constraints of domain {}: {}", t, format_pg_error(&e)))?;
+			let mut definition = format!("CREATE DOMAIN {} AS {}", format_type(client, t)?, row.get::<_, String>(0));
+			if row.get(1) {
+				definition.push_str(" NOT NULL");
+			}
+			for c in constraints {
+				definition.push(' ');
+				definition.push_str(c.get(0));
+			}
+			definition
+		},
+		Kind::Range(subtype) => {
+			collect_type_definitions(client, subtype, defined, definitions)?;
+			format!("CREATE TYPE {} AS RANGE (SUBTYPE = {})", format_type(client, t)?, format_type(client, subtype)?)
+		},
+		_ => return Ok(()),
+	};
+	definitions.push(definition);
+	Ok(())
+}
@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use parquet::basic::{LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::file::metadata::ParquetMetaDataReader;
+use parquet::schema::types::Type as ParquetType;
+use serde_json::json;
+
+use crate::parquet_writer::WriterStats;
+
+/// Adds the written Parquet file to the Delta Lake table in its directory, by writing a new commit into `_delta_log`.
+/// The first commit also creates the table (protocol and metadata actions), later commits must have the same schema.
+/// Only a single writer is supported, concurrent commits fail instead of being retried
+pub fn commit_to_delta_log(parquet_file: &PathBuf, stats: &WriterStats) -> Result<(), String> {
+	let table_dir = parquet_file.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+	let log_dir = table_dir.join("_delta_log");
+	std::fs::create_dir_all(&log_dir).map_err(|e| format!("Failed to create {:?}: {}", log_dir, e))?;
+
+	let file = std::fs::File::open(parquet_file).map_err(|e| format!("Failed to open {:?}: {}", parquet_file, e))?;
+	let metadata = ParquetMetaDataReader::new().parse_and_finish(&file).map_err(|e| format!("Failed to read parquet file {:?}: {}", parquet_file, e))?;
+	let schema = metadata.file_metadata().schema();
+	let fields = schema.get_fields().iter().map(|f| delta_field(f)).collect::<Result<Vec<_>, _>>()?;
+	let schema_string = json!({ "type": "struct", "fields": fields }).to_string();
+	let uses_timestamp_ntz = schema_string.contains("\"timestamp_ntz\"");
+
+	let last_version = last_commit_version(&log_dir)?;
+	let now = chrono::Utc::now().timestamp_millis();
+	let mut actions = Vec::new();
+	match last_version {
+		None => {
+			actions.push(if uses_timestamp_ntz {
+				json!({ "protocol": { "minReaderVersion": 3, "minWriterVersion": 7, "readerFeatures": ["timestampNtz"], "writerFeatures": ["timestampNtz"] } })
+			} else {
+				json!({ "protocol": { "minReaderVersion": 1, "minWriterVersion": 2 } })
+			});
+			actions.push(json!({ "metaData": {
+				"id": uuid::Uuid::new_v4().to_string(),
+				"format": { "provider": "parquet", "options": {} },
+				"schemaString": schema_string,
+				"partitionColumns": [],
+				"configuration": {},
+				"createdTime": now,
+			} }));
+		},
+		Some(version) => {
+			let table_schema = last_schema_string(&log_dir, version)?;
+			if table_schema.as_deref() != Some(schema_string.as_str()) {
+				return Err(format!("The schema of {:?} does not match the schema of the Delta table in {:?}", parquet_file, table_dir));
+			}
+		},
+	}
+	let file_size = std::fs::metadata(parquet_file).map(|m| m.len()).map_err(|e| format!("Failed to read {:?}: {}", parquet_file, e))?;
+	actions.push(json!({ "add": {
+		"path": parquet_file.file_name().unwrap().to_string_lossy(),
+		"partitionValues": {},
+		"size": file_size,
+		"modificationTime": now,
+		"dataChange": true,
+		"stats": json!({ "numRecords": stats.rows }).to_string(),
+	} }));
+	actions.push(json!({ "commitInfo": {
+		"timestamp": now,
+		"operation": "WRITE",
+		"operationParameters": { "mode": "Append" },
+		"engineInfo": format!("pg2parquet/{}", env!("CARGO_PKG_VERSION")),
+	} }));
+
+	let version = last_version.map_or(0, |v| v + 1);
+	let commit_file = log_dir.join(format!("{:020}.json", version));
+	let mut commit = std::fs::OpenOptions::new().write(true).create_new(true).open(&commit_file)
+		.map_err(|e| format!("Failed to create Delta commit {:?} (is another process writing into the table?): {}", commit_file, e))?;
+	let content = actions.iter().map(|a| a.to_string() + "\n").collect::<String>();
+	commit.write_all(content.as_bytes()).map_err(|e| format!("Failed to write {:?}: {}", commit_file, e))
+}
+
+fn last_commit_version(log_dir: &Path) -> Result<Option<u64>, String> {
+	let entries = std::fs::read_dir(log_dir).map_err(|e| format!("Failed to read {:?}: {}", log_dir, e))?;
+	Ok(entries
+		.filter_map(|e| e.ok())
+		.filter_map(|e| e.file_name().to_str()?.strip_suffix(".json")?.parse::<u64>().ok())
+		.max())
+}
+
+/// Finds the most recent metaData action, checkpoints are not supported
+fn last_schema_string(log_dir: &Path, last_version: u64) -> Result<Option<String>, String> {
+	for version in (0..=last_version).rev() {
+		let path = log_dir.join(format!("{:020}.json", version));
+		let Ok(content) = std::fs::read_to_string(&path) else {
+			return Err(format!("Delta log {:?} is missing, tables with checkpoints are not supported", path));
+		};
+		for line in content.lines() {
+			let action: serde_json::Value = serde_json::from_str(line).map_err(|e| format!("Invalid Delta log {:?}: {}", path, e))?;
+			if let Some(schema) = action.pointer("/metaData/schemaString").and_then(|s| s.as_str()) {
+				return Ok(Some(schema.to_string()));
+			}
+		}
+	}
+	Ok(None)
+}
+
+fn delta_field(t: &ParquetType) -> Result<serde_json::Value, String> {
+	let info = t.get_basic_info();
+	Ok(json!({
+		"name": info.name(),
+		"type": delta_type(t)?,
+		"nullable": info.repetition() != Repetition::REQUIRED,
+		"metadata": {},
+	}))
+}
+
+fn delta_type(t: &ParquetType) -> Result<serde_json::Value, String> {
+	let info = t.get_basic_info();
+	let unsupported = || Err(format!("Column {} has a type which is not supported by Delta Lake, convert it in the query", info.name()));
+	match t {
+		ParquetType::PrimitiveType { physical_type, .. } => {
+			let name = match (physical_type, info.logical_type()) {
+				(_, Some(LogicalType::Decimal { scale, precision })) => return Ok(format!("decimal({},{})", precision, scale).into()),
+				(PhysicalType::BOOLEAN, None) => "boolean",
+				(PhysicalType::INT32, Some(LogicalType::Integer { bit_width: 8, is_signed: true })) => "byte",
+				(PhysicalType::INT32, Some(LogicalType::Integer { bit_width: 16, is_signed: true })) => "short",
+				(PhysicalType::INT32, None | Some(LogicalType::Integer { bit_width: 32, is_signed: true })) => "integer",
+				(PhysicalType::INT32, Some(LogicalType::Date)) => "date",
+				(PhysicalType::INT64, None | Some(LogicalType::Integer { bit_width: 64, is_signed: true })) => "long",
+				(PhysicalType::INT64, Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: TimeUnit::MICROS(_) })) => "timestamp",
+				(PhysicalType::INT64, Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: false, unit: TimeUnit::MICROS(_) })) => "timestamp_ntz",
+				(PhysicalType::FLOAT, None) => "float",
+				(PhysicalType::DOUBLE, None) => "double",
+				(PhysicalType::BYTE_ARRAY, Some(LogicalType::String | LogicalType::Enum | LogicalType::Json)) => "string",
+				(PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY, None | Some(LogicalType::Uuid)) => "binary",
+				_ => return unsupported(),
+			};
+			Ok(name.into())
+		},
+		ParquetType::GroupType { fields, .. } => match info.logical_type() {
+			Some(LogicalType::List) => {
+				// LIST -> repeated group list -> element
+				let element = fields.first().and_then(|l| l.get_fields().first());
+				let Some(element) = element else { return unsupported() };
+				Ok(json!({
+					"type": "array",
+					"elementType": delta_type(element)?,
+					"containsNull": element.get_basic_info().repetition() != Repetition::REQUIRED,
+				}))
+			},
+			Some(LogicalType::Map) => {
+				let key_value = fields.first().map(|kv| kv.get_fields());
+				let Some([key, value]) = key_value else { return unsupported() };
+				Ok(json!({
+					"type": "map",
+					"keyType": delta_type(key)?,
+					"valueType": delta_type(value)?,
+					"valueContainsNull": value.get_basic_info().repetition() != Repetition::REQUIRED,
+				}))
+			},
+			None => Ok(json!({
+				"type": "struct",
+				"fields": fields.iter().map(|f| delta_field(f)).collect::<Result<Vec<_>, _>>()?,
+			})),
+			_ => unsupported(),
+		},
+	}
+}
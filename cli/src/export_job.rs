@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::{WriterProperties, WriterPropertiesBuilder};
+
+use crate::custom_type::CustomTypeMapper;
+use crate::parquet_writer::{OnRowError, WriterEngine, WriterStats};
+use crate::postgres_cloner::{execute_copy, order_by_clause, pg_connect, resolve_password, select_list, table_columns, CopyOptions, RetrySettings, SchemaSettings, SortColumn, SqlHooks};
+use crate::PostgresConnArgs;
+
+/// Export of a PostgreSQL table or query into a Parquet file, the equivalent of `pg2parquet export`.
+/// Unlike the command line tool, the file is written directly to the output path and the progress is not printed by default.
+///
+/// ```no_run
+/// let connection = pg2parquet::PostgresConnArgs { host: vec!["localhost".to_string()], dbname: Some("db".to_string()), ..Default::default() };
+/// let stats = pg2parquet::ExportJob::new(connection, "my_table.parquet")
+///     .table("my_table")
+///     .run()?;
+/// println!("Exported {} rows", stats.rows);
+/// # Ok::<(), String>(())
+/// ```
+pub struct ExportJob {
+	postgres: PostgresConnArgs,
+	output_file: PathBuf,
+	query: Option<String>,
+	table: Option<String>,
+	schema_settings: SchemaSettings,
+	writer_properties: WriterPropertiesBuilder,
+	created_by: Option<String>,
+	job_id: Option<String>,
+	as_map: Option<(String, String)>,
+	options: CopyOptions,
+}
+
+impl ExportJob {
+	pub fn new(postgres: PostgresConnArgs, output_file: impl Into<PathBuf>) -> Self {
+		ExportJob {
+			postgres,
+			output_file: output_file.into(),
+			query: None,
+			table: None,
+			schema_settings: SchemaSettings::default(),
+			writer_properties: WriterProperties::builder()
+				.set_compression(Compression::ZSTD(ZstdLevel::try_new(3).unwrap()))
+				.set_created_by(format!("pg2parquet version {}, using {}", env!("CARGO_PKG_VERSION"), parquet::file::properties::DEFAULT_CREATED_BY)),
+			created_by: None,
+			job_id: None,
+			as_map: None,
+			options: CopyOptions { quiet: true, ..CopyOptions::default() },
+		}
+	}
+
+	/// SQL query to export. Exclusive with `table`
+	pub fn query(mut self, query: impl Into<String>) -> Self {
+		self.query = Some(query.into());
+		self
+	}
+
+	/// Table to export, the name is used in the query as is. Exclusive with `query`
+	pub fn table(mut self, table: impl Into<String>) -> Self {
+		self.table = Some(table.into());
+		self
+	}
+
+	pub fn schema_settings(mut self, schema_settings: SchemaSettings) -> Self {
+		self.schema_settings = schema_settings;
+		self
+	}
+
+	/// Export the values of a type not supported by pg2parquet using the mapper. Must be called after `schema_settings`, which replaces the registered mappers
+	pub fn custom_type_mapper(mut self, mapper: impl CustomTypeMapper + 'static) -> Self {
+		self.schema_settings.custom_type_mappers.push(Arc::new(mapper));
+		self
+	}
+
+	/// Compression, writer version and other properties of the Parquet file. Default: zstd compression
+	pub fn writer_properties(mut self, writer_properties: WriterPropertiesBuilder) -> Self {
+		self.writer_properties = writer_properties;
+		self
+	}
+
+	/// Writer identity stored in the created_by field of the Parquet footer, see `--created-by`. Overrides the value from `writer_properties`
+	pub fn created_by(mut self, created_by: impl Into<String>) -> Self {
+		self.created_by = Some(created_by.into());
+		self
+	}
+
+	/// Identifier of the job written into the pg2parquet.job_id file metadata, see `--job-id`
+	pub fn job_id(mut self, job_id: impl Into<String>) -> Self {
+		self.job_id = Some(job_id.into());
+		self
+	}
+
+	/// Columns the output is sorted by, see `--sort-by`
+	pub fn sort_by(mut self, sort_by: Vec<SortColumn>) -> Self {
+		self.options.sort_by = sort_by;
+		self
+	}
+
+	/// Start a new row group whenever the value of the first `sort_by` column changes, see `--row-group-per-partition-value`
+	pub fn row_group_per_partition_value(mut self, enabled: bool) -> Self {
+		self.options.row_group_per_partition_value = enabled;
+		self
+	}
+
+	/// Collect the null count and approximate distinct count of each column into `WriterStats::columns`
+	pub fn column_stats(mut self, enabled: bool) -> Self {
+		self.options.column_stats = enabled;
+		self
+	}
+
+	/// What to do with rows which cannot be read, see `--on-row-error`. The failed rows are listed in `WriterStats::row_errors`
+	pub fn on_row_error(mut self, on_row_error: OnRowError) -> Self {
+		self.options.on_row_error = on_row_error;
+		self
+	}
+
+	/// Columns identifying the rows in error messages, see `--row-identifier-column`. The primary key of the table is used by default
+	pub fn row_identifier_columns(mut self, columns: Vec<String>) -> Self {
+		self.options.row_identifier = columns;
+		self
+	}
+
+	/// Record the minimum and maximum of the column in the file metadata, see `--watermark-column`
+	pub fn watermark_column(mut self, column: impl Into<String>) -> Self {
+		self.options.watermark_column = Some(column.into());
+		self
+	}
+
+	/// Read the table in chunks of the specified number of rows, see `--chunk-size`. Only applicable to `table` exports
+	pub fn chunk_size(mut self, rows: Option<usize>) -> Self {
+		self.options.chunk_size = rows;
+		self
+	}
+
+	/// Aggregate the `key` and `value` columns into a MAP column, grouped by the remaining columns, see `--as-map`
+	pub fn as_map(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+		self.as_map = Some((key.into(), value.into()));
+		self
+	}
+
+	/// Reconnect and retry the export when the database connection fails, see `--retries`
+	pub fn retry(mut self, retry: RetrySettings) -> Self {
+		self.options.retry = retry;
+		self
+	}
+
+	/// SQL executed on the export connection before the export query, see `--pre-sql`. Can be called multiple times
+	pub fn pre_sql(mut self, sql: impl Into<String>) -> Self {
+		self.options.hooks.pre_sql.push(sql.into());
+		self
+	}
+
+	/// SQL executed on the export connection after the data is written, see `--post-sql`. Can be called multiple times
+	pub fn post_sql(mut self, sql: impl Into<String>) -> Self {
+		self.options.hooks.post_sql.push(sql.into());
+		self
+	}
+
+	/// Maximum memory used by the buffered row group data, see `--max-buffer-memory`
+	pub fn max_buffer_memory(mut self, bytes: usize) -> Self {
+		self.options.max_buffer_memory = bytes;
+		self
+	}
+
+	/// Write out the current row group when its first row is older than `interval`, see `--flush-interval`
+	pub fn flush_interval(mut self, interval: Option<std::time::Duration>) -> Self {
+		self.options.flush_interval = interval;
+		self
+	}
+
+	/// Library used to encode the row groups, see `--engine`
+	pub fn engine(mut self, engine: WriterEngine) -> Self {
+		self.options.engine = engine;
+		self
+	}
+
+	/// Write a CRC32 checksum into the header of each page, see `--page-checksums`
+	pub fn page_checksums(mut self, enabled: bool) -> Self {
+		self.options.page_checksums = enabled;
+		self
+	}
+
+	/// Choose the zstd level and dictionary encoding of each column after the first row group, see `--compression=auto`.
+	/// The first row group is written using the compression of `writer_properties`
+	pub fn auto_compression(mut self, enabled: bool) -> Self {
+		self.options.auto_compression = enabled;
+		self
+	}
+
+	/// Print the schema and progress to stderr
+	pub fn print_progress(mut self, print: bool) -> Self {
+		self.options.quiet = !print;
+		self
+	}
+
+	pub fn run(mut self) -> Result<WriterStats, String> {
+		// the export opens several connections, the password is only asked for once
+		self.postgres = resolve_password(&self.postgres)?;
+		let query = match (self.query, &self.table) {
+			(Some(query), None) => query,
+			(None, Some(table)) => format!("SELECT {} FROM {}{}", select_list(&table_columns(&mut pg_connect(&self.postgres)?, table)?, &[]), table, order_by_clause(&self.options.sort_by)),
+			_ => return Err("Either query or table must be specified".to_string()),
+		};
+		let schema_settings = SchemaSettings {
+			source_table: self.schema_settings.source_table.clone().or_else(|| self.table.clone()),
+			job_id: self.job_id.clone().or_else(|| self.schema_settings.job_id.clone()),
+			as_map: self.as_map.clone(),
+			..self.schema_settings
+		};
+		let writer_properties = match self.created_by {
+			Some(created_by) => self.writer_properties.set_created_by(created_by),
+			None => self.writer_properties,
+		};
+		// execute_copy does not resume the table export if it rewrites the query, e.g. for --as-map
+		let options = CopyOptions { table: self.table.clone(), ..self.options };
+		execute_copy(&self.postgres, &query, &self.output_file, writer_properties, &schema_settings, &options, None)
+	}
+}
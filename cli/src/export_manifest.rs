@@ -0,0 +1,99 @@
+//! Manifest of the files written by a `--per-partition-files` export, which allows `--resume` to skip the completed partitions
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+pub const MANIFEST_FILE_NAME: &str = "_pg2parquet_manifest.json";
+
+/// Partition which was completely exported
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+	/// Qualified name of the partition
+	pub partition: String,
+	/// Partition bound, the range of the rows in the file
+	pub bound: String,
+	pub path: PathBuf,
+	pub rows: usize,
+	/// Size of the file, a file of a different size was replaced after the export and is not considered completed
+	pub size: u64,
+}
+
+/// Hash of the query and the export options, an export can only be resumed with the same ones
+pub fn options_hash(options: &str) -> String {
+	format!("{:x}", Sha256::digest(options))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportManifest {
+	/// The partitioned table
+	pub table: String,
+	/// See [options_hash]
+	pub options_hash: String,
+	pub entries: Vec<ManifestEntry>,
+}
+
+impl ExportManifest {
+	pub fn new(table: &str, options_hash: &str) -> ExportManifest {
+		ExportManifest { table: table.to_string(), options_hash: options_hash.to_string(), entries: Vec::new() }
+	}
+
+	/// Reads the manifest, None if the file does not exist. The paths of the files are resolved relative to the directory of the manifest
+	pub fn load(path: &Path) -> Result<Option<ExportManifest>, String> {
+		let content = match std::fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+		};
+		let invalid = || format!("Invalid export manifest {:?}", path);
+		let value: serde_json::Value = serde_json::from_str(&content).map_err(|e| format!("Invalid export manifest {:?}: {}", path, e))?;
+		let table = value["table"].as_str().ok_or_else(invalid)?.to_string();
+		let options_hash = value["options_hash"].as_str().ok_or_else(invalid)?.to_string();
+		let dir = path.parent().unwrap_or(Path::new(""));
+		let entries = value["files"].as_array().ok_or_else(invalid)?.iter()
+			.map(|f| Some(ManifestEntry {
+				partition: f["partition"].as_str()?.to_string(),
+				bound: f["bound"].as_str()?.to_string(),
+				path: dir.join(f["path"].as_str()?),
+				rows: f["rows"].as_u64()? as usize,
+				size: f["size"].as_u64()?,
+			}))
+			.collect::<Option<Vec<_>>>()
+			.ok_or_else(invalid)?;
+		Ok(Some(ExportManifest { table, options_hash, entries }))
+	}
+
+	/// Writes the manifest under a temporary name and renames it, so that a crash does not leave a truncated manifest behind.
+	/// The paths are stored relative to the directory of the manifest, so that the output directory can be moved before resuming
+	pub fn save(&self, path: &Path) -> Result<(), String> {
+		let dir = path.parent().unwrap_or(Path::new(""));
+		let content = json!({
+			"table": self.table,
+			"options_hash": self.options_hash,
+			"files": self.entries.iter().map(|e| json!({
+				"partition": e.partition,
+				"bound": e.bound,
+				"path": e.path.strip_prefix(dir).unwrap_or(&e.path),
+				"rows": e.rows,
+				"size": e.size,
+			})).collect::<Vec<_>>(),
+		});
+		let mut temp_file = path.as_os_str().to_owned();
+		temp_file.push(".tmp");
+		std::fs::write(&temp_file, serde_json::to_string_pretty(&content).unwrap()).map_err(|e| format!("Failed to write {:?}: {}", temp_file, e))?;
+		std::fs::rename(&temp_file, path).map_err(|e| format!("Failed to rename {:?} to {:?}: {}", temp_file, path, e))
+	}
+
+	/// The entry of the partition, if it was exported with the same bound and its file still exists unchanged
+	pub fn completed(&self, partition: &str, bound: &str) -> Option<&ManifestEntry> {
+		self.entries.iter()
+			.find(|e| e.partition == partition && e.bound == bound)
+			.filter(|e| std::fs::metadata(&e.path).is_ok_and(|m| m.len() == e.size))
+	}
+
+	/// Adds or replaces the entry of the partition
+	pub fn add(&mut self, entry: ManifestEntry) {
+		self.entries.retain(|e| e.partition != entry.partition);
+		self.entries.push(entry);
+	}
+}
@@ -23,7 +23,7 @@ impl<'a> LevelIndexList<'a> {
 		LevelIndexList {
 			index: 0,
 			level: self.level + 1,
-			parent: Some(&self)
+			parent: Some(self)
 		}
 	}
 
@@ -110,6 +110,6 @@ impl LevelIndexState {
 				return i as i16;
 			}
 		}
-		return self.indexes.len() as i16;
+		self.indexes.len() as i16
 	}
 }
@@ -0,0 +1,153 @@
+//! Exports PostgreSQL tables or queries into Parquet files. This is the library behind the `pg2parquet` command line tool,
+//! the [`ExportJob`] builder runs the same export as `pg2parquet export`.
+#![allow(unused_imports)]
+#![allow(dead_code)]
+use std::path::PathBuf;
+
+mod postgresutils;
+mod myfrom;
+mod level_index;
+pub mod parquetinfo;
+pub mod parquet_writer;
+pub mod postgres_cloner;
+pub mod parquet_importer;
+pub mod parquet_verifier;
+pub mod parquet_diff;
+pub mod parquet_compact;
+pub mod ddl;
+pub mod text_export;
+pub mod delta_log;
+pub mod export_manifest;
+pub mod schema_compat;
+pub mod type_catalog;
+mod custom_type;
+pub mod checksum;
+pub mod column_stats;
+pub mod table_stats;
+pub mod cancellation;
+pub mod change_stream;
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
+#[cfg(feature = "arrow")]
+mod arrow_engine;
+mod export_job;
+pub mod masking;
+pub mod output_template;
+pub mod column_definitions;
+mod pg_custom_types;
+mod datatypes;
+mod appenders;
+
+pub use custom_type::{CustomTypeMapper, CustomValue};
+pub use export_job::ExportJob;
+pub use parquet_writer::{OnRowError, WriterEngine, WriterStats};
+pub use postgres_cloner::{execute_copy, RetrySettings, SchemaSettings, SchemaSettingsArrayElementNullability, SchemaSettingsArrayHandling, SchemaSettingsBitHandling, SchemaSettingsBpcharHandling, SchemaSettingsColumnCase, SchemaSettingsCubeHandling, SchemaSettingsDecimalRounding, SchemaSettingsEnumHandling, SchemaSettingsFloatSpecialValues, SchemaSettingsInetHandling, SchemaSettingsIntervalHandling, SchemaSettingsInvalidUtf8, SchemaSettingsIsnHandling, SchemaSettingsJsonHandling, SchemaSettingsListEncoding, SchemaSettingsLoHandling, SchemaSettingsMacaddrHandling, SchemaSettingsMoneyHandling, SchemaSettingsNumericHandling, SchemaSettingsRegHandling, SchemaSettingsSparsevecHandling, SchemaSettingsUuidHandling, SchemaSettingsVectorElementType, SortColumn, SqlHooks};
+
+#[derive(clap::ValueEnum, Debug, Clone)]
+pub enum SslMode {
+    /// Do not use TLS.
+    Disable,
+    /// Attempt to connect with TLS but allow sessions without, the server certificate is not verified (default behavior compiled with SSL support).
+    Prefer,
+    /// Require the use of TLS, but do not verify the server certificate. With --ssl-root-cert, the certificate is verified the same way as with verify-ca.
+    Require,
+    /// Require the use of TLS and verify that the server certificate is signed by a trusted CA, but do not check the host name.
+    VerifyCa,
+    /// Require the use of TLS, verify the server certificate against a trusted CA and check that the host name matches the certificate.
+    VerifyFull,
+}
+
+#[derive(clap::Args, Clone, Default)]
+pub struct PostgresConnArgs {
+    /// Database server host. If not specified, PGHOST environment variable is used. Multiple hosts can be specified as a comma-separated list or by repeating the option, the hosts are then tried in order until a connection succeeds. Parallel exports (--jobs) distribute the connections across the hosts.
+    #[arg(short='H', long, value_delimiter = ',')]
+    pub host: Vec<String>,
+    /// Database user name. If not specified, PGUSER environment variable is used.
+    #[arg(short='U', long)]
+    pub user: Option<String>,
+    /// Database name. If not specified, PGDATABASE environment variable is used.
+    #[arg(short='d', long)]
+    pub dbname: Option<String>,
+    /// Database server port. If not specified, PGPORT environment variable is used, otherwise 5432.
+    #[arg(short='p', long)]
+    pub port: Option<u16>,
+    /// Password to use for the connection. It is recommended to use the PGPASSWORD environment variable or the ~/.pgpass file instead, since process arguments are visible to other users on the system.
+    #[arg(long)]
+    pub password: Option<String>,
+    /// Never ask for the password on the terminal, fail instead when it is not specified using --password, PGPASSWORD or ~/.pgpass. Useful in scripts, where the prompt would wait forever
+    #[arg(short='w', long, hide_short_help = true)]
+    pub no_password: bool,
+    /// Controls whether to use SSL/TLS to connect to the server. If not specified, PGSSLMODE environment variable is used.
+    #[arg(long="sslmode", alias="tlsmode", alias="ssl-mode", alias="tls-mode")]
+    pub sslmode: Option<SslMode>,
+    /// File with a TLS root certificate in PEM or DER (.crt) format. When specified, the default CA certificates are considered untrusted. The option can be specified multiple times. Using this options implies --sslmode=require, which then verifies that the server certificate is signed by one of these certificates.
+    #[arg(long="ssl-root-cert", alias="tls-root-cert")]
+    pub ssl_root_cert: Option<Vec<PathBuf>>,
+    /// Session setting (GUC) applied after connecting, in name=value format. Can be specified multiple times, for example `--pg-setting statement_timeout=0 --pg-setting work_mem=256MB`
+    #[arg(long="pg-setting", hide_short_help = true, value_parser = parse_key_value)]
+    pub pg_settings: Vec<(String, String)>,
+    /// Export data from the specified snapshot (as returned by `pg_export_snapshot()` in another session), using a REPEATABLE READ transaction. This allows exporting multiple tables in parallel pg2parquet processes, consistent with each other. The exporting transaction must remain open until all exports connect
+    #[arg(long, hide_short_help = true)]
+    pub snapshot: Option<String>,
+    /// Connect in read-only mode, all transactions are started with default_transaction_read_only. When multiple hosts are specified, a hot standby is preferred and the primary server is only used when no standby is available.
+    #[arg(long, hide_short_help = true)]
+    pub read_only: bool,
+    /// Send TCP keepalive probes after the connection is idle for this duration, and then repeatedly in the same interval. This keeps the connection open through firewalls and load balancers which drop idle connections, while the export waits for a large row group to be compressed. `0` disables the keepalives. Default: 1m
+    #[arg(long, hide_short_help = true, value_parser = parse_duration)]
+    pub keepalive_interval: Option<std::time::Duration>,
+}
+
+impl std::fmt::Debug for PostgresConnArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let password = self.password.as_ref().map(|_| "********");
+        f.debug_struct("PostgresConnArgs").field("host", &self.host).field("user", &self.user).field("dbname", &self.dbname).field("port", &self.port).field("password", &password).field("no_password", &self.no_password).field("sslmode", &self.sslmode).field("ssl_root_cert", &self.ssl_root_cert).field("pg_settings", &self.pg_settings).field("snapshot", &self.snapshot).field("read_only", &self.read_only).field("keepalive_interval", &self.keepalive_interval).finish()
+    }
+}
+
+impl PostgresConnArgs {
+    /// The --host values, or the comma-separated PGHOST environment variable
+    pub fn hosts(&self) -> Vec<String> {
+        if !self.host.is_empty() {
+            return self.host.clone();
+        }
+        match std::env::var("PGHOST") {
+            Ok(hosts) => hosts.split(',').map(|h| h.trim().to_string()).filter(|h| !h.is_empty()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Connection arguments of the n-th parallel reader, the host list is rotated so that the readers are spread round-robin across the hosts while keeping the failover to the other ones
+    pub fn for_reader(&self, n: usize) -> PostgresConnArgs {
+        let mut hosts = self.hosts();
+        // an exported snapshot only exists on the server it was exported from
+        if !hosts.is_empty() && self.snapshot.is_none() {
+            let len = hosts.len();
+            hosts.rotate_left(n % len);
+        }
+        PostgresConnArgs { host: hosts, ..self.clone() }
+    }
+}
+
+#[doc(hidden)]
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Invalid duration '{}', expected a number followed by ms, s, m or h", s))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("Invalid duration unit '{}', expected ms, s, m or h", unit.trim())),
+    };
+    Ok(std::time::Duration::from_secs_f64(seconds))
+}
+
+#[doc(hidden)]
+pub fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("Invalid value '{}', expected key=value", s)),
+    }
+}
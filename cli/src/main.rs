@@ -2,27 +2,26 @@
 #![allow(dead_code)]
 use std::{sync::Arc, path::PathBuf, process};
 
-use clap::{Parser, ValueEnum, Command};
-use parquet::{basic::{ZstdLevel, BrotliLevel, GzipLevel, Compression}, file::properties::DEFAULT_WRITE_BATCH_SIZE};
-use postgres_cloner::{SchemaSettingsArrayHandling, SchemaSettingsEnumHandling, SchemaSettingsIntervalHandling, SchemaSettingsJsonHandling, SchemaSettingsMacaddrHandling, SchemaSettingsNumericHandling};
-
-mod postgresutils;
-mod myfrom;
-mod level_index;
-mod parquetinfo;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, ValueEnum, Command};
+use clap::parser::ValueSource;
+use parquet::{basic::{ZstdLevel, BrotliLevel, GzipLevel, Compression}, file::{metadata::KeyValue, properties::{WriterPropertiesBuilder, WriterVersion, DEFAULT_WRITE_BATCH_SIZE}}};
+use pg2parquet::{cancellation, change_stream, checksum, ddl, delta_log, export_manifest, parquet_compact, parquet_diff, parquet_importer, parquet_verifier, parquetinfo, postgres_cloner, schema_compat, table_stats, text_export, parse_duration, parse_key_value, PostgresConnArgs};
+use pg2parquet::schema_compat::OnSchemaChange;
+use pg2parquet::postgres_cloner::{SchemaSettingsArrayElementNullability, SchemaSettingsArrayHandling, SchemaSettingsBitHandling, SchemaSettingsBpcharHandling, SchemaSettingsColumnCase, SchemaSettingsColumnOrder, SchemaSettingsCubeHandling, SchemaSettingsDecimalRounding, SchemaSettingsFieldIds, SchemaSettingsMetaColumn, SchemaSettingsEnumHandling, SchemaSettingsFloatSpecialValues, SchemaSettingsInetHandling, SchemaSettingsIntervalHandling, SchemaSettingsInvalidUtf8, SchemaSettingsIsnHandling, SchemaSettingsJsonHandling, SchemaSettingsListEncoding, SchemaSettingsLoHandling, SchemaSettingsMacaddrHandling, SchemaSettingsMoneyHandling, SchemaSettingsNumericHandling, SchemaSettingsRegHandling, SchemaSettingsSparsevecHandling, SchemaSettingsUuidHandling, SchemaSettingsVectorElementType, SortColumn};
+
 mod playground;
-mod parquet_writer;
-mod postgres_cloner;
-mod pg_custom_types;
-mod datatypes;
-mod appenders;
 
-#[cfg(not(any(target_family = "windows", target_arch = "riscv64")))]
+#[cfg(jemalloc_allocator)]
 use jemallocator::Jemalloc;
 
-use crate::postgres_cloner::SchemaSettings;
+use pg2parquet::{SchemaSettings, WriterStats};
+use pg2parquet::parquet_writer::{OnRowError, RowError, WriterEngine};
+use pg2parquet::masking::ColumnMask;
+use pg2parquet::output_template::{OutputTemplate, TemplateValue};
+use pg2parquet::column_definitions::ColumnDefinitions;
+use pg2parquet::column_stats::ColumnStats;
 
-#[cfg(not(any(target_family = "windows", target_arch = "riscv64")))]
+#[cfg(jemalloc_allocator)]
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
@@ -31,81 +30,399 @@ static GLOBAL: Jemalloc = Jemalloc;
 #[command(name = "pg2parquet")]
 #[command(bin_name = "pg2parquet")]
 #[command(version)]
+#[allow(clippy::large_enum_variant)]
 enum CliCommand {
-    /// Dumps something from a parquet file
-    #[command(arg_required_else_help = true, hide = true)]
-    ParquetInfo(ParquetInfoArgs),
+    /// Prints the schema, metadata and sizes of row groups and columns of a Parquet file
+    #[command(arg_required_else_help = true, alias = "parquet-info")]
+    Inspect(InspectArgs),
     #[command(arg_required_else_help = true, hide = true)]
     PlaygroundCreateSomething(PlaygroundCreateSomethingArgs),
     /// Exports a PostgreSQL table or query to a Parquet file
     #[command(arg_required_else_help = true)]
-    Export(ExportArgs)
+    Export(ExportArgs),
+    /// Imports a Parquet file into a PostgreSQL table
+    #[command(arg_required_else_help = true)]
+    Import(ImportArgs),
+    /// Compares a Parquet file with the PostgreSQL table or query it was exported from
+    #[command(arg_required_else_help = true)]
+    Verify(VerifyArgs),
+    /// Compares two Parquet files, for example two exports of the same table, and lists the schema changes and the added, removed and changed rows
+    #[command(arg_required_else_help = true)]
+    Diff(DiffArgs),
+    /// Merges Parquet files with the same schema, for example the files of a partitioned or rolling export, into a single file
+    #[command(arg_required_else_help = true)]
+    Compact(CompactArgs),
+    /// Continuously exports the changes from a logical replication slot into Parquet files
+    #[command(arg_required_else_help = true)]
+    Stream(StreamArgs),
+    /// Estimates the size of a Parquet export of a table by encoding a random sample of its rows with different compression settings
+    #[command(arg_required_else_help = true)]
+    Estimate(EstimateArgs),
+    /// Encodes a sample of table rows with a matrix of compression algorithms and levels, and compares the resulting sizes and encoding times
+    #[command(arg_required_else_help = true)]
+    Bench(BenchArgs),
 }
 
 #[derive(clap::Args, Debug, Clone)]
-struct ExportArgs {
-    /// Path to the output file. If the file exists, it will be overwritten.
+struct ImportArgs {
+    /// Path to the input Parquet file
+    #[arg(long, short = 'i')]
+    input_file: PathBuf,
+    /// Target table. If it does not exist, it is created with column types corresponding to the Parquet schema, otherwise the rows are appended to it. Structs are converted to composite types only when appending to an existing table, new columns use jsonb
+    #[arg(long, short = 't')]
+    table: String,
+    /// Avoid printing unnecessary information. Only errors will be written to stderr
+    #[arg(long, hide_short_help = true)]
+    quiet: bool,
+    #[command(flatten)]
+    postgres: PostgresConnArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct VerifyArgs {
+    /// Path to the Parquet file to verify
+    #[arg(long, short = 'i')]
+    input_file: PathBuf,
+    /// SQL query to compare the file with. Exclusive with --table. If neither is specified, the query stored in the pg2parquet.query file metadata is used
+    #[arg(long, short = 'q', conflicts_with = "table")]
+    query: Option<String>,
+    /// Table to compare the file with. Exclusive with --query
+    #[arg(long, short = 't')]
+    table: Option<String>,
+    /// Also compare minimum and maximum values of integer, date, timestamp and text columns with the Parquet statistics
+    #[arg(long, hide_short_help = true)]
+    min_max: bool,
+    #[command(flatten)]
+    postgres: PostgresConnArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct DiffArgs {
+    /// The older Parquet file
+    old_file: PathBuf,
+    /// The newer Parquet file
+    new_file: PathBuf,
+    /// Comma separated list of columns identifying the rows, for example the primary key of the exported table. The rows are matched by these columns, their values must be unique in each file
+    #[arg(long, short = 'k', required = true, value_delimiter = ',')]
+    key: Vec<String>,
+    /// Maximum number of the added, removed and changed rows listed in the output, all of them are counted
+    #[arg(long, default_value_t = 10)]
+    max_rows: usize,
+    /// Print the differences in JSON format
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct CompactArgs {
+    /// Input Parquet files or directories, all `*.parquet` files in a directory are merged, ordered by name
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Path to the output Parquet file. It may be in one of the input directories, it is not read as an input
     #[arg(long, short = 'o')]
     output_file: PathBuf,
+    /// Decode the rows and compress them again with this algorithm. By default, the compressed column chunks are copied without decoding and the row groups of the input files are kept
+    #[arg(long)]
+    compression: Option<ParquetCompression>,
+    /// Compression level used for zstd, brotli and gzip. Default: 3
+    #[arg(long, hide_short_help = true, requires = "compression")]
+    compression_level: Option<i32>,
+    /// Comma separated list of top-level columns the rows are sorted by, each optionally followed by DESC and NULLS FIRST/LAST. All rows are loaded into memory. The ordering is recorded in the sorting_columns metadata
+    #[arg(long, value_delimiter = ',', value_parser = parse_sort_column)]
+    sort_by: Vec<SortColumn>,
+    /// Maximum number of rows in a row group when the rows are compressed again or sorted. Smaller row groups of the input files are merged
+    #[arg(long, hide_short_help = true, default_value = "1000000", value_parser = parse_row_count)]
+    row_group_size: usize,
+    /// Parquet format version of the data pages written when the rows are compressed again or sorted, see `export --parquet-writer-version`
+    #[arg(long, hide_short_help = true)]
+    parquet_writer_version: Option<ParquetWriterVersion>,
+    /// Avoid printing unnecessary information. Only errors will be written to stderr
+    #[arg(long, hide_short_help = true)]
+    quiet: bool,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct StreamArgs {
+    /// Directory where the Parquet files are written. Each file contains complete transactions and is named by the slot and the LSN of its last change (`slot-00000000016B3748.parquet`). The columns are lsn, xid, commit_time, op (insert, update, delete or truncate), schema, table, before and after (JSON objects with the old and new values)
+    #[arg(long, short = 'o', required_unless_present = "output_template")]
+    output_dir: Option<PathBuf>,
+    /// Path of the written files with placeholders, used instead of --output-dir, for example `changes/{date}/{slot}-{lsn}.parquet`. {slot} is the slot name, {lsn} the LSN of the last change in hexadecimal, {date} the UTC date when the file is written and {seq} the number of the file since the start, counted from 0. The template must contain {lsn}, so that the files of different runs do not overwrite each other
+    #[arg(long, hide_short_help = true, conflicts_with = "output_dir", value_parser = parse_stream_template)]
+    output_template: Option<OutputTemplate>,
+    /// Name of the logical replication slot. The slot is advanced only after the changes are written into a file, changes not yet written when pg2parquet is stopped are exported again on the next start
+    #[arg(long)]
+    slot: String,
+    /// Output plugin of the replication slot
+    #[arg(long, default_value = "pgoutput")]
+    plugin: change_stream::OutputPlugin,
+    /// Comma separated list of publications to export, required with the pgoutput plugin
+    #[arg(long, value_delimiter = ',')]
+    publication: Vec<String>,
+    /// Create the replication slot if it does not exist
+    #[arg(long)]
+    create_slot: bool,
+    /// Start a new file when the current one has at least this many changes
+    #[arg(long, hide_short_help = true, default_value_t = 100000)]
+    rows_per_file: usize,
+    /// Start a new file when the first change of the current one is older than this, for example `30s` or `5m`
+    #[arg(long, hide_short_help = true, default_value = "1m", value_parser = parse_duration)]
+    file_interval: std::time::Duration,
+    /// How often to check for new changes
+    #[arg(long, hide_short_help = true, default_value = "1s", value_parser = parse_duration)]
+    poll_interval: std::time::Duration,
+    /// Stop after exporting all changes available in the slot instead of waiting for new ones
+    #[arg(long, hide_short_help = true)]
+    exit_when_idle: bool,
+    /// Avoid printing unnecessary information. Only errors will be written to stderr
+    #[arg(long, hide_short_help = true)]
+    quiet: bool,
+    #[command(flatten)]
+    postgres: PostgresConnArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct BenchArgs {
+    /// Which table should be sampled
+    #[arg(long, short = 't')]
+    table: String,
+    /// Number of randomly sampled rows, for example `100000` or `1e6`. The rows are read into memory before the encoding starts
+    #[arg(long, default_value = "100000", value_parser = parse_row_count)]
+    rows: usize,
+    /// Comma separated list of compression algorithms to compare
+    #[arg(long, value_delimiter = ',', default_value = "zstd,lz4-raw,snappy,gzip,brotli,none")]
+    compression: Vec<ParquetCompression>,
+    /// Comma separated list of compression levels tried with each of zstd, brotli and gzip. Levels which are out of range of an algorithm are skipped for it
+    #[arg(long, value_delimiter = ',', default_value = "1,3,9")]
+    compression_level: Vec<i32>,
+    /// Parquet format version of the data pages, see `export --parquet-writer-version`
+    #[arg(long, hide_short_help = true)]
+    parquet_writer_version: Option<ParquetWriterVersion>,
+    /// Print the results in JSON format
+    #[arg(long)]
+    json: bool,
+    #[command(flatten)]
+    postgres: PostgresConnArgs,
+    #[command(flatten)]
+    schema_settings: SchemaSettingsArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct EstimateArgs {
+    /// Which table should be estimated
+    #[arg(long, short = 't')]
+    table: String,
+    /// Number of randomly sampled rows, the estimate is extrapolated from their size using the number of rows in the table statistics
+    #[arg(long, default_value = "100000", value_parser = parse_row_count)]
+    sample_rows: usize,
+    /// Comma separated list of compression algorithms to compare
+    #[arg(long, value_delimiter = ',', default_value = "zstd,snappy,lz4-raw,gzip,none")]
+    compression: Vec<ParquetCompression>,
+    /// Compression level used for zstd, brotli and gzip. Default: 3
+    #[arg(long, hide_short_help = true)]
+    compression_level: Option<i32>,
+    /// Parquet format version of the data pages, see `export --parquet-writer-version`
+    #[arg(long, hide_short_help = true)]
+    parquet_writer_version: Option<ParquetWriterVersion>,
+    /// Print the estimate in JSON format
+    #[arg(long)]
+    json: bool,
+    #[command(flatten)]
+    postgres: PostgresConnArgs,
+    #[command(flatten)]
+    schema_settings: SchemaSettingsArgs,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct ExportArgs {
+    /// Path to the output file. If the file exists, it will be overwritten. The data is written into a temporary file (`output.parquet.tmp`), which is renamed to the final path when the export succeeds
+    #[arg(long, short = 'o', required_unless_present_any = ["dry_run", "output_template"])]
+    output_file: Option<PathBuf>,
+    /// Output path with placeholders, used instead of --output-file, for example `out/{table}/{date}/part-{seq:05}.parquet`. {table} is the exported table (or partition with --per-partition-files, `query` for --query exports), {date} is the UTC date of the export (YYYY-MM-DD) and {seq} is the number of the file, counted from 0. With --per-partition-files, the template must contain {table} or {seq}. Missing directories are created
+    #[arg(long, hide_short_help = true, conflicts_with = "output_file", value_parser = parse_export_template)]
+    output_template: Option<OutputTemplate>,
     /// SQL query to execute. Exclusive with --table
     #[arg(long, short = 'q')]
     query: Option<String>,
+    /// Column names and types of the --query result, for example `--columns 'id int, name text'`. Needed for functions returning SETOF record, such as `SELECT * FROM my_report_fn(1)`, the list is added to the function call as its column definition list. For other queries, the result columns are renamed and cast to the specified types
+    #[arg(long, hide_short_help = true, requires = "query", value_parser = ColumnDefinitions::parse)]
+    columns: Option<ColumnDefinitions>,
     /// Which table should be exported. Exclusive with --query
     #[arg(long, short = 't')]
     table: Option<String>,
-    /// Compression applied on the output file. Default: zstd, change to Snappy or None if it's too slow
+    /// SQL condition filtering the exported rows, for example `--where 'created_at > now() - interval 1 day'`. With --query, the query is wrapped in a subquery
+    #[arg(long = "where", hide_short_help = true)]
+    where_clause: Option<String>,
+    /// Add a column computed from an SQL expression to the exported table, for example `--add-column "full_name=first_name || ' ' || last_name"`. Only applicable with --table, the expression may reference all columns of the table
+    #[arg(long = "add-column", hide_short_help = true, value_parser = parse_key_value)]
+    add_columns: Vec<(String, String)>,
+    /// Export at most N rows
+    #[arg(long, hide_short_help = true)]
+    limit: Option<u64>,
+    /// Remove duplicate rows by adding DISTINCT to the query. With --query, the query is wrapped in a subquery
+    #[arg(long, hide_short_help = true)]
+    distinct: bool,
+    /// Comma separated list of columns, a row is skipped when it has the same values of these columns as the previous row. The duplicates are removed while writing, so the rows should be sorted by these columns (for example using --sort-by), only consecutive duplicates are detected
+    #[arg(long, hide_short_help = true, value_delimiter = ',')]
+    distinct_on: Vec<String>,
+    /// Record the minimum and maximum of the column in the `pg2parquet.watermark.min` and `pg2parquet.watermark.max` file metadata, so that the next incremental export (`--where 'updated_at > <max>'`) can be derived from the previously written files. Supports integer, date, timestamp and timestamptz columns
+    #[arg(long, hide_short_help = true)]
+    watermark_column: Option<String>,
+    /// Aggregate the rows into a MAP column, for exporting entity-attribute-value tables. `--as-map attribute value` groups the rows by the remaining columns and writes the attribute-value pairs of each group as a map named after the value column. Rows with a NULL key are skipped, the keys should be unique within each group
+    #[arg(long, hide_short_help = true, num_args = 2, value_names = ["KEY_COL", "VALUE_COL"], conflicts_with_all = ["chunk_size", "per_partition_files"])]
+    as_map: Option<Vec<String>>,
+    /// Read the table in chunks of N rows (for example `--chunk-size 10_000_000`), each using a separate query which continues after the primary key of the last exported row. This keeps the individual queries short on very large tables. Only applicable with --table, requires a primary key or a unique index on NOT NULL columns, which is appended to the --sort-by columns when they are not unique
+    #[arg(long, hide_short_help = true, value_parser = parse_row_count, conflicts_with_all = ["query", "where_clause", "limit", "sample", "add_columns", "distinct"])]
+    chunk_size: Option<usize>,
+    /// Export a random sample of the rows, the value is the fraction of rows to keep (for example 0.01 for 1%). Tables are sampled using `TABLESAMPLE BERNOULLI`, query results are filtered using random()
+    #[arg(long, hide_short_help = true, value_parser = parse_fraction)]
+    sample: Option<f64>,
+    /// Format of the output file. CSV and JSONL values are formatted by PostgreSQL, --mask, --rename-column and --column-case are applied in the query and the other type handling options can only be used with Parquet and Arrow
+    #[arg(long, hide_short_help = true, default_value = "parquet")]
+    format: OutputFormat,
+    /// Compression applied on the output file. Default: zstd, change to Snappy or None if it's too slow. With `auto`, incompressible binary columns are stored uncompressed, text is compressed with a higher zstd level and the dictionary is disabled for columns with mostly distinct values
     #[arg(long, hide_short_help = true)]
     compression: Option<ParquetCompression>,
     /// Compression level of the output file compressor. Only relevant for zstd, brotli and gzip. Default: 3
     #[arg(long, hide_short_help = true)]
     compression_level: Option<i32>,
+    /// Parquet format version of the written data pages. Version 2.0 uses data page V2 and delta encodings for integer and string columns, which usually leads to smaller files, but it is not supported by some older readers. Default: 1.0
+    #[arg(long, hide_short_help = true)]
+    parquet_writer_version: Option<ParquetWriterVersion>,
+    /// Only connect to the database and print the Parquet schema of the output to stdout, without exporting any data. `--dry-run=json` prints the schema in JSON format
+    #[arg(long, hide_short_help = true, num_args = 0..=1, require_equals = true, default_missing_value = "text")]
+    dry_run: Option<DryRunFormat>,
+    /// Print a summary of the export to stdout when it finishes (number of rows, sizes, duration, warnings and output files), or the error message if it fails. For Parquet exports, the summary also includes the null count and approximate distinct count of each column
+    #[arg(long, hide_short_help = true)]
+    report: Option<ReportFormat>,
+    /// Include the changes of the `pg_stat_user_tables` counters of the exported table during the export in the --report (sequential and index scans, read tuples), together with the live tuple estimate and the time of the last ANALYZE. The counters also include the activity of other sessions
+    #[arg(long, hide_short_help = true, requires_all = ["table", "report"])]
+    report_table_stats: bool,
     /// Avoid printing unnecessary information (schema and progress). Only errors will be written to stderr
     #[arg(long, hide_short_help = true)]
     quiet: bool,
+    /// Maximum memory used by the buffered column data of the current row group, for example `2GiB` or `500MB`. When it is exceeded, the row group is written out early, even if it has fewer rows than the configured row group size
+    #[arg(long, hide_short_help = true, default_value = "500MiB", value_parser = parse_byte_size)]
+    max_buffer_memory: usize,
+    /// Write out the current row group when its first row was received longer ago than this interval (for example `5m` or `1h`), even if it is under the size limits. Useful for slow queries (e.g. over foreign tables), so that the rows are not kept only in memory for hours.
+    #[arg(long, hide_short_help = true, value_parser = parse_duration)]
+    flush_interval: Option<std::time::Duration>,
+    /// Library used to encode the row groups. The `arrow` engine converts them into Arrow record batches written by the arrow-rs ArrowWriter, which also stores the Arrow schema in the file metadata. It only supports tables without nested columns (arrays, composites, ranges) and types with an Arrow equivalent, other tables are written by the default `parquet` engine
+    #[arg(long, hide_short_help = true, default_value = "parquet")]
+    engine: WriterEngine,
+    /// Write a CRC32 checksum into the header of each Parquet page, so that readers can detect files corrupted in storage or transit. Not supported by the arrow engine
+    #[arg(long, hide_short_help = true)]
+    page_checksums: bool,
+    /// Comma separated list of columns the output is sorted by, each optionally followed by DESC and NULLS FIRST/LAST (for example `--sort-by 'id,created_at DESC'`). With --table, the ORDER BY clause is added to the query, with --query it must already be sorted this way. The ordering is recorded in the sorting_columns metadata of each row group.
+    #[arg(long, hide_short_help = true, value_delimiter = ',', value_parser = parse_sort_column)]
+    sort_by: Vec<SortColumn>,
+    /// Start a new row group whenever the value of the first --sort-by column changes, so that the min/max statistics of each row group cover a single value (for example a single date) and readers can skip the row groups of other values
+    #[arg(long, hide_short_help = true, requires = "sort_by")]
+    row_group_per_partition_value: bool,
+    /// What to do when a value of a row cannot be read (for example invalid UTF-8 in a text column): fail the export, skip the row or write NULL instead of the column. The affected rows are listed in the --report
+    #[arg(long, hide_short_help = true, default_value = "fail")]
+    on_row_error: OnRowError,
+    /// Comma separated list of columns identifying the rows in error messages and in the --report (for example `--row-identifier-column id`). With --table, the primary key is used by default, otherwise the first text or integer value of the row is shown
+    #[arg(long, hide_short_help = true, value_delimiter = ',')]
+    row_identifier_column: Vec<String>,
+    /// Additional key=value pair written into the Parquet file metadata. Can be specified multiple times. pg2parquet also automatically adds pg2parquet.query, pg2parquet.source_table, pg2parquet.exported_at and pg2parquet.server_version entries. The expressions of generated columns are written into the field metadata of the columns (pg2parquet.generation_expression).
+    #[arg(long, hide_short_help = true, value_parser = parse_key_value)]
+    file_metadata: Vec<(String, String)>,
+    /// Identifier of the job or pipeline run, written into the pg2parquet.job_id file metadata entry, so that the files can be joined back to the run which produced them
+    #[arg(long, hide_short_help = true)]
+    job_id: Option<String>,
+    /// Writer identity stored in the created_by field of the Parquet footer, instead of `pg2parquet version X, using parquet-rs version Y`. Some readers apply workarounds for known bugs of the writer version parsed from this field
+    #[arg(long, hide_short_help = true)]
+    created_by: Option<String>,
+    /// How many times to reconnect and retry the export when the database connection fails. Table exports continue after the last exported row if the table has a primary key or a unique index on NOT NULL columns (appended to the --sort-by columns when they are not unique), other exports are restarted from the beginning
+    #[arg(long, hide_short_help = true, default_value_t = 0)]
+    retries: u32,
+    /// Delay before reconnecting after a connection failure, for example `500ms`, `30s` or `5m`. The delay is multiplied by the number of the attempt
+    #[arg(long, hide_short_help = true, default_value = "30s", value_parser = parse_duration)]
+    retry_backoff: std::time::Duration,
+    /// What to do when the export is interrupted by Ctrl+C (SIGINT) or SIGTERM. The running query is cancelled, so the export stops without waiting for the next row. Second Ctrl+C terminates pg2parquet immediately
+    #[arg(long, hide_short_help = true, default_value = "delete")]
+    on_cancel: CancelMode,
+    /// Fail if the output file already exists, instead of overwriting it
+    #[arg(long, hide_short_help = true)]
+    no_overwrite: bool,
+    /// Compute a checksum of the output file while writing it and store it in a sidecar file (`output.parquet.sha256`, in the `sha256sum` format). The checksum is also included in the --report
+    #[arg(long, hide_short_help = true)]
+    checksum: Option<ChecksumAlgorithm>,
+    /// Treat the directory of the output file as a Delta Lake table and commit the written file into its `_delta_log`. The table is created by the first export, following exports into the same directory must have the same schema
+    #[arg(long, hide_short_help = true)]
+    delta_log: bool,
+    /// Export each leaf partition of a partitioned --table into a separate file. The output path is a directory, which gets a `partition.parquet` file for each partition, sub-partitioned tables are written into subdirectories named by the partition. The partition bounds are stored in the pg2parquet.partition_bound metadata
+    #[arg(long, hide_short_help = true, requires = "table", conflicts_with_all = ["delta_log", "checksum", "sort_by"])]
+    per_partition_files: bool,
+    /// Number of partitions exported in parallel with --per-partition-files, each using a separate connection. When multiple hosts are specified, the partitions are distributed across them round-robin
+    #[arg(long, hide_short_help = true, default_value_t = 1, requires = "per_partition_files")]
+    jobs: usize,
+    /// Continue an interrupted --per-partition-files export: the partitions listed as completed in the `_pg2parquet_manifest.json` file in the output directory are skipped, unless their file was changed or removed since, and the other ones are exported. The query and the export options must be the same as in the interrupted export
+    #[arg(long, hide_short_help = true, requires = "per_partition_files")]
+    resume: bool,
+    /// Run REFRESH MATERIALIZED VIEW before exporting, the --table must be a materialized view
+    #[arg(long, hide_short_help = true, requires = "table")]
+    refresh_matview: bool,
+    /// When the --table is a foreign table, cancel the export if it takes longer than this (sets statement_timeout), for example `30s` or `10m`. Protects against foreign servers which are slow or stop responding
+    #[arg(long, hide_short_help = true, requires = "table", value_parser = parse_duration)]
+    fdw_timeout: Option<std::time::Duration>,
+    /// SQL executed on the export connection before the export query, for example `SET ROLE exporter`. Can be specified multiple times. Nothing is exported if it fails, so it can also validate the data, for example using a DO block which raises an exception. With --per-partition-files, it runs on the connection of each partition
+    #[arg(long, hide_short_help = true)]
+    pre_sql: Vec<String>,
+    /// SQL executed on the export connection after all data is written, for example to record the export in an audit table. The number of exported rows is available as `current_setting('pg2parquet.rows')`. Can be specified multiple times, the export fails if it fails. With --per-partition-files, it runs after each partition, with the rows of that partition
+    #[arg(long, hide_short_help = true)]
+    post_sql: Vec<String>,
+    /// Take a PostgreSQL advisory lock before exporting and hold it until the export finishes, so that overlapping runs (for example from cron) wait for each other. The key is an integer, or a string which is hashed. Without a value, the key is derived from the --table. The waiting can be limited using `--pg-setting lock_timeout=10min`
+    #[arg(long, hide_short_help = true, num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    lock_key: Option<String>,
+    /// Compare the schema of the export with an existing Parquet file (for example the previous export) before exporting anything. Columns are matched by name and compared by type and nullability, see --on-schema-change
+    #[arg(long, hide_short_help = true)]
+    schema_compat: Option<PathBuf>,
+    /// What to do when the schema differs from the --schema-compat file. Unless the export fails, the differences are listed in the pg2parquet.schema_changes file metadata
+    #[arg(long, hide_short_help = true, default_value = "error", requires = "schema_compat")]
+    on_schema_change: OnSchemaChange,
+    /// Also write the CREATE TABLE statement (and definitions of the used enums, composite types and domains) into the specified SQL file, so that the data can be loaded into another database
+    #[arg(long, hide_short_help = true)]
+    emit_ddl: Option<PathBuf>,
     #[command(flatten)]
     postgres: PostgresConnArgs,
     #[command(flatten)]
     schema_settings: SchemaSettingsArgs,
 }
 
-#[derive(clap::ValueEnum, Debug, Clone)]
-enum SslMode {
-    /// Do not use TLS.
-    Disable,
-    /// Attempt to connect with TLS but allow sessions without (default behavior compiled with SSL support).
-    Prefer,
-    /// Require the use of TLS.
-    Require,
-}
-
-#[derive(clap::Args, Clone)]
-pub struct PostgresConnArgs {
-    /// Database server host
-    #[arg(short='H', long)]
-    host: String,
-    /// Database user name. If not specified, PGUSER environment variable is used.
-    #[arg(short='U', long)]
-    user: Option<String>,
-    #[arg(short='d', long)]
-    dbname: String,
-    #[arg(short='p', long)]
-    port: Option<u16>,
-    /// Password to use for the connection. It is recommended to use the PGPASSWORD environment variable instead, since process arguments are visible to other users on the system.
-    #[arg(long)]
-    password: Option<String>,
-    /// Controls whether to use SSL/TLS to connect to the server.
-    #[arg(long="sslmode", alias="tlsmode", alias="ssl-mode", alias="tls-mode")]
-    sslmode: Option<SslMode>,
-    /// File with a TLS root certificate in PEM or DER (.crt) format. When specified, the default CA certificates are considered untrusted. The option can be specified multiple times. Using this options implies --sslmode=require.
-    #[arg(long="ssl-root-cert", alias="tls-root-cert")]
-    ssl_root_cert: Option<Vec<PathBuf>>
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Parquet,
+    /// Comma separated values with a header line
+    Csv,
+    /// Newline delimited JSON, one object per row
+    Jsonl,
+    /// Arrow IPC file (Feather v2), with the Arrow types of the Parquet schema
+    Arrow,
 }
 
-impl std::fmt::Debug for PostgresConnArgs {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let password = self.password.as_ref().map(|_| "********");
-        f.debug_struct("PostgresConnArgs").field("host", &self.host).field("user", &self.user).field("dbname", &self.dbname).field("port", &self.port).field("password", &password).field("sslmode", &self.sslmode).field("ssl_root_cert", &self.ssl_root_cert).finish()
-    }
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum CancelMode {
+    /// Finish the current row group and write the file footer, the output contains the rows exported so far
+    Finalize,
+    /// Delete the partially written file
+    Delete,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum ChecksumAlgorithm {
+    Sha256,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum DryRunFormat {
+    Text,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum ReportFormat {
+    Json,
 }
 
 #[derive(clap::Args, Debug, Clone)]
@@ -131,21 +448,213 @@ pub struct SchemaSettingsArgs {
     /// How many decimal digits are allowed in numeric/DECIMAL column. By default 38, the largest value which fits in 128 bits. If <= 9, the column is stored as INT32; if <= 18, the column is stored as INT64; otherwise BYTE_ARRAY.
     #[arg(long, hide_short_help = true, default_value_t = 38)]
     decimal_precision: u32,
+    /// How to round numeric values with more fractional digits than --decimal-scale. The number of rounded values is reported after the export
+    #[arg(long, hide_short_help = true, default_value = "half-even")]
+    decimal_rounding: SchemaSettingsDecimalRounding,
     /// Parquet does not support multi-dimensional arrays and arrays with different starting index. pg2parquet flattens the arrays, and this options allows including the stripped information in additional columns.
     #[arg(long, hide_short_help = true, default_value = "plain")]
     array_handling: SchemaSettingsArrayHandling,
+    /// Whether the elements of Parquet LIST columns are OPTIONAL or REQUIRED. PostgreSQL arrays may contain NULL elements, unless the element type is a NOT NULL domain. REQUIRED elements save the definition levels of the column
+    #[arg(long, hide_short_help = true, default_value = "auto")]
+    array_element_nullability: SchemaSettingsArrayElementNullability,
+    /// Encoding of the Parquet LIST columns. The standard three-level encoding supports NULL elements, the two-level encoding is expected by some legacy readers (older Hive and Java Parquet tools)
+    #[arg(long, hide_short_help = true, default_value = "three-level")]
+    list_encoding: SchemaSettingsListEncoding,
+    /// How to handle columns of the `oid` type (and of the `lo` extension type), which usually reference large objects. Only applies to Parquet and Arrow output formats
+    #[arg(long, hide_short_help = true, default_value = "oid")]
+    lo_handling: SchemaSettingsLoHandling,
+    /// Replace the values of a column, in the format `column=mask`. Mask is `sha256` (hex-encoded hash), `null`, `constant:<value>` or `redact-email` (keeps only the first letter and the domain). Can be specified multiple times. Null values are kept as NULL
+    #[arg(long, hide_short_help = true, value_parser = parse_column_mask)]
+    mask: Vec<(String, ColumnMask)>,
+    /// Write a column under a different name, in the format `old_name=new_name`. Can be specified multiple times
+    #[arg(long, hide_short_help = true, value_parser = parse_key_value)]
+    rename_column: Vec<(String, String)>,
+    /// Case folding of the column names, not applied to the columns renamed by --rename-column. Only top-level columns are renamed, fields of composite types are kept unless --flatten-composites is used
+    #[arg(long, hide_short_help = true, default_value = "preserve")]
+    column_case: SchemaSettingsColumnCase,
+    /// Write the fields of composite-typed columns as separate top-level columns named `column_field`, instead of a nested Parquet group. Useful for tools which do not support nested structs
+    #[arg(long, hide_short_help = true)]
+    flatten_composites: bool,
+    /// Order of the columns in the Parquet file: `schema` keeps the order of the query result, `name-asc` sorts the columns by name and `custom:a,b,c` writes the listed columns first (in the given order), followed by the remaining ones. The names refer to the output columns, after --rename-column and --column-case are applied
+    #[arg(long, hide_short_help = true, default_value = "schema")]
+    column_order: SchemaSettingsColumnOrder,
+    /// Assign Parquet field_ids to the top-level columns, so that readers which map the columns by id (such as Iceberg) can track them across renames. `attnum` uses the attribute numbers of the table columns, computed columns get no field_id
+    #[arg(long, hide_short_help = true, default_value = "none")]
+    field_ids: SchemaSettingsFieldIds,
+    /// Set the field_id of an output column explicitly, in the format `column=id`. Can be specified multiple times, overrides --field-ids
+    #[arg(long, hide_short_help = true, value_parser = parse_field_id)]
+    field_id: Vec<(String, i32)>,
+    /// Comma separated list of synthetic columns appended to every row, for tracking the origin of the rows when many files are combined: `row_number` (`_row_number`, number of the row in the file), `exported_at` (`_exported_at`, timestamp of the export) and `source_table` (`_source_table`, name of the exported table)
+    #[arg(long, hide_short_help = true, value_delimiter = ',')]
+    add_meta_columns: Vec<SchemaSettingsMetaColumn>,
+    /// How to handle the trailing spaces of char(n) values. PostgreSQL pads them to the declared length, but ignores the padding when comparing the values
+    #[arg(long, hide_short_help = true, default_value = "preserve")]
+    bpchar_handling: SchemaSettingsBpcharHandling,
+    /// How to handle `uuid` columns
+    #[arg(long, hide_short_help = true, default_value = "binary")]
+    uuid_handling: SchemaSettingsUuidHandling,
+    /// How to handle `inet` columns
+    #[arg(long, hide_short_help = true, default_value = "text")]
+    inet_handling: SchemaSettingsInetHandling,
+    /// How to handle `money` columns
+    #[arg(long, hide_short_help = true, default_value = "decimal")]
+    money_handling: SchemaSettingsMoneyHandling,
+    /// How many decimal digits after the decimal point are stored in `money` values. It depends on the lc_monetary setting of the database server, pg2parquet warns when it does not match
+    #[arg(long, hide_short_help = true, default_value_t = 2, value_parser = clap::value_parser!(i32).range(0..=18))]
+    money_scale: i32,
+    /// How to handle the OID alias types (`regclass`, `regtype`, `regproc`, ...)
+    #[arg(long, hide_short_help = true, default_value = "oid")]
+    reg_handling: SchemaSettingsRegHandling,
+    /// How to handle `bit(n)` columns
+    #[arg(long, hide_short_help = true, default_value = "text")]
+    bit_handling: SchemaSettingsBitHandling,
+    /// How to handle NaN and Infinity in float and numeric columns. Parquet statistics and some readers do not handle NaN well
+    #[arg(long, hide_short_help = true, default_value = "keep")]
+    float_special_values: SchemaSettingsFloatSpecialValues,
+    /// How to store the elements of pgvector `vector` and `halfvec` columns, for example to export quantized embeddings
+    #[arg(long, hide_short_help = true, default_value = "f32")]
+    vector_element_type: SchemaSettingsVectorElementType,
+    /// The factor by which the vector elements are multiplied when they are converted to integers by --vector-element-type=i8. The default maps the range -1..1 to -127..127
+    #[arg(long, hide_short_help = true, default_value_t = 127.0)]
+    vector_i8_scale: f32,
+    /// Declare the vector columns with a fixed dimension (such as `vector(1536)`) as FixedSizeList in the embedded Arrow schema, so that Arrow-based readers do not need to check the length of each value. The dimensions are always saved in the `pg2parquet.vector_dims` file metadata
+    #[arg(long, hide_short_help = true)]
+    vector_fixed_size_list: bool,
+    /// How to handle pgvector `sparsevec` columns
+    #[arg(long, hide_short_help = true, default_value = "map")]
+    sparsevec_handling: SchemaSettingsSparsevecHandling,
+    /// How to handle the `cube` and `seg` extension types
+    #[arg(long, hide_short_help = true, default_value = "bounds")]
+    cube_handling: SchemaSettingsCubeHandling,
+    /// Comma separated list of types which are converted to text by the server, for extension types which pg2parquet does not support (or which have no binary representation). `uri`, `semver` and `prefix_range` are always converted
+    #[arg(long, hide_short_help = true, value_delimiter = ',')]
+    text_types: Vec<String>,
+    /// How to handle the product and publication numbers of the `isn` extension (`ean13`, `isbn`, `issn`, `ismn`, `upc`)
+    #[arg(long, hide_short_help = true, default_value = "text")]
+    isn_handling: SchemaSettingsIsnHandling,
+    /// Write NULL values of text columns as this string, for consumers which cannot distinguish NULL from an empty string. In CSV output, it applies to the NULL values of all columns
+    #[arg(long, hide_short_help = true)]
+    null_string: Option<String>,
+    /// Write empty strings in text columns as NULL (or as the --null-string). Not supported in CSV and JSONL output
+    #[arg(long, hide_short_help = true)]
+    empty_string_as_null: bool,
+    /// How to handle text values which are not valid UTF-8, which databases with the SQL_ASCII encoding can return
+    #[arg(long, hide_short_help = true, default_value = "error")]
+    invalid_utf8: SchemaSettingsInvalidUtf8,
+}
+
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ParquetCompression {
+    None, Snappy, Gzip, Lzo, Brotli,
+    Lz4, // deprecated Hadoop framing of LZ4, lz4-raw should be preferred
+    Zstd,
+    #[clap(alias = "lz4raw", alias = "lz4_raw")]
+    Lz4Raw,
+    /// zstd with the level and dictionary encoding of each column chosen from how well it compressed in the first row group
+    Auto,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ParquetWriterVersion {
+    #[clap(name = "1.0", alias = "1", alias = "v1")]
+    V1,
+    #[clap(name = "2.0", alias = "2", alias = "v2")]
+    V2,
+}
+
+fn parse_sort_column(s: &str) -> Result<SortColumn, String> {
+    let mut parts = s.split_whitespace();
+    let name = parts.next().ok_or_else(|| "Column name is missing".to_string())?;
+    let modifiers: Vec<String> = parts.map(|p| p.to_uppercase()).collect();
+    let modifiers: Vec<&str> = modifiers.iter().map(|m| m.as_str()).collect();
+    let (descending, nulls) = match modifiers.as_slice() {
+        [] => (false, &[][..]),
+        ["ASC", rest @ ..] => (false, rest),
+        ["DESC", rest @ ..] => (true, rest),
+        rest => (false, rest),
+    };
+    let nulls_first = match nulls {
+        [] => descending, // PostgreSQL default: NULLS LAST for ASC, NULLS FIRST for DESC
+        ["NULLS", "FIRST"] => true,
+        ["NULLS", "LAST"] => false,
+        _ => return Err(format!("Invalid sort specification '{}', expected `column [ASC|DESC] [NULLS FIRST|NULLS LAST]`", s.trim())),
+    };
+    Ok(SortColumn { name: name.to_string(), descending, nulls_first })
 }
 
+fn parse_column_mask(s: &str) -> Result<(String, ColumnMask), String> {
+    let (column, mask) = parse_key_value(s)?;
+    Ok((column, mask.parse()?))
+}
+
+fn parse_field_id(s: &str) -> Result<(String, i32), String> {
+    let (column, id) = parse_key_value(s)?;
+    let id = id.trim().parse::<i32>().map_err(|_| format!("Invalid field_id '{}', expected an integer", id))?;
+    Ok((column, id))
+}
+
+fn parse_row_count(s: &str) -> Result<usize, String> {
+    let s_clean = s.replace('_', "");
+    match s_clean.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        // scientific notation, such as 1e6
+        _ => match s_clean.parse::<f64>() {
+            Ok(f) if f >= 1.0 && f.fract() == 0.0 && f <= usize::MAX as f64 => Ok(f as usize),
+            _ => Err(format!("Invalid number of rows '{}'", s)),
+        },
+    }
+}
 
-#[derive(ValueEnum, Debug, Clone)]
-enum ParquetCompression { None, Snappy, Gzip, Lzo, Brotli, Lz4, Zstd }
+fn parse_export_template(s: &str) -> Result<OutputTemplate, String> {
+    OutputTemplate::parse(s, &["table", "date", "seq"])
+}
+
+fn parse_stream_template(s: &str) -> Result<OutputTemplate, String> {
+    let template = OutputTemplate::parse(s, &["slot", "lsn", "date", "seq"])?;
+    if !template.contains("lsn") {
+        return Err("The output template must contain {lsn}".to_string());
+    }
+    Ok(template)
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(f) if f > 0.0 && f <= 1.0 => Ok(f),
+        _ => Err(format!("Invalid fraction '{}', expected a number between 0 and 1", s)),
+    }
+}
+
+fn parse_byte_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Invalid size '{}', expected a number followed by B, kB, MB, GB, KiB, MiB or GiB", s))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000 * 1000,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err(format!("Invalid size unit '{}', expected B, kB, MB, GB, KiB, MiB or GiB", unit.trim())),
+    };
+    match (number * multiplier as f64) as usize {
+        0 => Err(format!("Invalid size '{}', it must be greater than zero", s)),
+        size => Ok(size),
+    }
+}
 
 #[derive(clap::Args, Debug, Clone)]
-// #[command(author, version, about, long_about = None)]
-struct ParquetInfoArgs {
+struct InspectArgs {
     parquet_file: PathBuf,
-    // #[arg(long)]
-    // manifest_path: Option<std::path::PathBuf>,
+    /// Also print the first N rows of the file
+    #[arg(long, default_value_t = 0)]
+    sample_rows: usize,
+    /// Print the information in JSON format
+    #[arg(long)]
+    json: bool,
 }
 
 #[derive(clap::Args, Debug, Clone)]
@@ -169,31 +678,302 @@ fn handle_result<T, TErr: ToString>(r: Result<T, TErr>) -> T {
     }
 }
 
-fn get_compression(args: &ExportArgs) -> Result<parquet::basic::Compression, parquet::errors::ParquetError> {
-    let lvl = args.compression_level;
+fn get_compression(compression: Option<ParquetCompression>, lvl: Option<i32>) -> Result<parquet::basic::Compression, parquet::errors::ParquetError> {
     let level_not_supported = ||
         if lvl.is_some() {
             Err(parquet::errors::ParquetError::General(format!(
                 "Compression algorithm {:?} does not allow setting --compression-level option",
-                args.compression.as_ref().unwrap_or(&ParquetCompression::Zstd)
+                compression.as_ref().unwrap_or(&ParquetCompression::Zstd)
             )))
         } else {
             Ok(())
         };
-    let compression = match args.compression {
+    let compression = match compression {
         None => parquet::basic::Compression::ZSTD(ZstdLevel::try_new(lvl.unwrap_or(3))?),
+        // the first row group is compressed with the default, the other groups use the settings chosen from it
+        Some(ParquetCompression::Auto) => { level_not_supported()?; parquet::basic::Compression::ZSTD(ZstdLevel::default()) }
         Some(ParquetCompression::Brotli) => parquet::basic::Compression::BROTLI(BrotliLevel::try_new(lvl.unwrap_or(3) as u32)?),
         Some(ParquetCompression::Gzip) => parquet::basic::Compression::GZIP(GzipLevel::try_new(lvl.unwrap_or(3) as u32)?),
         Some(ParquetCompression::Zstd) => parquet::basic::Compression::ZSTD(ZstdLevel::try_new(lvl.unwrap_or(3))?),
         Some(ParquetCompression::Lzo) => { level_not_supported()?; parquet::basic::Compression::LZO }
         Some(ParquetCompression::Lz4) => { level_not_supported()?; parquet::basic::Compression::LZ4 }
+        Some(ParquetCompression::Lz4Raw) => { level_not_supported()?; parquet::basic::Compression::LZ4_RAW }
         Some(ParquetCompression::Snappy) => { level_not_supported()?; parquet::basic::Compression::SNAPPY }
         Some(ParquetCompression::None) => { level_not_supported()?; parquet::basic::Compression::UNCOMPRESSED }
     };
     Ok(compression)
 }
 
-fn perform_export(args: ExportArgs) {
+fn write_batch_size(compression: Compression) -> usize {
+    match compression {
+        // use smaller page size if shitty compression is chosen
+        Compression::UNCOMPRESSED | Compression::SNAPPY | Compression::LZO | Compression::LZ4 | Compression::LZ4_RAW =>
+            DEFAULT_WRITE_BATCH_SIZE,
+        Compression::ZSTD(lvl) if lvl.compression_level() <= 2 =>
+            DEFAULT_WRITE_BATCH_SIZE,
+        // otherwise prefer larger page size to improve the compression ratio slightly
+        // the parquet library doesn't parallelize compression anyway
+        _ => 1024 * 128,
+    }
+}
+
+fn writer_version(version: Option<ParquetWriterVersion>) -> WriterVersion {
+    match version {
+        None | Some(ParquetWriterVersion::V1) => WriterVersion::PARQUET_1_0,
+        Some(ParquetWriterVersion::V2) => WriterVersion::PARQUET_2_0,
+    }
+}
+
+fn schema_settings(args: &SchemaSettingsArgs) -> SchemaSettings {
+    SchemaSettings {
+        macaddr_handling: args.macaddr_handling,
+        json_handling: args.json_handling,
+        enum_handling: args.enum_handling,
+        interval_handling: args.interval_handling,
+        numeric_handling: args.numeric_handling,
+        decimal_scale: args.decimal_scale,
+        decimal_precision: args.decimal_precision,
+        decimal_rounding: args.decimal_rounding,
+        array_handling: args.array_handling,
+        array_element_nullability: args.array_element_nullability,
+        list_encoding: args.list_encoding,
+        lo_handling: args.lo_handling,
+        // set by perform_export
+        as_map: None,
+        column_definitions: None,
+        column_masks: args.mask.clone(),
+        column_renames: args.rename_column.clone(),
+        column_case: args.column_case,
+        flatten_composites: args.flatten_composites,
+        column_order: args.column_order.clone(),
+        field_ids: args.field_ids,
+        field_id_mapping: args.field_id.clone(),
+        meta_columns: args.add_meta_columns.clone(),
+        exported_at: chrono::Utc::now(),
+        source_table: None,
+        job_id: None,
+        bpchar_handling: args.bpchar_handling,
+        uuid_handling: args.uuid_handling,
+        inet_handling: args.inet_handling,
+        money_handling: args.money_handling,
+        money_scale: args.money_scale,
+        reg_handling: args.reg_handling,
+        bit_handling: args.bit_handling,
+        float_special_values: args.float_special_values,
+        vector_element_type: args.vector_element_type,
+        vector_i8_scale: args.vector_i8_scale,
+        vector_fixed_size_list: args.vector_fixed_size_list,
+        sparsevec_handling: args.sparsevec_handling,
+        cube_handling: args.cube_handling,
+        text_types: args.text_types.clone(),
+        isn_handling: args.isn_handling,
+        null_string: args.null_string.clone(),
+        empty_string_as_null: args.empty_string_as_null,
+        invalid_utf8: args.invalid_utf8,
+        reg_names: Default::default(),
+        custom_type_mappers: Vec::new(),
+        type_catalog: Default::default(),
+        value_counters: Default::default(),
+    }
+}
+
+fn export_query(args: &ExportArgs, table_columns: &[String]) -> String {
+    let filtered = args.where_clause.is_some() || args.limit.is_some() || args.sample.is_some() || args.distinct;
+    let select_list = postgres_cloner::select_list(table_columns, &args.add_columns);
+    let distinct = if args.distinct { "DISTINCT " } else { "" };
+    let mut conditions = Vec::new();
+    let mut query = match (&args.query, &args.table) {
+        (Some(query), _) if !filtered => return query.clone(),
+        (Some(query), _) => {
+            if let Some(fraction) = args.sample {
+                conditions.push(format!("random() < {}", fraction));
+            }
+            format!("SELECT {}* FROM ({}) q", distinct, query)
+        },
+        (None, Some(table)) => match args.sample {
+            Some(fraction) => format!("SELECT {}{} FROM {} TABLESAMPLE BERNOULLI ({})", distinct, select_list, table, fraction * 100.0),
+            None => format!("SELECT {}{} FROM {}", distinct, select_list, table),
+        },
+        (None, None) => unreachable!(),
+    };
+    if let Some(where_clause) = &args.where_clause {
+        conditions.push(format!("({})", where_clause));
+    }
+    if !conditions.is_empty() {
+        query.push_str(" WHERE ");
+        query.push_str(&conditions.join(" AND "));
+    }
+    if args.query.is_none() {
+        query.push_str(&postgres_cloner::order_by_clause(&args.sort_by));
+    }
+    if let Some(limit) = args.limit {
+        query.push_str(&format!(" LIMIT {}", limit));
+    }
+    query
+}
+
+fn has_compression_level(algorithm: ParquetCompression) -> bool {
+    matches!(algorithm, ParquetCompression::Zstd | ParquetCompression::Brotli | ParquetCompression::Gzip)
+}
+
+/// Writer properties used to encode the samples of `estimate` and `bench`, labeled by the compression
+fn sample_properties(algorithm: ParquetCompression, level: Option<i32>, version: Option<ParquetWriterVersion>) -> Result<(String, WriterPropertiesBuilder), parquet::errors::ParquetError> {
+    if let ParquetCompression::Auto = algorithm {
+        return Err(parquet::errors::ParquetError::General("auto compression is chosen during the export, it cannot be compared on a sample".to_string()));
+    }
+    let compression = get_compression(Some(algorithm), level)?;
+    let props = parquet::file::properties::WriterProperties::builder()
+        .set_compression(compression)
+        .set_write_batch_size(write_batch_size(compression))
+        .set_writer_version(writer_version(version));
+    let name = algorithm.to_possible_value().unwrap().get_name().to_string();
+    Ok((match compression {
+        Compression::ZSTD(_) | Compression::BROTLI(_) | Compression::GZIP(_) => format!("{}({})", name, level.unwrap_or(3)),
+        _ => name,
+    }, props))
+}
+
+fn perform_estimate(args: EstimateArgs) {
+    let mut properties = Vec::new();
+    for &algorithm in &args.compression {
+        let level = args.compression_level.filter(|_| has_compression_level(algorithm));
+        properties.push(sample_properties(algorithm, level, args.parquet_writer_version).unwrap_or_else(|e| {
+            eprintln!("Invalid combination of compression and compression_level: {}", e);
+            process::exit(1);
+        }));
+    }
+    let mut settings = schema_settings(&args.schema_settings);
+    settings.source_table = Some(args.table.clone());
+    let postgres = handle_result(postgres_cloner::resolve_password(&args.postgres));
+    let table_columns = handle_result(postgres_cloner::pg_connect(&postgres).and_then(|mut client| postgres_cloner::table_columns(&mut client, &args.table)));
+    let estimate = handle_result(postgres_cloner::estimate_size(&postgres, &args.table, &table_columns, args.sample_rows, &settings, properties));
+
+    if args.json {
+        let json = serde_json::json!({
+            "table_rows": estimate.table_rows,
+            "sampled_rows": estimate.sampled_rows,
+            "sampled_raw_bytes": estimate.sampled_raw_bytes,
+            "estimated_raw_bytes": estimate.extrapolate(estimate.sampled_raw_bytes),
+            "compression": estimate.sampled_sizes.iter().map(|sample| serde_json::json!({
+                "compression": sample.label,
+                "sampled_bytes": sample.bytes,
+                "estimated_bytes": estimate.extrapolate(sample.bytes),
+            })).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+    println!("Table rows: {}", estimate.table_rows);
+    println!("Sampled rows: {}, {} bytes raw", estimate.sampled_rows, estimate.sampled_raw_bytes);
+    println!("Estimated raw size: {} bytes", estimate.extrapolate(estimate.sampled_raw_bytes));
+    println!();
+    println!("Estimated Parquet size:");
+    for sample in &estimate.sampled_sizes {
+        let ratio = if sample.bytes == 0 { 0.0 } else { estimate.sampled_raw_bytes as f64 / sample.bytes as f64 };
+        println!("  {}: {} bytes ({:.2}x), sample encoded into {} bytes", sample.label, estimate.extrapolate(sample.bytes), ratio, sample.bytes);
+    }
+}
+
+fn perform_bench(args: BenchArgs) {
+    let mut properties = Vec::new();
+    for &algorithm in &args.compression {
+        if !has_compression_level(algorithm) {
+            properties.push(handle_result(sample_properties(algorithm, None, args.parquet_writer_version)));
+            continue;
+        }
+        let count = properties.len();
+        for &level in &args.compression_level {
+            if let Ok(props) = sample_properties(algorithm, Some(level), args.parquet_writer_version) {
+                properties.push(props);
+            }
+        }
+        if properties.len() == count {
+            eprintln!("None of the compression levels {:?} is valid for {}", args.compression_level, algorithm.to_possible_value().unwrap().get_name());
+            process::exit(1);
+        }
+    }
+    let mut settings = schema_settings(&args.schema_settings);
+    settings.source_table = Some(args.table.clone());
+    let postgres = handle_result(postgres_cloner::resolve_password(&args.postgres));
+    let table_columns = handle_result(postgres_cloner::pg_connect(&postgres).and_then(|mut client| postgres_cloner::table_columns(&mut client, &args.table)));
+    let estimate = handle_result(postgres_cloner::estimate_size(&postgres, &args.table, &table_columns, args.rows, &settings, properties));
+
+    let ratio = |bytes: usize| if bytes == 0 { 0.0 } else { estimate.sampled_raw_bytes as f64 / bytes as f64 };
+    // throughput of the raw data, in MB/s
+    let throughput = |duration: std::time::Duration| estimate.sampled_raw_bytes as f64 / duration.as_secs_f64().max(1e-9) / 1_000_000.0;
+    if args.json {
+        let json = serde_json::json!({
+            "sampled_rows": estimate.sampled_rows,
+            "sampled_raw_bytes": estimate.sampled_raw_bytes,
+            "results": estimate.sampled_sizes.iter().map(|sample| serde_json::json!({
+                "compression": sample.label,
+                "bytes": sample.bytes,
+                "ratio": ratio(sample.bytes),
+                "seconds": sample.duration.as_secs_f64(),
+                "mb_per_second": throughput(sample.duration),
+            })).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+    println!("Sampled rows: {}, {} bytes raw", estimate.sampled_rows, estimate.sampled_raw_bytes);
+    println!();
+    println!("{:<14} {:>14} {:>8} {:>10} {:>10}", "compression", "bytes", "ratio", "time", "MB/s");
+    for sample in &estimate.sampled_sizes {
+        println!("{:<14} {:>14} {:>7.2}x {:>9.3}s {:>10.1}", sample.label, sample.bytes, ratio(sample.bytes), sample.duration.as_secs_f64(), throughput(sample.duration));
+    }
+}
+
+fn perform_compact(args: CompactArgs) {
+    if matches!(args.compression, Some(ParquetCompression::Auto)) {
+        eprintln!("--compression auto is not supported by compact");
+        process::exit(1);
+    }
+    let compression = get_compression(args.compression.or(Some(ParquetCompression::Zstd)), args.compression_level).unwrap_or_else(|e| {
+        eprintln!("Invalid combination of compression and compression_level: {}", e);
+        process::exit(1);
+    });
+    let props = parquet::file::properties::WriterProperties::builder()
+        .set_compression(compression)
+        .set_write_batch_size(write_batch_size(compression))
+        .set_created_by(format!("pg2parquet version {}, using {}", env!("CARGO_PKG_VERSION"), parquet::file::properties::DEFAULT_CREATED_BY))
+        .set_writer_version(writer_version(args.parquet_writer_version));
+    let inputs = handle_result(parquet_compact::list_input_files(&args.inputs, &args.output_file));
+    let reencode = args.compression.is_some() || !args.sort_by.is_empty();
+    let stats = handle_result(parquet_compact::execute_compact(&inputs, &args.output_file, props, reencode, &args.sort_by, args.row_group_size));
+    if !args.quiet {
+        eprintln!("Merged {} files with {} rows into {} row groups, {:?} has {:.1} MB", stats.files, stats.rows, stats.row_groups, args.output_file, stats.bytes as f64 / 1024.0 / 1024.0);
+    }
+}
+
+fn perform_stream(args: StreamArgs) {
+    let settings = change_stream::StreamSettings {
+        slot: args.slot.clone(),
+        plugin: args.plugin,
+        publications: args.publication.clone(),
+        create_slot: args.create_slot,
+        output_template: args.output_template.clone().unwrap_or_else(|| {
+            let output_dir = args.output_dir.as_ref().unwrap().to_string_lossy();
+            OutputTemplate::literal(&output_dir).join(OutputTemplate::parse("{slot}-{lsn}.parquet", &["slot", "lsn"]).unwrap())
+        }),
+        rows_per_file: args.rows_per_file,
+        file_interval: args.file_interval,
+        poll_interval: args.poll_interval,
+        exit_when_idle: args.exit_when_idle,
+        quiet: args.quiet,
+    };
+    let props = parquet::file::properties::WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_created_by(format!("pg2parquet version {}, using {}", env!("CARGO_PKG_VERSION"), parquet::file::properties::DEFAULT_CREATED_BY));
+    // Ctrl+C writes the pending changes and stops
+    handle_result(cancellation::install_handler(true));
+    let stats = handle_result(change_stream::stream_changes(&args.postgres, &settings, props));
+    if !args.quiet {
+        eprintln!("Exported {} changes into {} files", stats.rows, stats.groups);
+    }
+}
+
+fn perform_export(args: ExportArgs, matches: &ArgMatches) {
     if args.query.is_some() && args.table.is_some() {
         eprintln!("Either query or table must be specified, but not both");
         process::exit(1);
@@ -202,52 +982,463 @@ fn perform_export(args: ExportArgs) {
         eprintln!("Either query or table must be specified");
         process::exit(1);
     }
+    if args.query.is_some() && !args.add_columns.is_empty() {
+        eprintln!("--add-column can only be used with --table, add the expression to the query instead");
+        process::exit(1);
+    }
 
-    let compression = get_compression(&args).unwrap_or_else(|e| {
+    postgres_cloner::set_quiet(args.quiet);
+
+    // the export opens several connections, the password is only asked for once
+    let mut args = args;
+    args.postgres = handle_result(postgres_cloner::resolve_password(&args.postgres));
+    // the lock is held until the end of the export, when the connection is closed
+    let _lock = match &args.lock_key {
+        Some(key) if args.dry_run.is_none() => Some(handle_result(postgres_cloner::advisory_lock(&args.postgres, Some(key.as_str()).filter(|k| !k.is_empty()), args.table.as_deref(), args.quiet))),
+        _ => None,
+    };
+
+    // connection for the catalog queries preparing the export
+    let mut metadata_client = handle_result(postgres_cloner::pg_connect(&args.postgres));
+    if let Some(table) = &args.table {
+        let kind = handle_result(postgres_cloner::relation_kind(&mut metadata_client, table));
+        match kind {
+            postgres_cloner::RelationKind::MaterializedView { .. } if args.refresh_matview => {
+                if !args.quiet {
+                    eprintln!("Refreshing materialized view {}", table);
+                }
+                handle_result(postgres_cloner::refresh_materialized_view(&mut metadata_client, table));
+            },
+            _ if args.refresh_matview => {
+                eprintln!("--refresh-matview can only be used when the --table is a materialized view");
+                process::exit(1);
+            },
+            postgres_cloner::RelationKind::MaterializedView { populated: false } => {
+                eprintln!("Materialized view {} has not been populated, use --refresh-matview to refresh it before the export", table);
+                process::exit(1);
+            },
+            postgres_cloner::RelationKind::ForeignTable => if let Some(timeout) = args.fdw_timeout {
+                args.postgres.pg_settings.push(("statement_timeout".to_string(), format!("{}ms", timeout.as_millis())));
+            },
+            _ => {},
+        }
+    }
+
+    let compression = get_compression(args.compression, args.compression_level).unwrap_or_else(|e| {
         eprintln!("Invalid combination of compression and compression_level: {}", e);
         process::exit(1);
     });
 
-    let batch_size = match compression {
-        // use smaller page size if shitty compression is chosen
-        Compression::UNCOMPRESSED | Compression::SNAPPY | Compression::LZO | Compression::LZ4 =>
-            DEFAULT_WRITE_BATCH_SIZE,
-        Compression::ZSTD(lvl) if lvl.compression_level() <= 2 =>
-            DEFAULT_WRITE_BATCH_SIZE,
-        // otherwise prefer larger page size to improve the compression ratio slightly
-        // the parquet library doesn't parallelize compression anyway
-        _ => 1024 * 128,
-    };
+    let batch_size = write_batch_size(compression);
+    let auto_compression = matches!(args.compression, Some(ParquetCompression::Auto));
 
-    let props =
+    let mut file_metadata: Vec<KeyValue> = args.file_metadata.iter()
+        .map(|(k, v)| KeyValue::new(k.clone(), v.clone()))
+        .collect();
+    if let Some(table) = &args.table {
+        file_metadata.push(KeyValue::new("pg2parquet.source_table".to_string(), table.clone()));
+    }
+
+    let writer_properties = |file_metadata: Vec<KeyValue>|
         parquet::file::properties::WriterProperties::builder()
             .set_compression(compression)
             .set_write_batch_size(batch_size)
-            .set_created_by(format!("pg2parquet version {}, using {}", env!("CARGO_PKG_VERSION"), parquet::file::properties::DEFAULT_CREATED_BY))
-        .build();
-    let props = Arc::new(props);
-
-    let settings = SchemaSettings {
-        macaddr_handling: args.schema_settings.macaddr_handling,
-        json_handling: args.schema_settings.json_handling,
-        enum_handling: args.schema_settings.enum_handling,
-        interval_handling: args.schema_settings.interval_handling,
-        numeric_handling: args.schema_settings.numeric_handling,
-        decimal_scale: args.schema_settings.decimal_scale,
-        decimal_precision: args.schema_settings.decimal_precision,
-        array_handling: args.schema_settings.array_handling,
+            .set_created_by(args.created_by.clone().unwrap_or_else(|| format!("pg2parquet version {}, using {}", env!("CARGO_PKG_VERSION"), parquet::file::properties::DEFAULT_CREATED_BY)))
+            .set_key_value_metadata(Some(file_metadata))
+            .set_writer_version(writer_version(args.parquet_writer_version));
+
+    let mut settings = schema_settings(&args.schema_settings);
+    settings.source_table = args.table.clone();
+    settings.job_id = args.job_id.clone();
+    // the table columns are listed explicitly instead of SELECT *, so that the query stored in the metadata describes the exported columns
+    let table_columns = match &args.table {
+        Some(table) => postgres_cloner::table_columns(&mut metadata_client, table).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        }),
+        None => Vec::new(),
     };
-    let query = args.query.unwrap_or_else(|| {
-        format!("SELECT * FROM {}", args.table.unwrap())
+    // it would stay idle during the export
+    drop(metadata_client);
+    // the query is rewritten on the export connection for --columns, --as-map, the large objects and the types without a binary representation
+    settings.column_definitions = args.columns.clone();
+    if let Some([key, value]) = args.as_map.as_deref() {
+        settings.as_map = Some((key.clone(), value.clone()));
+    }
+    let query = export_query(&args, &table_columns);
+    // filtered table exports and tables with computed columns cannot be resumed after a connection failure, they are retried like --query exports
+    let customized = args.where_clause.is_some() || args.limit.is_some() || args.sample.is_some() || args.distinct || !args.add_columns.is_empty() || args.as_map.is_some();
+    let resumable_table = args.table.as_deref().filter(|_| !customized);
+    if let Some(format) = args.dry_run {
+        let result = postgres_cloner::print_schema(&args.postgres, &query, &settings, matches!(format, DryRunFormat::Json));
+        handle_result(result);
+        return;
+    }
+    if matches!(args.format, OutputFormat::Csv | OutputFormat::Jsonl) {
+        // CSV and JSONL values are formatted by PostgreSQL, the options applied by the appenders would be silently ignored
+        let text_format_options = ["mask", "rename_column", "column_case", "null_string"];
+        let schema_options = SchemaSettingsArgs::augment_args(Command::new("export"));
+        let unsupported = schema_options.get_arguments()
+            .filter(|a| !text_format_options.contains(&a.get_id().as_str()))
+            .find(|a| matches.value_source(a.get_id().as_str()).is_some_and(|s| s != ValueSource::DefaultValue));
+        if let Some(option) = unsupported {
+            eprintln!("--{} can only be used with Parquet and Arrow output formats", option.get_long().unwrap());
+            process::exit(1);
+        }
+    }
+    if args.schema_settings.null_string.is_some() && args.format == OutputFormat::Jsonl {
+        eprintln!("--null-string cannot be used with JSONL output format, JSON has its own null value");
+        process::exit(1);
+    }
+    if args.as_map.is_some() && matches!(args.format, OutputFormat::Csv | OutputFormat::Jsonl) {
+        eprintln!("--as-map can only be used with Parquet and Arrow output formats");
+        process::exit(1);
+    }
+    if !args.distinct_on.is_empty() && matches!(args.format, OutputFormat::Csv | OutputFormat::Jsonl) {
+        eprintln!("--distinct-on can only be used with Parquet and Arrow output formats, use --distinct or DISTINCT ON in the query instead");
+        process::exit(1);
+    }
+    if args.delta_log && args.format != OutputFormat::Parquet {
+        eprintln!("--delta-log can only be used with Parquet output format");
+        process::exit(1);
+    }
+    let mut schema_changes = Vec::new();
+    if let Some(reference) = &args.schema_compat {
+        if matches!(args.format, OutputFormat::Csv | OutputFormat::Jsonl) {
+            eprintln!("--schema-compat can only be used with Parquet and Arrow output formats");
+            process::exit(1);
+        }
+        let differences = handle_result(schema_compat::check_schema_compat(&args.postgres, &query, &settings, reference, args.on_schema_change));
+        if !differences.is_empty() {
+            schema_changes.push(KeyValue::new("pg2parquet.schema_changes".to_string(), serde_json::to_string(&differences).unwrap()));
+        }
+    }
+    let mut options = postgres_cloner::CopyOptions {
+        table: resumable_table.map(|t| t.to_string()),
+        quiet: args.quiet,
+        max_buffer_memory: args.max_buffer_memory,
+        flush_interval: args.flush_interval,
+        engine: args.engine,
+        arrow_ipc: args.format == OutputFormat::Arrow,
+        page_checksums: args.page_checksums,
+        auto_compression,
+        sort_by: args.sort_by.clone(),
+        row_group_per_partition_value: args.row_group_per_partition_value,
+        column_stats: args.report.is_some(),
+        on_row_error: args.on_row_error,
+        row_identifier: args.row_identifier_column.clone(),
+        distinct_on: args.distinct_on.clone(),
+        watermark_column: args.watermark_column.clone(),
+        chunk_size: args.chunk_size,
+        retry: postgres_cloner::RetrySettings { retries: args.retries, backoff: args.retry_backoff },
+        hooks: postgres_cloner::SqlHooks { pre_sql: args.pre_sql.clone(), post_sql: args.post_sql.clone() },
+        ddl: None,
+    };
+    if args.per_partition_files {
+        perform_partition_export(&args, &table_columns, &writer_properties, &schema_changes, &settings, &options);
+        return;
+    }
+    let table_stats_before = read_table_stats_before(&args);
+    let start_time = std::time::Instant::now();
+    if let Some(template) = &args.output_template {
+        let table = args.table.as_deref().map_or("query".to_string(), |t| t.replace('"', ""));
+        let file = template.render(&[("table", TemplateValue::Text(&table)), ("date", TemplateValue::Text(&settings.exported_at.format("%Y-%m-%d").to_string())), ("seq", TemplateValue::Number(0))]);
+        if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+            handle_result(std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e)));
+        }
+        args.output_file = Some(file);
+    }
+    let output_file = args.output_file.as_ref().unwrap();
+    if args.no_overwrite && output_file.exists() {
+        eprintln!("The output file {:?} already exists", output_file);
+        process::exit(1);
+    }
+    // the file is written under a temporary name, so that an interrupted export does not leave a truncated file behind
+    let mut temp_file = output_file.clone().into_os_string();
+    temp_file.push(".tmp");
+    let temp_file = PathBuf::from(temp_file);
+    options.ddl = args.emit_ddl.clone().map(|file| ddl::DdlOutput { file, data_file: output_file.clone(), table: args.table.clone() });
+    handle_result(cancellation::install_handler(matches!(args.on_cancel, CancelMode::Finalize)));
+    file_metadata.extend(schema_changes);
+    let props = writer_properties(file_metadata);
+    let checksum = args.checksum.map(|_| checksum::Checksum::default());
+    let result = match args.format {
+        OutputFormat::Parquet => postgres_cloner::execute_copy(&args.postgres, &query, &temp_file, props, &settings, &options, checksum.as_ref()),
+        OutputFormat::Csv => text_export::export_csv(&args.postgres, &query, &temp_file, &settings, &options, checksum.as_ref()),
+        OutputFormat::Jsonl => text_export::export_jsonl(&args.postgres, &query, &temp_file, &settings, &options, checksum.as_ref()),
+        // the IPC file has no row group metadata which the written size would be counted from
+        OutputFormat::Arrow => postgres_cloner::execute_copy(&args.postgres, &query, &temp_file, props, &settings, &options, checksum.as_ref())
+            .map(|stats| WriterStats { bytes_out: std::fs::metadata(&temp_file).map(|m| m.len() as usize).unwrap_or(0), ..stats }),
+    };
+    let result = result.and_then(|stats| {
+        if args.no_overwrite && output_file.exists() {
+            return Err(format!("The output file {:?} was created by another process during the export", output_file));
+        }
+        std::fs::rename(&temp_file, output_file).map_err(|e| format!("Failed to rename {:?} to {:?}: {}", temp_file, output_file, e))?;
+        Ok(stats)
     });
-    let result = postgres_cloner::execute_copy(&args.postgres, &query, &args.output_file, props, args.quiet, &settings);
-    let _stats = handle_result(result);
+    if result.is_err() {
+        _ = std::fs::remove_file(&temp_file);
+    }
+    let result = match result {
+        Ok(stats) if args.delta_log => delta_log::commit_to_delta_log(output_file, &stats).map(|_| stats),
+        r => r,
+    };
+    let result = match (result, &checksum) {
+        (Ok(stats), Some(checksum)) => checksum.write_sidecar(output_file).map(|_| stats),
+        (r, _) => r,
+    };
+    let result = match result {
+        Ok(stats) if matches!(args.format, OutputFormat::Parquet) && settings.enum_handling == SchemaSettingsEnumHandling::Int =>
+            postgres_cloner::write_enums_sidecar(output_file).map(|_| stats),
+        r => r,
+    };
+    if let Some(ReportFormat::Json) = args.report {
+        print_json_report(&result, std::slice::from_ref(output_file), checksum.as_ref(), table_stats_report(&args, table_stats_before.as_ref()), start_time.elapsed());
+    }
+    let stats = handle_result(result);
+    if stats.duplicate_rows > 0 && !args.quiet {
+        eprintln!("Skipped {} duplicate rows", stats.duplicate_rows);
+    }
+    if cancellation::was_cancelled() {
+        eprintln!("The export was cancelled, {:?} contains only the first {} rows", output_file, stats.rows);
+        process::exit(130);
+    }
 
     // eprintln!("Wrote {} rows, {} bytes of raw data in {} groups", stats.rows, stats.bytes, stats.groups);
 }
 
-fn parse_args() -> CliCommand {
-    CliCommand::parse()
+/// Exports the leaf partitions of the table into separate files in the output directory, see --per-partition-files
+fn perform_partition_export(args: &ExportArgs, table_columns: &[String], writer_properties: &(dyn Fn(Vec<KeyValue>) -> WriterPropertiesBuilder + Sync), schema_changes: &[KeyValue], settings: &SchemaSettings, options: &postgres_cloner::CopyOptions) {
+    if args.format != OutputFormat::Parquet {
+        eprintln!("--per-partition-files can only be used with Parquet output format");
+        process::exit(1);
+    }
+    if args.output_template.as_ref().is_some_and(|t| !t.contains("table") && !t.contains("seq")) {
+        eprintln!("--output-template must contain {{table}} or {{seq}} with --per-partition-files, otherwise all partitions would be written into the same file");
+        process::exit(1);
+    }
+    let table_stats_before = read_table_stats_before(args);
+    let start_time = std::time::Instant::now();
+    let table = args.table.as_ref().unwrap();
+    let output_dir = args.output_file.as_ref();
+    let export_date = settings.exported_at.format("%Y-%m-%d").to_string();
+    let partitions = handle_result(postgres_cloner::leaf_partitions(&args.postgres, table));
+    handle_result(cancellation::install_handler(matches!(args.on_cancel, CancelMode::Finalize)));
+    let customized = args.where_clause.is_some() || args.limit.is_some() || args.sample.is_some() || args.distinct || !args.add_columns.is_empty();
+    // progress of parallel exports would be interleaved
+    let quiet = args.quiet || args.jobs > 1;
+
+    // the completed partitions are recorded in the manifest, so that --resume can skip them after a crash
+    let manifest_path = match &args.output_template {
+        Some(template) => template.base_dir().join(export_manifest::MANIFEST_FILE_NAME),
+        None => output_dir.unwrap().join(export_manifest::MANIFEST_FILE_NAME),
+    };
+    // the files of a resumed export must not differ from the ones exported before the crash
+    let options_hash = export_manifest::options_hash(&format!("{}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}",
+        export_query(args, table_columns), args.schema_settings, args.compression, args.compression_level, args.parquet_writer_version, args.engine,
+        args.page_checksums, args.file_metadata, args.on_row_error, args.row_identifier_column, args.distinct_on, args.chunk_size));
+    let previous_manifest = match handle_result(export_manifest::ExportManifest::load(&manifest_path)) {
+        Some(manifest) if args.resume && manifest.table != *table => {
+            eprintln!("Cannot resume the export, {:?} is a manifest of table {}", manifest_path, manifest.table);
+            process::exit(1);
+        },
+        Some(manifest) if args.resume && manifest.options_hash != options_hash => {
+            eprintln!("Cannot resume the export, the partitions in {:?} were exported with a different query or options", manifest_path);
+            process::exit(1);
+        },
+        Some(manifest) if args.resume => Some(manifest),
+        _ => None,
+    };
+    if let Some(dir) = manifest_path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        handle_result(std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e)));
+    }
+    let manifest = std::sync::Mutex::new(previous_manifest.clone().unwrap_or_else(|| export_manifest::ExportManifest::new(table, &options_hash)));
+    handle_result(manifest.lock().unwrap().save(&manifest_path));
+
+    let export_partition = |(index, partition): (usize, &postgres_cloner::TablePartition)| -> Result<(PathBuf, WriterStats), String> {
+        if let Some(entry) = previous_manifest.as_ref().and_then(|m| m.completed(&partition.name, &partition.bound)) {
+            if !args.quiet {
+                eprintln!("Skipping partition {}, it was already exported into {:?}", partition.name, entry.path);
+            }
+            return Ok((entry.path.clone(), WriterStats { rows: entry.rows, bytes_out: entry.size as usize, ..WriterStats::default() }));
+        }
+        let file = match &args.output_template {
+            Some(template) => template.render(&[("table", TemplateValue::Text(&partition.name.replace('"', ""))), ("date", TemplateValue::Text(&export_date)), ("seq", TemplateValue::Number(index as u64))]),
+            None => {
+                let mut file = output_dir.unwrap().clone();
+                file.extend(&partition.path);
+                // partition names can contain dots, which set_extension would replace
+                let mut file = file.into_os_string();
+                file.push(".parquet");
+                PathBuf::from(file)
+            },
+        };
+        if args.no_overwrite && file.exists() {
+            return Err(format!("The output file {:?} already exists", file));
+        }
+        if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        }
+        let partition_args = ExportArgs { table: Some(partition.name.clone()), ..args.clone() };
+        let query = export_query(&partition_args, table_columns);
+        let mut file_metadata: Vec<KeyValue> = args.file_metadata.iter()
+            .map(|(k, v)| KeyValue::new(k.clone(), v.clone()))
+            .collect();
+        file_metadata.push(KeyValue::new("pg2parquet.source_table".to_string(), partition.name.clone()));
+        file_metadata.push(KeyValue::new("pg2parquet.partition_of".to_string(), table.clone()));
+        file_metadata.push(KeyValue::new("pg2parquet.partition_bound".to_string(), partition.bound.clone()));
+        file_metadata.extend_from_slice(schema_changes);
+        let mut temp_file = file.clone().into_os_string();
+        temp_file.push(".tmp");
+        let temp_file = PathBuf::from(temp_file);
+        let resumable_table = Some(partition.name.as_str()).filter(|_| !customized);
+        let settings = &SchemaSettings { source_table: Some(partition.name.clone()), ..settings.clone() };
+        let options = postgres_cloner::CopyOptions { table: resumable_table.map(|t| t.to_string()), quiet, sort_by: Vec::new(), row_group_per_partition_value: false, column_stats: false, ..options.clone() };
+        let result = postgres_cloner::execute_copy(&args.postgres.for_reader(index), &query, &temp_file, writer_properties(file_metadata), settings, &options, None)
+            .and_then(|stats| {
+                std::fs::rename(&temp_file, &file).map_err(|e| format!("Failed to rename {:?} to {:?}: {}", temp_file, file, e))?;
+                Ok(stats)
+            });
+        if result.is_err() {
+            _ = std::fs::remove_file(&temp_file);
+        }
+        let stats = result.map_err(|e| format!("Failed to export partition {}: {}", partition.name, e))?;
+        // a cancelled partition contains only a part of the rows, it is exported again when resuming
+        if !cancellation::was_cancelled() {
+            let size = std::fs::metadata(&file).map_err(|e| format!("Failed to read {:?}: {}", file, e))?.len();
+            let mut manifest = manifest.lock().unwrap();
+            manifest.add(export_manifest::ManifestEntry { partition: partition.name.clone(), bound: partition.bound.clone(), path: file.clone(), rows: stats.rows, size });
+            manifest.save(&manifest_path)?;
+        }
+        if !args.quiet {
+            eprintln!("Exported partition {} into {:?}, {} rows", partition.name, file, stats.rows);
+        }
+        Ok((file, stats))
+    };
+    let pool = handle_result(rayon::ThreadPoolBuilder::new().num_threads(args.jobs.max(1)).build());
+    let results: Vec<_> = pool.install(|| {
+        use rayon::prelude::*;
+        partitions.par_iter().enumerate().map(export_partition).collect()
+    });
+
+    let mut stats = WriterStats::default();
+    let mut files = Vec::new();
+    let mut error = None;
+    for result in results {
+        match result {
+            Ok((file, s)) => {
+                stats.rows += s.rows;
+                stats.bytes += s.bytes;
+                stats.bytes_out += s.bytes_out;
+                stats.groups += s.groups;
+                stats.failed_rows += s.failed_rows;
+                stats.duplicate_rows += s.duplicate_rows;
+                stats.row_errors.extend(s.row_errors);
+                for (column, count) in s.special_float_values {
+                    *stats.special_float_values.entry(column).or_default() += count;
+                }
+                for (column, count) in s.rounded_decimal_values {
+                    *stats.rounded_decimal_values.entry(column).or_default() += count;
+                }
+                stats.add_column_sizes(s.column_sizes);
+                files.push(file);
+            },
+            Err(e) => { error.get_or_insert(e); },
+        }
+    }
+    let result = match error {
+        Some(e) => Err(e),
+        None => Ok(stats),
+    };
+    if let Some(ReportFormat::Json) = args.report {
+        print_json_report(&result, &files, None, table_stats_report(args, table_stats_before.as_ref()), start_time.elapsed());
+    }
+    let stats = handle_result(result);
+    if cancellation::was_cancelled() {
+        eprintln!("The export was cancelled, {:?} contain only the first {} rows", files, stats.rows);
+        process::exit(130);
+    }
+}
+
+/// Statistics of the exported table before the export, see --report-table-stats
+fn read_table_stats_before(args: &ExportArgs) -> Option<table_stats::TableStatCounters> {
+    match &args.table {
+        Some(table) if args.report_table_stats => Some(handle_result(table_stats::read_table_stats(&args.postgres, table))),
+        _ => None,
+    }
+}
+
+/// Changes of the table statistics for the --report, see --report-table-stats. The export has already finished, so failure to read them is only printed
+fn table_stats_report(args: &ExportArgs, before: Option<&table_stats::TableStatCounters>) -> Option<serde_json::Value> {
+    match table_stats::table_stats_report(&args.postgres, args.table.as_deref()?, before?) {
+        Ok(stats) => Some(stats),
+        Err(e) => {
+            eprintln!("Failed to read the table statistics: {}", e);
+            None
+        }
+    }
+}
+
+fn print_json_report(result: &Result<WriterStats, String>, output_files: &[PathBuf], checksum: Option<&checksum::Checksum>, table_stats: Option<serde_json::Value>, duration: std::time::Duration) {
+    let warnings: Vec<_> = postgres_cloner::collected_warnings().into_iter()
+        .map(|w| serde_json::json!({ "column": w.column, "message": w.message }))
+        .collect();
+    let mut report = match result {
+        Ok(stats) => serde_json::json!({
+            "status": "ok",
+            "rows": stats.rows,
+            "raw_bytes": stats.bytes,
+            "output_bytes": stats.bytes_out,
+            "row_groups": stats.groups,
+            "special_float_values": stats.special_float_values,
+            "rounded_decimal_values": stats.rounded_decimal_values,
+            "columns": column_stats_json(&stats.columns),
+            "column_sizes": stats.column_sizes.iter().map(|c| serde_json::json!({
+                "column": c.column,
+                "compressed_bytes": c.compressed_bytes,
+                "uncompressed_bytes": c.uncompressed_bytes,
+            })).collect::<Vec<_>>(),
+            "failed_rows": stats.failed_rows,
+            "duplicate_rows": stats.duplicate_rows,
+            "row_errors": row_errors_json(&stats.row_errors),
+            "duration_seconds": duration.as_secs_f64(),
+            "warnings": warnings,
+            "output_files": output_files.iter().map(|output_file| serde_json::json!({
+                "path": output_file,
+                "size": std::fs::metadata(output_file).map(|m| m.len()).ok(),
+                "sha256": checksum.map(|c| c.hex()),
+            })).collect::<Vec<_>>(),
+        }),
+        Err(e) => serde_json::json!({
+            "status": "error",
+            "error": e,
+            "duration_seconds": duration.as_secs_f64(),
+            "warnings": warnings,
+        }),
+    };
+    if let Some(table_stats) = table_stats {
+        report["table_stats"] = table_stats;
+    }
+    println!("{}", report);
+}
+
+fn column_stats_json(columns: &[ColumnStats]) -> serde_json::Value {
+    columns.iter().map(|c| serde_json::json!({
+        "name": c.name,
+        "null_count": c.null_count,
+        "approx_distinct_count": c.approx_distinct_count,
+    })).collect()
+}
+
+fn row_errors_json(errors: &[RowError]) -> serde_json::Value {
+    errors.iter().map(|e| serde_json::json!({ "row": e.row, "column": e.column, "error": e.message })).collect()
+}
+
+fn parse_args() -> (CliCommand, ArgMatches) {
+    let matches = CliCommand::command().get_matches();
+    let args = CliCommand::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    (args, matches)
 }
 
 fn main() {
@@ -257,19 +1448,46 @@ fn main() {
         eprintln!();
         eprintln!("pg2parquet probably should not crash in this way, could you please report a bug at https://github.com/exyi/pg2parquet/issues/new? (ideally with the backtrace and some info on what you did)");
     }));
-    let args = parse_args();
+    let (args, matches) = parse_args();
 
     match args {
-        CliCommand::ParquetInfo(args) => {
-            eprintln!("parquet file: {:?}", args.parquet_file);
-            parquetinfo::print_parquet_info(&args.parquet_file);
+        CliCommand::Inspect(args) => {
+            handle_result(parquetinfo::print_parquet_info(&args.parquet_file, args.sample_rows, args.json));
         },
         CliCommand::PlaygroundCreateSomething(args) => {
             eprintln!("parquet file: {:?}", args.parquet_file);
             playground::create_something(&args.parquet_file);
         },
         CliCommand::Export(args) => {
-            perform_export(args);
-        }
+            perform_export(args, matches.subcommand().unwrap().1);
+        },
+        CliCommand::Import(args) => {
+            let result = parquet_importer::execute_import(&args.postgres, &args.input_file, &args.table, args.quiet);
+            handle_result(result);
+        },
+        CliCommand::Verify(args) => {
+            let result = parquet_verifier::execute_verify(&args.postgres, &args.input_file, args.query.as_deref(), args.table.as_deref(), args.min_max);
+            if !handle_result(result) {
+                process::exit(2);
+            }
+        },
+        CliCommand::Diff(args) => {
+            let result = parquet_diff::execute_diff(&args.old_file, &args.new_file, &args.key, args.max_rows, args.json);
+            if !handle_result(result) {
+                process::exit(2);
+            }
+        },
+        CliCommand::Compact(args) => {
+            perform_compact(args);
+        },
+        CliCommand::Stream(args) => {
+            perform_stream(args);
+        },
+        CliCommand::Estimate(args) => {
+            perform_estimate(args);
+        },
+        CliCommand::Bench(args) => {
+            perform_bench(args);
+        },
     }
 }
@@ -0,0 +1,41 @@
+use sha2::{Digest, Sha256};
+
+/// Replacement of the values of a column, for exporting data without personal information
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnMask {
+	/// Hex-encoded SHA-256 of the binary PostgreSQL representation, which is the UTF-8 string for text columns
+	Sha256,
+	/// All values are NULL, the column keeps its type
+	Null,
+	/// All non-null values are replaced by the string
+	Constant(String),
+	/// The part of the e-mail address before @ is replaced by its first letter and `***`
+	RedactEmail,
+}
+
+impl std::str::FromStr for ColumnMask {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"sha256" => Ok(ColumnMask::Sha256),
+			"null" => Ok(ColumnMask::Null),
+			"redact-email" => Ok(ColumnMask::RedactEmail),
+			_ => match s.strip_prefix("constant:") {
+				Some(value) => Ok(ColumnMask::Constant(value.to_string())),
+				None => Err(format!("Unknown mask '{}', expected sha256, null, constant:<value> or redact-email", s)),
+			},
+		}
+	}
+}
+
+pub fn sha256_hex(value: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(value))
+}
+
+pub fn redact_email(value: &str) -> String {
+	match value.rsplit_once('@') {
+		Some((local, domain)) => format!("{}***@{}", local.chars().next().map(String::from).unwrap_or_default(), domain),
+		None => "***".to_string(),
+	}
+}
@@ -90,38 +90,38 @@ impl MyFrom<uuid::Uuid> for FixedLenByteArray {
 	}
 }
 
-impl<'a> MyFrom<PgEnum> for ByteArray {
+impl MyFrom<PgEnum> for ByteArray {
 	fn my_from(t: PgEnum) -> Self {
 		ByteArray::from(t.name.into_bytes())
 	}
 }
 
-impl<'a> MyFrom<eui48::MacAddress> for ByteArray {
+impl MyFrom<eui48::MacAddress> for ByteArray {
 	fn my_from(t: eui48::MacAddress) -> Self {
 		let str = t.to_hex_string();
 		ByteArray::from(str.into_bytes())
 	}
 }
-impl<'a> MyFrom<eui48::MacAddress> for FixedLenByteArray {
+impl MyFrom<eui48::MacAddress> for FixedLenByteArray {
 	fn my_from(t: eui48::MacAddress) -> Self {
 		let b = t.as_bytes();
 		FixedLenByteArray::from(b.to_vec())
 	}
 }
-impl<'a> MyFrom<eui48::MacAddress> for i64 {
+impl MyFrom<eui48::MacAddress> for i64 {
 	fn my_from(t: eui48::MacAddress) -> Self {
 		let mut b = [0u8; 8];
 		b[0..6].copy_from_slice(t.as_bytes());
 		i64::from_be_bytes(b)
 	}
 }
-impl<'a> MyFrom<IpAddr> for ByteArray {
+impl MyFrom<IpAddr> for ByteArray {
 	fn my_from(t: IpAddr) -> Self {
 		let str = t.to_string();
 		ByteArray::from(str.into_bytes())
 	}
 }
-impl<'a> MyFrom<BitVec> for ByteArray {
+impl MyFrom<BitVec> for ByteArray {
 	fn my_from(t: BitVec) -> Self {
 		// this format should be easiest to work with, and parquet should compress it anyway
 		let str = t.iter().map(|b| if b { '1' } else { '0' }).collect::<String>();
@@ -0,0 +1,114 @@
+//! Output paths given by `--output-template`, such as `out/{table}/{date}/part-{seq:05}.parquet`
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+	Literal(String),
+	/// Placeholder name and the width it is zero-padded to
+	Placeholder(String, usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputTemplate {
+	segments: Vec<Segment>,
+}
+
+/// Value substituted for a placeholder
+#[derive(Debug, Clone, Copy)]
+pub enum TemplateValue<'a> {
+	Text(&'a str),
+	Number(u64),
+}
+
+impl OutputTemplate {
+	/// Parses the template, `placeholders` lists the names which may be used in it.
+	/// A placeholder may specify a width it is zero-padded to (`{seq:05}`), `{{` and `}}` are literal braces
+	pub fn parse(template: &str, placeholders: &[&str]) -> Result<OutputTemplate, String> {
+		let mut segments = Vec::new();
+		let mut literal = String::new();
+		let mut chars = template.chars().peekable();
+		while let Some(c) = chars.next() {
+			match c {
+				'{' if chars.peek() == Some(&'{') => { chars.next(); literal.push('{'); },
+				'}' if chars.peek() == Some(&'}') => { chars.next(); literal.push('}'); },
+				'{' => {
+					let mut placeholder = String::new();
+					loop {
+						match chars.next() {
+							Some('}') => break,
+							Some(c) => placeholder.push(c),
+							None => return Err(format!("Unclosed placeholder {{{} in the output template", placeholder)),
+						}
+					}
+					let (name, width) = match placeholder.split_once(':') {
+						Some((name, format)) => match format.strip_prefix('0').map(|w| w.parse::<usize>()) {
+							Some(Ok(width)) => (name, width),
+							_ => return Err(format!("Invalid format of placeholder {{{}}}, only zero padding such as {{{}:05}} is supported", placeholder, name)),
+						},
+						None => (placeholder.as_str(), 0),
+					};
+					if !placeholders.contains(&name) {
+						return Err(format!("Unknown placeholder {{{}}} in the output template, the supported placeholders are {}", name,
+							placeholders.iter().map(|p| format!("{{{}}}", p)).collect::<Vec<_>>().join(", ")));
+					}
+					if !literal.is_empty() {
+						segments.push(Segment::Literal(std::mem::take(&mut literal)));
+					}
+					segments.push(Segment::Placeholder(name.to_string(), width));
+				},
+				'}' => return Err("Unmatched } in the output template, use }} for a literal brace".to_string()),
+				c => literal.push(c),
+			}
+		}
+		if !literal.is_empty() {
+			segments.push(Segment::Literal(literal));
+		}
+		Ok(OutputTemplate { segments })
+	}
+
+	/// Template of a fixed path, without any placeholders
+	pub fn literal(path: &str) -> OutputTemplate {
+		OutputTemplate { segments: vec![Segment::Literal(path.to_string())] }
+	}
+
+	/// Appends another template, for example a file name to a directory
+	pub fn join(mut self, other: OutputTemplate) -> OutputTemplate {
+		if !self.segments.is_empty() && !matches!(self.segments.last(), Some(Segment::Literal(l)) if l.ends_with(std::path::MAIN_SEPARATOR)) {
+			self.segments.push(Segment::Literal(std::path::MAIN_SEPARATOR.to_string()));
+		}
+		self.segments.extend(other.segments);
+		self
+	}
+
+	/// Directory which contains all the rendered paths, the literal part of the template before the first placeholder
+	pub fn base_dir(&self) -> PathBuf {
+		let prefix = match self.segments.first() {
+			Some(Segment::Literal(literal)) if literal.ends_with(['/', std::path::MAIN_SEPARATOR]) => PathBuf::from(literal),
+			Some(Segment::Literal(literal)) => Path::new(literal).parent().map(Path::to_path_buf).unwrap_or_default(),
+			_ => PathBuf::new(),
+		};
+		if prefix.as_os_str().is_empty() { PathBuf::from(".") } else { prefix }
+	}
+
+	pub fn contains(&self, placeholder: &str) -> bool {
+		self.segments.iter().any(|s| matches!(s, Segment::Placeholder(name, _) if name == placeholder))
+	}
+
+	/// Substitutes the placeholders, text values have the path separators replaced by underscores
+	pub fn render(&self, values: &[(&str, TemplateValue)]) -> PathBuf {
+		let mut path = String::new();
+		for segment in &self.segments {
+			match segment {
+				Segment::Literal(literal) => path.push_str(literal),
+				Segment::Placeholder(name, width) => {
+					let value = values.iter().find(|(n, _)| n == name).map(|(_, v)| *v).unwrap_or(TemplateValue::Text(""));
+					match value {
+						TemplateValue::Text(text) => path.push_str(&format!("{:0>width$}", text.replace(['/', '\\'], "_"), width = width)),
+						TemplateValue::Number(number) => path.push_str(&format!("{:0width$}", number, width = width)),
+					}
+				},
+			}
+		}
+		PathBuf::from(path)
+	}
+}
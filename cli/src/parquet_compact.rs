@@ -0,0 +1,407 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parquet::basic::{ConvertedType, LogicalType, Type as PhysicalType};
+use parquet::column::reader::{get_typed_column_reader, ColumnReader};
+use parquet::column::writer::ColumnCloseResult;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, DoubleType, FixedLenByteArray, FixedLenByteArrayType, FloatType, Int32Type, Int64Type, Int96, Int96Type};
+use parquet::file::metadata::ParquetMetaData;
+use parquet::file::properties::WriterPropertiesBuilder;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::format::{KeyValue, SortingColumn};
+use parquet::schema::types::ColumnDescriptor;
+
+use crate::postgres_cloner::SortColumn;
+use crate::schema_compat::schema_differences;
+
+/// Number of rows read or written by one call of the column reader or writer when the rows are re-encoded
+const BATCH_ROWS: usize = 8192;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompactStats {
+	pub files: usize,
+	pub rows: u64,
+	pub row_groups: usize,
+	pub bytes: u64,
+}
+
+/// Expands the directories into the `*.parquet` files they contain (not recursively), sorted by name. The output file is skipped, so that a directory can be compacted into itself
+pub fn list_input_files(inputs: &[PathBuf], output_file: &Path) -> Result<Vec<PathBuf>, String> {
+	let output = std::fs::canonicalize(output_file).ok();
+	let mut result = Vec::new();
+	for input in inputs {
+		if !input.is_dir() {
+			if output.is_some() && std::fs::canonicalize(input).ok() == output {
+				return Err(format!("The output file {:?} is also listed as an input", output_file));
+			}
+			result.push(input.clone());
+			continue;
+		}
+		let entries = std::fs::read_dir(input).map_err(|e| format!("Failed to list {:?}: {}", input, e))?;
+		let mut files = Vec::new();
+		for entry in entries {
+			let path = entry.map_err(|e| format!("Failed to list {:?}: {}", input, e))?.path();
+			if path.is_file() && path.extension().is_some_and(|e| e == "parquet") && (output.is_none() || std::fs::canonicalize(&path).ok() != output) {
+				files.push(path);
+			}
+		}
+		files.sort();
+		result.extend(files);
+	}
+	if result.is_empty() {
+		return Err("No Parquet files found in the inputs".to_string());
+	}
+	Ok(result)
+}
+
+/// Merges the Parquet files, which must have the same schema, into a single file. By default, the column chunks are copied without decoding,
+/// each input row group becomes a row group of the output and the compression is kept. The page indexes and bloom filters are not copied.
+/// With `reencode`, the rows are decoded and written again with the compression of `props`, with up to `row_group_size` rows in each row group.
+/// Rows are sorted by the `sort_by` columns, which requires loading all of them into memory.
+/// The key-value metadata is kept if all input files have the same value
+pub fn execute_compact(inputs: &[PathBuf], output_file: &Path, props: WriterPropertiesBuilder, reencode: bool, sort_by: &[SortColumn], row_group_size: usize) -> Result<CompactStats, String> {
+	let mut readers = Vec::new();
+	for path in inputs {
+		let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+		let reader = SerializedFileReader::new(file).map_err(|e| format!("Failed to read parquet file {:?}: {}", path, e))?;
+		readers.push(reader);
+	}
+	let first = readers[0].metadata();
+	for (reader, path) in readers.iter().zip(inputs).skip(1) {
+		check_schema(first, reader.metadata(), path, &inputs[0])?;
+	}
+
+	let mut props = props.set_key_value_metadata(common_metadata(&readers));
+	let sorting_columns = if !sort_by.is_empty() {
+		Some(resolve_sort_columns(first, sort_by)?.iter().map(|&(leaf, sc)| SortingColumn::new(leaf as i32, sc.descending, sc.nulls_first)).collect())
+	} else {
+		// each row group is still sorted the way it was, if it was declared the same in all of them
+		let mut row_groups = readers.iter().flat_map(|r| r.metadata().row_groups());
+		let sorting = row_groups.next().and_then(|rg| rg.sorting_columns().cloned());
+		if row_groups.all(|rg| rg.sorting_columns() == sorting.as_ref()) { sorting } else { None }
+	};
+	props = props.set_sorting_columns(sorting_columns);
+
+	let mut temp_file = output_file.as_os_str().to_owned();
+	temp_file.push(".tmp");
+	let temp_file = PathBuf::from(temp_file);
+	let output = File::create(&temp_file).map_err(|e| format!("Failed to create {:?}: {}", temp_file, e))?;
+	let schema = first.file_metadata().schema_descr().root_schema_ptr();
+	let mut writer = SerializedFileWriter::new(output, schema, Arc::new(props.build())).map_err(|e| format!("Failed to create parquet writer: {}", e))?;
+
+	let mut stats = CompactStats { files: inputs.len(), ..Default::default() };
+	if !reencode {
+		for (reader, path) in readers.iter().zip(inputs) {
+			let input = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+			for rg in reader.metadata().row_groups() {
+				let mut rg_writer = writer.next_row_group().map_err(|e| e.to_string())?;
+				for column in rg.columns() {
+					let close = ColumnCloseResult {
+						bytes_written: column.compressed_size() as u64,
+						rows_written: rg.num_rows() as u64,
+						metadata: column.clone(),
+						bloom_filter: None,
+						column_index: None,
+						offset_index: None,
+					};
+					rg_writer.append_column(&input, close).map_err(|e| format!("Failed to copy column {} from {:?}: {}", column.column_path(), path, e))?;
+				}
+				rg_writer.close().map_err(|e| e.to_string())?;
+				stats.rows += rg.num_rows() as u64;
+				stats.row_groups += 1;
+			}
+		}
+	} else if sort_by.is_empty() {
+		// the small row groups are merged, larger ones are not split
+		let mut group: Vec<(usize, usize)> = Vec::new();
+		let mut group_rows = 0;
+		let row_groups = readers.iter().enumerate().flat_map(|(f, r)| (0..r.num_row_groups()).map(move |rg| (f, rg)));
+		for (f, rg) in row_groups {
+			let rows = readers[f].metadata().row_group(rg).num_rows() as usize;
+			if !group.is_empty() && group_rows + rows > row_group_size {
+				stats.rows += write_row_groups(&mut writer, &readers, inputs, &group)? as u64;
+				stats.row_groups += 1;
+				group.clear();
+				group_rows = 0;
+			}
+			group.push((f, rg));
+			group_rows += rows;
+		}
+		if !group.is_empty() {
+			stats.rows += write_row_groups(&mut writer, &readers, inputs, &group)? as u64;
+			stats.row_groups += 1;
+		}
+	} else {
+		let all: Vec<(usize, usize)> = readers.iter().enumerate().flat_map(|(f, r)| (0..r.num_row_groups()).map(move |rg| (f, rg))).collect();
+		let columns = read_columns(&readers, inputs, &all)?;
+		let order = sort_order(first, &columns, sort_by)?;
+		for chunk in order.chunks(row_group_size) {
+			let mut rg_writer = writer.next_row_group().map_err(|e| e.to_string())?;
+			write_columns(&mut rg_writer, &columns, Some(chunk))?;
+			rg_writer.close().map_err(|e| e.to_string())?;
+			stats.rows += chunk.len() as u64;
+			stats.row_groups += 1;
+		}
+	}
+	writer.close().map_err(|e| format!("Failed to write {:?}: {}", temp_file, e))?;
+	stats.bytes = std::fs::metadata(&temp_file).map_err(|e| e.to_string())?.len();
+	std::fs::rename(&temp_file, output_file).map_err(|e| format!("Failed to rename {:?} to {:?}: {}", temp_file, output_file, e))?;
+	Ok(stats)
+}
+
+fn check_schema(reference: &ParquetMetaData, other: &ParquetMetaData, path: &Path, reference_path: &Path) -> Result<(), String> {
+	let differences = schema_differences(reference.file_metadata().schema(), other.file_metadata().schema());
+	if !differences.is_empty() {
+		return Err(format!("Schema of {:?} differs from {:?}:\n  {}", path, reference_path, differences.join("\n  ")));
+	}
+	let (a, b) = (reference.file_metadata().schema_descr(), other.file_metadata().schema_descr());
+	if a.columns().iter().zip(b.columns()).any(|(a, b)| a != b) {
+		return Err(format!("Columns of {:?} are in a different order or have different types than in {:?}", path, reference_path));
+	}
+	Ok(())
+}
+
+fn common_metadata(readers: &[SerializedFileReader<File>]) -> Option<Vec<KeyValue>> {
+	let metadata = |r: &SerializedFileReader<File>| r.metadata().file_metadata().key_value_metadata().cloned().unwrap_or_default();
+	let mut result = metadata(&readers[0]);
+	for reader in &readers[1..] {
+		let other = metadata(reader);
+		result.retain(|kv| other.contains(kv));
+	}
+	if result.is_empty() { None } else { Some(result) }
+}
+
+/// Decoded values of one leaf column, with the position of the first definition level and value of each row
+struct ColumnData {
+	descr: Arc<ColumnDescriptor>,
+	values: Values,
+	def_levels: Vec<i16>,
+	rep_levels: Vec<i16>,
+	/// Start of each row in the levels and values, followed by the end of the last row
+	row_starts: Vec<(usize, usize)>,
+}
+
+enum Values {
+	Bool(Vec<bool>),
+	Int32(Vec<i32>),
+	Int64(Vec<i64>),
+	Int96(Vec<Int96>),
+	Float(Vec<f32>),
+	Double(Vec<f64>),
+	ByteArray(Vec<ByteArray>),
+	FixedLenByteArray(Vec<FixedLenByteArray>),
+}
+
+impl ColumnData {
+	fn num_rows(&self) -> usize {
+		self.row_starts.len() - 1
+	}
+
+	/// Index of the value of the row in a column without repetition, None if it is null
+	fn value_index(&self, row: usize) -> Option<usize> {
+		let (start, end) = (self.row_starts[row].1, self.row_starts[row + 1].1);
+		if start < end { Some(start) } else { None }
+	}
+}
+
+fn read_typed<T: DataType>(reader: ColumnReader, descr: &ColumnDescriptor, values: &mut Vec<T::T>, def_levels: &mut Vec<i16>, rep_levels: &mut Vec<i16>) -> parquet::errors::Result<()> {
+	let mut reader = get_typed_column_reader::<T>(reader);
+	loop {
+		let def = if descr.max_def_level() > 0 { Some(&mut *def_levels) } else { None };
+		let rep = if descr.max_rep_level() > 0 { Some(&mut *rep_levels) } else { None };
+		let (records, _, _) = reader.read_records(BATCH_ROWS, def, rep, values)?;
+		if records == 0 {
+			return Ok(());
+		}
+	}
+}
+
+/// Reads the columns of the row groups `(file index, row group index)` into memory
+fn read_columns(readers: &[SerializedFileReader<File>], inputs: &[PathBuf], row_groups: &[(usize, usize)]) -> Result<Vec<ColumnData>, String> {
+	let schema = readers[0].metadata().file_metadata().schema_descr();
+	let mut result = Vec::new();
+	for (c, descr) in schema.columns().iter().enumerate() {
+		let mut values = match descr.physical_type() {
+			PhysicalType::BOOLEAN => Values::Bool(Vec::new()),
+			PhysicalType::INT32 => Values::Int32(Vec::new()),
+			PhysicalType::INT64 => Values::Int64(Vec::new()),
+			PhysicalType::INT96 => Values::Int96(Vec::new()),
+			PhysicalType::FLOAT => Values::Float(Vec::new()),
+			PhysicalType::DOUBLE => Values::Double(Vec::new()),
+			PhysicalType::BYTE_ARRAY => Values::ByteArray(Vec::new()),
+			PhysicalType::FIXED_LEN_BYTE_ARRAY => Values::FixedLenByteArray(Vec::new()),
+		};
+		let (mut def_levels, mut rep_levels) = (Vec::new(), Vec::new());
+		for &(f, rg) in row_groups {
+			let error = |e: parquet::errors::ParquetError| format!("Failed to read column {} from {:?}: {}", descr.path(), inputs[f], e);
+			let reader = readers[f].get_row_group(rg).and_then(|rg| rg.get_column_reader(c)).map_err(error)?;
+			match &mut values {
+				Values::Bool(v) => read_typed::<BoolType>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::Int32(v) => read_typed::<Int32Type>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::Int64(v) => read_typed::<Int64Type>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::Int96(v) => read_typed::<Int96Type>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::Float(v) => read_typed::<FloatType>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::Double(v) => read_typed::<DoubleType>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::ByteArray(v) => read_typed::<ByteArrayType>(reader, descr, v, &mut def_levels, &mut rep_levels),
+				Values::FixedLenByteArray(v) => read_typed::<FixedLenByteArrayType>(reader, descr, v, &mut def_levels, &mut rep_levels),
+			}.map_err(error)?;
+		}
+
+		let value_count = match &values {
+			Values::Bool(v) => v.len(),
+			Values::Int32(v) => v.len(),
+			Values::Int64(v) => v.len(),
+			Values::Int96(v) => v.len(),
+			Values::Float(v) => v.len(),
+			Values::Double(v) => v.len(),
+			Values::ByteArray(v) => v.len(),
+			Values::FixedLenByteArray(v) => v.len(),
+		};
+		let level_count = if descr.max_def_level() > 0 { def_levels.len() } else { value_count };
+		let mut row_starts = Vec::new();
+		let mut value = 0;
+		for level in 0..level_count {
+			if descr.max_rep_level() == 0 || rep_levels[level] == 0 {
+				row_starts.push((level, value));
+			}
+			if descr.max_def_level() == 0 || def_levels[level] == descr.max_def_level() {
+				value += 1;
+			}
+		}
+		row_starts.push((level_count, value));
+		result.push(ColumnData { descr: descr.clone(), values, def_levels, rep_levels, row_starts });
+	}
+	Ok(result)
+}
+
+fn write_typed<T: DataType>(writer: &mut SerializedRowGroupWriter<File>, column: &ColumnData, values: &[T::T], rows: Option<&[usize]>) -> parquet::errors::Result<()> {
+	let mut column_writer = writer.next_column()?.ok_or_else(|| parquet::errors::ParquetError::General("Missing column writer".to_string()))?;
+	let typed = column_writer.typed::<T>();
+	let (has_def, has_rep) = (column.descr.max_def_level() > 0, column.descr.max_rep_level() > 0);
+	match rows {
+		None => {
+			typed.write_batch(values, has_def.then_some(&column.def_levels[..]), has_rep.then_some(&column.rep_levels[..]))?;
+		},
+		Some(rows) => for chunk in rows.chunks(BATCH_ROWS) {
+			let (mut chunk_values, mut def_levels, mut rep_levels) = (Vec::new(), Vec::new(), Vec::new());
+			for &row in chunk {
+				let ((level_start, value_start), (level_end, value_end)) = (column.row_starts[row], column.row_starts[row + 1]);
+				chunk_values.extend_from_slice(&values[value_start..value_end]);
+				if has_def {
+					def_levels.extend_from_slice(&column.def_levels[level_start..level_end]);
+				}
+				if has_rep {
+					rep_levels.extend_from_slice(&column.rep_levels[level_start..level_end]);
+				}
+			}
+			typed.write_batch(&chunk_values, has_def.then_some(&def_levels[..]), has_rep.then_some(&rep_levels[..]))?;
+		},
+	}
+	column_writer.close()
+}
+
+/// Writes the rows of the columns into the row group, in the order of `rows` if specified
+fn write_columns(writer: &mut SerializedRowGroupWriter<File>, columns: &[ColumnData], rows: Option<&[usize]>) -> Result<(), String> {
+	for column in columns {
+		match &column.values {
+			Values::Bool(v) => write_typed::<BoolType>(writer, column, v, rows),
+			Values::Int32(v) => write_typed::<Int32Type>(writer, column, v, rows),
+			Values::Int64(v) => write_typed::<Int64Type>(writer, column, v, rows),
+			Values::Int96(v) => write_typed::<Int96Type>(writer, column, v, rows),
+			Values::Float(v) => write_typed::<FloatType>(writer, column, v, rows),
+			Values::Double(v) => write_typed::<DoubleType>(writer, column, v, rows),
+			Values::ByteArray(v) => write_typed::<ByteArrayType>(writer, column, v, rows),
+			Values::FixedLenByteArray(v) => write_typed::<FixedLenByteArrayType>(writer, column, v, rows),
+		}.map_err(|e| format!("Failed to write column {}: {}", column.descr.path(), e))?;
+	}
+	Ok(())
+}
+
+/// Re-encodes the row groups `(file index, row group index)` into a single output row group, returns the number of rows
+fn write_row_groups(writer: &mut SerializedFileWriter<File>, readers: &[SerializedFileReader<File>], inputs: &[PathBuf], row_groups: &[(usize, usize)]) -> Result<usize, String> {
+	let columns = read_columns(readers, inputs, row_groups)?;
+	let mut rg_writer = writer.next_row_group().map_err(|e| e.to_string())?;
+	write_columns(&mut rg_writer, &columns, None)?;
+	rg_writer.close().map_err(|e| e.to_string())?;
+	Ok(columns.first().map_or(0, |c| c.num_rows()))
+}
+
+/// Finds the leaf column index of each --sort-by column, only top-level primitive columns are supported
+fn resolve_sort_columns<'a>(metadata: &ParquetMetaData, sort_by: &'a [SortColumn]) -> Result<Vec<(usize, &'a SortColumn)>, String> {
+	let schema = metadata.file_metadata().schema_descr();
+	sort_by.iter().map(|sc| {
+		let leaf = schema.columns().iter().position(|c| c.path().parts() == [sc.name.as_str()])
+			.ok_or_else(|| format!("Column {} specified in --sort-by does not exist or is not a primitive column", sc.name))?;
+		let descr = schema.column(leaf);
+		if matches!(descr.logical_type(), Some(LogicalType::Float16)) || descr.converted_type() == ConvertedType::INTERVAL {
+			return Err(format!("Sorting by column {} is not supported, float16 and interval values cannot be compared", sc.name));
+		}
+		Ok((leaf, sc))
+	}).collect()
+}
+
+fn sort_order(metadata: &ParquetMetaData, columns: &[ColumnData], sort_by: &[SortColumn]) -> Result<Vec<usize>, String> {
+	let keys = resolve_sort_columns(metadata, sort_by)?;
+	let mut order: Vec<usize> = (0..columns.first().map_or(0, |c| c.num_rows())).collect();
+	order.sort_by(|&a, &b| {
+		for &(leaf, sc) in &keys {
+			let column = &columns[leaf];
+			let ordering = match (column.value_index(a), column.value_index(b)) {
+				(None, None) => Ordering::Equal,
+				(None, Some(_)) => if sc.nulls_first { Ordering::Less } else { Ordering::Greater },
+				(Some(_), None) => if sc.nulls_first { Ordering::Greater } else { Ordering::Less },
+				(Some(a), Some(b)) => {
+					let ordering = compare_values(column, a, b);
+					if sc.descending { ordering.reverse() } else { ordering }
+				},
+			};
+			if ordering != Ordering::Equal {
+				return ordering;
+			}
+		}
+		Ordering::Equal
+	});
+	Ok(order)
+}
+
+/// Compares two values of the column, in the sort order of its logical type
+fn compare_values(column: &ColumnData, a: usize, b: usize) -> Ordering {
+	let unsigned = matches!(column.descr.logical_type(), Some(LogicalType::Integer { is_signed: false, .. }))
+		|| matches!(column.descr.converted_type(), ConvertedType::UINT_8 | ConvertedType::UINT_16 | ConvertedType::UINT_32 | ConvertedType::UINT_64);
+	let decimal = matches!(column.descr.logical_type(), Some(LogicalType::Decimal { .. })) || column.descr.converted_type() == ConvertedType::DECIMAL;
+	match &column.values {
+		Values::Bool(v) => v[a].cmp(&v[b]),
+		Values::Int32(v) if unsigned => (v[a] as u32).cmp(&(v[b] as u32)),
+		Values::Int32(v) => v[a].cmp(&v[b]),
+		Values::Int64(v) if unsigned => (v[a] as u64).cmp(&(v[b] as u64)),
+		Values::Int64(v) => v[a].cmp(&v[b]),
+		// Julian day in the last element, nanoseconds of the day in the first two
+		Values::Int96(v) => {
+			let key = |x: &Int96| (x.data()[2], x.data()[1], x.data()[0]);
+			key(&v[a]).cmp(&key(&v[b]))
+		},
+		Values::Float(v) => v[a].total_cmp(&v[b]),
+		Values::Double(v) => v[a].total_cmp(&v[b]),
+		Values::ByteArray(v) if decimal => compare_big_endian_signed(v[a].data(), v[b].data()),
+		Values::ByteArray(v) => v[a].data().cmp(v[b].data()),
+		Values::FixedLenByteArray(v) if decimal => compare_big_endian_signed(v[a].data(), v[b].data()),
+		Values::FixedLenByteArray(v) => v[a].data().cmp(v[b].data()),
+	}
+}
+
+/// Compares two's complement big-endian integers of possibly different lengths, as used by the Parquet decimals
+fn compare_big_endian_signed(a: &[u8], b: &[u8]) -> Ordering {
+	let negative = |x: &[u8]| x.first().is_some_and(|b| b & 0x80 != 0);
+	match (negative(a), negative(b)) {
+		(true, false) => return Ordering::Less,
+		(false, true) => return Ordering::Greater,
+		_ => {},
+	}
+	let sign = if negative(a) { 0xFF } else { 0 };
+	let len = a.len().max(b.len());
+	let byte = |x: &[u8], i: usize| if i < len - x.len() { sign } else { x[i - (len - x.len())] };
+	(0..len).map(|i| byte(a, i).cmp(&byte(b, i))).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use parquet::file::metadata::ParquetMetaDataReader;
+use serde_json::{json, Map, Value};
+
+use crate::parquet_importer::read_rows;
+use crate::schema_compat::schema_differences;
+
+/// Rows of a file in the order of the file, with an index by the serialized key columns
+struct KeyedRows {
+	rows: Vec<Map<String, Value>>,
+	index: HashMap<String, usize>,
+}
+
+fn read_keyed_rows(path: &PathBuf, key: &[String]) -> Result<(parquet::schema::types::Type, KeyedRows), String> {
+	let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+	let metadata = ParquetMetaDataReader::new().parse_and_finish(&file).map_err(|e| format!("Failed to read parquet file {:?}: {}", path, e))?;
+	let schema = metadata.file_metadata().schema().clone();
+	for k in key {
+		if !schema.get_fields().iter().any(|f| f.name() == k) {
+			return Err(format!("Key column {} does not exist in {:?}", k, path));
+		}
+	}
+	let mut result = KeyedRows { rows: Vec::new(), index: HashMap::new() };
+	read_rows(file, &metadata, |row| {
+		let Value::Object(row) = row.to_json_value() else { unreachable!() };
+		let key_value = key_string(&row, key);
+		if result.index.insert(key_value.clone(), result.rows.len()).is_some() {
+			return Err(format!("Key {} is not unique in {:?}", key_value, path));
+		}
+		result.rows.push(row);
+		Ok(true)
+	}).map_err(|e| format!("Failed to read parquet file {:?}: {}", path, e))?;
+	Ok((schema, result))
+}
+
+fn key_string(row: &Map<String, Value>, key: &[String]) -> String {
+	Value::Array(key.iter().map(|k| row.get(k).cloned().unwrap_or(Value::Null)).collect()).to_string()
+}
+
+fn key_object(row: &Map<String, Value>, key: &[String]) -> Value {
+	Value::Object(key.iter().map(|k| (k.clone(), row.get(k).cloned().unwrap_or(Value::Null))).collect())
+}
+
+/// Compares two Parquet files with rows identified by the `key` columns. Reports the schema differences and the added, removed and changed rows,
+/// at most `max_rows` of each are listed. Only the columns present in both files are compared. Returns false if the files differ.
+/// Both files are loaded into memory
+pub fn execute_diff(old_file: &PathBuf, new_file: &PathBuf, key: &[String], max_rows: usize, json: bool) -> Result<bool, String> {
+	let (old_schema, old) = read_keyed_rows(old_file, key)?;
+	let (new_schema, new) = read_keyed_rows(new_file, key)?;
+	let schema_changes = schema_differences(&old_schema, &new_schema);
+	let common_columns: Vec<String> = old_schema.get_fields().iter()
+		.filter(|f| new_schema.get_fields().iter().any(|n| n.name() == f.name()))
+		.map(|f| f.name().to_string())
+		.collect();
+
+	let mut matched = vec![false; old.rows.len()];
+	let mut added = Vec::new();
+	let mut changed = Vec::new();
+	for row in &new.rows {
+		let Some(&i) = old.index.get(&key_string(row, key)) else {
+			added.push(row);
+			continue;
+		};
+		matched[i] = true;
+		let old_row = &old.rows[i];
+		let columns: Map<String, Value> = common_columns.iter()
+			.filter(|c| old_row.get(*c) != row.get(*c))
+			.map(|c| (c.clone(), json!({ "old": old_row.get(c), "new": row.get(c) })))
+			.collect();
+		if !columns.is_empty() {
+			changed.push((key_object(row, key), columns));
+		}
+	}
+	let removed: Vec<_> = old.rows.iter().zip(&matched).filter(|(_, m)| !**m).map(|(r, _)| r).collect();
+	let identical = schema_changes.is_empty() && added.is_empty() && removed.is_empty() && changed.is_empty();
+
+	if json {
+		let report = json!({
+			"identical": identical,
+			"schema_changes": schema_changes,
+			"old_rows": old.rows.len(),
+			"new_rows": new.rows.len(),
+			"added": added.len(),
+			"removed": removed.len(),
+			"changed": changed.len(),
+			"added_rows": added.iter().take(max_rows).collect::<Vec<_>>(),
+			"removed_rows": removed.iter().take(max_rows).collect::<Vec<_>>(),
+			"changed_rows": changed.iter().take(max_rows).map(|(key, columns)| json!({ "key": key, "columns": columns })).collect::<Vec<_>>(),
+		});
+		println!("{}", serde_json::to_string_pretty(&report).unwrap());
+		return Ok(identical);
+	}
+
+	if !schema_changes.is_empty() {
+		println!("Schema changes:");
+		for change in &schema_changes {
+			println!("  {}", change);
+		}
+		println!();
+	}
+	println!("Rows: {} in {:?}, {} in {:?}", old.rows.len(), old_file, new.rows.len(), new_file);
+	println!("Added: {}, removed: {}, changed: {}", added.len(), removed.len(), changed.len());
+	let more = |count: usize| if count > max_rows { println!("  ... and {} more", count - max_rows) };
+	if !added.is_empty() {
+		println!();
+		println!("Added rows:");
+		for row in added.iter().take(max_rows) {
+			println!("  + {}", Value::Object((*row).clone()));
+		}
+		more(added.len());
+	}
+	if !removed.is_empty() {
+		println!();
+		println!("Removed rows:");
+		for row in removed.iter().take(max_rows) {
+			println!("  - {}", Value::Object((*row).clone()));
+		}
+		more(removed.len());
+	}
+	if !changed.is_empty() {
+		println!();
+		println!("Changed rows:");
+		for (key, columns) in changed.iter().take(max_rows) {
+			let changes: Vec<String> = columns.iter().map(|(c, v)| format!("{}: {} -> {}", c, v["old"], v["new"])).collect();
+			println!("  ~ {}: {}", key, changes.join(", "));
+		}
+		more(changed.len());
+	}
+	Ok(identical)
+}
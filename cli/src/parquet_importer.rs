@@ -0,0 +1,428 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bytes::{BufMut, BytesMut};
+use parquet::basic::{ConvertedType, LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader, RowGroupMetaData};
+use parquet::file::properties::ReaderProperties;
+use parquet::file::reader::RowGroupReader;
+use parquet::file::serialized_reader::SerializedRowGroupReader;
+use parquet::record::{Field, Row};
+use parquet::schema::types::{SchemaDescriptor, Type as ParquetType, TypePtr};
+use pg_bigdecimal::{BigDecimal, BigInt, PgNumeric};
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::{IsNull, Kind, ToSql, Type as PgType, to_sql_checked};
+use postgres_protocol::types as pgtypes;
+
+use crate::postgres_cloner::pg_connect;
+use crate::postgresutils::{format_pg_error, quote_identifier};
+use crate::PostgresConnArgs;
+
+/// Seconds between 1970-01-01 and 2000-01-01 (PostgreSQL epoch)
+const PG_EPOCH_OFFSET_SECONDS: i64 = 946_684_800;
+
+/// Value read from the Parquet file, with the logical type information needed to convert it to PostgreSQL
+#[derive(Debug, Clone)]
+enum ImportValue {
+	Null,
+	Bool(bool),
+	Int(i64),
+	UInt(u64),
+	Float(f64),
+	Decimal(BigDecimal),
+	Str(String),
+	Bytes(Vec<u8>),
+	Uuid([u8; 16]),
+	/// days since 1970-01-01
+	Date(i32),
+	/// microseconds since midnight
+	Time(i64),
+	/// microseconds since 1970-01-01
+	Timestamp(i64),
+	Interval { months: i32, days: i32, microseconds: i64 },
+	List(Vec<ImportValue>),
+	Struct(Vec<(String, ImportValue)>),
+	Map(Vec<(ImportValue, ImportValue)>),
+}
+
+type BoxError = Box<dyn Error + Sync + Send>;
+
+pub fn execute_import(pg_args: &PostgresConnArgs, input_file: &PathBuf, table: &str, quiet: bool) -> Result<u64, String> {
+	let read_error = |e: parquet::errors::ParquetError| format!("Failed to read parquet file {:?}: {}", input_file, e);
+	let file = std::fs::File::open(input_file).map_err(|e| format!("Failed to open {:?}: {}", input_file, e))?;
+	let metadata = ParquetMetaDataReader::new().parse_and_finish(&file).map_err(read_error)?;
+	let schema = metadata.file_metadata().schema_descr().root_schema_ptr();
+	let columns = schema.get_fields().to_vec();
+
+	let mut client = pg_connect(pg_args)?;
+	let mut transaction = client.transaction().map_err(|e| format_pg_error(&e))?;
+
+	let table_exists = transaction.query_one("SELECT to_regclass($1::text) IS NOT NULL", &[&table])
+		.map_err(|e| format_pg_error(&e))?
+		.get::<_, bool>(0);
+	if !table_exists {
+		let definitions = columns.iter().map(|c| {
+			let not_null = if c.get_basic_info().repetition() == Repetition::REQUIRED { " NOT NULL" } else { "" };
+			format!("{} {}{}", quote_identifier(c.name()), pg_type_name(c), not_null)
+		}).collect::<Vec<_>>();
+		let create = format!("CREATE TABLE {} ({})", table, definitions.join(", "));
+		if !quiet {
+			eprintln!("{}", create);
+		}
+		transaction.batch_execute(&create).map_err(|e| format!("Failed to create table {}: {}", table, format_pg_error(&e)))?;
+	}
+
+	let column_list = columns.iter().map(|c| quote_identifier(c.name())).collect::<Vec<_>>().join(", ");
+	let types: Vec<PgType> = transaction.prepare(&format!("SELECT {} FROM {} LIMIT 0", column_list, table))
+		.map_err(|e| format!("Table {} does not have all columns of the Parquet file: {}", table, format_pg_error(&e)))?
+		.columns().iter().map(|c| c.type_().clone()).collect();
+
+	let copy = transaction.copy_in(&format!("COPY {} ({}) FROM STDIN (FORMAT binary)", table, column_list))
+		.map_err(|e| format_pg_error(&e))?;
+	let mut writer = BinaryCopyInWriter::new(copy, &types);
+
+	let mut row_count = 0u64;
+	read_rows(file, &metadata, |row| {
+		let values = row.get_column_iter().zip(columns.iter())
+			.map(|((_, field), column)| convert_field(field, column))
+			.collect::<Result<Vec<_>, _>>()?;
+		let params = values.iter().map(|v| v as &(dyn ToSql + Sync)).collect::<Vec<_>>();
+		writer.write(&params).map_err(|e| format!("Failed to write row {}: {}", row_count, format_pg_error(&e)))?;
+		row_count += 1;
+		if !quiet && row_count.is_multiple_of(100_000) {
+			eprint!("{} rows\r", row_count);
+		}
+		Ok(true)
+	}).map_err(|e| format!("Failed to import {:?}: {}", input_file, e))?;
+	writer.finish().map_err(|e| format_pg_error(&e))?;
+	transaction.commit().map_err(|e| format_pg_error(&e))?;
+	if !quiet {
+		eprintln!("Imported {} rows into {}", row_count, table);
+	}
+	Ok(row_count)
+}
+
+/// Reads the rows of the file until `f` returns false. Columns of types not supported by the parquet record reader
+/// are returned as plain integers / byte arrays, see `read_schema`
+pub fn read_rows(file: std::fs::File, metadata: &ParquetMetaData, mut f: impl FnMut(Row) -> Result<bool, String>) -> Result<(), String> {
+	let read_error = |e: parquet::errors::ParquetError| e.to_string();
+	let schema = metadata.file_metadata().schema_descr().root_schema_ptr();
+	let read_schema_descr = Arc::new(SchemaDescriptor::new(Arc::new(read_schema(&schema))));
+	let chunk_reader = Arc::new(file);
+	let reader_props = Arc::new(ReaderProperties::builder().build());
+	for row_group in metadata.row_groups() {
+		let row_group = RowGroupMetaData::from_thrift(read_schema_descr.clone(), row_group.to_thrift()).map_err(read_error)?;
+		let row_group_reader = SerializedRowGroupReader::new(chunk_reader.clone(), &row_group, None, reader_props.clone()).map_err(read_error)?;
+		for row in row_group_reader.get_row_iter(None).map_err(read_error)? {
+			if !f(row.map_err(read_error)?)? {
+				return Ok(());
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Schema used for reading the file. The parquet record reader does not support some logical types (it panics),
+/// so these columns are read as plain integers / byte arrays and converted in `convert_field`
+fn read_schema(t: &ParquetType) -> ParquetType {
+	match t {
+		ParquetType::PrimitiveType { basic_info, physical_type, type_length, .. } => {
+			let unsupported = matches!(basic_info.converted_type(), ConvertedType::TIME_MILLIS | ConvertedType::TIME_MICROS | ConvertedType::INTERVAL)
+				|| matches!(basic_info.logical_type(), Some(LogicalType::Time { .. } | LogicalType::Timestamp { unit: TimeUnit::NANOS(_), .. }));
+			if !unsupported {
+				return t.clone();
+			}
+			ParquetType::primitive_type_builder(basic_info.name(), *physical_type)
+				.with_repetition(basic_info.repetition())
+				.with_length(*type_length)
+				.build().unwrap()
+		},
+		ParquetType::GroupType { basic_info, fields } => {
+			let mut builder = ParquetType::group_type_builder(basic_info.name())
+				.with_fields(fields.iter().map(|f| Arc::new(read_schema(f))).collect())
+				.with_logical_type(basic_info.logical_type())
+				.with_converted_type(basic_info.converted_type());
+			if basic_info.has_repetition() {
+				builder = builder.with_repetition(basic_info.repetition());
+			}
+			builder.build().unwrap()
+		}
+	}
+}
+
+fn list_element_type(t: &ParquetType) -> Option<&TypePtr> {
+	let is_list = matches!(t.get_basic_info().logical_type(), Some(LogicalType::List)) || t.get_basic_info().converted_type() == ConvertedType::LIST;
+	if !is_list || t.get_fields().len() != 1 {
+		return None;
+	}
+	let repeated = &t.get_fields()[0];
+	// 2-level list (repeated primitive or struct) or the standard 3-level list
+	if repeated.is_primitive() || repeated.get_fields().len() != 1 || repeated.name() == "array" || repeated.name() == format!("{}_tuple", t.name()) {
+		Some(repeated)
+	} else {
+		Some(&repeated.get_fields()[0])
+	}
+}
+
+fn is_map(t: &ParquetType) -> bool {
+	matches!(t.get_basic_info().logical_type(), Some(LogicalType::Map)) || matches!(t.get_basic_info().converted_type(), ConvertedType::MAP | ConvertedType::MAP_KEY_VALUE)
+}
+
+/// PostgreSQL type of a newly created column
+fn pg_type_name(t: &ParquetType) -> String {
+	if !t.is_primitive() {
+		return match list_element_type(t) {
+			Some(element) if element.is_primitive() => pg_type_name(element) + "[]",
+			// nested lists, structs and maps
+			_ => "jsonb".to_string(),
+		};
+	}
+	let basic_info = t.get_basic_info();
+	match (basic_info.logical_type(), basic_info.converted_type()) {
+		(Some(LogicalType::String | LogicalType::Enum), _) | (_, ConvertedType::UTF8 | ConvertedType::ENUM) => "text".to_string(),
+		(Some(LogicalType::Json), _) | (_, ConvertedType::JSON) => "jsonb".to_string(),
+		(Some(LogicalType::Uuid), _) => "uuid".to_string(),
+		(Some(LogicalType::Date), _) | (_, ConvertedType::DATE) => "date".to_string(),
+		(Some(LogicalType::Time { .. }), _) | (_, ConvertedType::TIME_MILLIS | ConvertedType::TIME_MICROS) => "time".to_string(),
+		(Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, .. }), _) => "timestamptz".to_string(),
+		(Some(LogicalType::Timestamp { .. }), _) | (_, ConvertedType::TIMESTAMP_MILLIS | ConvertedType::TIMESTAMP_MICROS) => "timestamp".to_string(),
+		(_, ConvertedType::INTERVAL) => "interval".to_string(),
+		(Some(LogicalType::Decimal { precision, scale }), _) => format!("numeric({}, {})", precision, scale),
+		(_, ConvertedType::DECIMAL) => format!("numeric({}, {})", t.get_precision(), t.get_scale()),
+		(Some(LogicalType::Integer { bit_width, is_signed }), _) => match (bit_width, is_signed) {
+			(8 | 16, true) | (8, false) => "smallint",
+			(32, true) | (16, false) => "integer",
+			(64, true) | (32, false) => "bigint",
+			_ => "numeric(20, 0)",
+		}.to_string(),
+		(Some(LogicalType::Float16), _) => "real".to_string(),
+		_ => match t.get_physical_type() {
+			PhysicalType::BOOLEAN => "boolean",
+			PhysicalType::INT32 => "integer",
+			PhysicalType::INT64 => "bigint",
+			PhysicalType::INT96 => "timestamp",
+			PhysicalType::FLOAT => "real",
+			PhysicalType::DOUBLE => "double precision",
+			PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => "bytea",
+		}.to_string(),
+	}
+}
+
+fn convert_field(field: &Field, t: &ParquetType) -> Result<ImportValue, String> {
+	let basic_info = t.get_basic_info();
+	Ok(match field {
+		Field::Null => ImportValue::Null,
+		Field::Bool(v) => ImportValue::Bool(*v),
+		Field::Byte(v) => ImportValue::Int(*v as i64),
+		Field::Short(v) => ImportValue::Int(*v as i64),
+		Field::Int(v) => match (basic_info.logical_type(), basic_info.converted_type()) {
+			(_, ConvertedType::TIME_MILLIS) | (Some(LogicalType::Time { unit: TimeUnit::MILLIS(_), .. }), _) => ImportValue::Time(*v as i64 * 1000),
+			_ => ImportValue::Int(*v as i64),
+		},
+		Field::Long(v) => match (basic_info.logical_type(), basic_info.converted_type()) {
+			(Some(LogicalType::Time { unit: TimeUnit::NANOS(_), .. }), _) => ImportValue::Time(*v / 1000),
+			(Some(LogicalType::Time { .. }), _) | (_, ConvertedType::TIME_MICROS) => ImportValue::Time(*v),
+			(Some(LogicalType::Timestamp { unit: TimeUnit::NANOS(_), .. }), _) => ImportValue::Timestamp(v.div_euclid(1000)),
+			_ => ImportValue::Int(*v),
+		},
+		Field::UByte(v) => ImportValue::UInt(*v as u64),
+		Field::UShort(v) => ImportValue::UInt(*v as u64),
+		Field::UInt(v) => ImportValue::UInt(*v as u64),
+		Field::ULong(v) => ImportValue::UInt(*v),
+		Field::Float16(v) => ImportValue::Float(v.to_f64()),
+		Field::Float(v) => ImportValue::Float(*v as f64),
+		Field::Double(v) => ImportValue::Float(*v),
+		Field::Decimal(d) => ImportValue::Decimal(BigDecimal::new(BigInt::from_signed_bytes_be(d.data()), d.scale() as i64)),
+		Field::Str(v) => ImportValue::Str(v.clone()),
+		Field::Bytes(v) => {
+			let data = v.data();
+			if basic_info.converted_type() == ConvertedType::INTERVAL && data.len() == 12 {
+				let months = i32::from_le_bytes(data[0..4].try_into().unwrap());
+				let days = i32::from_le_bytes(data[4..8].try_into().unwrap());
+				let millis = i32::from_le_bytes(data[8..12].try_into().unwrap());
+				ImportValue::Interval { months, days, microseconds: millis as i64 * 1000 }
+			} else if matches!(basic_info.logical_type(), Some(LogicalType::Uuid)) && data.len() == 16 {
+				ImportValue::Uuid(data.try_into().unwrap())
+			} else {
+				ImportValue::Bytes(data.to_vec())
+			}
+		},
+		Field::Date(v) => ImportValue::Date(*v),
+		Field::TimestampMillis(v) => ImportValue::Timestamp(*v * 1000),
+		Field::TimestampMicros(v) => ImportValue::Timestamp(*v),
+		Field::Group(row) => {
+			let fields = row.get_column_iter().zip(t.get_fields().iter())
+				.map(|((name, f), ft)| Ok((name.clone(), convert_field(f, ft)?)))
+				.collect::<Result<Vec<_>, String>>()?;
+			ImportValue::Struct(fields)
+		},
+		Field::ListInternal(list) => {
+			let element_type = list_element_type(t).ok_or_else(|| format!("Column {} is not a valid Parquet LIST", t.name()))?;
+			ImportValue::List(list.elements().iter().map(|e| convert_field(e, element_type)).collect::<Result<_, _>>()?)
+		},
+		Field::MapInternal(map) => {
+			let key_value = t.get_fields().first().filter(|_| is_map(t))
+				.ok_or_else(|| format!("Column {} is not a valid Parquet MAP", t.name()))?;
+			let (key_type, value_type) = (&key_value.get_fields()[0], &key_value.get_fields()[1]);
+			ImportValue::Map(map.entries().iter()
+				.map(|(k, v)| Ok((convert_field(k, key_type)?, convert_field(v, value_type)?)))
+				.collect::<Result<_, String>>()?)
+		},
+	})
+}
+
+impl ImportValue {
+	fn to_json(&self) -> serde_json::Value {
+		match self {
+			ImportValue::Null => serde_json::Value::Null,
+			ImportValue::Bool(v) => (*v).into(),
+			ImportValue::Int(v) => (*v).into(),
+			ImportValue::UInt(v) => (*v).into(),
+			ImportValue::Float(v) => (*v).into(),
+			ImportValue::Decimal(v) => serde_json::Number::from_str(&v.to_string()).map(serde_json::Value::Number).unwrap_or_else(|_| v.to_string().into()),
+			ImportValue::Str(v) => v.as_str().into(),
+			ImportValue::List(v) => v.iter().map(|x| x.to_json()).collect(),
+			ImportValue::Struct(v) => v.iter().map(|(k, x)| (k.clone(), x.to_json())).collect::<serde_json::Map<_, _>>().into(),
+			ImportValue::Map(v) => v.iter().map(|(k, x)| {
+				let key = match k { ImportValue::Str(s) => s.clone(), k => k.to_json().to_string() };
+				(key, x.to_json())
+			}).collect::<serde_json::Map<_, _>>().into(),
+			// same textual representation as PostgreSQL uses in JSON
+			ImportValue::Bytes(v) => format!("\\x{}", v.iter().map(|b| format!("{:02x}", b)).collect::<String>()).into(),
+			ImportValue::Uuid(v) => uuid::Uuid::from_bytes(*v).to_string().into(),
+			ImportValue::Date(v) => chrono::DateTime::from_timestamp(*v as i64 * 86400, 0).map(|d| d.date_naive().to_string()).into(),
+			ImportValue::Time(v) => chrono::NaiveTime::from_num_seconds_from_midnight_opt((v / 1_000_000) as u32, (v % 1_000_000 * 1000) as u32).map(|t| t.to_string()).into(),
+			ImportValue::Timestamp(v) => chrono::DateTime::from_timestamp_micros(*v).map(|t| t.naive_utc().to_string()).into(),
+			ImportValue::Interval { months, days, microseconds } => serde_json::json!({ "months": months, "days": days, "microseconds": microseconds }),
+		}
+	}
+
+	fn as_i64(&self) -> Option<i64> {
+		match self {
+			ImportValue::Int(v) => Some(*v),
+			ImportValue::UInt(v) => i64::try_from(*v).ok(),
+			ImportValue::Bool(v) => Some(*v as i64),
+			_ => None,
+		}
+	}
+
+	fn write_sql(&self, ty: &PgType, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+		fn int<T: TryFrom<i64> + ToSql>(v: &ImportValue, ty: &PgType, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+			let i = v.as_i64().ok_or_else(|| format!("Cannot convert {:?} to {}", v, ty))?;
+			let i = T::try_from(i).map_err(|_| format!("Value {} is out of range for {}", i, ty))?;
+			i.to_sql(ty, out)
+		}
+
+		if let ImportValue::Null = self {
+			return Ok(IsNull::Yes);
+		}
+		match (self, ty.kind()) {
+			(_, Kind::Domain(inner)) => return self.write_sql(inner, out),
+			(ImportValue::Str(s), Kind::Enum(_)) => return s.as_str().to_sql(&PgType::TEXT, out),
+			(ImportValue::List(elements), Kind::Array(element_type)) => {
+				let dimensions = if elements.is_empty() { vec![] } else { vec![pgtypes::ArrayDimension { len: elements.len() as i32, lower_bound: 1 }] };
+				pgtypes::array_to_sql(dimensions, element_type.oid(), elements.iter(), |e, out| {
+					Ok(match e.write_sql(element_type, out)? {
+						IsNull::Yes => postgres_protocol::IsNull::Yes,
+						IsNull::No => postgres_protocol::IsNull::No,
+					})
+				}, out)?;
+				return Ok(IsNull::No);
+			},
+			(ImportValue::Struct(values), Kind::Composite(fields)) => {
+				// binary record format: number of fields, then (type oid, length, data) for each field
+				out.put_i32(fields.len() as i32);
+				for f in fields {
+					out.put_u32(f.type_().oid());
+					let len_index = out.len();
+					out.put_i32(-1);
+					let value = values.iter().find(|(name, _)| name == f.name()).map(|(_, v)| v).unwrap_or(&ImportValue::Null);
+					if let IsNull::No = value.write_sql(f.type_(), out)? {
+						let len = (out.len() - len_index - 4) as i32;
+						out[len_index..len_index + 4].copy_from_slice(&len.to_be_bytes());
+					}
+				}
+				return Ok(IsNull::No);
+			},
+			_ => {}
+		}
+
+		match (self, ty) {
+			(ImportValue::Bool(v), &PgType::BOOL) => v.to_sql(ty, out),
+			(_, &PgType::INT2) => int::<i16>(self, ty, out),
+			(_, &PgType::INT4) => int::<i32>(self, ty, out),
+			(_, &PgType::INT8) => int::<i64>(self, ty, out),
+			(_, &PgType::OID) => int::<u32>(self, ty, out),
+			(ImportValue::Float(v), &PgType::FLOAT4) => (*v as f32).to_sql(ty, out),
+			(ImportValue::Float(v), &PgType::FLOAT8) => v.to_sql(ty, out),
+			(ImportValue::Int(_) | ImportValue::UInt(_), &PgType::FLOAT4) => (self.as_i64().unwrap_or(i64::MAX) as f32).to_sql(ty, out),
+			(ImportValue::Int(_) | ImportValue::UInt(_), &PgType::FLOAT8) => (self.as_i64().unwrap_or(i64::MAX) as f64).to_sql(ty, out),
+			(_, &PgType::NUMERIC) => {
+				let n = match self {
+					ImportValue::Decimal(d) => Some(d.clone()),
+					ImportValue::Int(v) => Some(BigDecimal::from(*v)),
+					ImportValue::UInt(v) => Some(BigDecimal::from(*v)),
+					ImportValue::Float(v) if v.is_nan() => None,
+					ImportValue::Float(v) => Some(BigDecimal::from_str(&v.to_string())?),
+					ImportValue::Str(v) => Some(BigDecimal::from_str(v)?),
+					_ => return Err(format!("Cannot convert {:?} to {}", self, ty).into()),
+				};
+				PgNumeric { n }.to_sql(ty, out)
+			},
+			(ImportValue::Str(v), _) if is_text_type(ty) => v.as_str().to_sql(&PgType::TEXT, out),
+			(ImportValue::Str(v), &PgType::JSON) => v.as_str().to_sql(&PgType::TEXT, out),
+			(ImportValue::Str(v), &PgType::JSONB) => {
+				out.put_u8(1); // jsonb format version
+				out.put_slice(v.as_bytes());
+				Ok(IsNull::No)
+			},
+			(_, &PgType::JSON | &PgType::JSONB) => self.to_json().to_sql(ty, out),
+			(ImportValue::Bytes(v), &PgType::BYTEA) => v.as_slice().to_sql(ty, out),
+			(ImportValue::Uuid(v), &PgType::BYTEA) => v.as_slice().to_sql(ty, out),
+			(ImportValue::Uuid(v), &PgType::UUID) => uuid::Uuid::from_bytes(*v).to_sql(ty, out),
+			(ImportValue::Bytes(v), &PgType::UUID) if v.len() == 16 => uuid::Uuid::from_slice(v)?.to_sql(ty, out),
+			(ImportValue::Str(v), &PgType::UUID) => uuid::Uuid::parse_str(v)?.to_sql(ty, out),
+			(ImportValue::Date(v), &PgType::DATE) => {
+				pgtypes::date_to_sql(*v - (PG_EPOCH_OFFSET_SECONDS / 86400) as i32, out);
+				Ok(IsNull::No)
+			},
+			(ImportValue::Time(v), &PgType::TIME) => {
+				pgtypes::time_to_sql(*v, out);
+				Ok(IsNull::No)
+			},
+			(ImportValue::Timestamp(v), &PgType::TIMESTAMP | &PgType::TIMESTAMPTZ) => {
+				pgtypes::timestamp_to_sql(*v - PG_EPOCH_OFFSET_SECONDS * 1_000_000, out);
+				Ok(IsNull::No)
+			},
+			(ImportValue::Interval { months, days, microseconds }, &PgType::INTERVAL) => {
+				out.put_i64(*microseconds);
+				out.put_i32(*days);
+				out.put_i32(*months);
+				Ok(IsNull::No)
+			},
+			// --interval-handling=struct
+			(ImportValue::Struct(fields), &PgType::INTERVAL) => {
+				let get = |name: &str| fields.iter().find(|(n, _)| n == name).and_then(|(_, v)| v.as_i64()).unwrap_or(0);
+				ImportValue::Interval { months: get("months") as i32, days: get("days") as i32, microseconds: get("microseconds") }.write_sql(ty, out)
+			},
+			_ => Err(format!("Cannot convert {:?} to {}", self, ty).into()),
+		}
+	}
+}
+
+fn is_text_type(ty: &PgType) -> bool {
+	matches!(*ty, PgType::TEXT | PgType::VARCHAR | PgType::BPCHAR | PgType::NAME | PgType::UNKNOWN) || ty.name() == "citext"
+}
+
+impl ToSql for ImportValue {
+	fn to_sql(&self, ty: &PgType, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+		self.write_sql(ty, out)
+	}
+
+	/// The conversion is checked in `to_sql` for each value
+	fn accepts(_ty: &PgType) -> bool { true }
+
+	to_sql_checked!();
+}
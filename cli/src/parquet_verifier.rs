@@ -0,0 +1,197 @@
+use std::path::PathBuf;
+
+use parquet::basic::{LogicalType, TimeUnit, Type as PhysicalType};
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+use parquet::file::statistics::Statistics;
+use postgres::types::{Kind, Type as PgType};
+
+use crate::postgres_cloner::pg_connect;
+use crate::postgresutils::{format_pg_error, quote_identifier};
+use crate::PostgresConnArgs;
+
+/// Minimum or maximum of a column, as written in the Parquet statistics
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum StatValue {
+	Int(i64),
+	Bytes(Vec<u8>),
+}
+
+impl StatValue {
+	fn format(&self) -> String {
+		match self {
+			StatValue::Int(v) => v.to_string(),
+			StatValue::Bytes(v) => String::from_utf8_lossy(v).into_owned(),
+		}
+	}
+}
+
+enum Check {
+	Done(bool, String),
+	Compare { name: String, parquet_value: Option<String>, aggregate_index: usize },
+}
+
+#[derive(Debug, Default)]
+struct ColumnStatistics {
+	/// None if any of the row groups is missing the statistics
+	null_count: Option<u64>,
+	min: Option<StatValue>,
+	max: Option<StatValue>,
+	min_max_valid: bool,
+}
+
+/// Compares the Parquet file with the result of the query, returns false if any of the checks failed.
+/// Row count and null count of each column are always compared, min_max also compares minimum and maximum of
+/// integer, date, timestamp and text columns. The Parquet side is read from the file statistics, not from the data
+pub fn execute_verify(pg_args: &PostgresConnArgs, input_file: &PathBuf, query: Option<&str>, table: Option<&str>, min_max: bool) -> Result<bool, String> {
+	let file = std::fs::File::open(input_file).map_err(|e| format!("Failed to open {:?}: {}", input_file, e))?;
+	let metadata = ParquetMetaDataReader::new().parse_and_finish(&file).map_err(|e| format!("Failed to read parquet file {:?}: {}", input_file, e))?;
+
+	let query = match (query, table) {
+		(Some(q), _) => q.to_string(),
+		(None, Some(t)) => format!("SELECT * FROM {}", t),
+		(None, None) => metadata.file_metadata().key_value_metadata()
+			.and_then(|kv| kv.iter().find(|kv| kv.key == "pg2parquet.query"))
+			.and_then(|kv| kv.value.clone())
+			.ok_or_else(|| format!("The file {:?} does not contain the pg2parquet.query metadata, specify --query or --table", input_file))?,
+	};
+
+	let mut client = pg_connect(pg_args)?;
+	let statement = client.prepare(&query).map_err(|e| format!("Failed to prepare query: {}", format_pg_error(&e)))?;
+
+	// checks are evaluated after the aggregate query, each one compares the parquet value with a column of the query result
+	let mut checks: Vec<Check> = Vec::new();
+	let mut aggregates = vec!["count(*)::text".to_string()];
+	checks.push(Check::Compare { name: "row count".to_string(), parquet_value: Some(metadata.file_metadata().num_rows().to_string()), aggregate_index: 0 });
+	let mut compare = |checks: &mut Vec<Check>, name: String, parquet_value: Option<String>, aggregate: String| {
+		checks.push(Check::Compare { name, parquet_value, aggregate_index: aggregates.len() });
+		aggregates.push(aggregate);
+	};
+	for column in statement.columns() {
+		let name = column.name();
+		let Some(leaf_index) = top_level_leaf(&metadata, name) else {
+			if metadata.file_metadata().schema_descr().root_schema().get_fields().iter().any(|f| f.name() == name) {
+				checks.push(Check::Done(true, format!("SKIP  column {}: nested columns are not compared", name)));
+			} else {
+				checks.push(Check::Done(false, format!("FAIL  column {}: missing in the Parquet file", name)));
+			}
+			continue;
+		};
+		let stats = column_statistics(&metadata, leaf_index);
+		let quoted = format!("q.{}", quote_identifier(name));
+		compare(&mut checks, format!("null count of {}", name), stats.null_count.map(|c| c.to_string()), format!("(count(*) - count({}))::text", quoted));
+
+		if min_max {
+			let descr = metadata.file_metadata().schema_descr().column(leaf_index);
+			match min_max_expression(column.type_(), descr.physical_type(), descr.logical_type(), &quoted) {
+				Some((min, max)) if stats.min_max_valid => {
+					compare(&mut checks, format!("min of {}", name), stats.min.as_ref().map(StatValue::format), min);
+					compare(&mut checks, format!("max of {}", name), stats.max.as_ref().map(StatValue::format), max);
+				},
+				Some(_) => checks.push(Check::Done(true, format!("SKIP  min/max of {}: statistics are missing or inexact", name))),
+				None => checks.push(Check::Done(true, format!("SKIP  min/max of {}: not supported for type {}", name, column.type_()))),
+			}
+		}
+	}
+
+	let aggregate_query = format!("SELECT {} FROM ({}) q", aggregates.join(", "), query);
+	let row = client.query_one(&aggregate_query, &[]).map_err(|e| format!("Failed to query the database: {}", format_pg_error(&e)))?;
+
+	let report: Vec<(bool, String)> = checks.into_iter().map(|check| match check {
+		Check::Done(ok, line) => (ok, line),
+		Check::Compare { name, parquet_value: None, .. } => (true, format!("SKIP  {}: statistics are missing in the Parquet file", name)),
+		Check::Compare { name, parquet_value, aggregate_index } => {
+			let db_value: Option<String> = row.get(aggregate_index);
+			check_line(name, db_value.as_ref(), parquet_value.as_ref())
+		},
+	}).collect();
+
+	for (_, line) in &report {
+		println!("{}", line);
+	}
+	let failed = report.iter().filter(|(ok, _)| !ok).count();
+	if failed == 0 {
+		println!("Verification passed");
+	} else {
+		println!("Verification failed: {} of {} checks did not match", failed, report.len());
+	}
+	Ok(failed == 0)
+}
+
+fn check_line(name: String, db_value: Option<&String>, parquet_value: Option<&String>) -> (bool, String) {
+	let fmt = |v: Option<&String>| v.map(|v| v.as_str()).unwrap_or("NULL").to_string();
+	if db_value == parquet_value {
+		(true, format!("OK    {}: {}", name, fmt(db_value)))
+	} else {
+		(false, format!("FAIL  {}: database {}, parquet {}", name, fmt(db_value), fmt(parquet_value)))
+	}
+}
+
+/// Index of the leaf column corresponding to a top-level primitive field, None for groups (lists, structs, ...)
+fn top_level_leaf(metadata: &ParquetMetaData, name: &str) -> Option<usize> {
+	let schema = metadata.file_metadata().schema_descr();
+	(0..schema.num_columns()).find(|&i| {
+		let column = schema.column(i);
+		let parts = column.path().parts();
+		parts.len() == 1 && parts[0] == name
+	})
+}
+
+fn column_statistics(metadata: &ParquetMetaData, leaf_index: usize) -> ColumnStatistics {
+	let mut result = ColumnStatistics { null_count: Some(0), min_max_valid: true, ..Default::default() };
+	for row_group in metadata.row_groups() {
+		let Some(stats) = row_group.column(leaf_index).statistics() else {
+			return ColumnStatistics::default();
+		};
+		result.null_count = result.null_count.zip(stats.null_count_opt()).map(|(a, b)| a + b);
+		// row group with only nulls has no min/max, but it does not invalidate the others
+		if row_group.num_rows() as u64 == stats.null_count_opt().unwrap_or(u64::MAX) {
+			continue;
+		}
+		let (min, max) = match stats {
+			Statistics::Int32(s) => (s.min_opt().map(|v| StatValue::Int(*v as i64)), s.max_opt().map(|v| StatValue::Int(*v as i64))),
+			Statistics::Int64(s) => (s.min_opt().map(|v| StatValue::Int(*v)), s.max_opt().map(|v| StatValue::Int(*v))),
+			Statistics::ByteArray(s) => (s.min_opt().map(|v| StatValue::Bytes(v.data().to_vec())), s.max_opt().map(|v| StatValue::Bytes(v.data().to_vec()))),
+			_ => (None, None),
+		};
+		match (min, max) {
+			(Some(min), Some(max)) if stats.min_is_exact() && stats.max_is_exact() => {
+				if result.min.as_ref().is_none_or(|m| min < *m) {
+					result.min = Some(min);
+				}
+				if result.max.as_ref().is_none_or(|m| max > *m) {
+					result.max = Some(max);
+				}
+			},
+			_ => result.min_max_valid = false,
+		}
+	}
+	result
+}
+
+/// SQL expressions computing min and max of the column in the same representation as the Parquet statistics.
+/// Text is compared in the "C" collation, which orders the strings by bytes, same as Parquet
+fn min_max_expression(pg_type: &PgType, physical_type: PhysicalType, logical_type: Option<LogicalType>, column: &str) -> Option<(String, String)> {
+	let wrap = |expr: &str| Some((expr.replace("{}", &format!("min({})", column)), expr.replace("{}", &format!("max({})", column))));
+	let base_type = match pg_type.kind() {
+		Kind::Domain(t) => t,
+		_ => pg_type,
+	};
+	match (base_type, physical_type) {
+		(&PgType::INT2 | &PgType::INT4 | &PgType::INT8, PhysicalType::INT32 | PhysicalType::INT64) => wrap("{}::text"),
+		(&PgType::DATE, PhysicalType::INT32) => wrap("({} - DATE '1970-01-01')::text"),
+		(&PgType::TIMESTAMP | &PgType::TIMESTAMPTZ, PhysicalType::INT64) => {
+			let multiplier = match logical_type {
+				Some(LogicalType::Timestamp { unit: TimeUnit::MILLIS(_), .. }) => "1000",
+				Some(LogicalType::Timestamp { unit: TimeUnit::MICROS(_), .. }) => "1000000",
+				Some(LogicalType::Timestamp { unit: TimeUnit::NANOS(_), .. }) => "1000000000",
+				_ => return None,
+			};
+			wrap(&format!("(extract(epoch from {{}}) * {})::int8::text", multiplier))
+		},
+		(&PgType::TEXT | &PgType::VARCHAR | &PgType::NAME, PhysicalType::BYTE_ARRAY) =>
+			Some((format!("min({}::text COLLATE \"C\")", column), format!("max({}::text COLLATE \"C\")", column))),
+		_ if matches!(base_type.kind(), Kind::Enum(_)) && physical_type == PhysicalType::BYTE_ARRAY =>
+			Some((format!("min({}::text COLLATE \"C\")", column), format!("max({}::text COLLATE \"C\")", column))),
+		_ => None,
+	}
+}
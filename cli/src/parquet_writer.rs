@@ -1,8 +1,19 @@
-use std::{borrow::Cow, cell::RefCell, fmt::Display, io::Write, mem, os, rc::Rc, sync::Arc, usize};
+use std::{borrow::Cow, collections::BTreeMap, fmt::Display, io::Write, mem, os, rc::Rc, sync::{mpsc, Arc}, thread::JoinHandle};
 
+use bytes::Bytes;
+use parquet::basic::{Compression, ConvertedType, Encoding, LogicalType, Type as PhysicalType, ZstdLevel};
+use parquet::column::page::{Page, PageReader};
+use parquet::column::writer::ColumnCloseResult;
+use parquet::file::serialized_reader::SerializedPageReader;
 use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::file::metadata::{KeyValue, RowGroupMetaDataPtr};
+use parquet::file::properties::{WriterProperties, WriterPropertiesPtr};
+use parquet::schema::types::{ColumnDescPtr, ColumnDescriptor, TypePtr};
+use rayon::prelude::*;
 
-use crate::{level_index::LevelIndexList, postgresutils::identify_row, pg_custom_types::PgAbstractRow, appenders::{new_dynamic_serialized_writer, Arcell, DynColumnAppender}};
+use crate::column_stats::{ColumnStats, ColumnStatsCollector, WatermarkCollector};
+use crate::postgres_cloner::warn;
+use crate::{level_index::LevelIndexList, postgresutils::identify_row, pg_custom_types::{PgAbstractRow, PgAny}, appenders::{InMemoryColumnWriter, ColumnAppender, ColumnAppenderBase, DynamicMergedAppender}};
 
 
 #[derive(Debug, Clone, Default)]
@@ -10,20 +21,212 @@ pub struct WriterStats {
 	pub rows: usize,
 	pub bytes: usize,
 	pub bytes_out: usize,
-	pub groups: usize
+	pub groups: usize,
+	/// Statistics of each top-level column, only collected when enabled by `WriterSettings::column_stats`
+	pub columns: Vec<ColumnStats>,
+	/// Number of rows which could not be read, see `OnRowError`
+	pub failed_rows: usize,
+	/// Number of rows skipped as duplicates of the previous row, see `WriterSettings::distinct_on`
+	pub duplicate_rows: usize,
+	/// Errors of the first failed rows, at most MAX_RECORDED_ROW_ERRORS
+	pub row_errors: Vec<RowError>,
+	/// Compressed and uncompressed size of each leaf column, collected from the metadata of the written row groups
+	pub column_sizes: Vec<ColumnSize>,
+	/// Number of NaN and Infinity values found in each float or numeric column
+	pub special_float_values: BTreeMap<String, usize>,
+	/// Number of numeric values in each column which had more fractional digits than `--decimal-scale` and were rounded
+	pub rounded_decimal_values: BTreeMap<String, usize>,
+}
+
+impl WriterStats {
+	/// Adds the sizes of the column chunks to the sizes of the columns with the same path
+	pub fn add_column_sizes(&mut self, sizes: impl IntoIterator<Item = ColumnSize>) {
+		for size in sizes {
+			match self.column_sizes.iter_mut().find(|c| c.column == size.column) {
+				Some(c) => {
+					c.compressed_bytes += size.compressed_bytes;
+					c.uncompressed_bytes += size.uncompressed_bytes;
+				},
+				None => self.column_sizes.push(size),
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnSize {
+	/// Dot-separated path of the leaf column
+	pub column: String,
+	pub compressed_bytes: u64,
+	pub uncompressed_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RowError {
+	/// Identification of the row, see `identify_row`
+	pub row: String,
+	/// Column replaced by NULL, None if the row was skipped
+	pub column: Option<String>,
+	pub message: String,
+}
+
+const MAX_RECORDED_ROW_ERRORS: usize = 1000;
+
+/// The column size summary printed after the export lists only the largest columns
+const MAX_PRINTED_COLUMN_SIZES: usize = 20;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum OnRowError {
+	/// The export fails
+	#[default]
+	Fail,
+	/// The row is not written into the output
+	Skip,
+	/// Columns which cannot be read are written as NULL
+	NullColumn,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum WriterEngine {
+	/// Columns are encoded by pg2parquet directly into Parquet column chunks
+	#[default]
+	Parquet,
+	/// Row groups are converted into Arrow record batches and written by the arrow-rs ArrowWriter. Only supports tables without nested columns, other tables fall back to the parquet engine
+	#[cfg(feature = "arrow")]
+	Arrow,
+}
+
+/// Writer of the row groups into the output file, see `WriterEngine`
+pub enum GroupWriter<W: Write + Send> {
+	Parquet {
+		writer: SerializedFileWriter<W>,
+		page_checksums: bool,
+		/// Properties of the column writers, replaced by `auto_column_properties` after the first row group if `tune_compression` is set
+		column_props: WriterPropertiesPtr,
+		tune_compression: bool,
+	},
+	#[cfg(feature = "arrow")]
+	Arrow(parquet::arrow::ArrowWriter<W>, arrow_schema::SchemaRef),
+	/// Arrow IPC file instead of Parquet, see `--format arrow`
+	#[cfg(feature = "arrow")]
+	ArrowIpc(crate::arrow_export::IpcWriter<W>),
+}
+
+impl<W: Write + Send> GroupWriter<W> {
+	/// Creates the writer of the selected engine, falls back to the parquet engine if the Arrow engine does not support the schema,
+	/// or page checksums or automatic compression are requested, which only the parquet engine supports.
+	/// With `auto_compression`, the first row group is written using `props` and the compression of the other groups is chosen for each column, see `auto_column_properties`.
+	/// The `metadata` is written into the file, the Arrow engine takes the field metadata from its ARROW:schema entry
+	pub fn new(output: W, schema: TypePtr, props: WriterPropertiesPtr, engine: WriterEngine, page_checksums: bool, auto_compression: bool, metadata: Vec<KeyValue>) -> Result<Self, String> {
+		let mut writer = Self::create(output, schema, props, engine, page_checksums, auto_compression, &metadata)?;
+		for kv in metadata {
+			writer.append_key_value_metadata(kv);
+		}
+		Ok(writer)
+	}
+
+	/// Creates the writer of an Arrow IPC file with the schema converted from the Parquet `schema`, the `metadata` is written into the Arrow schema
+	#[cfg_attr(not(feature = "arrow"), allow(unused_variables))]
+	pub fn new_arrow_ipc(output: W, schema: TypePtr, metadata: Vec<KeyValue>) -> Result<Self, String> {
+		#[cfg(feature = "arrow")]
+		return crate::arrow_export::IpcWriter::new(output, schema, &metadata).map(GroupWriter::ArrowIpc);
+		#[cfg(not(feature = "arrow"))]
+		Err("pg2parquet was built without Arrow support, enable the arrow feature".to_string())
+	}
+
+	#[cfg_attr(not(feature = "arrow"), allow(unused_variables))]
+	fn create(output: W, schema: TypePtr, props: WriterPropertiesPtr, engine: WriterEngine, page_checksums: bool, auto_compression: bool, metadata: &[KeyValue]) -> Result<Self, String> {
+		match engine {
+			WriterEngine::Parquet => {},
+			#[cfg(feature = "arrow")]
+			WriterEngine::Arrow if page_checksums => warn(None, "The Arrow engine cannot write page checksums, the parquet engine is used instead".to_string()),
+			#[cfg(feature = "arrow")]
+			WriterEngine::Arrow if auto_compression => warn(None, "The Arrow engine cannot choose the compression of each column, the parquet engine is used instead".to_string()),
+			#[cfg(feature = "arrow")]
+			WriterEngine::Arrow => match crate::arrow_engine::arrow_schema(&schema, metadata) {
+				Ok(arrow_schema) => {
+					let writer = crate::arrow_engine::create_writer(output, &schema, arrow_schema.clone(), Arc::unwrap_or_clone(props))?;
+					return Ok(GroupWriter::Arrow(writer, arrow_schema));
+				},
+				Err(e) => warn(None, format!("The Arrow engine cannot write this table, because {}. The parquet engine is used instead", e)),
+			},
+		}
+		let writer = SerializedFileWriter::new(output, schema, props.clone())
+			.map_err(|e| format!("Failed to create parquet writer: {}", e))?;
+		Ok(GroupWriter::Parquet { writer, page_checksums, column_props: props, tune_compression: auto_compression })
+	}
+
+	pub fn append_key_value_metadata(&mut self, kv: KeyValue) {
+		match self {
+			GroupWriter::Parquet { writer, .. } => writer.append_key_value_metadata(kv),
+			// the ArrowWriter writes its own schema
+			#[cfg(feature = "arrow")]
+			GroupWriter::Arrow(_, _) if kv.key == parquet::arrow::ARROW_SCHEMA_META_KEY => {},
+			#[cfg(feature = "arrow")]
+			GroupWriter::Arrow(writer, _) => writer.append_key_value_metadata(kv),
+			#[cfg(feature = "arrow")]
+			GroupWriter::ArrowIpc(writer) => writer.append_key_value_metadata(kv),
+		}
+	}
+
+	/// Writes the buffered values of the appender as one row group, returns its metadata
+	fn write_group(&mut self, appender: &mut RowAppender) -> Result<Option<RowGroupMetaDataPtr>, String> {
+		match self {
+			GroupWriter::Parquet { writer, page_checksums, column_props, tune_compression } => write_group(writer, column_props, *page_checksums, tune_compression, appender).map(Some),
+			#[cfg(feature = "arrow")]
+			GroupWriter::Arrow(writer, schema) => crate::arrow_engine::write_group(writer, schema, appender),
+			// the IPC file has no row group metadata, its size is taken from the file after the export
+			#[cfg(feature = "arrow")]
+			GroupWriter::ArrowIpc(writer) => writer.write_group(appender).map(|_| None),
+		}
+	}
+
+	fn close(self) -> Result<(), String> {
+		match self {
+			GroupWriter::Parquet { writer, .. } => writer.close().map(|_| ()).map_err(|e| e.to_string()),
+			#[cfg(feature = "arrow")]
+			GroupWriter::Arrow(writer, _) => writer.close().map(|_| ()).map_err(|e| e.to_string()),
+			#[cfg(feature = "arrow")]
+			GroupWriter::ArrowIpc(writer) => writer.close(),
+		}
+	}
 }
 
 #[derive(Debug, Clone)]
 pub struct WriterSettings {
 	pub row_group_byte_limit: usize,
-	pub row_group_row_limit: usize
+	pub row_group_row_limit: usize,
+	/// A row group whose first row was written longer ago is flushed even if it is under the size limits
+	pub flush_interval: Option<std::time::Duration>,
+	/// Index of the column whose value changes start a new row group
+	pub partition_column: Option<usize>,
+	/// Indices of the columns identifying duplicate rows, a row with the same values as the previous row is skipped
+	pub distinct_on: Vec<usize>,
+	/// Names of the columns to collect null counts and distinct value estimates for, None to skip it
+	pub column_stats: Option<Vec<String>>,
+	/// Index and name of the column whose minimum and maximum is written into the file metadata, see `WatermarkCollector`
+	pub watermark_column: Option<(usize, String)>,
+	/// What to do when a value cannot be read from the row
+	pub on_row_error: OnRowError,
+	/// Columns identifying the rows in error messages, see `identify_row`
+	pub row_identifier: Vec<String>
 }
 
-pub struct ParquetRowWriter<W: Write + Send> {
-	writer: SerializedFileWriter<W>,
+/// Appender of the whole row, each top-level column is encoded separately
+pub type RowAppender = DynamicMergedAppender<Arc<postgres::Row>>;
+
+/// Filled appenders are sent to the worker thread, which encodes and compresses them into a row group
+/// and sends them back cleared, together with the metadata of the group (None if nothing was written)
+type GroupResult = Result<(RowAppender, Option<RowGroupMetaDataPtr>), String>;
+
+pub struct ParquetRowWriter<W: Write + Send + 'static> {
 	schema: parquet::schema::types::TypePtr,
-	// row_group_writer: SerializedRowGroupWriter<'a, W>,
-	appender: DynColumnAppender<Arc<postgres::Row>>,
+	appender: RowAppender,
+	/// Empty appender for the next row group, None while the worker is writing the previous group
+	spare_appender: Option<RowAppender>,
+	group_sender: Option<mpsc::SyncSender<RowAppender>>,
+	group_receiver: mpsc::Receiver<GroupResult>,
+	worker: Option<JoinHandle<Option<GroupWriter<W>>>>,
 	stats: WriterStats,
 	last_timestep_stats: WriterStats,
 	last_timestep_time: std::time::Instant,
@@ -32,24 +235,43 @@ pub struct ParquetRowWriter<W: Write + Send> {
 	quiet: bool,
 	settings: WriterSettings,
 	current_group_bytes: usize,
-	current_group_rows: usize
+	current_group_rows: usize,
+	/// When the first row of the current row group was written
+	current_group_start: std::time::Instant,
+	/// Raw value of the partition column in the last written row
+	last_partition_value: Option<Vec<u8>>,
+	/// Raw values of the distinct_on columns in the last row
+	last_distinct_values: Option<Vec<Option<Vec<u8>>>>,
+	column_stats: Option<ColumnStatsCollector>,
+	watermark: Option<WatermarkCollector>,
 }
 
-impl <W: Write + Send> ParquetRowWriter<W> {
+impl <W: Write + Send + 'static> ParquetRowWriter<W> {
+	/// The row groups are written on a background thread, while the next group is filled into `spare_appender`,
+	/// which must be created from the same schema as `appender`
 	pub fn new(
-		writer: SerializedFileWriter<W>,
+		writer: GroupWriter<W>,
 		schema: parquet::schema::types::TypePtr,
-		appender: DynColumnAppender<Arc<postgres::Row>>,
+		appender: RowAppender,
+		spare_appender: RowAppender,
 		quiet: bool,
 		settings: WriterSettings
 	) -> parquet::errors::Result<Self> {
-		// let mut row_group_writer = writer.next_row_group()?;
+		let (group_sender, worker_receiver) = mpsc::sync_channel(1);
+		let (worker_sender, group_receiver) = mpsc::sync_channel(1);
+		let worker = std::thread::Builder::new()
+			.name("pg2parquet-writer".to_string())
+			.spawn(move || write_groups(writer, worker_receiver, worker_sender))?;
 		let start_time = std::time::Instant::now();
+		let column_stats = settings.column_stats.clone().map(ColumnStatsCollector::new);
+		let watermark = settings.watermark_column.clone().map(|(column, name)| WatermarkCollector::new(column, name));
 		Ok(ParquetRowWriter {
-			writer,
 			schema,
-			// row_group_writer,
 			appender,
+			spare_appender: Some(spare_appender),
+			group_sender: Some(group_sender),
+			group_receiver,
+			worker: Some(worker),
 			stats: WriterStats::default(),
 			last_timestep_stats: WriterStats::default(),
 			last_timestep_time: start_time,
@@ -58,57 +280,173 @@ impl <W: Write + Send> ParquetRowWriter<W> {
 			quiet,
 			settings,
 			current_group_bytes: 0,
-			current_group_rows: 0
+			current_group_rows: 0,
+			current_group_start: start_time,
+			last_partition_value: None,
+			last_distinct_values: None,
+			column_stats,
+			watermark,
 		})
 	}
 
-	fn flush_group(&mut self) -> Result<(), String> {
-		let row_group_writer = self.writer.next_row_group().map_err(|e| format!("Error creating row group: {}", e))?;
-		let row_group_writer: Arcell<_> = Arc::new(RefCell::new(Some(row_group_writer)));
-		let mut dyn_writer = new_dynamic_serialized_writer(row_group_writer.clone());
-
-		self.appender.write_columns(0, dyn_writer.as_mut())?;
+	/// Waits until the worker finishes the previous row group
+	fn receive_written_group(&mut self) -> Result<RowAppender, String> {
+		let (appender, metadata) = self.group_receiver.recv().map_err(|_| "Parquet writer thread has crashed".to_string())??;
+		self.stats.groups += 1;
+		if let Some(metadata) = metadata {
+			self.stats.bytes_out += metadata.compressed_size() as usize;
+			self.stats.add_column_sizes(metadata.columns().iter().map(|c| ColumnSize {
+				column: c.column_path().string(),
+				compressed_bytes: c.compressed_size() as u64,
+				uncompressed_bytes: c.uncompressed_size() as u64,
+			}));
+		}
+		Ok(appender)
+	}
 
-		mem::drop(dyn_writer);
-		let hack123 = RefCell::new(None);
-		row_group_writer.swap(&hack123);
-		let hack1234 = hack123.into_inner().unwrap();
-		let metadata = hack1234.close().map_err(|e| format!("Error closing row group: {}", e))?;
+	fn flush_group(&mut self) -> Result<(), String> {
+		let empty_appender = match self.spare_appender.take() {
+			Some(appender) => appender,
+			None => self.receive_written_group()?,
+		};
+		let filled_appender = mem::replace(&mut self.appender, empty_appender);
+		self.group_sender.as_ref().unwrap().send(filled_appender).map_err(|_| "Parquet writer thread has crashed".to_string())?;
 
-		self.stats.groups += 1;
-		self.stats.bytes_out += metadata.compressed_size() as usize;
 		self.current_group_bytes = 0;
 		self.current_group_rows = 0;
 
 		Ok(())
 	}
 
+	/// Time when the current row group should be written out because of the `flush_interval`, None if it is empty or the interval is not set
+	pub fn flush_deadline(&self) -> Option<std::time::Instant> {
+		match self.settings.flush_interval {
+			Some(interval) if self.current_group_rows > 0 => Some(self.current_group_start + interval),
+			_ => None,
+		}
+	}
+
+	/// Writes out the current row group if it is older than the `flush_interval`
+	pub fn flush_expired_group(&mut self) -> Result<(), String> {
+		if self.flush_deadline().is_some_and(|deadline| deadline <= std::time::Instant::now()) {
+			self.flush_group()?;
+		}
+		Ok(())
+	}
+
 	pub fn write_row(&mut self, row: Arc<postgres::Row>) -> Result<(), String> {
+		if !self.settings.distinct_on.is_empty() {
+			let values = self.settings.distinct_on.iter()
+				.map(|&i| row.try_get::<_, Option<PgAny>>(i).map(|v| v.map(|v| v.value)))
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(|e| format!("Could not read the --distinct-on columns of Row[{}]: {}", identify_row(&row, &self.settings.row_identifier), e))?;
+			if self.last_distinct_values.as_ref() == Some(&values) {
+				self.stats.duplicate_rows += 1;
+				return Ok(());
+			}
+			self.last_distinct_values = Some(values);
+		}
+		if let Some(partition_column) = self.settings.partition_column {
+			let value = row.try_get::<_, Option<PgAny>>(partition_column)
+				.map_err(|e| format!("Could not read the partition column of Row[{}]: {}", identify_row(&row, &self.settings.row_identifier), e))?
+				.map(|v| v.value);
+			if self.current_group_rows > 0 && self.last_partition_value != value {
+				self.flush_group()?;
+			}
+			self.last_partition_value = value;
+		}
+
+		if self.current_group_rows == 0 {
+			self.current_group_start = std::time::Instant::now();
+		}
 		let lvl = LevelIndexList::new_i(self.stats.rows);
-		let bytes = self.appender.copy_value(&lvl, Cow::Borrowed(&row))
-			.map_err(|e| format!("Could not copy Row[{}]:", identify_row(&row)) + &e)?;
+		let bytes = match self.settings.on_row_error {
+			OnRowError::Fail => self.appender.copy_value(&lvl, Cow::Borrowed(&row))
+				.map_err(|e| format!("Could not copy Row[{}]:", identify_row(&row, &self.settings.row_identifier)) + &e)?,
+			OnRowError::Skip => {
+				self.appender.begin_row();
+				match self.appender.copy_value(&lvl, Cow::Borrowed(&row)) {
+					Ok(bytes) => bytes,
+					Err(e) => {
+						self.appender.rollback_row();
+						self.stats.failed_rows += 1;
+						self.record_row_error(&row, None, e);
+						return Ok(());
+					}
+				}
+			},
+			OnRowError::NullColumn => self.copy_row_nulling_columns(&lvl, &row)?,
+		};
+
+		if let Some(column_stats) = &mut self.column_stats {
+			column_stats.add_row(&row).map_err(|e| format!("Could not collect statistics of Row[{}]: {}", identify_row(&row, &self.settings.row_identifier), e))?;
+		}
+		if let Some(watermark) = &mut self.watermark {
+			watermark.add_row(&row).map_err(|e| format!("Could not read the watermark of Row[{}]: {}", identify_row(&row, &self.settings.row_identifier), e))?;
+		}
 
 		self.current_group_bytes += bytes;
 		self.current_group_rows += 1;
 		self.stats.bytes += bytes;
 		self.stats.rows += 1;
 
-		if self.current_group_bytes >= self.settings.row_group_byte_limit || self.current_group_rows >= self.settings.row_group_row_limit {
+		let group_expired = self.settings.flush_interval.is_some_and(|interval| self.current_group_start.elapsed() >= interval);
+		if self.current_group_bytes >= self.settings.row_group_byte_limit || self.current_group_rows >= self.settings.row_group_row_limit || group_expired {
 			self.flush_group()?;
 		}
 
-		if !self.quiet && self.stats.rows % 256 == 0 {
+		if !self.quiet && self.stats.rows.is_multiple_of(256) {
 			self.print_stats(false);
 		}
 
 		Ok(())
 	}
 
+	/// Copies the row column by column, the columns which cannot be read are written as NULL
+	fn copy_row_nulling_columns(&mut self, lvl: &LevelIndexList, row: &Arc<postgres::Row>) -> Result<usize, String> {
+		let mut bytes = 0;
+		let mut failed_columns = vec![];
+		for (i, column) in self.appender.columns_mut().iter_mut().enumerate() {
+			column.begin_row();
+			match column.copy_value(lvl, Cow::Borrowed(row)) {
+				Ok(b) => bytes += b,
+				Err(e) => {
+					column.rollback_row();
+					bytes += column.write_null(lvl, 0)?;
+					failed_columns.push((i, e));
+				}
+			}
+		}
+		if !failed_columns.is_empty() {
+			self.stats.failed_rows += 1;
+		}
+		for (i, e) in failed_columns {
+			let column = self.schema.get_fields()[i].name().to_string();
+			self.record_row_error(row, Some(column), e);
+		}
+		Ok(bytes)
+	}
+
+	fn record_row_error(&mut self, row: &postgres::Row, column: Option<String>, message: String) {
+		if self.stats.row_errors.len() >= MAX_RECORDED_ROW_ERRORS {
+			return;
+		}
+		let row = identify_row(row, &self.settings.row_identifier);
+		match &column {
+			Some(column) => warn(Some(column.clone()), format!("Column {} of Row[{}] is replaced by NULL: {}", column, row, message)),
+			None => warn(None, format!("Skipping Row[{}]: {}", row, message)),
+		}
+		if self.stats.row_errors.len() + 1 == MAX_RECORDED_ROW_ERRORS {
+			warn(None, "Too many rows failed, further errors are not reported".to_string());
+		}
+		self.stats.row_errors.push(RowError { row, column, message });
+	}
+
 	pub fn print_stats(&mut self, summary: bool) {
 		fn format_number<T: Display>(n: T) -> String {
 			let mut result = format!("{}", n);
 			// let mut last_index = result.len() - 1;
-			let mut last_index = result.find(|c| c == '.' || c == 'e').unwrap_or(result.len());
+			let mut last_index = result.find(['.', 'e']).unwrap_or(result.len());
 			while last_index > 3 {
 				last_index -= 3;
 				result.insert(last_index, '_');
@@ -153,13 +491,171 @@ impl <W: Write + Send> ParquetRowWriter<W> {
 	pub fn get_stats(&mut self) -> WriterStats { self.stats.clone() }
 
 	pub fn close(mut self) -> Result<WriterStats, String> {
-		self.flush_group().map_err(|e| e)?;
+		self.flush_group()?;
+		self.spare_appender = Some(self.receive_written_group()?);
+
+		mem::drop(self.group_sender.take());
+		let writer = self.worker.take().unwrap().join().map_err(|_| "Parquet writer thread has crashed".to_string())?;
 
-		self.print_stats(true);
+		if !self.quiet {
+			self.print_stats(true);
+			print_column_sizes(&self.stats.column_sizes);
+		}
 
 		// self.row_group_writer.close().map_err(|e| e.to_string())?;
-		self.writer.close().map_err(|e| e.to_string())?;
+		let mut writer = writer.unwrap();
+		if let Some(watermark) = &self.watermark {
+			for kv in watermark.metadata() {
+				writer.append_key_value_metadata(kv);
+			}
+		}
+		writer.close()?;
+
+		if let Some(column_stats) = &self.column_stats {
+			self.stats.columns = column_stats.stats();
+		}
+		Ok(self.stats.clone())
+	}
+}
 
-		Ok(self.stats)
+impl<W: Write + Send + 'static> Drop for ParquetRowWriter<W> {
+	fn drop(&mut self) {
+		// the file must not be written after the writer is dropped, it might be replaced by a new export attempt
+		mem::drop(self.group_sender.take());
+		if let Some(worker) = self.worker.take() {
+			_ = worker.join();
+		}
+	}
+}
+
+fn write_groups<W: Write + Send>(
+	mut writer: GroupWriter<W>,
+	receiver: mpsc::Receiver<RowAppender>,
+	sender: mpsc::SyncSender<GroupResult>
+) -> Option<GroupWriter<W>> {
+	for mut appender in receiver {
+		let result = writer.write_group(&mut appender);
+		let failed = result.is_err();
+		_ = sender.send(result.map(|metadata| (appender, metadata)));
+		if failed {
+			return None;
+		}
+	}
+	Some(writer)
+}
+
+pub(crate) fn write_group<W: Write + Send>(writer: &mut SerializedFileWriter<W>, column_props: &mut WriterPropertiesPtr, page_checksums: bool, tune_compression: &mut bool, appender: &mut RowAppender) -> Result<RowGroupMetaDataPtr, String> {
+	// leaf columns of each top-level column are encoded and compressed into memory in parallel, then copied into the file in order
+	let schema = writer.schema_descr();
+	let mut leaf_columns: Vec<Vec<ColumnDescPtr>> = vec![Vec::new(); schema.root_schema().get_fields().len()];
+	for (i, column) in schema.columns().iter().enumerate() {
+		leaf_columns[schema.get_column_root_idx(i)].push(column.clone());
+	}
+	let props = column_props.clone();
+	let chunks = appender.columns_mut().par_iter_mut().zip(leaf_columns).enumerate().map(|(i, (column_appender, leaf_columns))| {
+		let mut column_writer = InMemoryColumnWriter::new(leaf_columns, props.clone(), page_checksums);
+		column_appender.write_columns(i, &mut column_writer)?;
+		Ok(column_writer.into_chunks())
+	}).collect::<Result<Vec<_>, String>>()?;
+	let chunks: Vec<(Bytes, ColumnCloseResult)> = chunks.into_iter().flatten().collect();
+	if mem::take(tune_compression) {
+		*column_props = auto_column_properties(column_props, &chunks)?;
+	}
+
+	let mut row_group_writer = writer.next_row_group().map_err(|e| format!("Error creating row group: {}", e))?;
+	for (data, close_result) in chunks {
+		let column_name = close_result.metadata.column_path().string();
+		row_group_writer.append_column(&data, close_result).map_err(|e| format!("Couldn't write data of {}: {}", column_name, e))?;
+	}
+	row_group_writer.close().map_err(|e| format!("Error closing row group: {}", e))
+}
+
+/// Prints the compressed and uncompressed size of the largest columns, to show which of them dominate the file size
+fn print_column_sizes(columns: &[ColumnSize]) {
+	fn format_size(bytes: u64) -> String {
+		match bytes {
+			0..1024 => format!("{} B", bytes),
+			1024..1048576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+			_ => format!("{:.1} MiB", bytes as f64 / 1024.0 / 1024.0),
+		}
+	}
+	// the Arrow IPC output has no column chunks
+	if columns.is_empty() {
+		return;
+	}
+	let total: u64 = columns.iter().map(|c| c.compressed_bytes).sum();
+	let mut columns: Vec<&ColumnSize> = columns.iter().collect();
+	columns.sort_by_key(|c| std::cmp::Reverse(c.compressed_bytes));
+	eprintln!("Column sizes:");
+	for c in columns.iter().take(MAX_PRINTED_COLUMN_SIZES) {
+		eprintln!("  {}: {} compressed, {} uncompressed ({:.2}x), {:.1}% of the file",
+			c.column, format_size(c.compressed_bytes), format_size(c.uncompressed_bytes),
+			c.uncompressed_bytes as f64 / c.compressed_bytes.max(1) as f64,
+			c.compressed_bytes as f64 * 100.0 / total.max(1) as f64);
+	}
+	if columns.len() > MAX_PRINTED_COLUMN_SIZES {
+		eprintln!("  ... and {} more columns", columns.len() - MAX_PRINTED_COLUMN_SIZES);
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnKind { Numeric, String, Binary }
+
+fn column_kind(column: &ColumnDescriptor) -> ColumnKind {
+	match column.physical_type() {
+		PhysicalType::BYTE_ARRAY | PhysicalType::FIXED_LEN_BYTE_ARRAY => match (column.logical_type(), column.converted_type()) {
+			(Some(LogicalType::String | LogicalType::Enum | LogicalType::Json), _) | (_, ConvertedType::UTF8 | ConvertedType::ENUM | ConvertedType::JSON) => ColumnKind::String,
+			(Some(LogicalType::Decimal { .. } | LogicalType::Float16), _) | (_, ConvertedType::DECIMAL) => ColumnKind::Numeric,
+			_ => ColumnKind::Binary,
+		},
+		_ => ColumnKind::Numeric,
+	}
+}
+
+/// Properties of the column writers for `--compression=auto`, with the compression and dictionary encoding of each leaf column
+/// chosen from how well it compressed in the first row group, written with `base`
+fn auto_column_properties(base: &WriterProperties, chunks: &[(Bytes, ColumnCloseResult)]) -> Result<WriterPropertiesPtr, String> {
+	let mut builder = WriterProperties::builder()
+		.set_writer_version(base.writer_version())
+		.set_data_page_size_limit(base.data_page_size_limit())
+		.set_data_page_row_count_limit(base.data_page_row_count_limit())
+		.set_dictionary_page_size_limit(base.dictionary_page_size_limit())
+		.set_write_batch_size(base.write_batch_size())
+		.set_offset_index_disabled(base.offset_index_disabled())
+		.set_column_index_truncate_length(base.column_index_truncate_length())
+		.set_statistics_truncate_length(base.statistics_truncate_length());
+	for (data, close_result) in chunks {
+		let metadata = &close_result.metadata;
+		let path = metadata.column_path().clone();
+		let ratio = metadata.uncompressed_size() as f64 / metadata.compressed_size().max(1) as f64;
+		let compression = match column_kind(metadata.column_descr()) {
+			// most likely already compressed data, such as images
+			ColumnKind::Binary if ratio < 1.1 => Compression::UNCOMPRESSED,
+			// random numbers or identifiers, higher levels would only be slower
+			_ if ratio < 1.2 => Compression::ZSTD(ZstdLevel::try_new(1).unwrap()),
+			// text usually benefits from a higher level
+			ColumnKind::String => Compression::ZSTD(ZstdLevel::try_new(6).unwrap()),
+			ColumnKind::Numeric | ColumnKind::Binary => Compression::ZSTD(ZstdLevel::try_new(3).unwrap()),
+		};
+		// the dictionary is disabled if it overflowed into plain encoded pages, or if most values are distinct
+		let mut dictionary = base.dictionary_enabled(&path);
+		if metadata.dictionary_page_offset().is_some() {
+			let mut pages = SerializedPageReader::new(Arc::new(data.clone()), metadata, close_result.rows_written as usize, None)
+				.map_err(|e| format!("Failed to read the pages of {}: {}", path.string(), e))?;
+			while let Some(page) = pages.get_next_page().map_err(|e| format!("Failed to read the pages of {}: {}", path.string(), e))? {
+				dictionary &= match page {
+					Page::DictionaryPage { num_values, .. } => num_values as i64 * 2 <= metadata.num_values(),
+					Page::DataPage { encoding, .. } | Page::DataPageV2 { encoding, .. } => matches!(encoding, Encoding::RLE_DICTIONARY | Encoding::PLAIN_DICTIONARY),
+				};
+			}
+		}
+		builder = builder
+			.set_column_compression(path.clone(), compression)
+			.set_column_dictionary_enabled(path.clone(), dictionary)
+			.set_column_statistics_enabled(path.clone(), base.statistics_enabled(&path));
+		if let Some(encoding) = base.encoding(&path) {
+			builder = builder.set_column_encoding(path, encoding);
+		}
 	}
+	Ok(Arc::new(builder.build()))
 }
@@ -1,145 +1,129 @@
-use parquet;
-use parquet::basic::{LogicalType, ConvertedType};
-use parquet::column::reader::ColumnReaderImpl;
-use parquet::data_type::{DataType, BoolType};
-use parquet::file::reader::{FileReader, SerializedFileReader};
-use parquet::column::reader::ColumnReader;
-use parquet::schema::types::ColumnDescriptor;
-use std::fmt::{Display, Debug, Formatter};
+use std::path::PathBuf;
 
-fn print_col_info<T: DataType<T = T2>, T2: Default + Clone + ParquetTypeFormat>(col_name: &str, col: &ColumnDescriptor, reader: &mut ColumnReaderImpl<T>) {
-	let batch_size = 300;
-	let mut data: Vec<T2> = vec![<T as DataType>::T::default(); batch_size];
-	let mut dls = vec![0; batch_size];
-	let mut rls = vec![0; batch_size];
-	let (record_count, valuecount, totalcount) = reader.read_records(batch_size, Some(&mut dls), Some(&mut rls), &mut data).unwrap();
+use parquet::file::metadata::{ParquetMetaData, ParquetMetaDataReader};
+use serde_json::json;
 
-	let data_display = DisplayDataRow { vec: data[0..valuecount].to_vec(), lt: col.logical_type(), ct: col.converted_type() };
-	println!("{}: {:?} {}", col_name, (record_count, valuecount, totalcount), data_display);
+use crate::parquet_importer::read_rows;
+use crate::postgres_cloner::{format_schema, schema_to_json};
 
-	if dls.iter().any(|x| *x != 0) {
-		println!("dls: {:?}", dls[0..totalcount].to_vec());
-	}
-	if rls.iter().any(|x| *x != 0) {
-		println!("rls: {:?}", rls[0..totalcount].to_vec());
-	}
+/// Sizes and statistics of one column, summed over all row groups
+struct ColumnSummary {
+	path: String,
+	compression: String,
+	encodings: Vec<String>,
+	compressed_bytes: i64,
+	uncompressed_bytes: i64,
+	/// None if any of the row groups is missing the statistics
+	null_count: Option<u64>,
 }
 
-#[cfg(not(debug_assertions))]
-pub fn print_parquet_info(_path: &std::path::PathBuf) {
-	println!("Disabled in release build")
+fn ratio(compressed: i64, uncompressed: i64) -> f64 {
+	if compressed == 0 { 0.0 } else { uncompressed as f64 / compressed as f64 }
 }
 
-#[cfg(debug_assertions)]
-pub fn print_parquet_info(path: &std::path::PathBuf) {
-	let file = std::fs::File::open(path).unwrap();
-	let reader = SerializedFileReader::new(file).unwrap();
-	let meta = reader.metadata();
-	let schema = meta.file_metadata().schema_descr();
-
-	for row_group_i in 0..reader.num_row_groups() {
-		let rg = reader.get_row_group(row_group_i).unwrap();
-		for column_i in 0..rg.num_columns() {
-
-			let column = rg.get_column_reader(column_i).unwrap();
-			let column_meta = rg.metadata().columns()[column_i].clone();
-			let column_type = schema.column(column_i);
-			let name = column_meta.column_path().string();
-			println!("column: {} {:?}", name, column_meta);
-
-			match column {
-				ColumnReader::BoolColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::Int32ColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::Int64ColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::Int96ColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::FloatColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::DoubleColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::ByteArrayColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
-				ColumnReader::FixedLenByteArrayColumnReader(mut c) => print_col_info(&name, &column_type, &mut c),
+fn column_summaries(metadata: &ParquetMetaData) -> Vec<ColumnSummary> {
+	let schema = metadata.file_metadata().schema_descr();
+	(0..schema.num_columns()).map(|i| {
+		let mut summary = ColumnSummary {
+			path: schema.column(i).path().string(),
+			compression: String::new(),
+			encodings: Vec::new(),
+			compressed_bytes: 0,
+			uncompressed_bytes: 0,
+			null_count: Some(0),
+		};
+		for row_group in metadata.row_groups() {
+			let column = row_group.column(i);
+			// the compression level is not stored in the file, the reader only shows the default one
+			summary.compression = column.compression().to_string().split('(').next().unwrap().to_string();
+			for encoding in column.encodings() {
+				let encoding = encoding.to_string();
+				if !summary.encodings.contains(&encoding) {
+					summary.encodings.push(encoding);
+				}
 			}
+			summary.compressed_bytes += column.compressed_size();
+			summary.uncompressed_bytes += column.uncompressed_size();
+			summary.null_count = summary.null_count.zip(column.statistics().and_then(|s| s.null_count_opt())).map(|(a, b)| a + b);
 		}
-	}
+		summary
+	}).collect()
 }
 
-trait ParquetTypeFormat {
-	fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result;
-}
+/// Prints the schema, metadata, row group and column sizes and optionally the first `sample_rows` rows of the file
+pub fn print_parquet_info(path: &PathBuf, sample_rows: usize, json: bool) -> Result<(), String> {
+	let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+	let metadata = ParquetMetaDataReader::new().parse_and_finish(&file).map_err(|e| format!("Failed to read parquet file {:?}: {}", path, e))?;
+	let file_metadata = metadata.file_metadata();
+	let columns = column_summaries(&metadata);
 
-impl ParquetTypeFormat for bool {
-    fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(self, f)
-    }
-}
-impl ParquetTypeFormat for i32 {
-	fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		Display::fmt(self, f)
-	}
-}
-impl ParquetTypeFormat for i64 {
-	fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		Display::fmt(self, f)
+	let mut samples = Vec::new();
+	if sample_rows > 0 {
+		read_rows(file, &metadata, |row| {
+			samples.push(row);
+			Ok(samples.len() < sample_rows)
+		}).map_err(|e| format!("Failed to read parquet file {:?}: {}", path, e))?;
 	}
-}
-impl ParquetTypeFormat for parquet::data_type::Int96 {
-	fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		Display::fmt(self, f)
+
+	let key_value_metadata: Vec<(String, String)> = file_metadata.key_value_metadata().into_iter().flatten()
+		.map(|kv| (kv.key.clone(), kv.value.clone().unwrap_or_default()))
+		.collect();
+
+	if json {
+		let info = json!({
+			"rows": file_metadata.num_rows(),
+			"version": file_metadata.version(),
+			"created_by": file_metadata.created_by(),
+			"metadata": key_value_metadata.iter().map(|(k, v)| (k.clone(), json!(v))).collect::<serde_json::Map<_, _>>(),
+			"schema": schema_to_json(file_metadata.schema()),
+			"row_groups": metadata.row_groups().iter().map(|rg| json!({
+				"rows": rg.num_rows(),
+				"compressed_bytes": rg.compressed_size(),
+				"uncompressed_bytes": rg.total_byte_size(),
+			})).collect::<Vec<_>>(),
+			"columns": columns.iter().map(|c| json!({
+				"path": c.path,
+				"compression": c.compression,
+				"encodings": c.encodings,
+				"compressed_bytes": c.compressed_bytes,
+				"uncompressed_bytes": c.uncompressed_bytes,
+				"compression_ratio": ratio(c.compressed_bytes, c.uncompressed_bytes),
+				"null_count": c.null_count,
+			})).collect::<Vec<_>>(),
+			"sample_rows": samples.iter().map(|r| r.to_json_value()).collect::<Vec<_>>(),
+		});
+		println!("{}", serde_json::to_string_pretty(&info).unwrap());
+		return Ok(());
 	}
-}
-impl ParquetTypeFormat for f32 {
-	fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		Display::fmt(self, f)
+
+	println!("Rows: {}", file_metadata.num_rows());
+	println!("Format version: {}", file_metadata.version());
+	if let Some(created_by) = file_metadata.created_by() {
+		println!("Created by: {}", created_by);
 	}
-}
-impl ParquetTypeFormat for f64 {
-	fn show(&self, _lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		Display::fmt(self, f)
+	for (key, value) in &key_value_metadata {
+		println!("Metadata {}: {}", key, value);
 	}
-}
-impl ParquetTypeFormat for parquet::data_type::ByteArray {
-	fn show(&self, lt: &Option<LogicalType>, ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		match lt {
-			Some(LogicalType::String) | Some(LogicalType::Json) | Some(LogicalType::Enum) => {
-				let s = std::str::from_utf8(self.data()).unwrap();
-				f.write_str(&s)
-			},
-			_ => {
-				match ct {
-					ConvertedType::UTF8 | ConvertedType::ENUM | ConvertedType::JSON => {
-						let s = std::str::from_utf8(self.data()).unwrap();
-						f.write_str(&s)
-					},
-					_ => {
-						write!(f, "{:x?}", self.data())
-					}
-				}
-			}
-		}
+	println!();
+	println!("{}", format_schema(file_metadata.schema(), 0));
+	println!();
+	println!("Row groups:");
+	for (i, rg) in metadata.row_groups().iter().enumerate() {
+		println!("  {}: {} rows, {} bytes compressed, {} bytes uncompressed ({:.2}x)", i, rg.num_rows(), rg.compressed_size(), rg.total_byte_size(), ratio(rg.compressed_size(), rg.total_byte_size()));
 	}
-}
-impl ParquetTypeFormat for parquet::data_type::FixedLenByteArray {
-	fn show(&self, lt: &Option<LogicalType>, _ct: &ConvertedType, f: &mut Formatter<'_>) -> std::fmt::Result {
-		match lt {
-			_ => {
-				write!(f, "{:x?}", self.data())
-			}
-		}
+	println!();
+	println!("Columns:");
+	for c in &columns {
+		let nulls = c.null_count.map(|n| n.to_string()).unwrap_or_else(|| "?".to_string());
+		println!("  {}: {} [{}], {} bytes compressed, {} bytes uncompressed ({:.2}x), {} nulls",
+			c.path, c.compression, c.encodings.join(", "), c.compressed_bytes, c.uncompressed_bytes, ratio(c.compressed_bytes, c.uncompressed_bytes), nulls);
 	}
-}
-
-struct DisplayDataRow<T: ParquetTypeFormat> {
-	pub vec: Vec<T>,
-	pub lt: Option<LogicalType>,
-	pub ct: ConvertedType,
-}
-impl<T: ParquetTypeFormat> Display for DisplayDataRow<T> {
-	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-		write!(f, "[")?;
-		for (i, v) in self.vec.iter().enumerate() {
-			if i != 0 {
-				write!(f, ", ")?;
-			}
-			v.show(&self.lt, &self.ct, f)?;
+	if !samples.is_empty() {
+		println!();
+		println!("Sample rows:");
+		for row in &samples {
+			println!("  {}", row);
 		}
-		write!(f, "]")
 	}
+	Ok(())
 }
@@ -1,13 +1,22 @@
 use std::{sync::Arc, any::TypeId, io::Read};
 
 use byteorder::{ReadBytesExt, BigEndian};
-use postgres::types::{FromSql, Kind, WrongType, Field};
+use postgres::types::{FromSql, ToSql, IsNull, Kind, WrongType, Field, to_sql_checked};
 use postgres_protocol::types as pgtypes;
 
 fn read_pg_len(bytes: &[u8]) -> i32 {
 	let mut x = [0u8; 4];
 	x.copy_from_slice(&bytes[0..4]);
-	return i32::from_be_bytes(x);
+	i32::from_be_bytes(x)
+}
+
+/// Underlying type of a domain, PostgreSQL sends domain values in the format of the base type.
+/// Top-level columns are already described by the base type, but array elements, range bounds and composite fields are not
+pub fn strip_domains(mut ty: &postgres::types::Type) -> &postgres::types::Type {
+	while let Kind::Domain(base) = ty.kind() {
+		ty = base;
+	}
+	ty
 }
 
 #[derive(Debug, Clone)]
@@ -17,17 +26,44 @@ pub struct PgEnum {
 
 impl<'a> FromSql<'a> for PgEnum {
 	fn from_sql(ty: &postgres::types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
-		debug_assert!(match ty.kind() { Kind::Enum(_) => true, _ => false });
+		debug_assert!(matches!(ty.kind(), Kind::Enum(_)));
 		let s = String::from_utf8(raw.to_vec())?;
 		Ok(PgEnum { name: s })
 	}
 
 	fn accepts(ty: &postgres::types::Type) -> bool {
-		match ty.kind() {
-			Kind::Enum(_) => true,
-			_ => false
-		}
+		matches!(ty.kind(), Kind::Enum(_))
+	}
+}
+
+/// Value of an extension type from the string category, which is sent in the same binary format as text
+#[derive(Debug, Clone)]
+pub struct PgText(pub String);
+
+impl<'a> FromSql<'a> for PgText {
+	fn from_sql(_ty: &postgres::types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		Ok(PgText(String::from_utf8(raw.to_vec())?))
 	}
+
+	fn accepts(_ty: &postgres::types::Type) -> bool { true }
+}
+impl From<PgText> for String {
+	fn from(v: PgText) -> String { v.0 }
+}
+
+/// Text value with the invalid UTF-8 sequences replaced by U+FFFD, see `--invalid-utf8=replace`
+#[derive(Debug, Clone)]
+pub struct PgLossyText(pub String);
+
+impl<'a> FromSql<'a> for PgLossyText {
+	fn from_sql(_ty: &postgres::types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+		Ok(PgLossyText(String::from_utf8_lossy(raw).into_owned()))
+	}
+
+	fn accepts(_ty: &postgres::types::Type) -> bool { true }
+}
+impl From<PgLossyText> for String {
+	fn from(v: PgLossyText) -> String { v.0 }
 }
 
 #[derive(Debug, Clone)]
@@ -37,18 +73,33 @@ pub struct PgAny {
 }
 impl<'a> FromSql<'a> for PgAny {
 	fn from_sql(ty: &postgres::types::Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
-		match ty.kind() {
-			Kind::Array(_) => panic!("Nooo {}", ty),
-			_ => {}
-		};
 		Ok(PgAny {
-			ty: ty.clone(),
+			ty: strip_domains(ty).clone(),
 			value: raw.to_vec()
 		})
 	}
 
 	fn accepts(_ty: &postgres::types::Type) -> bool { true }
 }
+/// Sends the raw binary value back to the server, the parameter must be of the same type as the value was read from
+impl ToSql for PgAny {
+	fn to_sql(&self, _ty: &postgres::types::Type, out: &mut bytes::BytesMut) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+		out.extend_from_slice(&self.value);
+		Ok(IsNull::No)
+	}
+
+	fn accepts(_ty: &postgres::types::Type) -> bool { true }
+
+	to_sql_checked!();
+}
+/// Entry of a MAP column built from an array of keys and an array of values, see `--as-map`
+#[derive(Debug, Clone)]
+pub struct PgMapEntry {
+	pub key: PgAny,
+	pub value_type: postgres::types::Type,
+	pub value: Option<Vec<u8>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PgAnyRef<'a> {
 	pub ty: postgres::types::Type,
@@ -100,7 +151,7 @@ fn read_byte_vec(raw: &mut &[u8], len: usize) -> Result<Vec<u8>, Box<dyn std::er
 impl<'a> FromSql<'a> for PgRawRange {
 	fn from_sql(ty: &postgres::types::Type, mut raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
 		let element_type = match ty.kind() {
-			Kind::Range(inner_t) => inner_t.clone(),
+			Kind::Range(inner_t) => strip_domains(inner_t).clone(),
 			_ => panic!("Not a range type")
 		};
 		// /* A range's flags byte contains these bits: */
@@ -153,10 +204,7 @@ impl<'a> FromSql<'a> for PgRawRange {
 	}
 
 	fn accepts(ty: &postgres::types::Type) -> bool {
-		match ty.kind() {
-			Kind::Range(_) => true,
-			_ => false
-		}
+		matches!(ty.kind(), Kind::Range(_))
 	}
 }
 #[derive(Debug, Clone)]
@@ -181,13 +229,13 @@ impl<'a> FromSql<'a> for PgRawRecord {
 		let data_buffer = raw[index..].to_vec();
 		index = 0;
 		let mut values = Vec::with_capacity(num_cols);
-		for field_i in 0..num_cols {
-			// println!("Reading field {}, bytes {:?}", fields[field_i].name(), &raw[index..]);
+		for field in &fields[..num_cols] {
+			// println!("Reading field {}, bytes {:?}", field.name(), &raw[index..]);
 			let oid = read_pg_len(&data_buffer[index..]) as u32;
 			index += 4;
-			debug_assert_eq!(oid, fields[field_i].type_().oid());
+			debug_assert_eq!(oid, field.type_().oid());
 			let len = read_pg_len(&data_buffer[index..]);
-			// println!("Reading field {}: {}, len {}", fields[field_i].name(), oid, len);
+			// println!("Reading field {}: {}, len {}", field.name(), oid, len);
 			if len < 0 {
 				values.push(None);
 				index += 4;
@@ -205,10 +253,7 @@ impl<'a> FromSql<'a> for PgRawRecord {
     }
 
     fn accepts(ty: &postgres::types::Type) -> bool {
-		match ty.kind() {
-			Kind::Composite(_) => true,
-			_ => false
-		}
+		matches!(ty.kind(), Kind::Composite(_))
     }
 }
 
@@ -227,13 +272,17 @@ impl<'a> FromSql<'a> for PgRawRecord {
 
 
 pub trait PgAbstractRow {
-	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> T;
+	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String>;
 	fn ab_len(&self) -> usize;
 }
 
 impl PgAbstractRow for postgres::Row {
-	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> T {
-		self.get(index)
+	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
+		self.try_get(index).map_err(|e| match std::error::Error::source(&e) {
+			// the error itself only says "error deserializing column N"
+			Some(cause) => format!("Could not read column {}: {}", self.columns()[index].name(), cause),
+			None => format!("Could not read column {}: {}", self.columns()[index].name(), e),
+		})
 	}
 
 	fn ab_len(&self) -> usize {
@@ -241,8 +290,8 @@ impl PgAbstractRow for postgres::Row {
 	}
 }
 
-impl<'b> PgAbstractRow for PgRawRange {
-    fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> T {
+impl PgAbstractRow for PgRawRange {
+    fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
 		// println!("ab_get: {:?} {:?}", index, &self);
 		let r = match index {
 			0 => self.lower.as_ref(),
@@ -250,7 +299,7 @@ impl<'b> PgAbstractRow for PgRawRange {
 			_ => panic!("Invalid index")
 		};
 		assert!(T::accepts(&self.element_type));
-		T::from_sql_nullable(&self.element_type, r.map(|x| &x[..])).unwrap()
+		T::from_sql_nullable(&self.element_type, r.map(|x| &x[..])).map_err(|e| format!("Could not read range bound: {}", e))
 	}
 
     fn ab_len(&self) -> usize {
@@ -259,23 +308,26 @@ impl<'b> PgAbstractRow for PgRawRange {
 }
 
 impl PgAbstractRow for PgRawRecord {
-    fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> T {
+    fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
 		// println!("ab_get: {:?} {:?}", index, &self);
 		let f = match self.ty.kind() {
 			Kind::Composite(fields) => &fields[index],
 			_ => unreachable!()
 		};
-		assert!(T::accepts(f.type_()));
-		if self.fields.len() < index {
-			return T::from_sql_null(f.type_()).unwrap()
-		}
-		match &self.fields[index] {
-			None => T::from_sql_null(f.type_()).unwrap(),
-			Some(x) => {
-				let len = read_pg_len(&self.data[*x..]) as usize;
-				T::from_sql(f.type_(), &self.data[*x+4 .. x+4+len]).unwrap()
+		let field_type = strip_domains(f.type_());
+		assert!(T::accepts(field_type));
+		let value = if self.fields.len() < index {
+			T::from_sql_null(field_type)
+		} else {
+			match &self.fields[index] {
+				None => T::from_sql_null(field_type),
+				Some(x) => {
+					let len = read_pg_len(&self.data[*x..]) as usize;
+					T::from_sql(field_type, &self.data[*x+4 .. x+4+len])
+				}
 			}
-		}
+		};
+		value.map_err(|e| format!("Could not read field {}: {}", f.name(), e))
 	}
 
     fn ab_len(&self) -> usize {
@@ -288,25 +340,37 @@ impl PgAbstractRow for PgRawRecord {
 
 
 impl PgAbstractRow for PgAny {
-	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> T {
+	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
 		debug_assert_eq!(0, index);
-		T::from_sql(&self.ty, &self.value).unwrap()
+		T::from_sql(&self.ty, &self.value).map_err(|e| format!("Could not read {} value: {}", self.ty, e))
 	}
 
 	fn ab_len(&self) -> usize { 1 }
 }
 
+impl PgAbstractRow for PgMapEntry {
+	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
+		match index {
+			0 => T::from_sql(&self.key.ty, &self.key.value).map_err(|e| format!("Could not read map key: {}", e)),
+			1 => T::from_sql_nullable(&self.value_type, self.value.as_deref()).map_err(|e| format!("Could not read map value: {}", e)),
+			_ => panic!("Invalid index")
+		}
+	}
+
+	fn ab_len(&self) -> usize { 2 }
+}
+
 impl<'b> PgAbstractRow for PgAnyRef<'b> {
-	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> T {
+	fn ab_get<'a, T: FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
 		debug_assert_eq!(0, index);
-		T::from_sql(&self.ty, &self.value).unwrap()
+		T::from_sql(&self.ty, self.value).map_err(|e| format!("Could not read {} value: {}", self.ty, e))
 	}
 
 	fn ab_len(&self) -> usize { 1 }
 }
 
 impl<TRow: PgAbstractRow> PgAbstractRow for Arc<TRow> {
-    fn ab_get<'a, T: postgres::types::FromSql<'a>>(&'a self, index: usize) -> T {
+    fn ab_get<'a, T: postgres::types::FromSql<'a>>(&'a self, index: usize) -> Result<T, String> {
         self.as_ref().ab_get(index)
     }
 
@@ -314,21 +378,3 @@ impl<TRow: PgAbstractRow> PgAbstractRow for Arc<TRow> {
         self.as_ref().ab_len()
     }
 }
-
-pub struct UnclonableHack<T>(pub T);
-
-impl<T> Clone for UnclonableHack<T> {
-	fn clone(&self) -> Self {
-		panic!("Cloning of type {} is disabled", std::any::type_name::<T>())
-	}
-}
-
-impl<TRow: PgAbstractRow> PgAbstractRow for UnclonableHack<TRow> {
-    fn ab_get<'a, T: postgres::types::FromSql<'a>>(&'a self, index: usize) -> T {
-        self.0.ab_get(index)
-    }
-
-    fn ab_len(&self) -> usize {
-        self.0.ab_len()
-    }
-}
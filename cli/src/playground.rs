@@ -13,7 +13,7 @@ pub fn create_something(_file_path: &std::path::PathBuf) {
 
 #[cfg(debug_assertions)]
 pub fn create_something(file_path: &std::path::PathBuf) {
-	let file = fs::File::create(&file_path).unwrap();
+	let file = fs::File::create(file_path).unwrap();
 	let props =
 		WriterProperties::builder()
 			.set_compression(Compression::SNAPPY)
@@ -45,25 +45,25 @@ pub fn create_something(file_path: &std::path::PathBuf) {
 
 	let mut col_id = rg.next_column().unwrap().unwrap();
 	col_id.typed::<Int32Type>().write_batch(
-		&vec![1, 0, 2],
-		Some(&vec![1, 0, 1]),
+		&[1, 0, 2],
+		Some(&[1, 0, 1]),
 		None
 	).unwrap();
 	col_id.close().unwrap();
 
 	let mut col_nested_x = rg.next_column().unwrap().unwrap();
 	col_nested_x.typed::<Int32Type>().write_batch(
-		&vec![     1, 2, 3, 4,       10, 0,     0],
-		Some(&vec![2, 2, 2, 2,        2, 1,     0]),
-		Some(&vec![0, 1, 1, 1,        0, 1,     0])
+		&[     1, 2, 3, 4,       10, 0,     0],
+		Some(&[2, 2, 2, 2,        2, 1,     0]),
+		Some(&[0, 1, 1, 1,        0, 1,     0])
 	).unwrap();
 	col_nested_x.close().unwrap();
 
 	let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
 	let mut y = rg.next_column().unwrap().unwrap();
 	y.typed::<Int64Type>().write_batch(
-		&vec![     now_ms,   0,     0],
-		Some(&vec![2,        1,     0]),
+		&[     now_ms,   0,     0],
+		Some(&[2,        1,     0]),
 		None
 	).unwrap();
 	y.close().unwrap();
@@ -1,20 +1,21 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::io::{self, Write};
 use std::marker::PhantomData;
 use std::net::IpAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use clap::error::Error;
 use parquet::basic::{Repetition, self, ConvertedType, LogicalType};
 use parquet::data_type::{DataType, BoolType, Int32Type, Int64Type, FloatType, DoubleType, ByteArray, ByteArrayType, FixedLenByteArrayType, FixedLenByteArray};
-use parquet::file::properties::WriterPropertiesPtr;
-use parquet::file::writer::SerializedFileWriter;
-use parquet::format::TimestampType;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{WriterPropertiesBuilder, WriterPropertiesPtr};
+use parquet::format::{SortingColumn, TimestampType};
 use pg_bigdecimal::PgNumeric;
 use postgres::error::SqlState;
 use postgres::types::{Kind, Type as PgType, FromSql};
@@ -24,17 +25,116 @@ use parquet::schema::types::{Type as ParquetType, TypePtr, GroupTypeBuilder};
 
 use crate::datatypes::array::{PgMultidimArray, PgMultidimArrayLowerBounds};
 use crate::PostgresConnArgs;
-use crate::appenders::{new_autoconv_generic_appender, new_static_merged_appender, ArrayColumnAppender, BasicPgRowColumnAppender, ColumnAppender, ColumnAppenderBase, DynColumnAppender, DynamicMergedAppender, GenericColumnAppender, PreprocessAppender, PreprocessExt, RcWrapperAppender, RealMemorySize, StaticMergedAppender};
+use crate::cancellation;
+use crate::column_definitions::ColumnDefinitions;
+use crate::column_stats;
+use crate::ddl::{self, DdlOutput};
+use crate::checksum::{Checksum, ChecksumWriter};
+use crate::masking::{self, ColumnMask};
+use crate::appenders::{byte_array, new_autoconv_generic_appender, AlwaysNullAppender, new_static_merged_appender, ArrayColumnAppender, BasicPgRowColumnAppender, PgRowPairColumnAppender, ColumnAppender, ColumnAppenderBase, DynColumnAppender, DynamicMergedAppender, GenericColumnAppender, PreprocessAppender, PreprocessExt, RealMemorySize, RowIndexAppender, StaticMergedAppender, UnwrapOptionAppender};
+use crate::datatypes::float::{FloatSpecialValuesAppender, SpecialFloatValue};
+use crate::datatypes::inet::PgInet;
 use crate::datatypes::interval::PgInterval;
 use crate::datatypes::jsonb::PgRawJsonb;
 use crate::datatypes::money::PgMoney;
-use crate::datatypes::numeric::{new_decimal_bytes_appender, new_decimal_int_appender};
+use crate::datatypes::numeric::{new_decimal_bytes_appender, new_decimal_int_appender, new_decimal_struct_appender};
+use crate::datatypes::snapshot::PgSnapshot;
+use crate::datatypes::cube::PgCube;
+use crate::datatypes::vector::{PgSparseVec, PgVector};
+use crate::datatypes::text::TextNullHandlingAppender;
 use crate::myfrom::{MyFrom, self};
-use crate::parquet_writer::{WriterStats, ParquetRowWriter, WriterSettings};
-use crate::pg_custom_types::{PgEnum, PgRawRange, PgAbstractRow, PgRawRecord, PgAny, PgAnyRef, UnclonableHack};
+use crate::parquet_writer::{GroupWriter, OnRowError, WriterEngine, WriterStats, ParquetRowWriter, RowAppender, WriterSettings};
+use crate::type_catalog::TypeCatalog;
+use crate::custom_type::{CustomTypeAppender, CustomTypeMapper, CustomValue};
+use crate::pg_custom_types::{strip_domains, PgEnum, PgRawRange, PgAbstractRow, PgRawRecord, PgAny, PgAnyRef, PgMapEntry, PgText, PgLossyText};
+use crate::postgresutils::{find_pgpass_password, format_pg_error, quote_identifier};
 
 type ResolvedColumn<TRow> = (DynColumnAppender<TRow>, ParquetType);
 
+#[derive(Clone, Debug)]
+pub struct ExportWarning {
+	pub column: Option<String>,
+	pub message: String,
+}
+
+static WARNINGS: Mutex<Vec<ExportWarning>> = Mutex::new(Vec::new());
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// With `--quiet`, the warnings are only collected for the export report
+pub fn set_quiet(quiet: bool) {
+	QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Prints the warning to stderr and remembers it for the export report
+pub(crate) fn warn(column: Option<String>, message: String) {
+	if !QUIET.load(Ordering::Relaxed) {
+		eprintln!("Warning: {}", message);
+	}
+	WARNINGS.lock().unwrap().push(ExportWarning { column, message });
+}
+
+/// Same as `warn`, unless the warning was already reported, for example by the export of another partition
+fn warn_once(column: Option<String>, message: String) {
+	let mut warnings = WARNINGS.lock().unwrap();
+	if warnings.iter().any(|w| w.column == column && w.message == message) {
+		return;
+	}
+	if !QUIET.load(Ordering::Relaxed) {
+		eprintln!("Warning: {}", message);
+	}
+	warnings.push(ExportWarning { column, message });
+}
+
+pub fn collected_warnings() -> Vec<ExportWarning> {
+	WARNINGS.lock().unwrap().clone()
+}
+
+/// Returns the collected warnings and forgets them, so that the next export in the same process reports only its own warnings
+pub fn take_warnings() -> Vec<ExportWarning> {
+	std::mem::take(&mut *WARNINGS.lock().unwrap())
+}
+
+/// Numbers of the values changed by an export, counted by the appenders of each column and reported in `WriterStats`.
+/// Each export has its own counters, so that the parallel exports of --per-partition-files do not mix their counts
+#[derive(Debug, Default)]
+pub struct ValueCounters {
+	/// Number of NaN and Infinity values found in each float or numeric column
+	special_float_values: Mutex<BTreeMap<String, usize>>,
+	/// Number of numeric values in each column which had more fractional digits than `--decimal-scale` and were rounded
+	rounded_decimal_values: Mutex<BTreeMap<String, usize>>,
+}
+
+impl ValueCounters {
+	pub(crate) fn count_special_float_value(&self, column: &str) {
+		*self.special_float_values.lock().unwrap().entry(column.to_string()).or_default() += 1;
+	}
+
+	pub(crate) fn count_rounded_decimal_value(&self, column: &str) {
+		*self.rounded_decimal_values.lock().unwrap().entry(column.to_string()).or_default() += 1;
+	}
+
+	fn add_to(&self, stats: &mut WriterStats) {
+		stats.special_float_values = self.special_float_values.lock().unwrap().clone();
+		stats.rounded_decimal_values = self.rounded_decimal_values.lock().unwrap().clone();
+	}
+}
+
+fn report_special_float_values(stats: &WriterStats, settings: &SchemaSettings) {
+	for (column, count) in &stats.special_float_values {
+		let action = match settings.float_special_values {
+			SchemaSettingsFloatSpecialValues::Null => ", they were replaced by NULL",
+			_ => "",
+		};
+		warn(Some(column.clone()), format!("Column {} contains {} NaN or Infinity values{}", column, count, action));
+	}
+}
+
+fn report_rounded_decimal_values(stats: &WriterStats, settings: &SchemaSettings) {
+	for (column, count) in &stats.rounded_decimal_values {
+		warn(Some(column.clone()), format!("Column {} contains {} values with more than {} fractional digits, they were rounded ({})", column, count, settings.decimal_scale, clap::ValueEnum::to_possible_value(&settings.decimal_rounding).unwrap().get_name()));
+	}
+}
+
 #[derive(Clone, Debug)]
 pub struct SchemaSettings {
 	pub macaddr_handling: SchemaSettingsMacaddrHandling,
@@ -44,7 +144,98 @@ pub struct SchemaSettings {
 	pub numeric_handling: SchemaSettingsNumericHandling,
 	pub decimal_scale: i32,
 	pub decimal_precision: u32,
+	pub decimal_rounding: SchemaSettingsDecimalRounding,
 	pub array_handling: SchemaSettingsArrayHandling,
+	pub array_element_nullability: SchemaSettingsArrayElementNullability,
+	pub list_encoding: SchemaSettingsListEncoding,
+	/// Columns of the `oid` type (and domains over it, such as `lo`) are dereferenced as large objects by `dereference_large_objects`
+	pub lo_handling: SchemaSettingsLoHandling,
+	/// Key and value array columns written as a single MAP column named after the value column, the query is aggregated by `aggregate_as_map`
+	pub as_map: Option<(String, String)>,
+	/// Explicit types of the query result columns, the query is rewritten by `ColumnDefinitions::apply`, see `--columns`
+	pub column_definitions: Option<ColumnDefinitions>,
+	/// Top-level columns whose values are replaced, see `--mask`
+	pub column_masks: Vec<(String, ColumnMask)>,
+	/// Top-level columns written under a different name, see `--rename-column`
+	pub column_renames: Vec<(String, String)>,
+	/// Case folding of the top-level column names which are not renamed explicitly
+	pub column_case: SchemaSettingsColumnCase,
+	/// Top-level composite columns are written as separate `column_field` columns instead of a Parquet group
+	pub flatten_composites: bool,
+	/// Order of the top-level Parquet columns, see `--column-order`
+	pub column_order: SchemaSettingsColumnOrder,
+	/// Source of the field_id of the top-level Parquet columns
+	pub field_ids: SchemaSettingsFieldIds,
+	/// Explicit field_id of the top-level Parquet columns (by the output column name), overrides `field_ids`
+	pub field_id_mapping: Vec<(String, i32)>,
+	/// Synthetic columns appended after the query columns, see `--add-meta-columns`
+	pub meta_columns: Vec<SchemaSettingsMetaColumn>,
+	/// Time of the export, written into the `_exported_at` column and the pg2parquet.exported_at metadata
+	pub exported_at: chrono::DateTime<chrono::Utc>,
+	/// Value of the `_source_table` column, None for query exports
+	pub source_table: Option<String>,
+	/// Identifier of the job or pipeline run which produced the file, written into the pg2parquet.job_id metadata
+	pub job_id: Option<String>,
+	pub bpchar_handling: SchemaSettingsBpcharHandling,
+	pub uuid_handling: SchemaSettingsUuidHandling,
+	pub inet_handling: SchemaSettingsInetHandling,
+	pub money_handling: SchemaSettingsMoneyHandling,
+	/// Number of fractional digits of money values, depends on the lc_monetary setting of the server
+	pub money_scale: i32,
+	pub reg_handling: SchemaSettingsRegHandling,
+	pub bit_handling: SchemaSettingsBitHandling,
+	pub float_special_values: SchemaSettingsFloatSpecialValues,
+	pub vector_element_type: SchemaSettingsVectorElementType,
+	/// Factor by which the vector elements are multiplied when they are converted to i8
+	pub vector_i8_scale: f32,
+	/// Vector columns with a fixed dimension are declared as FixedSizeList in the embedded Arrow schema
+	pub vector_fixed_size_list: bool,
+	pub sparsevec_handling: SchemaSettingsSparsevecHandling,
+	/// Columns of the `seg` type, which has no binary representation, are converted in the query by `convert_column_types`
+	pub cube_handling: SchemaSettingsCubeHandling,
+	/// Names of the types which are converted to text in the query by `convert_column_types`, in addition to `DEFAULT_TEXT_TYPES`
+	pub text_types: Vec<String>,
+	/// Columns of the `isn` types (ean13, isbn, issn, ...), which have no binary representation, are converted in the query by `convert_column_types`
+	pub isn_handling: SchemaSettingsIsnHandling,
+	/// NULL values of text columns are written as this string, see `--null-string`
+	pub null_string: Option<String>,
+	/// Empty strings in text columns are written as NULL (or `null_string`)
+	pub empty_string_as_null: bool,
+	/// Handling of text values which are not valid UTF-8, which databases with the SQL_ASCII encoding can return
+	pub invalid_utf8: SchemaSettingsInvalidUtf8,
+	/// Names of the objects referenced by reg* values (by the type OID and object OID), loaded from the system catalogs before the export when `reg_handling` is Text
+	pub reg_names: HashMap<u32, Arc<HashMap<u32, String>>>,
+	/// Mappers of the types not supported by pg2parquet, provided by the library user
+	pub custom_type_mappers: Vec<Arc<dyn CustomTypeMapper>>,
+	/// Information about the extension and user-defined types in the query result, loaded from the catalog before the export
+	pub type_catalog: Arc<TypeCatalog>,
+	/// Counters of the export, replaced by new ones when the export starts
+	pub value_counters: Arc<ValueCounters>,
+}
+
+impl SchemaSettings {
+	/// Name of the Parquet column created from the PostgreSQL column `name`
+	pub fn output_column_name(&self, name: &str) -> String {
+		if let Some((_, new_name)) = self.column_renames.iter().find(|(old_name, _)| old_name == name) {
+			return new_name.clone();
+		}
+		self.fold_case(name)
+	}
+
+	fn fold_case(&self, name: &str) -> String {
+		match self.column_case {
+			SchemaSettingsColumnCase::Preserve => name.to_string(),
+			SchemaSettingsColumnCase::Lower => name.to_lowercase(),
+			SchemaSettingsColumnCase::Snake => snake_case(name),
+		}
+	}
+}
+
+/// Same as the defaults of the command line options
+impl Default for SchemaSettings {
+	fn default() -> Self {
+		default_settings()
+	}
 }
 
 #[derive(clap::ValueEnum, Clone, Copy, Debug)]
@@ -57,6 +248,108 @@ pub enum SchemaSettingsMacaddrHandling {
 	Int64
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsUuidHandling {
+	/// UUID is stored as fixed byte array of length 16, with the UUID logical type
+	Binary,
+	/// UUID is converted to the canonical hyphenated string
+	Text,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsInetHandling {
+	/// Address is converted to a string, without the network prefix length
+	Text,
+	/// Address and prefix length are stored in a fixed byte array of length 17: the 16-byte IPv6 address (IPv4 is mapped to ::ffff:a.b.c.d), followed by the prefix length. Values are ordered by the address
+	Binary,
+	/// Address is stored as struct of { family: int (4 or 6), prefix: int, addr: bytes (4 or 16) }
+	Struct,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsMoneyHandling {
+	/// Money is stored as Decimal(18, money-scale)
+	Decimal,
+	/// Money is stored as Int64 amount of the smallest currency unit (i.e. cents)
+	Int64,
+	/// Money is converted to a decimal string without the currency symbol, i.e. `1234.56`
+	String,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsFloatSpecialValues {
+	/// NaN and Infinity values are written as they are (numeric NaN becomes NULL when it is stored as decimal)
+	Keep,
+	/// NaN and Infinity values are replaced by NULL
+	Null,
+	/// The export fails when a NaN or Infinity value is found
+	Error,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsVectorElementType {
+	/// 32-bit floats (FLOAT), halfvec values are converted without loss
+	F32,
+	/// 16-bit floats, stored as FIXED_LEN_BYTE_ARRAY(2) with the Float16 logical type
+	F16,
+	/// bfloat16, stored as little-endian FIXED_LEN_BYTE_ARRAY(2) without a logical type (Parquet has no bfloat16 type)
+	Bf16,
+	/// 8-bit integers, the values are multiplied by --vector-i8-scale, rounded and clamped to -128..127
+	I8,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsSparsevecHandling {
+	/// MAP<int32, float> of the zero-based indices and values of the non-zero elements
+	Map,
+	/// struct { indices: List[int32], values: List[float], dim: int32 } of the non-zero elements, the indices are zero-based
+	Arrays,
+	/// The vector is converted to a dense vector, stored the same way as `vector` columns (including --vector-element-type)
+	Dense,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsCubeHandling {
+	/// `cube` is written as struct { lower: List[float64], upper: List[float64] } of the opposite corners, `seg` as List[float64] of its lower and upper bound
+	Bounds,
+	/// The value is converted to text, in the same format as PostgreSQL prints it
+	Text,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsIsnHandling {
+	/// The number is converted to text in its canonical hyphenated form, such as `0-316-14841-5` for an ISBN
+	Text,
+	/// The number is converted to EAN13 and its 13 digits are written as int64, such as 9780316148412 for the same ISBN
+	Int64,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsInvalidUtf8 {
+	/// The export fails on the first text value which is not valid UTF-8
+	Error,
+	/// Invalid UTF-8 sequences are replaced by the U+FFFD replacement character
+	Replace,
+	/// Text, json and enum columns are written as BYTE_ARRAY without the String logical type, the bytes are copied unchanged
+	Bytes,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsBitHandling {
+	/// Bit string is converted to text of `0` and `1`
+	Text,
+	/// bit(n) columns are stored as FIXED_LEN_BYTE_ARRAY of ceil(n/8) bytes, first bit is the most significant bit of the first byte. Lengths are saved in the pg2parquet.bit_lengths file metadata. varbit and bit fields of composite types are still exported as text
+	Binary,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsRegHandling {
+	/// Stored as the UInt32 OID of the referenced object
+	Oid,
+	/// Stored as the name of the referenced object (e.g. `public.my_table`), resolved using the system catalogs when the export starts
+	Text,
+}
+
 #[derive(clap::ValueEnum, Clone, Copy, Debug)]
 pub enum SchemaSettingsJsonHandling {
 	/// JSON is stored as a Parquet JSON type. This is essentially the same as text, but with a different ConvertedType, so it may not be supported in all tools.
@@ -71,7 +364,7 @@ pub enum SchemaSettingsEnumHandling {
 	Text,
 	/// Enum is stored as the postgres enum name, Parquet LogicalType is set to String
 	PlainText,
-	/// Enum is stored as an 32-bit integer (one-based index of the value in the enum definition)
+	/// Enum is stored as an 32-bit integer (one-based index of the value in the enum definition). The labels are written into the `output.parquet.enums.json` sidecar file
 	Int
 }
 
@@ -93,7 +386,21 @@ pub enum SchemaSettingsNumericHandling {
 	/// Numeric is converted to float32 (FLOAT).
 	Float32,
 	/// Convert the numeric to a string and store it as UTF8 text. This option never looses precision. Note that text "NaN" may be present if NaN is present in the database.
-	String
+	String,
+	/// Numeric is stored as struct { unscaled: BYTE_ARRAY, scale: i32 }, the value is unscaled * 10^-scale and the unscaled integer is encoded as in the DECIMAL type. This option never looses precision, NaN is stored as NULL.
+	Struct,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsDecimalRounding {
+	/// Round to the nearest value, ties are rounded to the even digit (banker's rounding)
+	HalfEven,
+	/// Round to the nearest value, ties are rounded away from zero, like the PostgreSQL round() function
+	HalfUp,
+	/// The extra digits are cut off (rounded towards zero)
+	Truncate,
+	/// The export fails when a value has more fractional digits than --decimal-scale
+	Error,
 }
 
 #[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
@@ -108,6 +415,163 @@ pub enum SchemaSettingsArrayHandling {
 	DimensionsAndLowerBound,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsArrayElementNullability {
+	/// Elements are REQUIRED if the element type is a domain with a NOT NULL constraint, which PostgreSQL enforces for array elements
+	Auto,
+	/// Elements are always OPTIONAL
+	Optional,
+	/// Elements are always REQUIRED, the export fails when an array contains a NULL element
+	Required,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsListEncoding {
+	/// Standard LIST encoding: `optional group a (LIST) { repeated group list { optional T element } }`
+	ThreeLevel,
+	/// Legacy LIST encoding without the middle group: `optional group a (LIST) { repeated T array }`. Elements cannot be NULL, the export fails on a NULL element
+	TwoLevel,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsLoHandling {
+	/// The OID of the large object is exported as a number
+	Oid,
+	/// Content of the large object is read using `lo_get()` and exported as a BYTE_ARRAY column
+	InlineBytes,
+	/// The column is left out of the export
+	Skip,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsBpcharHandling {
+	/// char(n) values are exported as returned by PostgreSQL, padded with spaces to the declared length
+	Preserve,
+	/// Trailing spaces of char(n) values are removed, as PostgreSQL ignores them when comparing the values
+	Trim,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsColumnCase {
+	/// Column names are kept as returned by PostgreSQL
+	Preserve,
+	/// Column names are converted to lowercase
+	Lower,
+	/// Column names are converted to snake_case (`UserID` -> `user_id`, `HTTPServer` -> `http_server`)
+	Snake,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsFieldIds {
+	/// The Parquet fields have no field_id, unless assigned by --field-id
+	None,
+	/// Top-level columns read directly from a table get the attribute number of the table column (pg_attribute.attnum) as field_id, which does not change when the column is renamed
+	Attnum,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaSettingsColumnOrder {
+	/// Columns are written in the order of the query result
+	Schema,
+	/// Columns are sorted by their output name
+	NameAsc,
+	/// The listed output columns are written first in the specified order, followed by the remaining columns in the order of the query result
+	Custom(Vec<String>),
+}
+
+impl std::str::FromStr for SchemaSettingsColumnOrder {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"schema" => Ok(SchemaSettingsColumnOrder::Schema),
+			"name-asc" => Ok(SchemaSettingsColumnOrder::NameAsc),
+			_ => match s.strip_prefix("custom:") {
+				Some(columns) => Ok(SchemaSettingsColumnOrder::Custom(columns.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect())),
+				None => Err(format!("Unknown column order '{}', expected schema, name-asc or custom:<column>,<column>,...", s)),
+			},
+		}
+	}
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SchemaSettingsMetaColumn {
+	/// `_row_number`: number of the row in the output file, starting from 1
+	#[clap(name = "row_number", alias = "row-number")]
+	RowNumber,
+	/// `_exported_at`: timestamp of the export, the same for all rows
+	#[clap(name = "exported_at", alias = "exported-at")]
+	ExportedAt,
+	/// `_source_table`: name of the exported table, NULL for --query exports
+	#[clap(name = "source_table", alias = "source-table")]
+	SourceTable,
+}
+
+impl SchemaSettingsMetaColumn {
+	pub fn column_name(&self) -> &'static str {
+		match self {
+			SchemaSettingsMetaColumn::RowNumber => "_row_number",
+			SchemaSettingsMetaColumn::ExportedAt => "_exported_at",
+			SchemaSettingsMetaColumn::SourceTable => "_source_table",
+		}
+	}
+}
+
+/// Converts CamelCase names to snake_case, spaces and dashes are replaced by underscores
+fn snake_case(name: &str) -> String {
+	let chars: Vec<char> = name.chars().collect();
+	let mut result = String::with_capacity(name.len() + 4);
+	for (i, &c) in chars.iter().enumerate() {
+		if c == ' ' || c == '-' {
+			result.push('_');
+		} else if c.is_uppercase() {
+			let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+			let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+			// a new word starts after a lowercase letter, or at the last capital letter of an acronym
+			let word_start = prev.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit() || (p.is_uppercase() && next_lower));
+			if word_start {
+				result.push('_');
+			}
+			result.extend(c.to_lowercase());
+		} else {
+			result.push(c);
+		}
+	}
+	result
+}
+
+/// Column by which the exported rows are sorted, specified by the --sort-by option
+#[derive(Clone, Debug)]
+pub struct SortColumn {
+	pub name: String,
+	pub descending: bool,
+	pub nulls_first: bool,
+}
+
+pub fn order_by_clause(sort_by: &[SortColumn]) -> String {
+	if sort_by.is_empty() {
+		return String::new();
+	}
+	let columns = sort_by.iter().map(|c| format!("{} {} NULLS {}",
+		quote_identifier(&c.name),
+		if c.descending { "DESC" } else { "ASC" },
+		if c.nulls_first { "FIRST" } else { "LAST" }
+	)).collect::<Vec<_>>();
+	format!(" ORDER BY {}", columns.join(", "))
+}
+
+/// Columns selected from a table: all table columns followed by the computed `(name, expression)` columns.
+/// The table columns are listed explicitly (see `table_columns`), `*` is used when the list is empty
+pub fn select_list(table_columns: &[String], computed_columns: &[(String, String)]) -> String {
+	let mut columns = if table_columns.is_empty() {
+		vec!["*".to_string()]
+	} else {
+		table_columns.iter().map(|name| quote_identifier(name)).collect()
+	};
+	columns.extend(computed_columns.iter().map(|(name, expr)| format!("({}) AS {}", expr, quote_identifier(name))));
+	columns.join(", ")
+}
+
 pub fn default_settings() -> SchemaSettings {
 	SchemaSettings {
 		macaddr_handling: SchemaSettingsMacaddrHandling::Text,
@@ -117,17 +581,67 @@ pub fn default_settings() -> SchemaSettings {
 		numeric_handling: SchemaSettingsNumericHandling::Double,
 		decimal_scale: 18,
 		decimal_precision: 38,
+		decimal_rounding: SchemaSettingsDecimalRounding::HalfEven,
 		array_handling: SchemaSettingsArrayHandling::Plain,
+		array_element_nullability: SchemaSettingsArrayElementNullability::Auto,
+		list_encoding: SchemaSettingsListEncoding::ThreeLevel,
+		lo_handling: SchemaSettingsLoHandling::Oid,
+		as_map: None,
+		column_definitions: None,
+		column_masks: Vec::new(),
+		column_renames: Vec::new(),
+		column_case: SchemaSettingsColumnCase::Preserve,
+		flatten_composites: false,
+		column_order: SchemaSettingsColumnOrder::Schema,
+		meta_columns: Vec::new(),
+		field_ids: SchemaSettingsFieldIds::None,
+		field_id_mapping: Vec::new(),
+		exported_at: chrono::Utc::now(),
+		source_table: None,
+		job_id: None,
+		bpchar_handling: SchemaSettingsBpcharHandling::Preserve,
+		uuid_handling: SchemaSettingsUuidHandling::Binary,
+		inet_handling: SchemaSettingsInetHandling::Text,
+		money_handling: SchemaSettingsMoneyHandling::Decimal,
+		money_scale: 2,
+		reg_handling: SchemaSettingsRegHandling::Oid,
+		bit_handling: SchemaSettingsBitHandling::Text,
+		float_special_values: SchemaSettingsFloatSpecialValues::Keep,
+		vector_element_type: SchemaSettingsVectorElementType::F32,
+		vector_i8_scale: 127.0,
+		vector_fixed_size_list: false,
+		sparsevec_handling: SchemaSettingsSparsevecHandling::Map,
+		cube_handling: SchemaSettingsCubeHandling::Bounds,
+		text_types: Vec::new(),
+		isn_handling: SchemaSettingsIsnHandling::Text,
+		null_string: None,
+		empty_string_as_null: false,
+		invalid_utf8: SchemaSettingsInvalidUtf8::Error,
+		reg_names: HashMap::new(),
+		custom_type_mappers: Vec::new(),
+		type_catalog: Arc::new(TypeCatalog::default()),
+		value_counters: Arc::new(ValueCounters::default()),
 	}
 }
 
 fn read_password(user: &str) -> Result<String, String> {
-	let password = rpassword::prompt_password(&format!("Password for user {}: ", user));
+	let password = rpassword::prompt_password(format!("Password for user {}: ", user));
 	password.map_err(|e| format!("Failed to read password from TTY: {}", e))
 }
 
-#[cfg(any(target_os = "macos", target_os="windows", all(target_os="linux", not(target_env="musl"), any(target_arch="x86_64", target_arch="aarch64"))))]
-fn build_tls_connector(certificates: &Option<Vec<PathBuf>>) -> Result<postgres_native_tls::MakeTlsConnector, String> {
+/// Which parts of the server certificate are checked, decided by the --sslmode
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CertVerification {
+	/// `prefer` and `require` only encrypt the connection, any certificate is accepted
+	None,
+	/// `verify-ca` checks that the certificate is signed by a trusted CA
+	Ca,
+	/// `verify-full` also checks that the host name matches the certificate
+	Full,
+}
+
+#[cfg(tls_backend = "native")]
+fn build_tls_connector(certificates: &Option<Vec<PathBuf>>, verification: CertVerification) -> Result<postgres_native_tls::MakeTlsConnector, String> {
 	fn load_cert(f: &PathBuf) -> Result<native_tls::Certificate, String> {
 		let bytes = std::fs::read(f).map_err(|e| format!("Failed to read certificate file {:?}: {}", f, e))?;
 		if let Ok(pem) = native_tls::Certificate::from_pem(&bytes) {
@@ -140,6 +654,8 @@ fn build_tls_connector(certificates: &Option<Vec<PathBuf>>) -> Result<postgres_n
 		Err(format!("Failed to load certificate from file {:?}", f))
 	}
 	let mut builder = native_tls::TlsConnector::builder();
+	builder.danger_accept_invalid_certs(verification == CertVerification::None);
+	builder.danger_accept_invalid_hostnames(verification != CertVerification::Full);
 	match certificates {
 		None => {},
 		Some(certificates) => {
@@ -149,99 +665,1345 @@ fn build_tls_connector(certificates: &Option<Vec<PathBuf>>) -> Result<postgres_n
 			}
 		}
 	}
-	let connector = builder.build().map_err(|e| format!("Creating TLS connector failed: {}", e.to_string()))?;
-	let pg_connector = postgres_native_tls::MakeTlsConnector::new(connector);
-	Ok(pg_connector)
+	let connector = builder.build().map_err(|e| format!("Creating TLS connector failed: {}", e))?;
+	let pg_connector = postgres_native_tls::MakeTlsConnector::new(connector);
+	Ok(pg_connector)
+}
+
+#[cfg(tls_backend = "rustls")]
+fn build_tls_connector(certificates: &Option<Vec<PathBuf>>, verification: CertVerification) -> Result<tokio_postgres_rustls::MakeRustlsConnect, String> {
+	use rustls::pki_types::{CertificateDer, pem::PemObject};
+
+	fn load_certs(f: &PathBuf) -> Result<Vec<CertificateDer<'static>>, String> {
+		let bytes = std::fs::read(f).map_err(|e| format!("Failed to read certificate file {:?}: {}", f, e))?;
+		let pem: Vec<_> = CertificateDer::pem_slice_iter(&bytes).collect::<Result<_, _>>().unwrap_or_default();
+		if !pem.is_empty() {
+			return Ok(pem);
+		}
+		Ok(vec![CertificateDer::from(bytes)])
+	}
+	let mut roots = rustls::RootCertStore::empty();
+	match certificates {
+		None => {
+			roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+		},
+		Some(certificates) => {
+			for cert_file in certificates {
+				for cert in load_certs(cert_file)? {
+					roots.add(cert).map_err(|e| format!("Failed to load certificate from file {:?}: {}", cert_file, e))?;
+				}
+			}
+		}
+	}
+	let provider = Arc::new(rustls::crypto::ring::default_provider());
+	let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+		.with_safe_default_protocol_versions()
+		.map_err(|e| format!("Creating TLS connector failed: {}", e))?;
+	let config = match verification {
+		CertVerification::Full => builder.with_root_certificates(roots).with_no_client_auth(),
+		CertVerification::Ca => {
+			let verifier = rustls::client::WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+				.build()
+				.map_err(|e| format!("Creating TLS connector failed: {}", e))?;
+			builder.dangerous()
+				.with_custom_certificate_verifier(Arc::new(rustls_verifier::IgnoreHostname(verifier)))
+				.with_no_client_auth()
+		},
+		CertVerification::None => builder.dangerous()
+			.with_custom_certificate_verifier(Arc::new(rustls_verifier::AcceptAny(provider)))
+			.with_no_client_auth(),
+	};
+	Ok(tokio_postgres_rustls::MakeRustlsConnect::new(config))
+}
+
+/// Server certificate verifiers for the modes which do not check everything
+#[cfg(tls_backend = "rustls")]
+mod rustls_verifier {
+	use std::sync::Arc;
+	use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+	use rustls::crypto::CryptoProvider;
+	use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+	use rustls::{CertificateError, DigitallySignedStruct, Error, SignatureScheme};
+
+	/// `--sslmode=verify-ca`, checks the certificate chain but accepts any host name
+	#[derive(Debug)]
+	pub struct IgnoreHostname(pub Arc<rustls::client::WebPkiServerVerifier>);
+
+	impl ServerCertVerifier for IgnoreHostname {
+		fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>], server_name: &ServerName<'_>, ocsp_response: &[u8], now: UnixTime) -> Result<ServerCertVerified, Error> {
+			match self.0.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now) {
+				Err(Error::InvalidCertificate(CertificateError::NotValidForName)) |
+				Err(Error::InvalidCertificate(CertificateError::NotValidForNameContext { .. })) => Ok(ServerCertVerified::assertion()),
+				x => x,
+			}
+		}
+		fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, Error> {
+			self.0.verify_tls12_signature(message, cert, dss)
+		}
+		fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, Error> {
+			self.0.verify_tls13_signature(message, cert, dss)
+		}
+		fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+			self.0.supported_verify_schemes()
+		}
+	}
+
+	/// `--sslmode=prefer` and `require`, accepts any certificate. The handshake signatures are still checked, so the server has the key of the certificate it sent
+	#[derive(Debug)]
+	pub struct AcceptAny(pub Arc<CryptoProvider>);
+
+	impl ServerCertVerifier for AcceptAny {
+		fn verify_server_cert(&self, _end_entity: &CertificateDer<'_>, _intermediates: &[CertificateDer<'_>], _server_name: &ServerName<'_>, _ocsp_response: &[u8], _now: UnixTime) -> Result<ServerCertVerified, Error> {
+			Ok(ServerCertVerified::assertion())
+		}
+		fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, Error> {
+			rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+		}
+		fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, Error> {
+			rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+		}
+		fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+			self.0.signature_verification_algorithms.supported_schemes()
+		}
+	}
+}
+
+#[cfg(tls_backend = "none")]
+fn build_tls_connector(certificates: &Option<Vec<PathBuf>>, _verification: CertVerification) -> Result<NoTls, String> {
+	if certificates.is_some() {
+		return Err("SSL/TLS is not supported in this build of pg2parquet".to_string());
+	}
+	Ok(NoTls)
+}
+
+/// Hosts, database name, port and user name of the connection, from the arguments or the environment variables
+fn connection_target(args: &PostgresConnArgs) -> Result<(Vec<String>, String, u16, String), String> {
+	let hosts = args.hosts();
+	if hosts.is_empty() {
+		return Err("Database host is not specified, use the --host option or PGHOST environment variable".to_string());
+	}
+	let dbname = args.dbname.clone().or_else(|| std::env::var("PGDATABASE").ok())
+		.ok_or_else(|| "Database name is not specified, use the --dbname option or PGDATABASE environment variable".to_string())?;
+	let port = match args.port {
+		Some(port) => port,
+		None => match std::env::var("PGPORT") {
+			Ok(port) => port.parse().map_err(|_| format!("Invalid PGPORT environment variable: {}", port))?,
+			Err(_) => 5432,
+		}
+	};
+	let user = args.user.clone().or_else(|| std::env::var("PGUSER").ok()).unwrap_or_else(|| dbname.clone());
+	Ok((hosts, dbname, port, user))
+}
+
+fn find_password(args: &PostgresConnArgs, hosts: &[String], port: u16, dbname: &str, user: &str) -> Result<String, String> {
+	if let Some(password) = args.password.as_ref() {
+		Ok(password.clone())
+	} else if let Ok(password) = std::env::var("PGPASSWORD") {
+		Ok(password)
+	} else if let Some(password) = hosts.iter().find_map(|host| find_pgpass_password(host, port, dbname, user)) {
+		Ok(password)
+	} else if args.no_password {
+		Err("Password is not specified, use the --password option, PGPASSWORD environment variable or the ~/.pgpass file".to_string())
+	} else {
+		Ok(read_password(user)?.trim().to_string())
+	}
+}
+
+/// Fills in the password from PGPASSWORD, ~/.pgpass or the terminal, so that the following connections (for example the parallel readers) do not ask for it again
+pub fn resolve_password(args: &PostgresConnArgs) -> Result<PostgresConnArgs, String> {
+	let (hosts, dbname, port, user) = connection_target(args)?;
+	let password = find_password(args, &hosts, port, &dbname, &user)?;
+	Ok(PostgresConnArgs { password: Some(password), ..args.clone() })
+}
+
+/// Resolves the connection parameters (possibly asking for password) and returns a function which opens a new connection
+// without TLS support, the connector is the NoTls unit struct
+#[cfg_attr(tls_backend = "none", allow(clippy::clone_on_copy))]
+fn pg_connector(args: &PostgresConnArgs) -> Result<impl Fn() -> Result<Client, ExportError>, String> {
+	let (hosts, dbname, port, user) = connection_target(args)?;
+	let sslmode = match &args.sslmode {
+		Some(mode) => Some(mode.clone()),
+		None => match std::env::var("PGSSLMODE") {
+			Ok(mode) => Some(<crate::SslMode as clap::ValueEnum>::from_str(&mode, true)
+				.map_err(|_| format!("Invalid or unsupported PGSSLMODE environment variable: {}", mode))?),
+			Err(_) => None,
+		}
+	};
+
+	let mut pg_config = postgres::Config::new();
+	pg_config.dbname(&dbname)
+		.application_name("pg2parquet")
+		.port(port)
+		.user(&user);
+	for host in &hosts {
+		pg_config.host(host);
+	}
+	// the connection is idle while the writer compresses a row group, which may take minutes
+	let keepalive_interval = args.keepalive_interval.unwrap_or(std::time::Duration::from_secs(60));
+	if keepalive_interval.is_zero() {
+		pg_config.keepalives(false);
+	} else if keepalive_interval < std::time::Duration::from_secs(1) {
+		return Err(format!("Keepalive interval {:?} is too short, TCP keepalives can be sent at most once per second", keepalive_interval));
+	} else {
+		pg_config.keepalives(true)
+			.keepalives_idle(keepalive_interval)
+			.keepalives_interval(keepalive_interval);
+	}
+
+	pg_config.password(&find_password(args, &hosts, port, &dbname, &user)?);
+
+	#[cfg(tls_backend = "none")]
+	match &sslmode {
+		None | Some(crate::SslMode::Disable) => {},
+		Some(x) => return Err(format!("SSL/TLS is disabled in this build of pg2parquet, so ssl mode {:?} cannot be used. Only 'disable' option is allowed.", x)),
+	}
+	match &sslmode {
+		None => {
+			if args.ssl_root_cert.is_some() {
+				pg_config.ssl_mode(postgres::config::SslMode::Require);
+			} else {
+				pg_config.ssl_mode(postgres::config::SslMode::Prefer);
+			}
+		},
+		Some(crate::SslMode::Disable) => {
+			pg_config.ssl_mode(postgres::config::SslMode::Disable);
+		},
+		Some(crate::SslMode::Prefer) => {
+			pg_config.ssl_mode(postgres::config::SslMode::Prefer);
+		},
+		Some(crate::SslMode::Require | crate::SslMode::VerifyCa | crate::SslMode::VerifyFull) => {
+			pg_config.ssl_mode(postgres::config::SslMode::Require);
+		},
+	}
+
+	let verification = match &sslmode {
+		Some(crate::SslMode::VerifyFull) => CertVerification::Full,
+		Some(crate::SslMode::VerifyCa) => CertVerification::Ca,
+		// same as libpq, require with a root certificate checks the certificate chain
+		None | Some(crate::SslMode::Require) if args.ssl_root_cert.is_some() => CertVerification::Ca,
+		_ => CertVerification::None,
+	};
+	let connector = build_tls_connector(&args.ssl_root_cert, verification)?;
+
+	if let Some(snapshot) = &args.snapshot {
+		if snapshot.is_empty() || !snapshot.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+			return Err(format!("Invalid snapshot identifier '{}', expected a value returned by pg_export_snapshot()", snapshot));
+		}
+	}
+
+	let mut pg_settings = args.pg_settings.clone();
+	if args.read_only {
+		pg_settings.insert(0, ("default_transaction_read_only".to_string(), "on".to_string()));
+	}
+	// prefer a hot standby, the primary is used when none of the hosts is in recovery
+	let mut standby_config = None;
+	if args.read_only && hosts.len() > 1 {
+		let mut config = pg_config.clone();
+		config.target_session_attrs(postgres::config::TargetSessionAttrs::ReadOnly);
+		standby_config = Some(config);
+	}
+	let snapshot = args.snapshot.clone();
+	Ok(move || {
+		let standby = standby_config.as_ref().and_then(|config| config.connect(connector.clone()).ok());
+		let mut client = match standby {
+			Some(client) => client,
+			None => pg_config.connect(connector.clone()).map_err(|e| pg_error(e, "DB connection failed"))?,
+		};
+		let (cancel_token, cancel_connector) = (client.cancel_token(), connector.clone());
+		cancellation::set_query_canceller(move || _ = cancel_token.cancel_query(cancel_connector));
+		for (name, value) in &pg_settings {
+			client.execute("SELECT set_config($1, $2, false)", &[name, value])
+				.map_err(|e| pg_error(e, &format!("Failed to set {} = {}", name, value)))?;
+		}
+		if let Some(snapshot) = &snapshot {
+			// the transaction is never committed, it is only read from and ends with the connection
+			client.batch_execute(&format!("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY; SET TRANSACTION SNAPSHOT '{}'", snapshot))
+				.map_err(|e| pg_error(e, &format!("Failed to import snapshot {}", snapshot)))?;
+		}
+		Ok(client)
+	})
+}
+
+pub fn pg_connect(args: &PostgresConnArgs) -> Result<Client, String> {
+	let connect = pg_connector(args)?;
+	Ok(connect()?)
+}
+
+/// Leaf partition of a partitioned table
+#[derive(Debug, Clone)]
+pub struct TablePartition {
+	/// Qualified name of the partition, usable in a query
+	pub name: String,
+	/// Names of the partitions from the partitioned table down to this one, more than one for sub-partitioned tables
+	pub path: Vec<String>,
+	/// Partition bound, for example `FOR VALUES FROM ('2024-01-01') TO ('2024-02-01')`
+	pub bound: String,
+}
+
+/// Lists the leaf partitions of a partitioned table, ordered by their path
+pub fn leaf_partitions(args: &PostgresConnArgs, table: &str) -> Result<Vec<TablePartition>, String> {
+	let mut client = pg_connect(args)?;
+	let kind: String = client.query_one("SELECT relkind::text FROM pg_class WHERE oid = $1::text::regclass", &[&table])
+		.map_err(|e| format!("Failed to query table {}: {}", table, format_pg_error(&e)))?
+		.get(0);
+	if kind != "p" {
+		return Err(format!("Table {} is not partitioned", table));
+	}
+	let rows = client.query("
+		WITH RECURSIVE tree AS (
+			SELECT $1::text::regclass::oid AS oid, ARRAY[]::name[] AS path
+			UNION ALL
+			SELECT i.inhrelid, t.path || c.relname
+			FROM tree t
+			JOIN pg_inherits i ON i.inhparent = t.oid
+			JOIN pg_class c ON c.oid = i.inhrelid
+		)
+		SELECT t.oid::regclass::text, t.path::text[], coalesce(pg_get_expr(c.relpartbound, c.oid), '')
+		FROM tree t
+		JOIN pg_class c ON c.oid = t.oid
+		WHERE cardinality(t.path) > 0 AND NOT EXISTS (SELECT FROM pg_inherits i WHERE i.inhparent = t.oid)
+		ORDER BY t.path", &[&table])
+		.map_err(|e| format!("Failed to query partitions of {}: {}", table, format_pg_error(&e)))?;
+	Ok(rows.iter().map(|r| TablePartition { name: r.get(0), path: r.get(1), bound: r.get(2) }).collect())
+}
+
+/// Rewrites the export query according to `SchemaSettings::column_definitions`, `lo_handling`, the types converted by `convert_column_types`
+/// and `as_map`. It runs on the connection which executes the query, as the result columns may depend on the session. Returns None if the query does not need to be changed
+fn rewrite_query(client: &mut Client, query: &str, settings: &SchemaSettings) -> Result<Option<String>, ExportError> {
+	let mut rewritten = match &settings.column_definitions {
+		Some(columns) => Some(columns.apply(client, query)?),
+		None => None,
+	};
+	let prepare = |client: &mut Client, query: &str| client.prepare(query).map_err(|e| pg_error(e, "Failed to prepare the query"));
+	let mut statement = prepare(client, rewritten.as_deref().unwrap_or(query))?;
+	if let Some(dereferenced) = dereference_large_objects(statement.columns(), rewritten.as_deref().unwrap_or(query), settings.lo_handling) {
+		statement = prepare(client, &dereferenced)?;
+		rewritten = Some(dereferenced);
+	}
+	if let Some(converted) = convert_column_types(client, statement.columns(), rewritten.as_deref().unwrap_or(query), settings)? {
+		statement = prepare(client, &converted)?;
+		rewritten = Some(converted);
+	}
+	if let Some((key, value)) = &settings.as_map {
+		rewritten = Some(aggregate_as_map(statement.columns(), rewritten.as_deref().unwrap_or(query), key, value)?);
+	}
+	Ok(rewritten)
+}
+
+/// Wraps the query so that the `key` and `value` columns are aggregated into arrays, grouped by the remaining columns.
+/// The arrays are written as a MAP column when `SchemaSettings::as_map` is set, see `--as-map`
+fn aggregate_as_map(columns: &[Column], query: &str, key: &str, value: &str) -> Result<String, String> {
+	for name in [key, value] {
+		match columns.iter().find(|c| c.name() == name) {
+			None => return Err(format!("Column {} specified in --as-map is not in the result of the query", name)),
+			Some(c) if matches!(c.type_().kind(), Kind::Array(_)) => return Err(format!("Column {} specified in --as-map is an array, which cannot be aggregated into the map", name)),
+			Some(_) => {},
+		}
+	}
+	let group_by = columns.iter()
+		.filter(|c| c.name() != key && c.name() != value)
+		.map(|c| format!("q.{}", quote_identifier(c.name())))
+		.collect::<Vec<_>>();
+	let (key, value) = (quote_identifier(key), quote_identifier(value));
+	// Parquet map keys cannot be NULL, a group without any entries gets a NULL map
+	let aggregates = format!("array_agg(q.{key} ORDER BY q.{key}) FILTER (WHERE q.{key} IS NOT NULL) AS {key}, array_agg(q.{value} ORDER BY q.{key}) FILTER (WHERE q.{key} IS NOT NULL) AS {value}", key = key, value = value);
+	Ok(if group_by.is_empty() {
+		format!("SELECT {} FROM ({}) AS q", aggregates, query)
+	} else {
+		format!("SELECT {}, {} FROM ({}) AS q GROUP BY {}", group_by.join(", "), aggregates, query, group_by.join(", "))
+	})
+}
+
+/// Wraps the query so that the `oid` columns (including the `lo` extension type) are replaced by the content of the referenced large objects,
+/// or left out, according to `SchemaSettings::lo_handling`. Returns None if the query does not need to be changed
+fn dereference_large_objects(columns: &[Column], query: &str, handling: SchemaSettingsLoHandling) -> Option<String> {
+	if handling == SchemaSettingsLoHandling::Oid || !columns.iter().any(|c| *strip_domains(c.type_()) == PgType::OID) {
+		return None;
+	}
+	let columns = columns.iter()
+		.filter_map(|c| {
+			let name = quote_identifier(c.name());
+			match handling {
+				_ if *strip_domains(c.type_()) != PgType::OID => Some(format!("q.{}", name)),
+				SchemaSettingsLoHandling::InlineBytes => Some(format!("lo_get(q.{}) AS {}", name, name)),
+				SchemaSettingsLoHandling::Skip => None,
+				SchemaSettingsLoHandling::Oid => unreachable!(),
+			}
+		})
+		.collect::<Vec<_>>();
+	Some(format!("SELECT {} FROM ({}) AS q", columns.join(", "), query))
+}
+
+/// Extension types without a binary representation, which are always converted to text by the export query, in addition to `SchemaSettings::text_types`
+pub const DEFAULT_TEXT_TYPES: &[&str] = &["uri", "semver", "prefix_range"];
+
+/// Types of the `isn` extension, which has no binary representation
+const ISN_TYPES: &[&str] = &["ean13", "isbn13", "ismn13", "issn13", "isbn", "ismn", "issn", "upc"];
+
+/// Conversion of a column by `convert_column_types`, the extension types store the quoted schema of the type
+enum TypeConversion {
+	Text,
+	TextArray,
+	/// Array of a type which pg2parquet cannot map (by the element type name), its elements are converted to text
+	UnsupportedArray(String),
+	Seg(String),
+	Isn(String),
+	IsnArray(String),
+}
+
+/// Wraps the query so that the columns of the text cast types (`DEFAULT_TEXT_TYPES` and `SchemaSettings::text_types`, including arrays of them)
+/// and the arrays of unsupported element types are converted to text, and the columns of the `seg` and `isn` extension types according to `SchemaSettings::cube_handling` and `SchemaSettings::isn_handling`.
+/// Returns None if the query does not need to be changed
+fn convert_column_types(client: &mut Client, columns: &[Column], query: &str, settings: &SchemaSettings) -> Result<Option<String>, String> {
+	let is_text_type = |t: &PgType| DEFAULT_TEXT_TYPES.contains(&t.name()) || settings.text_types.iter().any(|name| name == t.name());
+	// the arrays are converted only when pg2parquet cannot map them
+	let is_supported = |t: &PgType, settings: &SchemaSettings| map_schema_column::<Arc<Row>>(t, &ColumnInfo::root(0, "column".to_string()), settings).is_ok();
+	let is_array = |t: &PgType| matches!(strip_domains(t).kind(), Kind::Array(_));
+	// the converted extension types are not builtin, the catalog does not need to be queried for the common case of only builtin types
+	if settings.text_types.is_empty() && columns.iter().all(|c| PgType::from_oid(strip_domains(c.type_()).oid()).is_some() && (!is_array(c.type_()) || is_supported(c.type_(), settings))) {
+		return Ok(None);
+	}
+	let catalog = TypeCatalog::default().load(client, columns)?;
+	let mapping_settings = SchemaSettings { type_catalog: Arc::new(catalog.clone()), ..settings.clone() };
+	let extension_schema = |t: &PgType, extension: &str, names: &[&str]| catalog.get(t.oid())
+		.filter(|_| names.iter().any(|name| catalog.is_extension_type(t, extension, name)))
+		.map(|info| quote_identifier(&info.schema));
+	let conversion = |t: &PgType| -> Option<TypeConversion> {
+		let t = strip_domains(t);
+		match t.kind() {
+			Kind::Array(element) if is_text_type(strip_domains(element)) => Some(TypeConversion::TextArray),
+			Kind::Array(element) => extension_schema(strip_domains(element), "isn", ISN_TYPES).map(TypeConversion::IsnArray)
+				.or_else(|| Some(TypeConversion::UnsupportedArray(element.name().to_string())).filter(|_| !is_supported(t, &mapping_settings))),
+			_ if is_text_type(t) => Some(TypeConversion::Text),
+			_ => extension_schema(t, "seg", &["seg"]).map(TypeConversion::Seg)
+				.or_else(|| extension_schema(t, "isn", ISN_TYPES).map(TypeConversion::Isn)),
+		}
+	};
+	if !columns.iter().any(|c| conversion(c.type_()).is_some()) {
+		return Ok(None);
+	}
+	// all isn types can be converted to EAN13, its digits fit into int64
+	let isn_int64 = |value: &str, schema: &str| format!("regexp_replace({}::{}.ean13::text, '[^0-9]', '', 'g')::int8", value, schema);
+	let columns = columns.iter()
+		.map(|c| {
+			let name = quote_identifier(c.name());
+			match conversion(c.type_()) {
+				None => format!("q.{}", name),
+				Some(TypeConversion::Text) => format!("q.{}::text AS {}", name, name),
+				Some(TypeConversion::TextArray) => format!("q.{}::text[] AS {}", name, name),
+				Some(TypeConversion::UnsupportedArray(element)) => {
+					warn_once(Some(c.name().to_string()), format!("Column {} is an array of the unsupported type {}, its elements are exported as text", c.name(), element));
+					format!("q.{}::text[] AS {}", name, name)
+				},
+				Some(TypeConversion::Seg(_)) if settings.cube_handling == SchemaSettingsCubeHandling::Text => format!("q.{}::text AS {}", name, name),
+				// the bounds are float4, converted through text to get the same decimal value in float8
+				Some(TypeConversion::Seg(schema)) =>
+					format!("CASE WHEN q.{name} IS NOT NULL THEN ARRAY[{schema}.seg_lower(q.{name})::text::float8, {schema}.seg_upper(q.{name})::text::float8] END AS {name}", name = name, schema = schema),
+				Some(TypeConversion::Isn(_)) if settings.isn_handling == SchemaSettingsIsnHandling::Text => format!("q.{}::text AS {}", name, name),
+				Some(TypeConversion::IsnArray(_)) if settings.isn_handling == SchemaSettingsIsnHandling::Text => format!("q.{}::text[] AS {}", name, name),
+				Some(TypeConversion::Isn(schema)) => format!("{} AS {}", isn_int64(&format!("q.{}", name), &schema), name),
+				Some(TypeConversion::IsnArray(schema)) =>
+					format!("CASE WHEN q.{name} IS NOT NULL THEN ARRAY(SELECT {element} FROM unnest(q.{name}) WITH ORDINALITY AS u(e, n) ORDER BY u.n) END AS {name}", name = name, element = isn_int64("u.e", &schema)),
+			}
+		})
+		.collect::<Vec<_>>();
+	Ok(Some(format!("SELECT {} FROM ({}) AS q", columns.join(", "), query)))
+}
+
+/// Names of the table columns in information_schema.columns, which only lists the columns the user is allowed to access
+pub fn table_columns(client: &mut Client, table: &str) -> Result<Vec<String>, String> {
+	// pg_attribute instead of information_schema.columns, which does not list the columns of materialized views
+	let rows = client.query("
+		SELECT a.attname::text
+		FROM pg_attribute a
+		WHERE a.attrelid = $1::text::regclass AND a.attnum > 0 AND NOT a.attisdropped
+			AND has_column_privilege(a.attrelid, a.attnum, 'SELECT')
+		ORDER BY a.attnum", &[&table])
+		.map_err(|e| format!("Failed to query columns of {}: {}", table, format_pg_error(&e)))?;
+	Ok(rows.iter().map(|r| r.get(0)).collect())
+}
+
+/// Kind of the relation exported by --table, from pg_class.relkind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationKind {
+	Table,
+	PartitionedTable,
+	View,
+	/// Reading a materialized view which was created WITH NO DATA (or not refreshed since) fails
+	MaterializedView { populated: bool },
+	/// Table of a foreign data wrapper, reading it may be slow as the data comes from another server
+	ForeignTable,
+	Other,
+}
+
+pub fn relation_kind(client: &mut Client, table: &str) -> Result<RelationKind, String> {
+	let row = client.query_one("SELECT relkind::text, relispopulated FROM pg_class WHERE oid = $1::text::regclass", &[&table])
+		.map_err(|e| format!("Failed to query the kind of {}: {}", table, format_pg_error(&e)))?;
+	let (relkind, populated): (String, bool) = (row.get(0), row.get(1));
+	Ok(match relkind.as_str() {
+		"r" => RelationKind::Table,
+		"p" => RelationKind::PartitionedTable,
+		"v" => RelationKind::View,
+		"m" => RelationKind::MaterializedView { populated },
+		"f" => RelationKind::ForeignTable,
+		_ => RelationKind::Other,
+	})
+}
+
+/// Runs REFRESH MATERIALIZED VIEW, the connection must not be read-only
+pub fn refresh_materialized_view(client: &mut Client, matview: &str) -> Result<(), String> {
+	client.batch_execute(&format!("REFRESH MATERIALIZED VIEW {}", matview))
+		.map_err(|e| format!("Failed to refresh materialized view {}: {}", matview, format_pg_error(&e)))
+}
+
+/// Session-level advisory lock taken by `advisory_lock`, it is released when the connection is closed
+pub struct AdvisoryLock {
+	_client: Client,
+}
+
+/// Takes the advisory lock for --lock-key, waiting while another session holds it. An integer key is used as is, other strings are hashed.
+/// Without a key, it is derived from the OID of the table
+pub fn advisory_lock(args: &PostgresConnArgs, key: Option<&str>, table: Option<&str>, quiet: bool) -> Result<AdvisoryLock, String> {
+	let mut client = pg_connect(args)?;
+	let key: i64 = match (key, table) {
+		(Some(key), _) => match key.parse() {
+			Ok(key) => key,
+			Err(_) => client.query_one("SELECT hashtextextended($1, 0)", &[&key])
+				.map_err(|e| format!("Failed to hash the lock key: {}", format_pg_error(&e)))?
+				.get(0),
+		},
+		(None, Some(table)) => client.query_one("SELECT hashtextextended('pg2parquet ' || $1::text::regclass::oid, 0)", &[&table])
+			.map_err(|e| format!("Failed to derive the lock key from {}: {}", table, format_pg_error(&e)))?
+			.get(0),
+		(None, None) => return Err("--lock-key requires a value when exporting a --query".to_string()),
+	};
+	let locked: bool = client.query_one("SELECT pg_try_advisory_lock($1)", &[&key])
+		.map_err(|e| format!("Failed to take advisory lock {}: {}", key, format_pg_error(&e)))?
+		.get(0);
+	if !locked {
+		if !quiet {
+			eprintln!("Waiting for advisory lock {}, which is held by another session", key);
+		}
+		client.execute("SELECT pg_advisory_lock($1)", &[&key])
+			.map_err(|e| format!("Failed to take advisory lock {}: {}", key, format_pg_error(&e)))?;
+	}
+	Ok(AdvisoryLock { _client: client })
+}
+
+/// How many times the export is retried when the database connection fails
+#[derive(Clone, Debug)]
+pub struct RetrySettings {
+	pub retries: u32,
+	pub backoff: Duration,
+}
+
+impl Default for RetrySettings {
+	fn default() -> Self {
+		RetrySettings { retries: 0, backoff: Duration::from_secs(30) }
+	}
+}
+
+/// Options of `execute_copy` which do not affect the Parquet schema
+#[derive(Clone, Debug)]
+pub struct CopyOptions {
+	/// Table read by the query. Only specified when the query reads all columns of the table in the original form,
+	/// the export is then resumed after the last written row and the table can be read in chunks
+	pub table: Option<String>,
+	pub quiet: bool,
+	/// Maximum memory used by the buffered row group data, see `--max-buffer-memory`
+	pub max_buffer_memory: usize,
+	pub flush_interval: Option<Duration>,
+	pub engine: WriterEngine,
+	/// Write an Arrow IPC file instead of Parquet, see `--format arrow`. The engine and the Parquet writer properties are then not used
+	pub arrow_ipc: bool,
+	pub page_checksums: bool,
+	pub auto_compression: bool,
+	pub sort_by: Vec<SortColumn>,
+	pub row_group_per_partition_value: bool,
+	/// Collect the null count and approximate distinct count of each column into `WriterStats::columns`
+	pub column_stats: bool,
+	pub on_row_error: OnRowError,
+	pub row_identifier: Vec<String>,
+	pub distinct_on: Vec<String>,
+	pub watermark_column: Option<String>,
+	pub chunk_size: Option<usize>,
+	pub retry: RetrySettings,
+	pub hooks: SqlHooks,
+	/// Also write the CREATE TABLE statement of the exported columns, see `--emit-ddl`
+	pub ddl: Option<DdlOutput>,
+}
+
+impl Default for CopyOptions {
+	fn default() -> Self {
+		CopyOptions {
+			table: None,
+			quiet: false,
+			max_buffer_memory: 500 * 1024 * 1024,
+			flush_interval: None,
+			engine: WriterEngine::default(),
+			arrow_ipc: false,
+			page_checksums: false,
+			auto_compression: false,
+			sort_by: Vec::new(),
+			row_group_per_partition_value: false,
+			column_stats: false,
+			on_row_error: OnRowError::default(),
+			row_identifier: Vec::new(),
+			distinct_on: Vec::new(),
+			watermark_column: None,
+			chunk_size: None,
+			retry: RetrySettings::default(),
+			hooks: SqlHooks::default(),
+			ddl: None,
+		}
+	}
+}
+
+/// SQL statements executed on the connection which reads the exported data, see `--pre-sql` and `--post-sql`
+#[derive(Clone, Debug, Default)]
+pub struct SqlHooks {
+	/// Executed after connecting, before the export query. The export fails when any of them fails
+	pub pre_sql: Vec<String>,
+	/// Executed after the output is written, the number of exported rows is set in the `pg2parquet.rows` setting
+	pub post_sql: Vec<String>,
+}
+
+impl SqlHooks {
+	fn execute(client: &mut Client, statements: &[String], option: &str) -> Result<(), ExportError> {
+		for sql in statements {
+			client.batch_execute(sql).map_err(|e| pg_error(e, &format!("{} failed", option)))?;
+		}
+		Ok(())
+	}
+
+	pub fn run_pre_sql(&self, client: &mut Client) -> Result<(), String> {
+		Ok(Self::execute(client, &self.pre_sql, "--pre-sql")?)
+	}
+
+	/// Runs the --post-sql statements, unless the export was cancelled
+	pub fn run_post_sql(&self, client: &mut Client, stats: &WriterStats) -> Result<(), String> {
+		if self.post_sql.is_empty() || cancellation::was_cancelled() {
+			return Ok(());
+		}
+		client.execute("SELECT set_config('pg2parquet.rows', $1, false)", &[&stats.rows.to_string()])
+			.map_err(|e| format!("Failed to set pg2parquet.rows: {}", format_pg_error(&e)))?;
+		Ok(Self::execute(client, &self.post_sql, "--post-sql")?)
+	}
+}
+
+enum ExportError {
+	/// The database connection failed, the operation may succeed after reconnecting
+	Connection(String),
+	Fatal(String),
+}
+
+impl From<String> for ExportError {
+	fn from(e: String) -> Self { ExportError::Fatal(e) }
+}
+impl From<ExportError> for String {
+	fn from(e: ExportError) -> Self {
+		match e { ExportError::Connection(e) | ExportError::Fatal(e) => e }
+	}
+}
+
+fn pg_error(e: postgres::Error, context: &str) -> ExportError {
+	let detail = format_pg_error(&e);
+	let message = if context.is_empty() { detail } else { format!("{}: {}", context, detail) };
+	let is_connection_error = match e.as_db_error() {
+		// class 08 - Connection Exception, or the server is shutting down
+		Some(db_error) => db_error.code().code().starts_with("08") || [SqlState::ADMIN_SHUTDOWN, SqlState::CRASH_SHUTDOWN, SqlState::CANNOT_CONNECT_NOW].contains(db_error.code()),
+		None => e.is_closed() || std::error::Error::source(&e).is_some_and(|source| source.is::<io::Error>()),
+	};
+	if is_connection_error { ExportError::Connection(message) } else { ExportError::Fatal(message) }
+}
+
+fn with_retries<T>(retry: &RetrySettings, failed_attempts: &mut u32, quiet: bool, mut f: impl FnMut() -> Result<T, ExportError>) -> Result<T, String> {
+	loop {
+		match f() {
+			Ok(x) => return Ok(x),
+			Err(ExportError::Connection(e)) if *failed_attempts < retry.retries => {
+				*failed_attempts += 1;
+				let wait = retry.backoff * *failed_attempts;
+				if !quiet {
+					eprintln!();
+					eprintln!("{}", e);
+					eprintln!("Retrying in {:?} (attempt {}/{})", wait, failed_attempts, retry.retries);
+				}
+				std::thread::sleep(wait);
+			},
+			Err(ExportError::Connection(e) | ExportError::Fatal(e)) => return Err(e),
+		}
+	}
+}
+
+/// Finds the unique key which can be used to resume an interrupted table export: the --sort-by columns or the primary key.
+/// When the --sort-by columns are not unique, the columns of the primary key (or of a unique index) are appended to them, otherwise the rows with the same values would be skipped after the last exported row
+fn find_resume_key(client: &mut Client, table: &str, sort_by: &[SortColumn]) -> Result<Option<Vec<SortColumn>>, ExportError> {
+	if !sort_by.is_empty() && is_unique_key(client, table, sort_by)? {
+		return Ok(Some(sort_by.to_vec()));
+	}
+	let key_columns = find_unique_key(client, table)?;
+	if key_columns.is_empty() {
+		return Ok(None);
+	}
+	let tiebreaker = key_columns.into_iter()
+		.filter(|name| !sort_by.iter().any(|c| c.name == *name))
+		.map(|name| SortColumn { name, descending: false, nulls_first: false });
+	Ok(Some(sort_by.iter().cloned().chain(tiebreaker).collect()))
+}
+
+/// Whether the columns include all columns of a unique index, which must be NOT NULL, as unique indexes allow multiple NULL values.
+/// Views and foreign tables have no indexes, the --sort-by columns are trusted to be unique
+fn is_unique_key(client: &mut Client, table: &str, key: &[SortColumn]) -> Result<bool, ExportError> {
+	let names: Vec<&str> = key.iter().map(|c| c.name.as_str()).collect();
+	let row = client.query_one("
+		SELECT c.relkind IN ('v', 'f') OR EXISTS (
+			SELECT FROM pg_index i
+			WHERE i.indrelid = c.oid AND i.indisunique AND i.indpred IS NULL AND i.indexprs IS NULL
+				AND NOT EXISTS (
+					SELECT FROM pg_attribute a
+					WHERE a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey) AND NOT (a.attnotnull AND a.attname::text = ANY($2))))
+		FROM pg_class c
+		WHERE c.oid = $1::text::regclass", &[&table, &names])
+		.map_err(|e| pg_error(e, "Failed to query unique indexes"))?;
+	Ok(row.get(0))
+}
+
+/// Columns of the primary key, or of the unique index with the fewest columns if the table has no primary key
+fn find_unique_key(client: &mut Client, table: &str) -> Result<Vec<String>, ExportError> {
+	let pk_columns = find_primary_key(client, table)?;
+	if !pk_columns.is_empty() {
+		return Ok(pk_columns);
+	}
+	let index = client.query_opt("
+		SELECT array(SELECT a.attname::text FROM unnest(i.indkey::int2[]) WITH ORDINALITY k(attnum, n) JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum ORDER BY k.n)
+		FROM pg_index i
+		WHERE i.indrelid = $1::text::regclass AND i.indisunique AND i.indpred IS NULL AND i.indexprs IS NULL
+			AND NOT EXISTS (SELECT FROM pg_attribute a WHERE a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey) AND NOT a.attnotnull)
+		ORDER BY i.indnatts, i.indexrelid
+		LIMIT 1", &[&table])
+		.map_err(|e| pg_error(e, "Failed to query unique indexes"))?;
+	Ok(index.map(|r| r.get(0)).unwrap_or_default())
+}
+
+fn find_primary_key(client: &mut Client, table: &str) -> Result<Vec<String>, ExportError> {
+	let pk_columns = client.query("
+		SELECT a.attname::text
+		FROM pg_index i
+		JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+		WHERE i.indrelid = $1::text::regclass AND i.indisprimary
+		ORDER BY array_position(i.indkey::int2[], a.attnum)", &[&table])
+		.map_err(|e| pg_error(e, "Failed to query primary key"))?;
+	Ok(pk_columns.iter().map(|r| r.get(0)).collect())
+}
+
+/// Columns identifying the rows in error messages, the specified ones or the primary key of the table
+fn find_row_identifier(client: &mut Client, table: Option<&str>, row_identifier: &[String], columns: &[Column]) -> Result<Vec<String>, ExportError> {
+	if let Some(name) = row_identifier.iter().find(|name| !columns.iter().any(|c| c.name() == *name)) {
+		return Err(format!("Column {} specified in --row-identifier-column does not exist in the result", name).into());
+	}
+	match table {
+		Some(table) if row_identifier.is_empty() => {
+			let pk_columns = find_primary_key(client, table)?;
+			// the primary key might not be selected if the table query is customized
+			Ok(pk_columns.into_iter().filter(|name| columns.iter().any(|c| c.name() == name)).collect())
+		},
+		_ => Ok(row_identifier.to_vec()),
+	}
+}
+
+/// Query continuing a table export after the last exported row, ordered by the `key`.
+fn resume_query(table: &str, key: &[SortColumn], last_row: &Row) -> Result<(String, Vec<Option<PgAny>>), ExportError> {
+	let mut conditions = vec![];
+	let mut equal_prefix = vec![];
+	let mut params = vec![];
+	for col in key {
+		let name = quote_identifier(&col.name);
+		let value: Option<PgAny> = last_row.try_get(col.name.as_str())
+			.map_err(|e| format!("Could not read column {} of the last exported row: {}", col.name, e))?;
+		let (equal, after) = match value {
+			Some(value) => {
+				params.push(Some(value));
+				let p = params.len();
+				let op = if col.descending { "<" } else { ">" };
+				let after = if col.nulls_first { format!("{} {} ${}", name, op, p) } else { format!("({} {} ${} OR {} IS NULL)", name, op, p, name) };
+				(format!("{} = ${}", name, p), after)
+			},
+			None => {
+				let after = if col.nulls_first { format!("{} IS NOT NULL", name) } else { "false".to_string() };
+				(format!("{} IS NULL", name), after)
+			},
+		};
+		conditions.push(equal_prefix.iter().chain([&after]).cloned().collect::<Vec<_>>().join(" AND "));
+		equal_prefix.push(equal);
+	}
+	let columns: Vec<String> = last_row.columns().iter().map(|c| c.name().to_string()).collect();
+	let query = format!("SELECT {} FROM {} WHERE ({}){}", select_list(&columns, &[]), table, conditions.join(") OR ("), order_by_clause(key));
+	Ok((query, params))
+}
+
+/// Number of rows fetched ahead of the Parquet writer
+const FETCH_BUFFER_ROWS: usize = 4096;
+
+/// The export query and how it is resumed, decided on the first connection of `execute_copy`
+struct ExportPlan {
+	query: String,
+	/// The table read by the query, None if the query reads it with a filter or converts its columns
+	table: Option<String>,
+	/// Unique key of the table, the export continues after the last exported row when the connection fails
+	resume_key: Option<Vec<SortColumn>>,
+}
+
+fn plan_export(client: &mut Client, query: &str, schema_settings: &SchemaSettings, options: &CopyOptions) -> Result<ExportPlan, ExportError> {
+	// the result columns may depend on the session set up by --pre-sql
+	let rewritten = rewrite_query(client, query, schema_settings)?;
+	// the converted query cannot be resumed by the primary key of the table
+	let table = options.table.as_deref().filter(|_| rewritten.is_none());
+	let resume_key = match table {
+		Some(table) if options.retry.retries > 0 || options.chunk_size.is_some() => find_resume_key(client, table, &options.sort_by)?,
+		_ => None,
+	};
+	if options.chunk_size.is_some() && resume_key.is_none() {
+		return Err("--chunk-size requires a --table export of a table with a primary key or a unique index on NOT NULL columns".to_string().into());
+	}
+	if options.retry.retries > 0 && resume_key.is_none() {
+		warn_once(None, format!("The export cannot be resumed, because {}. It will be restarted from the beginning if the connection fails.",
+			if table.is_some() { "the table has no primary key or unique index on NOT NULL columns" } else { "it is a --query export or the table query is customized" }));
+	}
+	let query = match (table, &resume_key) {
+		(Some(table), Some(key)) => {
+			let statement = client.prepare(query).map_err(|e| pg_error(e, ""))?;
+			let table_columns = statement.columns().iter().map(|c| c.name().to_string()).collect::<Vec<_>>();
+			format!("SELECT {} FROM {}{}", select_list(&table_columns, &[]), table, order_by_clause(key))
+		},
+		_ => rewritten.unwrap_or_else(|| query.to_string()),
+	};
+	Ok(ExportPlan { query, table: table.map(|t| t.to_string()), resume_key })
+}
+
+pub fn execute_copy(pg_args: &PostgresConnArgs, query: &str, output_file: &Path, output_props: WriterPropertiesBuilder, schema_settings: &SchemaSettings, options: &CopyOptions, checksum: Option<&Checksum>) -> Result<WriterStats, String> {
+	let (quiet, retry, hooks, chunk_size) = (options.quiet, &options.retry, &options.hooks, options.chunk_size);
+	let row_group_per_partition_value = options.row_group_per_partition_value;
+	if row_group_per_partition_value && options.sort_by.is_empty() {
+		return Err("--row-group-per-partition-value requires --sort-by, the first sort column is used as the partition column".to_string());
+	}
+
+	let connect = pg_connector(pg_args)?;
+	let mut failed_attempts = 0;
+	let mut plan = None;
+	let mut output_props_builder = Some(output_props);
+	let mut output_props: Option<WriterPropertiesPtr> = None;
+	let mut row_writer = None;
+	let mut last_row: Option<Arc<Row>> = None;
+	let mut value_counters = schema_settings.value_counters.clone();
+	let mut client = with_retries(retry, &mut failed_attempts, quiet, || {
+		let mut client = connect()?;
+		SqlHooks::execute(&mut client, &hooks.pre_sql, "--pre-sql")?;
+		if plan.is_none() {
+			plan = Some(plan_export(&mut client, query, schema_settings, options)?);
+		}
+		let ExportPlan { query, table, resume_key } = plan.as_ref().unwrap();
+		let table = table.as_deref();
+		let sort_by = resume_key.as_deref().unwrap_or(&options.sort_by);
+		let mut resuming = row_writer.is_some() && resume_key.is_some();
+		// with --chunk-size, the table is read by multiple queries, each one continuing after the last row of the previous one
+		loop {
+			let (current_query, params) = match (table, &resume_key, &last_row) {
+				(Some(table), Some(key), Some(last_row)) => resume_query(table, key, last_row)?,
+				_ => (query.clone(), vec![]),
+			};
+			let current_query = match chunk_size {
+				Some(chunk_size) => format!("{} LIMIT {}", current_query, chunk_size),
+				None => current_query,
+			};
+			let statement = client.prepare(&current_query).map_err(|e| pg_error(e, ""))?;
+
+			if row_writer.is_none() || resume_key.is_none() {
+				// start from scratch, or overwrite the partially written file
+				row_writer = None;
+				last_row = None;
+				value_counters = Arc::new(ValueCounters::default());
+				let schema_settings = &SchemaSettings { value_counters: value_counters.clone(), ..load_catalog_info(&mut client, statement.columns(), schema_settings)? };
+				let (row_appender, schema) = map_schema_root(statement.columns(), schema_settings)?;
+				if !quiet {
+					eprintln!("Schema: {}", format_schema(&schema, 0));
+				}
+				if let Some(builder) = output_props_builder.take() {
+					let sorting_columns = resolve_sorting_columns(&schema, sort_by, schema_settings)?;
+					output_props = Some(Arc::new(builder.set_sorting_columns(sorting_columns).build()));
+				}
+				// the second appender buffers the next row group while the previous one is being compressed
+				let (spare_appender, _) = map_schema_root(statement.columns(), schema_settings)?;
+				let partition_column = match sort_by.first() {
+					Some(sc) if row_group_per_partition_value => Some(statement.columns().iter().position(|c| c.name() == sc.name)
+						.ok_or_else(|| format!("Column {} specified in --sort-by does not exist in the result", sc.name))?),
+					_ => None,
+				};
+				let distinct_on = options.distinct_on.iter().map(|name| statement.columns().iter().position(|c| c.name() == name)
+					.ok_or_else(|| format!("Column {} specified in --distinct-on does not exist in the result", name)))
+					.collect::<Result<Vec<_>, _>>()?;
+				let watermark_column = match &options.watermark_column {
+					Some(name) => {
+						let i = statement.columns().iter().position(|c| c.name() == name)
+							.ok_or_else(|| format!("Column {} specified in --watermark-column does not exist in the result", name))?;
+						if !column_stats::is_watermark_type(statement.columns()[i].type_()) {
+							return Err(format!("Column {} specified in --watermark-column has type {}, only integer, date, timestamp and timestamptz columns are supported", name, statement.columns()[i].type_()).into());
+						}
+						Some((i, name.to_string()))
+					},
+					None => None,
+				};
+				let row_identifier = find_row_identifier(&mut client, table, &options.row_identifier, statement.columns())?;
+				if let Some(ddl) = &options.ddl {
+					ddl::write_ddl(&mut client, statement.columns(), ddl)?;
+				}
+				let output_props = output_props.clone().unwrap();
+				let column_stats = options.column_stats.then(|| statement.columns().iter().map(|c| schema_settings.output_column_name(c.name())).collect());
+				let settings = WriterSettings { row_group_byte_limit: options.max_buffer_memory, row_group_row_limit: output_props.max_row_group_size(), flush_interval: options.flush_interval, partition_column, distinct_on, column_stats, watermark_column, on_row_error: options.on_row_error, row_identifier };
+
+				let schema = Arc::new(schema);
+				let output_file_f = ChecksumWriter::new(std::fs::File::create(output_file).map_err(|e| format!("Failed to create {:?}: {}", output_file, e))?, checksum);
+				let metadata = export_metadata(&mut client, query, statement.columns(), &schema, schema_settings)?;
+				let pq_writer = if options.arrow_ipc {
+					GroupWriter::new_arrow_ipc(output_file_f, schema.clone(), metadata)?
+				} else {
+					GroupWriter::new(output_file_f, schema.clone(), output_props, options.engine, options.page_checksums, options.auto_compression, metadata)?
+				};
+				check_money_scale(&mut client, statement.columns(), schema_settings)?;
+				row_writer = Some(ParquetRowWriter::new(pq_writer, schema, row_appender, spare_appender, quiet, settings)
+					.map_err(|e| format!("Failed to create row writer: {}", e))?);
+			} else if resuming && !quiet {
+				eprintln!("Resuming the export after the last exported row");
+			}
+			resuming = false;
+			let row_writer = row_writer.as_mut().unwrap();
+
+			let rows = client.query_raw(&statement, params.iter());
+			// the query was cancelled by the signal handler
+			if rows.is_err() && cancellation::should_stop()? {
+				drop(rows);
+				return Ok(client);
+			}
+			let rows: RowIter = rows.map_err(|e| pg_error(e, ""))?;
+			let mut chunk_rows = 0;
+			// the rows are fetched on a separate thread, so that reading from the network continues while
+			// the writer is busy, and the bounded channel stops the fetching when the writer falls behind
+			std::thread::scope(|scope| -> Result<(), String> {
+				let (row_sender, row_receiver) = std::sync::mpsc::sync_channel(FETCH_BUFFER_ROWS);
+				scope.spawn(move || {
+					for row in rows.iterator() {
+						if row_sender.send(row.map(Arc::new)).is_err() {
+							// the writer has stopped
+							break;
+						}
+					}
+				});
+				loop {
+					cancellation::check_aborted()?;
+					let row = match row_writer.flush_deadline() {
+						Some(deadline) => match row_receiver.recv_timeout(deadline.saturating_duration_since(std::time::Instant::now())) {
+							Ok(row) => row,
+							Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+								row_writer.flush_expired_group()?;
+								continue;
+							},
+							Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+						},
+						None => match row_receiver.recv() {
+							Ok(row) => row,
+							Err(_) => break,
+						},
+					};
+					let row = match row {
+						Ok(row) => row,
+						Err(_) if cancellation::should_stop()? => break,
+						Err(e) => return Err(pg_error(e, "").into()),
+					};
+
+					row_writer.write_row(row.clone())?;
+					last_row = Some(row);
+					chunk_rows += 1;
+				}
+				Ok(())
+			})?;
+			match chunk_size {
+				Some(chunk_size) if chunk_rows == chunk_size && !cancellation::was_cancelled() => continue,
+				_ => return Ok(client),
+			}
+		}
+	})?;
+
+	let mut stats = row_writer.unwrap().close()?;
+	value_counters.add_to(&mut stats);
+	report_special_float_values(&stats, schema_settings);
+	report_rounded_decimal_values(&stats, schema_settings);
+	hooks.run_post_sql(&mut client, &stats)?;
+	Ok(stats)
+}
+
+/// Resolves the Parquet schema of the query result, without exporting any data
+pub fn resolve_schema(pg_args: &PostgresConnArgs, query: &str, schema_settings: &SchemaSettings) -> Result<ParquetType, String> {
+	let mut client = pg_connect(pg_args)?;
+	let query = rewrite_query(&mut client, query, schema_settings)?.unwrap_or_else(|| query.to_string());
+	let statement = client.prepare(&query).map_err(|db_err| { db_err.to_string() })?;
+
+	let schema_settings = &load_catalog_info(&mut client, statement.columns(), schema_settings)?;
+	let (_, schema) = map_schema_root(statement.columns(), schema_settings)?;
+	Ok(schema)
+}
+
+/// Resolves the Parquet schema of the query result and prints it to stdout, without exporting any data
+pub fn print_schema(pg_args: &PostgresConnArgs, query: &str, schema_settings: &SchemaSettings, json: bool) -> Result<(), String> {
+	let schema = resolve_schema(pg_args, query, schema_settings)?;
+	if json {
+		println!("{}", serde_json::to_string_pretty(&schema_to_json(&schema)).unwrap());
+	} else {
+		println!("{}", format_schema(&schema, 0));
+	}
+	Ok(())
+}
+
+/// Result of `estimate_size`
+#[derive(Debug, Clone)]
+pub struct SizeEstimate {
+	/// Number of rows of the table, estimated by the planner statistics
+	pub table_rows: u64,
+	pub sampled_rows: usize,
+	/// Size of the raw values of the sample, before encoding and compression
+	pub sampled_raw_bytes: usize,
+	/// Encoded size of the sample with each of the writer properties
+	pub sampled_sizes: Vec<SampleEncoding>,
+}
+
+/// Size of the sample encoded with one of the writer properties, see `estimate_size`
+#[derive(Debug, Clone)]
+pub struct SampleEncoding {
+	/// Label of the writer properties
+	pub label: String,
+	pub bytes: usize,
+	/// Time spent encoding and compressing the sample
+	pub duration: std::time::Duration,
+}
+
+impl SizeEstimate {
+	/// Extrapolates the size of the sample to the whole table
+	pub fn extrapolate(&self, sample_bytes: usize) -> u64 {
+		if self.sampled_rows == 0 {
+			return 0;
+		}
+		(sample_bytes as f64 * self.table_rows as f64 / self.sampled_rows as f64).round() as u64
+	}
 }
 
-#[cfg(not(any(target_os = "macos", target_os="windows", all(target_os="linux", not(target_env="musl"), any(target_arch="x86_64", target_arch="aarch64")))))]
-fn build_tls_connector(certificates: &Option<Vec<PathBuf>>) -> Result<NoTls, String> {
-	if certificates.is_some() {
-		return Err("SSL/TLS is not supported in this build of pg2parquet".to_string());
+/// Reads a random sample of the table rows and encodes it with each of the writer properties, without writing any file
+pub fn estimate_size(pg_args: &PostgresConnArgs, table: &str, table_columns: &[String], sample_rows: usize, schema_settings: &SchemaSettings, properties: Vec<(String, WriterPropertiesBuilder)>) -> Result<SizeEstimate, String> {
+	let mut client = pg_connect(pg_args)?;
+	let row = client.query_one("
+		SELECT sum(c.reltuples)::int8, bool_or(c.reltuples < 0)
+		FROM pg_class c
+		WHERE c.oid IN (SELECT relid FROM pg_partition_tree($1::text::regclass) WHERE isleaf)
+			OR (c.oid = $1::text::regclass AND c.relkind <> 'p')", &[&table])
+		.map_err(|e| format!("Failed to query the size of table {}: {}", table, format_pg_error(&e)))?;
+	let (reltuples, not_analyzed): (Option<i64>, Option<bool>) = (row.get(0), row.get(1));
+	let table_rows = if not_analyzed == Some(true) {
+		// the table was never vacuumed or analyzed
+		let row = client.query_one(&format!("SELECT count(*) FROM {}", table), &[])
+			.map_err(|e| format!("Failed to count rows of table {}: {}", table, format_pg_error(&e)))?;
+		row.get::<_, i64>(0)
+	} else {
+		reltuples.unwrap_or(0)
+	}.max(0) as u64;
+
+	let mut query = format!("SELECT {} FROM {}", select_list(table_columns, &[]), table);
+	if table_rows > sample_rows as u64 {
+		// slightly larger fraction, so that the sample is not smaller than requested when the statistics are imprecise
+		let percent = (sample_rows as f64 / table_rows as f64 * 120.0).min(100.0);
+		query.push_str(&format!(" TABLESAMPLE BERNOULLI ({}) REPEATABLE (0)", percent));
 	}
-	Ok(NoTls)
+	query.push_str(&format!(" LIMIT {}", sample_rows));
+	let statement = client.prepare(&query).map_err(|e| pg_error(e, ""))?;
+	let rows: Vec<Arc<Row>> = client.query(&statement, &[]).map_err(|e| pg_error(e, ""))?
+		.into_iter().map(Arc::new).collect();
+
+	let schema_settings = &load_catalog_info(&mut client, statement.columns(), schema_settings)?;
+	let mut estimate = SizeEstimate { table_rows, sampled_rows: rows.len(), sampled_raw_bytes: 0, sampled_sizes: Vec::new() };
+	for (label, builder) in properties {
+		let (row_appender, schema) = map_schema_root(statement.columns(), schema_settings)?;
+		let (spare_appender, _) = map_schema_root(statement.columns(), schema_settings)?;
+		let schema = Arc::new(schema);
+		let props = Arc::new(builder.build());
+		let settings = WriterSettings { row_group_byte_limit: usize::MAX, row_group_row_limit: props.max_row_group_size(), flush_interval: None, partition_column: None, distinct_on: Vec::new(), column_stats: None, watermark_column: None, on_row_error: OnRowError::Fail, row_identifier: Vec::new() };
+		let pq_writer = GroupWriter::new(io::sink(), schema.clone(), props, WriterEngine::Parquet, false, false, Vec::new())?;
+		let mut row_writer = ParquetRowWriter::new(pq_writer, schema, row_appender, spare_appender, true, settings)
+			.map_err(|e| format!("Failed to create row writer: {}", e))?;
+		let start_time = std::time::Instant::now();
+		for row in &rows {
+			row_writer.write_row(row.clone())?;
+		}
+		let stats = row_writer.close()?;
+		estimate.sampled_raw_bytes = stats.bytes;
+		estimate.sampled_sizes.push(SampleEncoding { label, bytes: stats.bytes_out, duration: start_time.elapsed() });
+	}
+	Ok(estimate)
 }
 
-fn pg_connect(args: &PostgresConnArgs) -> Result<Client, String> {
-	let user_env = std::env::var("PGUSER").ok();
-
-	let mut pg_config = postgres::Config::new();
-	pg_config.dbname(&args.dbname)
-		.application_name("pg2parquet")
-		.host(&args.host)
-		.port(args.port.unwrap_or(5432))
-		.user(args.user.as_ref().or(user_env.as_ref()).unwrap_or(&args.dbname));
-
-	if let Some(password) = args.password.as_ref() {
-		pg_config.password(password);
-	} else if let Ok(password) = std::env::var("PGPASSWORD") {
-		pg_config.password(&password);
+/// Automatic file metadata entries describing where the data came from
+fn export_metadata(client: &mut Client, query: &str, columns: &[Column], schema: &ParquetType, settings: &SchemaSettings) -> Result<Vec<KeyValue>, String> {
+	let server_version: String = client.query_one("SHOW server_version", &[])
+		.map_err(|e| format!("Failed to query server version: {}", e))?
+		.get(0);
+	let mut metadata = vec![
+		KeyValue::new("pg2parquet.query".to_string(), query.to_string()),
+		KeyValue::new("pg2parquet.exported_at".to_string(), settings.exported_at.to_rfc3339()),
+		KeyValue::new("pg2parquet.server_version".to_string(), server_version),
+	];
+	if let Some(job_id) = &settings.job_id {
+		metadata.push(KeyValue::new("pg2parquet.job_id".to_string(), job_id.clone()));
+	}
+	if settings.bit_handling == SchemaSettingsBitHandling::Binary {
+		let bit_lengths: serde_json::Map<String, serde_json::Value> = columns.iter()
+			.filter(|c| c.type_modifier() > 0 && contains_type(c.type_(), &|t| *t == PgType::BIT))
+			.filter(|c| !settings.column_masks.iter().any(|(name, _)| name == c.name()))
+			.map(|c| (settings.output_column_name(c.name()), c.type_modifier().into()))
+			.collect();
+		if !bit_lengths.is_empty() {
+			metadata.push(KeyValue::new("pg2parquet.bit_lengths".to_string(), serde_json::Value::Object(bit_lengths).to_string()));
+		}
+	}
+	let generated = generated_columns(client, columns, settings)?;
+	let vector_columns: Vec<&Column> = columns.iter()
+		.filter(|c| c.type_modifier() > 0 && contains_type(c.type_(), &|t| is_vector_type(t, settings) || settings.type_catalog.is_extension_type(t, "vector", "sparsevec")))
+		.filter(|c| !settings.column_masks.iter().any(|(name, _)| name == c.name()))
+		.collect();
+	let fixed_size: Vec<(String, i32)> = if settings.vector_fixed_size_list {
+		vector_columns.iter()
+			.filter(|c| is_vector_type(c.type_(), settings) || (settings.sparsevec_handling == SchemaSettingsSparsevecHandling::Dense && settings.type_catalog.is_extension_type(c.type_(), "vector", "sparsevec")))
+			.map(|c| (settings.output_column_name(c.name()), c.type_modifier()))
+			.collect()
 	} else {
-		pg_config.password(&read_password(pg_config.get_user().unwrap())?.trim());
+		Vec::new()
+	};
+	let field_metadata: Vec<(String, HashMap<String, String>)> = generated.into_iter()
+		.map(|(name, expression)| (name, HashMap::from([(GENERATION_EXPRESSION_METADATA_KEY.to_string(), expression)])))
+		.collect();
+	if !fixed_size.is_empty() || !field_metadata.is_empty() {
+		metadata.extend(arrow_schema_metadata(schema, &fixed_size, &field_metadata)?);
 	}
-
-	#[cfg(not(any(target_os = "macos", target_os="windows", all(target_os="linux", not(target_env="musl"), any(target_arch="x86_64", target_arch="aarch64")))))]
-	match &args.sslmode {
-		None | Some(crate::SslMode::Disable) => {},
-		Some(x) => return Err(format!("SSL/TLS is disabled in this build of pg2parquet, so ssl mode {:?} cannot be used. Only 'disable' option is allowed.", x)),
+	let vector_dims: serde_json::Map<String, serde_json::Value> = vector_columns.iter()
+		.map(|c| (settings.output_column_name(c.name()), c.type_modifier().into()))
+		.collect();
+	if !vector_dims.is_empty() {
+		metadata.push(KeyValue::new("pg2parquet.vector_dims".to_string(), serde_json::Value::Object(vector_dims).to_string()));
 	}
-	match &args.sslmode {
-		None => {
-			if args.ssl_root_cert.is_some() {
-				pg_config.ssl_mode(postgres::config::SslMode::Require);
-			} else {
-				pg_config.ssl_mode(postgres::config::SslMode::Prefer);
-			}
-		},
-		Some(crate::SslMode::Disable) => {
-			pg_config.ssl_mode(postgres::config::SslMode::Disable);
-		},
-		Some(crate::SslMode::Prefer) => {
-			pg_config.ssl_mode(postgres::config::SslMode::Prefer);
-		},
-		Some(crate::SslMode::Require) => {
-			pg_config.ssl_mode(postgres::config::SslMode::Require);
-		},
+	let case_insensitive = case_insensitive_columns(columns, settings);
+	if !case_insensitive.is_empty() {
+		metadata.push(KeyValue::new("pg2parquet.case_insensitive_columns".to_string(), serde_json::to_string(&case_insensitive).unwrap()));
+	}
+	if settings.enum_handling == SchemaSettingsEnumHandling::Int {
+		let enums = enum_labels(columns, settings);
+		if !enums.is_empty() {
+			metadata.push(KeyValue::new(ENUMS_METADATA_KEY.to_string(), serde_json::Value::Object(enums).to_string()));
+		}
 	}
+	Ok(metadata)
+}
 
-	let connector = build_tls_connector(&args.ssl_root_cert)?;
+fn is_vector_type(t: &PgType, settings: &SchemaSettings) -> bool {
+	settings.type_catalog.is_extension_type(t, "vector", "vector") || settings.type_catalog.is_extension_type(t, "vector", "halfvec")
+}
 
-	let client = pg_config.connect(connector).map_err(|e| format!("DB connection failed: {}", e.to_string()))?;
+/// ARROW:schema metadata entry, which makes Arrow readers load the listed columns as FixedSizeList of the given size instead of List,
+/// and which holds the field metadata of the columns, since Parquet itself has no metadata of the columns
+#[cfg(feature = "arrow")]
+fn arrow_schema_metadata(schema: &ParquetType, fixed_size: &[(String, i32)], field_metadata: &[(String, HashMap<String, String>)]) -> Result<Option<KeyValue>, String> {
+	use arrow_schema::{DataType, Field, Schema};
+	let descriptor = parquet::schema::types::SchemaDescriptor::new(Arc::new(schema.clone()));
+	let arrow_schema = parquet::arrow::parquet_to_arrow_schema(&descriptor, None)
+		.map_err(|e| format!("Failed to convert the schema to Arrow: {}", e))?;
+	let fields: Vec<Field> = arrow_schema.fields().iter().map(|f| {
+		let field = match (f.data_type(), fixed_size.iter().find(|(name, _)| name == f.name())) {
+			(DataType::List(element), Some((_, size))) =>
+				f.as_ref().clone().with_data_type(DataType::FixedSizeList(element.clone(), *size)),
+			_ => f.as_ref().clone(),
+		};
+		match field_metadata.iter().find(|(name, _)| name == f.name()) {
+			Some((_, metadata)) => field.with_metadata(metadata.clone()),
+			None => field,
+		}
+	}).collect();
+	Ok(Some(KeyValue::new(parquet::arrow::ARROW_SCHEMA_META_KEY.to_string(), parquet::arrow::encode_arrow_schema(&Schema::new(fields)))))
+}
 
-	Ok(client)
+/// Without Arrow, the field metadata cannot be written
+#[cfg(not(feature = "arrow"))]
+fn arrow_schema_metadata(_schema: &ParquetType, fixed_size: &[(String, i32)], _field_metadata: &[(String, HashMap<String, String>)]) -> Result<Option<KeyValue>, String> {
+	if !fixed_size.is_empty() {
+		return Err("--vector-fixed-size-list requires pg2parquet built with the arrow feature".to_string());
+	}
+	Ok(None)
 }
 
-pub fn execute_copy(pg_args: &PostgresConnArgs, query: &str, output_file: &PathBuf, output_props: WriterPropertiesPtr, quiet: bool, schema_settings: &SchemaSettings) -> Result<WriterStats, String> {
+/// Key of the field metadata entry with the expression of a generated column
+pub const GENERATION_EXPRESSION_METADATA_KEY: &str = "pg2parquet.generation_expression";
 
-	let mut client = pg_connect(pg_args)?;
-	let statement = client.prepare(query).map_err(|db_err| { db_err.to_string() })?;
+/// Generation expressions of the exported columns which come from generated table columns, by output column name
+fn generated_columns(client: &mut Client, columns: &[Column], settings: &SchemaSettings) -> Result<Vec<(String, String)>, String> {
+	let (table_oids, column_ids): (Vec<u32>, Vec<i16>) = columns.iter()
+		.filter_map(|c| Some((c.table_oid()?, c.column_id()?)))
+		.unzip();
+	if table_oids.is_empty() {
+		return Ok(Vec::new());
+	}
+	let rows = client.query("
+		SELECT u.relid, u.attnum, c.generation_expression::text
+		FROM information_schema.columns c
+		JOIN unnest($1::oid[], $2::int2[]) u(relid, attnum)
+			ON format('%I.%I', c.table_schema, c.table_name)::regclass = u.relid AND c.ordinal_position = u.attnum
+		WHERE c.is_generated = 'ALWAYS'", &[&table_oids, &column_ids])
+		.map_err(|e| format!("Failed to query generated columns: {}", format_pg_error(&e)))?;
+	Ok(columns.iter()
+		.filter_map(|c| {
+			let row = rows.iter().find(|r| Some(r.get::<_, u32>(0)) == c.table_oid() && Some(r.get::<_, i16>(1)) == c.column_id())?;
+			Some((settings.output_column_name(c.name()), row.get::<_, String>(2)))
+		})
+		.collect())
+}
+
+/// Warns when the exported money values have a different number of fractional digits than --money-scale
+fn check_money_scale(client: &mut Client, columns: &[Column], settings: &SchemaSettings) -> Result<(), String> {
+	if settings.money_handling == SchemaSettingsMoneyHandling::Int64 || !columns.iter().any(|c| contains_type(c.type_(), &|t| *t == PgType::MONEY)) {
+		return Ok(());
+	}
+	// money to numeric conversion uses the fractional digits of the lc_monetary locale
+	let row = client.query_one("SELECT scale(0::money::numeric), current_setting('lc_monetary')", &[])
+		.map_err(|e| format!("Failed to query the money format: {}", format_pg_error(&e)))?;
+	let (server_scale, locale): (i32, String) = (row.get(0), row.get(1));
+	if server_scale != settings.money_scale {
+		warn(None, format!("The money values have {} fractional digits in the server locale (lc_monetary = {}), but they are exported with {}. Use --money-scale {} to export them correctly.", server_scale, locale, settings.money_scale, server_scale));
+	}
+	Ok(())
+}
 
-	let (row_appender, schema) = map_schema_root(statement.columns(), schema_settings)?;
-	if !quiet {
-		eprintln!("Schema: {}", format_schema(&schema, 0));
+/// Whether the type is `predicate`, or an array, domain or composite type containing it
+fn contains_type(t: &PgType, predicate: &dyn Fn(&PgType) -> bool) -> bool {
+	match t.kind() {
+		Kind::Array(element) | Kind::Domain(element) => contains_type(element, predicate),
+		Kind::Composite(fields) => fields.iter().any(|f| contains_type(f.type_(), predicate)),
+		_ => predicate(t),
 	}
-	let schema = Arc::new(schema);
+}
 
-	let settings = WriterSettings { row_group_byte_limit: 500 * 1024 * 1024, row_group_row_limit: output_props.max_row_group_size() };
+/// System catalogs of the objects referenced by reg* types
+const REG_TYPE_CATALOGS: &[(PgType, &str)] = &[
+	(PgType::REGCLASS, "pg_class"),
+	(PgType::REGTYPE, "pg_type"),
+	(PgType::REGPROC, "pg_proc"),
+	(PgType::REGPROCEDURE, "pg_proc"),
+	(PgType::REGOPER, "pg_operator"),
+	(PgType::REGOPERATOR, "pg_operator"),
+	(PgType::REGCONFIG, "pg_ts_config"),
+	(PgType::REGDICTIONARY, "pg_ts_dict"),
+	(PgType::REGNAMESPACE, "pg_namespace"),
+	(PgType::REGROLE, "pg_roles"),
+	(PgType::REGCOLLATION, "pg_collation"),
+];
+
+/// Loads the catalog information needed to map the types of the query result: the extension and user-defined types,
+/// and the names of all objects referenced by the reg* types (for `--reg-handling=text`)
+fn load_catalog_info(client: &mut Client, columns: &[Column], settings: &SchemaSettings) -> Result<SchemaSettings, String> {
+	let mut settings = settings.clone();
+	settings.type_catalog = Arc::new(settings.type_catalog.load(client, columns)?);
+	if settings.reg_handling != SchemaSettingsRegHandling::Text {
+		return Ok(settings);
+	}
+	let mut reg_names = HashMap::new();
+	for (t, catalog) in REG_TYPE_CATALOGS {
+		if !columns.iter().any(|c| contains_type(c.type_(), &|ct| ct == t)) {
+			continue;
+		}
+		let rows = client.query(&format!("SELECT oid, oid::{}::text FROM pg_catalog.{}", t.name(), catalog), &[])
+			.map_err(|e| format!("Failed to load the names of {} values: {}", t.name(), format_pg_error(&e)))?;
+		let names = rows.iter().map(|r| (r.get::<_, u32>(0), r.get::<_, String>(1))).collect();
+		reg_names.insert(t.oid(), Arc::new(names));
+	}
+	settings.reg_names = reg_names;
+	Ok(settings)
+}
 
-	let output_file_f = std::fs::File::create(output_file).unwrap();
-	let pq_writer = SerializedFileWriter::new(output_file_f, schema.clone(), output_props)
-		.map_err(|e| format!("Failed to create parquet writer: {}", e))?;
-	let mut row_writer = ParquetRowWriter::new(pq_writer, schema.clone(), row_appender, quiet, settings)
-		.map_err(|e| format!("Failed to create row writer: {}", e))?;
+fn read_oid(v: &PgAny) -> u32 {
+	u32::from_be_bytes(v.value[..4].try_into().unwrap())
+}
 
-	let rows: RowIter = client.query_raw::<Statement, &i32, &[i32]>(&statement, &[]).unwrap();
-	for row in rows.iterator() {
-		let row = row.map_err(|err| err.to_string())?;
-		let row = Arc::new(row);
+/// Paths of the Parquet columns exported from citext values, which are compared case-insensitively in PostgreSQL.
+/// Fields of composite types are separated by a dot, arrays are named by the column
+fn case_insensitive_columns(columns: &[Column], settings: &SchemaSettings) -> Vec<String> {
+	fn collect(t: &PgType, path: String, flatten: bool, settings: &SchemaSettings, result: &mut Vec<String>) {
+		match t.kind() {
+			Kind::Array(element) | Kind::Domain(element) => collect(element, path, false, settings, result),
+			Kind::Composite(fields) => for f in fields {
+				let field_path = if flatten { format!("{}_{}", path, settings.fold_case(f.name())) } else { format!("{}.{}", path, f.name()) };
+				collect(f.type_(), field_path, flatten, settings, result);
+			},
+			_ if settings.type_catalog.is_extension_type(t, "citext", "citext") => result.push(path),
+			_ => {},
+		}
+	}
+	let mut result = vec![];
+	for c in columns {
+		if !settings.column_masks.iter().any(|(name, _)| name == c.name()) {
+			collect(c.type_(), settings.output_column_name(c.name()), settings.flatten_composites, settings, &mut result);
+		}
+	}
+	result
+}
 
-		row_writer.write_row(row)?;
+/// Labels of the enums exported as integers (`--enum-handling=int`), as a map from the Parquet column path to a map from the integer value to the label.
+/// Paths are formed the same way as in `case_insensitive_columns`
+fn enum_labels(columns: &[Column], settings: &SchemaSettings) -> serde_json::Map<String, serde_json::Value> {
+	fn collect(t: &PgType, path: String, flatten: bool, settings: &SchemaSettings, result: &mut serde_json::Map<String, serde_json::Value>) {
+		match t.kind() {
+			Kind::Array(element) | Kind::Domain(element) => collect(element, path, false, settings, result),
+			Kind::Composite(fields) => for f in fields {
+				let field_path = if flatten { format!("{}_{}", path, settings.fold_case(f.name())) } else { format!("{}.{}", path, f.name()) };
+				collect(f.type_(), field_path, flatten, settings, result);
+			},
+			Kind::Enum(labels) => {
+				let labels = labels.iter().enumerate().map(|(i, label)| ((i + 1).to_string(), serde_json::Value::from(label.as_str()))).collect();
+				result.insert(path, serde_json::Value::Object(labels));
+			},
+			_ => {},
+		}
+	}
+	let mut result = serde_json::Map::new();
+	for c in columns {
+		if !settings.column_masks.iter().any(|(name, _)| name == c.name()) {
+			collect(c.type_(), settings.output_column_name(c.name()), settings.flatten_composites, settings, &mut result);
+		}
 	}
+	result
+}
 
-	Ok(row_writer.close()?)
+/// Key of the file metadata entry with the enum labels, see `enum_labels`
+pub const ENUMS_METADATA_KEY: &str = "pg2parquet.enums";
+
+/// Copies the enum labels from the metadata of the written Parquet file into the `file.enums.json` sidecar file.
+/// Returns None if the file does not contain any enums exported as integers
+pub fn write_enums_sidecar(output_file: &PathBuf) -> Result<Option<PathBuf>, String> {
+	let file = std::fs::File::open(output_file).map_err(|e| format!("Failed to open {:?}: {}", output_file, e))?;
+	let metadata = parquet::file::metadata::ParquetMetaDataReader::new().parse_and_finish(&file).map_err(|e| format!("Failed to read parquet file {:?}: {}", output_file, e))?;
+	let Some(enums) = metadata.file_metadata().key_value_metadata()
+		.and_then(|kv| kv.iter().find(|kv| kv.key == ENUMS_METADATA_KEY))
+		.and_then(|kv| kv.value.clone()) else {
+		return Ok(None);
+	};
+	let mut sidecar = output_file.clone().into_os_string();
+	sidecar.push(".enums.json");
+	let sidecar = PathBuf::from(sidecar);
+	std::fs::write(&sidecar, enums).map_err(|e| format!("Failed to write {:?}: {}", sidecar, e))?;
+	Ok(Some(sidecar))
 }
 
-fn format_schema(schema: &ParquetType, indent: u32) -> String {
+fn format_logical_type(t: &LogicalType) -> String {
 	fn format_time_unit(u: &parquet::format::TimeUnit) -> &str {
 		match u {
 			basic::TimeUnit::MILLIS(_) => "ms",
@@ -249,20 +2011,55 @@ fn format_schema(schema: &ParquetType, indent: u32) -> String {
 			basic::TimeUnit::NANOS(_) => "ns",
 		}
 	}
-	fn format_logical_type(t: &LogicalType) -> String {
-		match t {
-			LogicalType::Decimal { scale, precision } => format!("Decimal({}, {})", precision, scale),
-			LogicalType::Time { is_adjusted_to_u_t_c, unit } =>
-				format!("Time({}, utc={:?})", format_time_unit(unit), is_adjusted_to_u_t_c),
-			LogicalType::Timestamp { is_adjusted_to_u_t_c, unit } =>
-				format!("Timestamp({}, utc={:?})", format_time_unit(unit), is_adjusted_to_u_t_c),
-			LogicalType::Integer { bit_width, is_signed } => {
-				let sign = if *is_signed { "" } else { "U" };
-				format!("{}Int{}", sign, bit_width)
+	match t {
+		LogicalType::Decimal { scale, precision } => format!("Decimal({}, {})", precision, scale),
+		LogicalType::Time { is_adjusted_to_u_t_c, unit } =>
+			format!("Time({}, utc={:?})", format_time_unit(unit), is_adjusted_to_u_t_c),
+		LogicalType::Timestamp { is_adjusted_to_u_t_c, unit } =>
+			format!("Timestamp({}, utc={:?})", format_time_unit(unit), is_adjusted_to_u_t_c),
+		LogicalType::Integer { bit_width, is_signed } => {
+			let sign = if *is_signed { "" } else { "U" };
+			format!("{}Int{}", sign, bit_width)
+		}
+		_ => format!("{:?}", t)
+	}
+}
+
+pub fn schema_to_json(schema: &ParquetType) -> serde_json::Value {
+	let basic_info = schema.get_basic_info();
+	let mut result = serde_json::Map::new();
+	result.insert("name".to_string(), basic_info.name().into());
+	if basic_info.has_repetition() {
+		result.insert("repetition".to_string(), basic_info.repetition().to_string().to_lowercase().into());
+	}
+	if let Some(lt) = basic_info.logical_type() {
+		result.insert("logical_type".to_string(), format_logical_type(&lt).into());
+	}
+	if basic_info.converted_type() != ConvertedType::NONE {
+		result.insert("converted_type".to_string(), basic_info.converted_type().to_string().into());
+	}
+	if basic_info.has_id() {
+		result.insert("field_id".to_string(), basic_info.id().into());
+	}
+	match schema {
+		ParquetType::PrimitiveType { physical_type, type_length, scale, precision, .. } => {
+			result.insert("physical_type".to_string(), physical_type.to_string().into());
+			if *type_length >= 0 {
+				result.insert("type_length".to_string(), (*type_length).into());
+			}
+			if *precision > 0 {
+				result.insert("precision".to_string(), (*precision).into());
+				result.insert("scale".to_string(), (*scale).into());
 			}
-			_ => format!("{:?}", t)
+		},
+		ParquetType::GroupType { fields, .. } => {
+			result.insert("fields".to_string(), fields.iter().map(|f| schema_to_json(f)).collect());
 		}
 	}
+	result.into()
+}
+
+pub fn format_schema(schema: &ParquetType, indent: u32) -> String {
 	let basic_info = schema.get_basic_info();
 	let logical_type =
 		basic_info.logical_type().map(|lt| format_logical_type(&lt))
@@ -322,21 +2119,129 @@ fn count_columns(p: &ParquetType) -> usize {
 	}
 }
 
+/// Finds the leaf column indices of the --sort-by columns. Only top-level primitive columns can be listed in the Parquet sorting_columns metadata
+fn resolve_sorting_columns(schema: &ParquetType, sort_by: &[SortColumn], settings: &SchemaSettings) -> Result<Option<Vec<SortingColumn>>, String> {
+	if sort_by.is_empty() {
+		return Ok(None);
+	}
+	let mut result = vec![];
+	for sc in sort_by {
+		let mut leaf_index = 0;
+		let mut found = None;
+		let name = settings.output_column_name(&sc.name);
+		for f in schema.get_fields() {
+			if f.name() == name {
+				found = Some(f);
+				break;
+			}
+			leaf_index += count_columns(f);
+		}
+		let Some(field) = found else {
+			return Err(format!("Column {} specified in --sort-by does not exist in the result", sc.name));
+		};
+		if !field.is_primitive() {
+			warn(Some(sc.name.clone()), format!("Column {} is not a primitive Parquet column, the sort order will not be recorded in the sorting_columns metadata", sc.name));
+			break;
+		}
+		result.push(SortingColumn::new(leaf_index as i32, sc.descending, sc.nulls_first));
+	}
+	Ok(if result.is_empty() { None } else { Some(result) })
+}
+
 
-fn map_schema_root<'a>(row: &[Column], s: &SchemaSettings) -> Result<ResolvedColumn<Arc<Row>>, String> {
+fn map_schema_root(row: &[Column], s: &SchemaSettings) -> Result<(RowAppender, ParquetType), String> {
 	let mut fields: Vec<ResolvedColumn<Arc<Row>>> = vec![];
+	// names of the columns created by --flatten-composites, with the composite column they come from
+	let mut flattened_names: Vec<(String, &str)> = vec![];
 	for (col_i, c) in row.iter().enumerate() {
 
 		let t = c.type_();
 
-		let schema = map_schema_column(t, &ColumnInfo::root(col_i, c.name().to_owned()), s)?;
-		fields.push(schema)
+		if let Some((key, value)) = &s.as_map {
+			// the MAP column replaces the key column, the value column is read by its appender
+			if c.name() == value {
+				continue;
+			}
+			if c.name() == key {
+				let value_i = row.iter().position(|c| c.name() == value)
+					.ok_or_else(|| format!("Column {} specified in --as-map is not in the result of the query", value))?;
+				let column = ColumnInfo::root(col_i, s.output_column_name(value));
+				fields.push(map_key_value_column(t, row[value_i].type_(), &column, value_i, s)?);
+				continue;
+			}
+		}
+
+		let column = ColumnInfo::root(col_i, s.output_column_name(c.name())).with_type_modifier(c.type_modifier());
+		let mask = s.column_masks.iter().find(|(name, _)| name == c.name());
+		let field = match mask {
+			Some((_, mask)) => map_masked_column(t, &column, mask, s)?,
+			None if s.flatten_composites && matches!(t.kind(), Kind::Composite(_)) => {
+				let flattened = map_flattened_composite(t, &column, s)?;
+				flattened_names.extend(flattened.iter().map(|(_, f)| (f.name().to_string(), c.name())));
+				fields.extend(flattened);
+				continue;
+			},
+			None => map_schema_column(t, &column, s)?,
+		};
+		fields.push(match (s.field_ids, c.table_oid(), c.column_id()) {
+			(SchemaSettingsFieldIds::Attnum, Some(_), Some(attnum)) => (field.0, with_field_id(&field.1, attnum as i32)),
+			_ => field,
+		});
+	}
+	if let Some((name, _)) = s.column_masks.iter().find(|(name, _)| !row.iter().any(|c| c.name() == name)) {
+		return Err(format!("Column {} specified in --mask is not in the result of the query", name));
+	}
+	if let Some((name, _)) = s.column_renames.iter().find(|(name, _)| !row.iter().any(|c| c.name() == name)) {
+		return Err(format!("Column {} specified in --rename-column is not in the result of the query", name));
+	}
+	for (i, a) in row.iter().enumerate() {
+		if let Some(b) = row[..i].iter().find(|b| b.name() != a.name() && s.output_column_name(b.name()) == s.output_column_name(a.name())) {
+			return Err(format!("Columns {} and {} would both be named {} in the Parquet file", b.name(), a.name(), s.output_column_name(a.name())));
+		}
+	}
+	for (name, composite) in &flattened_names {
+		let same_name = row.iter().filter(|c| s.output_column_name(c.name()) == *name).count() + flattened_names.iter().filter(|(n, _)| n == name).count();
+		if same_name > 1 {
+			return Err(format!("Column {} created by flattening the composite column {} conflicts with another column of the same name, use --rename-column to rename it", name, composite));
+		}
+	}
+
+
+	for meta_column in &s.meta_columns {
+		let name = meta_column.column_name();
+		if fields.iter().any(|(_, t)| t.name() == name) {
+			return Err(format!("The query result already contains a column named {}, which conflicts with --add-meta-columns", name));
+		}
+		fields.push(map_meta_column(*meta_column, s));
+	}
+
+	for (name, id) in &s.field_id_mapping {
+		let Some(field) = fields.iter_mut().find(|(_, t)| t.name() == name) else {
+			return Err(format!("Column {} specified in --field-id is not in the output", name));
+		};
+		field.1 = with_field_id(&field.1, *id);
+	}
+	for (i, (_, a)) in fields.iter().enumerate() {
+		if let Some((_, b)) = fields[..i].iter().find(|(_, b)| a.get_basic_info().has_id() && b.get_basic_info().has_id() && a.get_basic_info().id() == b.get_basic_info().id()) {
+			return Err(format!("Columns {} and {} have the same field_id {}, use --field-id to assign unique ids", b.name(), a.name(), a.get_basic_info().id()));
+		}
 	}
 
+	// the appenders read the values by the column index, so the fields can be written in any order
+	match &s.column_order {
+		SchemaSettingsColumnOrder::Schema => {},
+		SchemaSettingsColumnOrder::NameAsc => fields.sort_by(|(_, a), (_, b)| a.name().cmp(b.name())),
+		SchemaSettingsColumnOrder::Custom(order) => {
+			if let Some(name) = order.iter().find(|name| !fields.iter().any(|(_, t)| t.name() == name.as_str())) {
+				return Err(format!("Column {} specified in --column-order is not in the output", name));
+			}
+			fields.sort_by_key(|(_, t)| order.iter().position(|name| name == t.name()).unwrap_or(order.len()));
+		},
+	}
 
 	let (column_appenders, parquet_types): (Vec<_>, Vec<_>) = fields.into_iter().unzip();
 
-	let merged_appender: DynColumnAppender<Arc<Row>> = Box::new(DynamicMergedAppender::new(column_appenders, 0, 0));
+	let merged_appender = DynamicMergedAppender::new(column_appenders, 0, 0);
 	let struct_type = ParquetType::group_type_builder("root")
 		.with_fields(parquet_types.into_iter().map(Arc::new).collect())
 		.build()
@@ -345,11 +2250,191 @@ fn map_schema_root<'a>(row: &[Column], s: &SchemaSettings) -> Result<ResolvedCol
 	Ok((merged_appender, struct_type))
 }
 
+/// Key array and value array of a `--as-map` column, the value array is NULL when the value column is NULL
+type KeyValueArrays = (PgMultidimArray<Option<PgAny>>, Option<PgMultidimArray<Option<PgAny>>>);
+
+/// MAP column of the entries of the key array (column `c`) and the value array (column `value_i`), see `--as-map`
+fn map_key_value_column<TRow: PgAbstractRow + Clone + 'static>(key_type: &PgType, value_type: &PgType, c: &ColumnInfo, value_i: usize, s: &SchemaSettings) -> Result<ResolvedColumn<TRow>, String> {
+	let (Kind::Array(key_element), Kind::Array(value_element)) = (key_type.kind(), value_type.kind()) else {
+		return Err(format!("The key and value columns of --as-map must be arrays, got {} and {}", key_type, value_type));
+	};
+	let entries_column = c.nest("key_value", 0).as_array();
+	let mut key_column = entries_column.nest("key", 0);
+	// map keys are REQUIRED, the key is mapped as OPTIONAL one level up like the REQUIRED array elements
+	key_column.definition_level -= 1;
+	let value_column = entries_column.nest("value", 1);
+	let (key_appender, key_schema) = map_schema_column::<PgMapEntry>(key_element, &key_column, s)?;
+	let (value_appender, value_schema) = map_schema_column::<PgMapEntry>(value_element, &value_column, s)?;
+	let schema = make_map_schema(c.col_name(), with_name_and_repetition(&key_schema, "key", Repetition::REQUIRED), value_schema);
+
+	let value_element = strip_domains(value_element).clone();
+	let entries_appender = DynamicMergedAppender::new(vec![key_appender, value_appender], c.definition_level + 2, c.repetition_level + 1);
+	let appender = ArrayColumnAppender::new(entries_appender, true, false, c.definition_level + 1, c.repetition_level)
+		.preprocess(move |arrays: Cow<KeyValueArrays>| {
+			let (keys, values) = arrays.as_ref();
+			let mut values = values.as_ref().map(|v| v.data.iter());
+			Cow::<Vec<Option<PgMapEntry>>>::Owned(keys.data.iter().map(|key| {
+				let value = values.as_mut().and_then(|v| v.next()).cloned().flatten();
+				key.clone().map(|key| PgMapEntry { key, value_type: value_element.clone(), value: value.map(|v| v.value) })
+			}).collect())
+		});
+	Ok((Box::new(PgRowPairColumnAppender::new(c.col_i, value_i, appender)), schema))
+}
+
+/// MAP group with the REQUIRED key and the value
+fn make_map_schema(name: &str, key_schema: ParquetType, value_schema: ParquetType) -> ParquetType {
+	ParquetType::group_type_builder(name)
+		.with_logical_type(Some(LogicalType::Map))
+		.with_repetition(Repetition::OPTIONAL)
+		.with_fields(vec![
+			Arc::new(ParquetType::group_type_builder("key_value")
+				.with_repetition(Repetition::REPEATED)
+				.with_fields(vec![ Arc::new(key_schema), Arc::new(value_schema) ])
+				.build().unwrap())
+		])
+		.build().unwrap()
+}
+
+/// Copy of the Parquet type with a different name and repetition
+fn with_name_and_repetition(t: &ParquetType, name: &str, repetition: Repetition) -> ParquetType {
+	let info = t.get_basic_info();
+	let id = if info.has_id() { Some(info.id()) } else { None };
+	match t {
+		ParquetType::PrimitiveType { physical_type, type_length, scale, precision, .. } =>
+			ParquetType::primitive_type_builder(name, *physical_type)
+				.with_repetition(repetition)
+				.with_logical_type(info.logical_type())
+				.with_converted_type(info.converted_type())
+				.with_length(*type_length)
+				.with_precision(*precision)
+				.with_scale(*scale)
+				.with_id(id)
+				.build().unwrap(),
+		ParquetType::GroupType { fields, .. } =>
+			ParquetType::group_type_builder(name)
+				.with_repetition(repetition)
+				.with_logical_type(info.logical_type())
+				.with_converted_type(info.converted_type())
+				.with_fields(fields.clone())
+				.with_id(id)
+				.build().unwrap(),
+	}
+}
+
+/// Copy of the Parquet type with the field_id set
+fn with_field_id(t: &ParquetType, id: i32) -> ParquetType {
+	let info = t.get_basic_info();
+	match t {
+		ParquetType::PrimitiveType { physical_type, type_length, scale, precision, .. } =>
+			ParquetType::primitive_type_builder(info.name(), *physical_type)
+				.with_repetition(info.repetition())
+				.with_logical_type(info.logical_type())
+				.with_converted_type(info.converted_type())
+				.with_length(*type_length)
+				.with_precision(*precision)
+				.with_scale(*scale)
+				.with_id(Some(id))
+				.build().unwrap(),
+		ParquetType::GroupType { fields, .. } =>
+			ParquetType::group_type_builder(info.name())
+				.with_repetition(info.repetition())
+				.with_logical_type(info.logical_type())
+				.with_converted_type(info.converted_type())
+				.with_fields(fields.clone())
+				.with_id(Some(id))
+				.build().unwrap(),
+	}
+}
+
+/// Maps each field of the composite column `c` to a separate top-level column named `column_field`.
+/// Nested composite fields are flattened recursively, a NULL composite value is written as NULL in all its columns
+fn map_flattened_composite<TRow: PgAbstractRow + Clone + 'static>(
+	t: &PgType,
+	c: &ColumnInfo,
+	settings: &SchemaSettings,
+) -> Result<Vec<ResolvedColumn<TRow>>, String> {
+	let Kind::Composite(fields) = t.kind() else { unreachable!() };
+	let mut result = vec![];
+	for (i, f) in fields.iter().enumerate() {
+		let field_column = ColumnInfo::root(i, format!("{}_{}", c.col_name(), settings.fold_case(f.name())));
+		let field_columns = match f.type_().kind() {
+			Kind::Composite(_) => map_flattened_composite::<PgRawRecord>(f.type_(), &field_column, settings)?,
+			_ => vec![map_schema_column::<PgRawRecord>(f.type_(), &field_column, settings)?],
+		};
+		for (appender, schema) in field_columns {
+			let appender = wrap_pg_row_reader::<TRow, PgRawRecord>(c, appender);
+			result.push((Box::new(appender) as DynColumnAppender<TRow>, schema));
+		}
+	}
+	Ok(result)
+}
+
+fn map_meta_column<TRow: PgAbstractRow + Clone + 'static>(column: SchemaSettingsMetaColumn, s: &SchemaSettings) -> ResolvedColumn<TRow> {
+	let name = column.column_name();
+	match column {
+		SchemaSettingsMetaColumn::RowNumber => {
+			let appender = GenericColumnAppender::<i64, Int64Type, _>::new(1, 0, |v| v);
+			let t = ParquetType::primitive_type_builder(name, basic::Type::INT64).build().unwrap();
+			(Box::new(RowIndexAppender::new(appender, |index| index as i64 + 1)), t)
+		},
+		SchemaSettingsMetaColumn::ExportedAt => {
+			let exported_at = s.exported_at.timestamp_micros();
+			let appender = GenericColumnAppender::<i64, Int64Type, _>::new(1, 0, |v| v);
+			let t = ParquetType::primitive_type_builder(name, basic::Type::INT64)
+				.with_logical_type(Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: parquet::format::TimeUnit::MICROS(parquet::format::MicroSeconds {}) }))
+				.build().unwrap();
+			(Box::new(RowIndexAppender::new(appender, move |_| exported_at)), t)
+		},
+		SchemaSettingsMetaColumn::SourceTable => {
+			let table = s.source_table.as_deref().map(ByteArray::from);
+			let appender = UnwrapOptionAppender::new(GenericColumnAppender::<ByteArray, ByteArrayType, _>::new(1, 0, |v| v));
+			let t = ParquetType::primitive_type_builder(name, basic::Type::BYTE_ARRAY)
+				.with_logical_type(Some(LogicalType::String))
+				.with_converted_type(ConvertedType::UTF8)
+				.build().unwrap();
+			(Box::new(RowIndexAppender::new(appender, move |_| table.clone())), t)
+		},
+	}
+}
+
+fn map_masked_column<TRow: PgAbstractRow + Clone + 'static>(
+	t: &PgType,
+	c: &ColumnInfo,
+	mask: &ColumnMask,
+	settings: &SchemaSettings,
+) -> Result<ResolvedColumn<TRow>, String> {
+	if matches!(t.kind(), Kind::Array(_)) && *mask != ColumnMask::Null {
+		return Err(format!("Column {} is an array, only the null mask is supported for arrays", c.full_name()));
+	}
+	let name = c.col_name();
+	Ok(match mask {
+		ColumnMask::Null => {
+			let (appender, schema) = map_schema_column::<TRow>(t, c, settings)?;
+			(Box::new(AlwaysNullAppender::new(appender)), schema)
+		},
+		ColumnMask::Sha256 =>
+			resolve_primitive_conv::<PgAny, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), |v| ByteArray::from(masking::sha256_hex(&v.value).as_str())),
+		ColumnMask::Constant(value) => {
+			let value = ByteArray::from(value.as_str());
+			resolve_primitive_conv::<PgAny, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), move |_| value.clone())
+		},
+		ColumnMask::RedactEmail => {
+			if !<String as FromSql>::accepts(t) {
+				return Err(format!("Column {} has type {}, redact-email mask requires a text column", c.full_name(), t));
+			}
+			resolve_primitive_conv::<String, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), |v| ByteArray::from(masking::redact_email(&v).as_str()))
+		},
+	})
+}
+
 fn map_schema_column<TRow: PgAbstractRow + Clone + 'static>(
 	t: &PgType,
 	c: &ColumnInfo,
 	settings: &SchemaSettings,
 ) -> Result<ResolvedColumn<TRow>, String> {
+	if let Some(mapper) = settings.custom_type_mappers.iter().find(|m| m.type_name() == t.name()) {
+		return resolve_custom(mapper, c);
+	}
 	match t.kind() {
 		Kind::Simple =>
 			map_simple_type(t, c, settings),
@@ -364,34 +2449,46 @@ fn map_schema_column<TRow: PgAbstractRow + Clone + 'static>(
 						*mapping.get(&e.name).unwrap_or_else(|| panic!("Could not map enum value {}. Was new enum case added while pg2parquet is running?", &e.name))
 					))
 				},
+				// enum labels are sent as text
 				SchemaSettingsEnumHandling::Text =>
-					Ok(resolve_primitive::<PgEnum, ByteArrayType, _>(c.col_name(), c, Some(LogicalType::Enum), None)),
+					Ok(resolve_plain_text(settings, c.col_name(), c, LogicalType::Enum, false)),
 				SchemaSettingsEnumHandling::PlainText =>
-					Ok(resolve_primitive::<PgEnum, ByteArrayType, _>(c.col_name(), c, Some(LogicalType::String), None)),
+					Ok(resolve_plain_text(settings, c.col_name(), c, LogicalType::String, false)),
 			}
 		Kind::Array(ref element_type) => {
 			let list_column = c.nest("list", 0).as_array();
-			let element_column = list_column.nest("element", 0);
+			// type modifier of an array column applies to its elements
+			let mut element_column = list_column.nest("element", 0).with_type_modifier(c.type_modifier);
+			let required_elements = settings.list_encoding == SchemaSettingsListEncoding::TwoLevel || match settings.array_element_nullability {
+				SchemaSettingsArrayElementNullability::Auto => settings.type_catalog.is_not_null_domain(element_type),
+				SchemaSettingsArrayElementNullability::Optional => false,
+				SchemaSettingsArrayElementNullability::Required => true,
+			};
+			if required_elements {
+				// the element is mapped as OPTIONAL one level up, so its own definition level disappears when it's marked REQUIRED
+				element_column.definition_level -= 1;
+			}
 
 			let (element_appender, element_schema) = map_schema_column(element_type, &element_column, settings)?;
+			let element_schema = if required_elements { with_name_and_repetition(&element_schema, "element", Repetition::REQUIRED) } else { element_schema };
 			
 			debug_assert_eq!(element_schema.name(), "element");
 
 			let plain_schema = settings.array_handling == SchemaSettingsArrayHandling::Plain;
 
 			let schema = if plain_schema {
-				make_list_schema(c.col_name(), Repetition::OPTIONAL, element_schema)
+				make_list_schema(c.col_name(), Repetition::OPTIONAL, element_schema, settings.list_encoding)
 			} else {
-				make_list_schema("data", Repetition::REQUIRED, element_schema)
+				make_list_schema("data", Repetition::REQUIRED, element_schema, settings.list_encoding)
 			};
 
 			assert_eq!(element_appender.max_dl(), element_column.definition_level + 1);
 			assert_eq!(element_appender.max_rl(), element_column.repetition_level);
-			let array_appender = create_array_appender(element_appender, &c, plain_schema);
-			let dim_appender = create_array_dim_appender::<PgAny, TRow>(&c);
-			let lb_appender = create_array_lower_bound_appender::<PgAny, TRow>(&c);
-			let dim_schema = make_list_schema("dims", Repetition::REQUIRED, ParquetType::primitive_type_builder("element", basic::Type::INT32).with_repetition(Repetition::REQUIRED).with_logical_type(Some(LogicalType::Integer { bit_width: 32, is_signed: false })).build().unwrap());
-			let lb_schema = make_list_schema("lower_bound", Repetition::REQUIRED, ParquetType::primitive_type_builder("element", basic::Type::INT32).with_repetition(Repetition::REQUIRED).with_logical_type(Some(LogicalType::Integer { bit_width: 32, is_signed: true })).build().unwrap());
+			let array_appender = create_array_appender(element_appender, c, plain_schema, !required_elements);
+			let dim_appender = create_array_dim_appender::<PgAny, TRow>(c);
+			let lb_appender = create_array_lower_bound_appender::<PgAny, TRow>(c);
+			let dim_schema = make_list_schema("dims", Repetition::REQUIRED, ParquetType::primitive_type_builder("element", basic::Type::INT32).with_repetition(Repetition::REQUIRED).with_logical_type(Some(LogicalType::Integer { bit_width: 32, is_signed: false })).build().unwrap(), settings.list_encoding);
+			let lb_schema = make_list_schema("lower_bound", Repetition::REQUIRED, ParquetType::primitive_type_builder("element", basic::Type::INT32).with_repetition(Repetition::REQUIRED).with_logical_type(Some(LogicalType::Integer { bit_width: 32, is_signed: true })).build().unwrap(), settings.list_encoding);
 			match settings.array_handling {
 				SchemaSettingsArrayHandling::Plain => Ok((Box::new(array_appender), schema)),
 				SchemaSettingsArrayHandling::Dimensions => Ok((
@@ -417,9 +2514,9 @@ fn map_schema_column<TRow: PgAbstractRow + Clone + 'static>(
 		Kind::Domain(ref element_type) => {
 			map_schema_column(element_type, c, settings)
 		},
-		&Kind::Range(ref element_type) => {
-			let col_lower = map_schema_column::<UnclonableHack<PgRawRange>>(element_type, &c.nest("lower", 0), settings)?;
-			let col_upper = map_schema_column::<UnclonableHack<PgRawRange>>(element_type, &c.nest("upper", 1), settings)?;
+		Kind::Range(element_type) => {
+			let col_lower = map_schema_column::<PgRawRange>(element_type, &c.nest("lower", 0), settings)?;
+			let col_upper = map_schema_column::<PgRawRange>(element_type, &c.nest("upper", 1), settings)?;
 
 			let schema = ParquetType::group_type_builder(c.col_name())
 				.with_fields(vec![
@@ -433,33 +2530,29 @@ fn map_schema_column<TRow: PgAbstractRow + Clone + 'static>(
 				.build()
 				.unwrap();
 
-			let appender = new_static_merged_appender::<UnclonableHack<PgRawRange>>(c.definition_level + 1, c.repetition_level)
+			let appender = new_static_merged_appender::<PgRawRange>(c.definition_level + 1, c.repetition_level)
 				.add_appender(col_lower.0)
 				.add_appender(col_upper.0)
 				.add_appender_map(
 					new_autoconv_generic_appender::<bool, BoolType>(c.definition_level + 2, c.repetition_level),
-					|r| Cow::Owned(r.0.lower_inclusive)
+					|r| Cow::Owned(r.lower_inclusive)
 				)
 				.add_appender_map(
 					new_autoconv_generic_appender::<bool, BoolType>(c.definition_level + 2, c.repetition_level),
-					|r| Cow::Owned(r.0.upper_inclusive)
+					|r| Cow::Owned(r.upper_inclusive)
 				)
 				.add_appender_map(
 					new_autoconv_generic_appender::<bool, BoolType>(c.definition_level + 2, c.repetition_level),
-					|r| Cow::Owned(r.0.is_empty)
-				)
-				.preprocess(|x: Cow<PgRawRange>| match x {
-					Cow::Owned(x) => Cow::Owned(UnclonableHack(x)),
-					Cow::Borrowed(_) => panic!()
-				});
+					|r| Cow::Owned(r.is_empty)
+				);
 
 			let appender_dyn = wrap_pg_row_reader(c, appender);
 
 			Ok((Box::new(appender_dyn), schema))
 		},
-		&Kind::Composite(ref fields) => {
+		Kind::Composite(fields) => {
 			let (mut column_appenders, mut parquet_types) = (vec![], vec![]);
-			for (i, f) in fields.into_iter().enumerate() {
+			for (i, f) in fields.iter().enumerate() {
 				let (c, t) = map_schema_column(f.type_(), &c.nest(f.name(), i), settings)?;
 				column_appenders.push(c);
 				parquet_types.push(t);
@@ -479,7 +2572,17 @@ fn map_schema_column<TRow: PgAbstractRow + Clone + 'static>(
 	}
 }
 
-fn make_list_schema(name: &str, repetition: Repetition, element_schema: ParquetType) -> ParquetType {
+/// LIST group containing the elements. The two-level encoding requires a REQUIRED element, which becomes the repeated field
+fn make_list_schema(name: &str, repetition: Repetition, element_schema: ParquetType, encoding: SchemaSettingsListEncoding) -> ParquetType {
+	if encoding == SchemaSettingsListEncoding::TwoLevel {
+		assert_eq!(element_schema.get_basic_info().repetition(), Repetition::REQUIRED, "Two-level lists cannot have OPTIONAL elements");
+		return ParquetType::group_type_builder(name)
+			.with_logical_type(Some(LogicalType::List))
+			.with_repetition(repetition)
+			// readers recognize a single-field repeated group named "array" as the element, not as the middle level of a 3-level list
+			.with_fields(vec![ Arc::new(with_name_and_repetition(&element_schema, "array", Repetition::REPEATED)) ])
+			.build().unwrap();
+	}
 	ParquetType::group_type_builder(name)
 		.with_logical_type(Some(LogicalType::List))
 		.with_repetition(repetition)
@@ -507,23 +2610,35 @@ fn map_simple_type<TRow: PgAbstractRow + Clone + 'static>(
 		"int4" => resolve_primitive::<i32, Int32Type, _>(name, c, None, None),
 		"oid" => resolve_primitive::<u32, Int32Type, _>(name, c, Some(LogicalType::Integer { bit_width: 32, is_signed: false }), None),
 		"int8" => resolve_primitive::<i64, Int64Type, _>(name, c, None, None),
-		"float4" => resolve_primitive::<f32, FloatType, _>(name, c, None, None),
-		"float8" => resolve_primitive::<f64, DoubleType, _>(name, c, None, None),
+		"float4" => resolve_float::<f32, FloatType, _>(s, name, c),
+		"float8" => resolve_float::<f64, DoubleType, _>(s, name, c),
 		"numeric" => {
 			resolve_numeric(s, name, c)?
 		},
-		"money" => resolve_primitive::<PgMoney, Int64Type, _>(name, c, Some(LogicalType::Decimal { scale: 2, precision: 18 }), None),
+		"money" =>
+			match s.money_handling {
+				SchemaSettingsMoneyHandling::Decimal =>
+					resolve_primitive::<PgMoney, Int64Type, _>(name, c, Some(LogicalType::Decimal { scale: s.money_scale, precision: 18 }), None),
+				SchemaSettingsMoneyHandling::Int64 =>
+					resolve_primitive::<PgMoney, Int64Type, _>(name, c, None, None),
+				SchemaSettingsMoneyHandling::String => {
+					let scale = s.money_scale;
+					resolve_primitive_conv::<PgMoney, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), move |v| ByteArray::from(v.to_decimal_string(scale).as_str()))
+				},
+			},
 		"char" => resolve_primitive::<i8, Int32Type, _>(name, c, Some(LogicalType::Integer { bit_width: 8, is_signed: false }), None),
-		"bytea" => resolve_primitive::<Vec<u8>, ByteArrayType, _>(name, c, None, None),
-		"name" | "text" | "xml" | "bpchar" | "varchar" | "citext" =>
-			resolve_primitive::<String, ByteArrayType, _>(name, c, Some(LogicalType::String), Some(ConvertedType::UTF8)),
-			// (Box::new(crate::appenders::byte_array::create_pg_raw_appender(c.definition_level + 1, c.repetition_level, c.col_i)),
-			// 	ParquetType::primitive_type_builder(name, basic::Type::BYTE_ARRAY).with_logical_type(Some(LogicalType::String)).with_converted_type(ConvertedType::UTF8).build().unwrap()),
+		"bytea" => resolve_raw_bytes(name, c, None, false),
+		"bpchar" if s.bpchar_handling == SchemaSettingsBpcharHandling::Trim =>
+			resolve_text::<String, _>(s, name, c, |v| ByteArray::from(v.trim_end_matches(' ')))?,
+		"name" | "text" | "xml" | "bpchar" | "varchar" if s.null_string.is_none() && !s.empty_string_as_null =>
+			resolve_plain_text(s, name, c, LogicalType::String, false),
+		"name" | "text" | "xml" | "bpchar" | "varchar" =>
+			resolve_text::<String, _>(s, name, c, MyFrom::my_from)?,
 		"jsonb" | "json" =>
-			resolve_primitive::<PgRawJsonb, ByteArrayType, _>(name, c, Some(match s.json_handling {
+			resolve_plain_text(s, name, c, match s.json_handling {
 				SchemaSettingsJsonHandling::Text => LogicalType::String,
 				SchemaSettingsJsonHandling::TextMarkedAsJson => LogicalType::Json
-			}), None),
+			}, t.name() == "jsonb"),
 		"timestamptz" =>
 			resolve_primitive::<chrono::DateTime<chrono::Utc>, Int64Type, _>(name, c, Some(LogicalType::Timestamp { is_adjusted_to_u_t_c: true, unit: parquet::format::TimeUnit::MICROS(parquet::format::MicroSeconds {  }) }), None),
 		"timestamp" =>
@@ -534,26 +2649,87 @@ fn map_simple_type<TRow: PgAbstractRow + Clone + 'static>(
 			resolve_primitive::<chrono::NaiveTime, Int64Type, _>(name, c, Some(LogicalType::Time { is_adjusted_to_u_t_c: false, unit: parquet::format::TimeUnit::MICROS(parquet::format::MicroSeconds {  }) }), None),
 
 		"uuid" =>
-			resolve_primitive_conv::<uuid::Uuid, FixedLenByteArrayType, _, _>(name, c, Some(16), Some(LogicalType::Uuid), None, |v| MyFrom::my_from(v)),
+			match s.uuid_handling {
+				SchemaSettingsUuidHandling::Binary =>
+					resolve_primitive_conv::<uuid::Uuid, FixedLenByteArrayType, _, _>(name, c, Some(16), Some(LogicalType::Uuid), None, MyFrom::my_from),
+				SchemaSettingsUuidHandling::Text =>
+					resolve_primitive_conv::<uuid::Uuid, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), |v| ByteArray::from(v.hyphenated().to_string().as_str())),
+			},
 
 		"macaddr" =>
 			match s.macaddr_handling {
 				SchemaSettingsMacaddrHandling::Text =>
 					resolve_primitive::<eui48::MacAddress, ByteArrayType, _>(name, c, Some(LogicalType::String), None),
 				SchemaSettingsMacaddrHandling::ByteArray =>
-					resolve_primitive_conv::<eui48::MacAddress, FixedLenByteArrayType, _, _>(name, c, Some(6), None, None, |v| MyFrom::my_from(v)),
+					resolve_primitive_conv::<eui48::MacAddress, FixedLenByteArrayType, _, _>(name, c, Some(6), None, None, MyFrom::my_from),
 				SchemaSettingsMacaddrHandling::Int64 =>
 					resolve_primitive::<eui48::MacAddress, Int64Type, _>(name, c, None, None),
 			},
+		"regclass" | "regtype" | "regproc" | "regprocedure" | "regoper" | "regoperator" | "regconfig" | "regdictionary" | "regnamespace" | "regrole" | "regcollation" =>
+			match s.reg_handling {
+				SchemaSettingsRegHandling::Oid =>
+					resolve_primitive_conv::<PgAny, Int32Type, _, _>(name, c, None, Some(LogicalType::Integer { bit_width: 32, is_signed: false }), None, |v| read_oid(&v) as i32),
+				SchemaSettingsRegHandling::Text => {
+					let names = s.reg_names.get(&t.oid()).cloned().unwrap_or_default();
+					resolve_primitive_conv::<PgAny, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), move |v| {
+						let oid = read_oid(&v);
+						// same as the text output of PostgreSQL, for objects which do not exist (anymore)
+						match names.get(&oid) {
+							Some(name) => ByteArray::from(name.as_str()),
+							None if oid == 0 => ByteArray::from("-"),
+							None => ByteArray::from(oid.to_string().as_str()),
+						}
+					})
+				},
+			},
+		"txid_snapshot" | "pg_snapshot" => {
+			let xid_type = |name| ParquetType::primitive_type_builder(name, basic::Type::INT64).with_repetition(Repetition::REQUIRED).with_logical_type(Some(LogicalType::Integer { bit_width: 64, is_signed: false })).build().unwrap();
+			let t = GroupTypeBuilder::new(c.col_name())
+				.with_repetition(Repetition::OPTIONAL)
+				.with_fields(vec![
+					Arc::new(xid_type("xmin")),
+					Arc::new(xid_type("xmax")),
+					Arc::new(make_list_schema("xip", Repetition::REQUIRED, xid_type("element"), s.list_encoding)),
+				])
+				.build().unwrap();
+			let xip_appender = ArrayColumnAppender::new(new_autoconv_generic_appender::<i64, Int64Type>(c.definition_level + 2, c.repetition_level + 1), false, false, c.definition_level + 1, c.repetition_level);
+			let appender = new_static_merged_appender::<PgSnapshot>(c.definition_level + 1, c.repetition_level)
+				.add_appender_map(new_autoconv_generic_appender::<i64, Int64Type>(c.definition_level + 1, c.repetition_level), |s| Cow::Owned(s.xmin as i64))
+				.add_appender_map(new_autoconv_generic_appender::<i64, Int64Type>(c.definition_level + 1, c.repetition_level), |s| Cow::Owned(s.xmax as i64))
+				.add_appender_map(xip_appender, |s| Cow::<Vec<Option<i64>>>::Owned(s.xip.iter().map(|x| Some(*x as i64)).collect()));
+			(Box::new(wrap_pg_row_reader(c, appender)), t)
+		},
 		"inet" =>
-			resolve_primitive::<IpAddr, ByteArrayType, _>(name, c, Some(LogicalType::String), None),
+			match s.inet_handling {
+				SchemaSettingsInetHandling::Text =>
+					resolve_primitive::<IpAddr, ByteArrayType, _>(name, c, Some(LogicalType::String), None),
+				SchemaSettingsInetHandling::Binary =>
+					resolve_primitive_conv::<PgInet, FixedLenByteArrayType, _, _>(name, c, Some(17), None, None, MyFrom::my_from),
+				SchemaSettingsInetHandling::Struct => {
+					let t = GroupTypeBuilder::new(c.col_name())
+						.with_repetition(Repetition::OPTIONAL)
+						.with_fields(vec![
+							Arc::new(ParquetType::primitive_type_builder("family", basic::Type::INT32).with_logical_type(Some(LogicalType::Integer { bit_width: 8, is_signed: false })).build().unwrap()),
+							Arc::new(ParquetType::primitive_type_builder("prefix", basic::Type::INT32).with_logical_type(Some(LogicalType::Integer { bit_width: 8, is_signed: false })).build().unwrap()),
+							Arc::new(ParquetType::primitive_type_builder("addr", basic::Type::BYTE_ARRAY).build().unwrap()),
+						])
+						.build().unwrap();
+					let appender = new_static_merged_appender::<PgInet>(c.definition_level + 1, c.repetition_level)
+						.add_appender_map(new_autoconv_generic_appender::<i32, Int32Type>(c.definition_level + 2, c.repetition_level), |i| Cow::Owned(i.family as i32))
+						.add_appender_map(new_autoconv_generic_appender::<i32, Int32Type>(c.definition_level + 2, c.repetition_level), |i| Cow::Owned(i.prefix as i32))
+						.add_appender_map(new_autoconv_generic_appender::<Vec<u8>, ByteArrayType>(c.definition_level + 2, c.repetition_level), |i| Cow::Owned(i.addr.clone()));
+					(Box::new(wrap_pg_row_reader(c, appender)), t)
+				},
+			},
+		"bit" if s.bit_handling == SchemaSettingsBitHandling::Binary && c.type_modifier > 0 =>
+			resolve_primitive_conv::<bit_vec::BitVec, FixedLenByteArrayType, _, _>(name, c, Some((c.type_modifier + 7) / 8), None, None, |v| FixedLenByteArray::from(v.to_bytes())),
 		"bit" | "varbit" =>
 			resolve_primitive::<bit_vec::BitVec, ByteArrayType, _>(name, c, Some(LogicalType::String), None),
 
 		"interval" =>
 			match s.interval_handling {
 				SchemaSettingsIntervalHandling::Interval =>
-					resolve_primitive_conv::<PgInterval, FixedLenByteArrayType, _, _>(name, c, Some(12), None, Some(ConvertedType::INTERVAL), |v| MyFrom::my_from(v)),
+					resolve_primitive_conv::<PgInterval, FixedLenByteArrayType, _, _>(name, c, Some(12), None, Some(ConvertedType::INTERVAL), MyFrom::my_from),
 				SchemaSettingsIntervalHandling::Struct => {
 					let t = GroupTypeBuilder::new(c.col_name())
 						.with_repetition(Repetition::OPTIONAL)
@@ -571,14 +2747,124 @@ fn map_simple_type<TRow: PgAbstractRow + Clone + 'static>(
 				},
 			},
 
-		// TODO: Regproc Tid Xid Cid PgNodeTree Point Lseg Path Box Polygon Line Cidr Unknown Circle Macaddr8 Aclitem Bpchar Timetz Refcursor Regprocedure Regoper Regoperator Regclass Regtype TxidSnapshot PgLsn PgNdistinct PgDependencies TsVector Tsquery GtsVector Regconfig Regdictionary Jsonpath Regnamespace Regrole Regcollation PgMcvList PgSnapshot Xid9
+		// TODO: Tid Xid Cid PgNodeTree Point Lseg Path Box Polygon Line Cidr Unknown Circle Macaddr8 Aclitem Bpchar Timetz Refcursor PgLsn PgNdistinct PgDependencies TsVector Tsquery GtsVector Jsonpath PgMcvList Xid9
 
 
+		_ if is_vector_type(t, s) =>
+			resolve_vector(s, name, c),
+		_ if s.type_catalog.is_extension_type(t, "vector", "sparsevec") =>
+			resolve_sparsevec(s, name, c),
+		_ if s.type_catalog.is_extension_type(t, "cube", "cube") =>
+			resolve_cube(s, name, c),
+		// extension types such as citext, their binary representation is the same as of text
+		_ if s.type_catalog.is_string_category(t) && s.null_string.is_none() && !s.empty_string_as_null =>
+			resolve_plain_text(s, name, c, LogicalType::String, false),
+		_ if s.type_catalog.is_string_category(t) =>
+			resolve_text::<PgText, _>(s, name, c, MyFrom::my_from)?,
 		n => 
-			return Err(format!("Could not map column {}, unsupported primitive type: {}", c.full_name(), n)),
+			return Err(format!("Could not map column {}, unsupported primitive type: {}. Use --text-types {} to export it as text", c.full_name(), n, n)),
 	})
 }
 
+fn resolve_vector<TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo) -> ResolvedColumn<TRow> {
+	let (dl, rl) = (c.definition_level + 2, c.repetition_level + 1);
+	match s.vector_element_type {
+		SchemaSettingsVectorElementType::F32 =>
+			resolve_vector_elements(name, c, basic::Type::FLOAT, None, None, s.list_encoding, GenericColumnAppender::<f32, FloatType, _>::new(dl, rl, |v| v)),
+		SchemaSettingsVectorElementType::F16 =>
+			resolve_vector_elements(name, c, basic::Type::FIXED_LEN_BYTE_ARRAY, Some(2), Some(LogicalType::Float16), s.list_encoding,
+				byte_array::FixedLenByteArrayColumnAppender::new(dl, rl, 2, |v: &f32, buffer: &mut Vec<u8>| buffer.extend_from_slice(&half::f16::from_f32(*v).to_le_bytes()))),
+		SchemaSettingsVectorElementType::Bf16 =>
+			resolve_vector_elements(name, c, basic::Type::FIXED_LEN_BYTE_ARRAY, Some(2), None, s.list_encoding,
+				byte_array::FixedLenByteArrayColumnAppender::new(dl, rl, 2, |v: &f32, buffer: &mut Vec<u8>| buffer.extend_from_slice(&half::bf16::from_f32(*v).to_le_bytes()))),
+		SchemaSettingsVectorElementType::I8 => {
+			let scale = s.vector_i8_scale;
+			resolve_vector_elements(name, c, basic::Type::INT32, None, Some(LogicalType::Integer { bit_width: 8, is_signed: true }), s.list_encoding,
+				GenericColumnAppender::<f32, Int32Type, _>::new(dl, rl, move |v| (v * scale).round().clamp(-128.0, 127.0) as i32))
+		},
+	}
+}
+
+fn resolve_sparsevec<TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo) -> ResolvedColumn<TRow> {
+	match s.sparsevec_handling {
+		SchemaSettingsSparsevecHandling::Dense => resolve_vector(s, name, c),
+		SchemaSettingsSparsevecHandling::Map => {
+			let t = make_map_schema(name,
+				ParquetType::primitive_type_builder("key", basic::Type::INT32).with_repetition(Repetition::REQUIRED).build().unwrap(),
+				ParquetType::primitive_type_builder("value", basic::Type::FLOAT).with_repetition(Repetition::REQUIRED).build().unwrap());
+			let entry_appender = new_static_merged_appender::<(i32, f32)>(c.definition_level + 2, c.repetition_level + 1)
+				.add_appender_map(new_autoconv_generic_appender::<i32, Int32Type>(c.definition_level + 2, c.repetition_level + 1), |e| Cow::Owned(e.0))
+				.add_appender_map(new_autoconv_generic_appender::<f32, FloatType>(c.definition_level + 2, c.repetition_level + 1), |e| Cow::Owned(e.1));
+			let appender = ArrayColumnAppender::new(entry_appender, true, false, c.definition_level + 1, c.repetition_level)
+				.preprocess(|v: Cow<PgSparseVec>| Cow::<Vec<Option<(i32, f32)>>>::Owned(v.indices.iter().zip(&v.values).map(|(i, x)| Some((*i, *x))).collect()));
+			(Box::new(wrap_pg_row_reader(c, appender)), t)
+		},
+		SchemaSettingsSparsevecHandling::Arrays => {
+			let element = |t| ParquetType::primitive_type_builder("element", t).with_repetition(Repetition::REQUIRED).build().unwrap();
+			let t = GroupTypeBuilder::new(name)
+				.with_repetition(Repetition::OPTIONAL)
+				.with_fields(vec![
+					Arc::new(make_list_schema("indices", Repetition::REQUIRED, element(basic::Type::INT32), s.list_encoding)),
+					Arc::new(make_list_schema("values", Repetition::REQUIRED, element(basic::Type::FLOAT), s.list_encoding)),
+					Arc::new(ParquetType::primitive_type_builder("dim", basic::Type::INT32).with_repetition(Repetition::REQUIRED).build().unwrap()),
+				])
+				.build().unwrap();
+			let indices_appender = ArrayColumnAppender::new(new_autoconv_generic_appender::<i32, Int32Type>(c.definition_level + 2, c.repetition_level + 1), false, false, c.definition_level + 1, c.repetition_level);
+			let values_appender = ArrayColumnAppender::new(new_autoconv_generic_appender::<f32, FloatType>(c.definition_level + 2, c.repetition_level + 1), false, false, c.definition_level + 1, c.repetition_level);
+			let appender = new_static_merged_appender::<PgSparseVec>(c.definition_level + 1, c.repetition_level)
+				.add_appender_map(indices_appender, |v| Cow::<Vec<Option<i32>>>::Owned(v.indices.iter().map(|i| Some(*i)).collect()))
+				.add_appender_map(values_appender, |v| Cow::<Vec<Option<f32>>>::Owned(v.values.iter().map(|x| Some(*x)).collect()))
+				.add_appender_map(new_autoconv_generic_appender::<i32, Int32Type>(c.definition_level + 1, c.repetition_level), |v| Cow::Owned(v.dim));
+			(Box::new(wrap_pg_row_reader(c, appender)), t)
+		},
+	}
+}
+
+fn resolve_cube<TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo) -> ResolvedColumn<TRow> {
+	match s.cube_handling {
+		SchemaSettingsCubeHandling::Text =>
+			resolve_primitive_conv::<PgCube, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), |v| ByteArray::from(v.to_string().as_str())),
+		SchemaSettingsCubeHandling::Bounds => {
+			let element = || ParquetType::primitive_type_builder("element", basic::Type::DOUBLE).with_repetition(Repetition::REQUIRED).build().unwrap();
+			let t = GroupTypeBuilder::new(name)
+				.with_repetition(Repetition::OPTIONAL)
+				.with_fields(vec![
+					Arc::new(make_list_schema("lower", Repetition::REQUIRED, element(), s.list_encoding)),
+					Arc::new(make_list_schema("upper", Repetition::REQUIRED, element(), s.list_encoding)),
+				])
+				.build().unwrap();
+			let lower_appender = ArrayColumnAppender::new(new_autoconv_generic_appender::<f64, DoubleType>(c.definition_level + 2, c.repetition_level + 1), false, false, c.definition_level + 1, c.repetition_level);
+			let upper_appender = ArrayColumnAppender::new(new_autoconv_generic_appender::<f64, DoubleType>(c.definition_level + 2, c.repetition_level + 1), false, false, c.definition_level + 1, c.repetition_level);
+			let appender = new_static_merged_appender::<PgCube>(c.definition_level + 1, c.repetition_level)
+				.add_appender_map(lower_appender, |v| Cow::<Vec<Option<f64>>>::Owned(v.lower.iter().map(|x| Some(*x)).collect()))
+				.add_appender_map(upper_appender, |v| Cow::<Vec<Option<f64>>>::Owned(v.upper.iter().map(|x| Some(*x)).collect()));
+			(Box::new(wrap_pg_row_reader(c, appender)), t)
+		},
+	}
+}
+
+/// pgvector value as a list of non-null elements, which are converted by `element_appender`
+fn resolve_vector_elements<TAppender: ColumnAppender<f32> + Send + 'static, TRow: PgAbstractRow + Clone + 'static>(
+	name: &str,
+	c: &ColumnInfo,
+	physical_type: basic::Type,
+	length: Option<i32>,
+	logical_type: Option<LogicalType>,
+	encoding: SchemaSettingsListEncoding,
+	element_appender: TAppender
+) -> ResolvedColumn<TRow> {
+	let mut element = ParquetType::primitive_type_builder("element", physical_type)
+		.with_repetition(Repetition::REQUIRED)
+		.with_logical_type(logical_type);
+	if let Some(l) = length {
+		element = element.with_length(l);
+	}
+	let schema = make_list_schema(name, Repetition::OPTIONAL, element.build().unwrap(), encoding);
+	let appender = ArrayColumnAppender::new(element_appender, true, false, c.definition_level + 1, c.repetition_level)
+		.preprocess(|v: Cow<PgVector>| Cow::<Vec<Option<f32>>>::Owned(v.values.iter().map(|x| Some(*x)).collect()));
+	(Box::new(wrap_pg_row_reader(c, appender)), schema)
+}
+
 fn resolve_numeric<TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo) -> Result<ResolvedColumn<TRow>, String> {
 	match s.numeric_handling {
 		SchemaSettingsNumericHandling::Decimal => {
@@ -597,28 +2883,141 @@ fn resolve_numeric<TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, na
 				.with_scale(scale)
 				.build().unwrap();
 		let cp: DynColumnAppender<TRow> = if pq_type == basic::Type::INT32 {
-				let appender = new_decimal_int_appender::<i32, Int32Type>(c.definition_level + 1, c.repetition_level, precision, scale);
-				Box::new(wrap_pg_row_reader(c, appender))
+				let appender = new_decimal_int_appender::<i32, Int32Type>(c.definition_level + 1, c.repetition_level, precision, scale, s.decimal_rounding, c.full_name(), s.value_counters.clone());
+				Box::new(wrap_pg_row_reader(c, FloatSpecialValuesAppender::new(appender, s.float_special_values, c.full_name(), s.value_counters.clone())))
 			} else if pq_type == basic::Type::INT64 {
-				let appender = new_decimal_int_appender::<i64, Int64Type>(c.definition_level + 1, c.repetition_level, precision, scale);
-				Box::new(wrap_pg_row_reader(c, appender))
+				let appender = new_decimal_int_appender::<i64, Int64Type>(c.definition_level + 1, c.repetition_level, precision, scale, s.decimal_rounding, c.full_name(), s.value_counters.clone());
+				Box::new(wrap_pg_row_reader(c, FloatSpecialValuesAppender::new(appender, s.float_special_values, c.full_name(), s.value_counters.clone())))
 			} else {
-				let appender = new_decimal_bytes_appender(c.definition_level + 1, c.repetition_level, s.decimal_precision, s.decimal_scale);
-				Box::new(wrap_pg_row_reader(c, appender))
+				let appender = new_decimal_bytes_appender(c.definition_level + 1, c.repetition_level, precision, scale, s.decimal_rounding, c.full_name(), s.value_counters.clone());
+				Box::new(wrap_pg_row_reader(c, FloatSpecialValuesAppender::new(appender, s.float_special_values, c.full_name(), s.value_counters.clone())))
 			};
 			Ok((cp, schema))
 		},
 
+		SchemaSettingsNumericHandling::Struct => {
+			let schema = GroupTypeBuilder::new(name)
+				.with_repetition(Repetition::OPTIONAL)
+				.with_fields(vec![
+					Arc::new(ParquetType::primitive_type_builder("unscaled", basic::Type::BYTE_ARRAY).build().unwrap()),
+					Arc::new(ParquetType::primitive_type_builder("scale", basic::Type::INT32).build().unwrap()),
+				])
+				.build().unwrap();
+			let appender = new_decimal_struct_appender(c.definition_level + 1, c.repetition_level);
+			Ok((Box::new(wrap_pg_row_reader(c, FloatSpecialValuesAppender::new(appender, s.float_special_values, c.full_name(), s.value_counters.clone()))), schema))
+		},
 		SchemaSettingsNumericHandling::Double =>
-			Ok(resolve_primitive::<PgNumeric, DoubleType, _>(name, c, None, None)),
+			Ok(resolve_float::<PgNumeric, DoubleType, _>(s, name, c)),
 		SchemaSettingsNumericHandling::Float32 =>
-			Ok(resolve_primitive::<PgNumeric, FloatType, _>(name, c, None, None)),
-		SchemaSettingsNumericHandling::String =>
-			Ok(resolve_primitive_conv::<PgNumeric, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), None, |v: PgNumeric| match v.n {
+			Ok(resolve_float::<PgNumeric, FloatType, _>(s, name, c)),
+		SchemaSettingsNumericHandling::String => {
+			let schema = ParquetType::primitive_type_builder(name, basic::Type::BYTE_ARRAY)
+				.with_logical_type(Some(LogicalType::String))
+				.build().unwrap();
+			let appender = GenericColumnAppender::<PgNumeric, ByteArrayType, _>::new(c.definition_level + 1, c.repetition_level, |v: PgNumeric| match v.n {
 				Some(n) => ByteArray::my_from(n.to_string()),
 				None => ByteArray::my_from("NaN".to_string())
-			}))
+			});
+			Ok((Box::new(wrap_pg_row_reader(c, FloatSpecialValuesAppender::new(appender, s.float_special_values, c.full_name(), s.value_counters.clone()))), schema))
+		}
+	}
+}
+
+/// Text, json or enum column copied from the Postgres binary representation, with invalid UTF-8 handled according to --invalid-utf8.
+/// The values are written without the logical type with --invalid-utf8=bytes
+fn resolve_plain_text<TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo, logical_type: LogicalType, is_jsonb: bool) -> ResolvedColumn<TRow> {
+	let replace_invalid = match s.invalid_utf8 {
+		SchemaSettingsInvalidUtf8::Error => false,
+		SchemaSettingsInvalidUtf8::Replace => true,
+		SchemaSettingsInvalidUtf8::Bytes => return resolve_raw_bytes(name, c, None, is_jsonb),
+	};
+	let schema = ParquetType::primitive_type_builder(name, basic::Type::BYTE_ARRAY)
+		.with_logical_type(Some(logical_type))
+		.build().unwrap();
+	let utf8_check = byte_array::Utf8Check { column_name: c.full_name(), replace_invalid };
+	let appender: DynColumnAppender<TRow> = if is_jsonb {
+		Box::new(byte_array::create_jsonb_appender(c.definition_level + 1, c.repetition_level, c.col_i, Some(utf8_check)))
+	} else {
+		Box::new(byte_array::create_pg_text_appender(c.definition_level + 1, c.repetition_level, c.col_i, utf8_check))
+	};
+	(appender, schema)
+}
+
+/// Text column which is converted to String, invalid UTF-8 is replaced or causes an error according to --invalid-utf8
+fn resolve_text<T: for<'a> FromSql<'a> + Clone + Into<String> + 'static, TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo, convert: impl Fn(String) -> ByteArray + Send + 'static) -> Result<ResolvedColumn<TRow>, String> {
+	match s.invalid_utf8 {
+		SchemaSettingsInvalidUtf8::Error => Ok(resolve_text_as::<T, _>(s, name, c, convert)),
+		SchemaSettingsInvalidUtf8::Replace => Ok(resolve_text_as::<PgLossyText, _>(s, name, c, convert)),
+		SchemaSettingsInvalidUtf8::Bytes =>
+			Err(format!("Could not map column {}, --invalid-utf8=bytes cannot be combined with --null-string, --empty-string-as-null or --bpchar-handling=trim", c.full_name())),
+	}
+}
+
+/// Text column with NULL and empty values handled according to --null-string and --empty-string-as-null
+fn resolve_text_as<T: for<'a> FromSql<'a> + Clone + Into<String> + 'static, TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo, convert: impl Fn(String) -> ByteArray + Send + 'static) -> ResolvedColumn<TRow> {
+	if s.null_string.is_none() && !s.empty_string_as_null {
+		return resolve_primitive_conv::<T, ByteArrayType, _, _>(name, c, None, Some(LogicalType::String), Some(ConvertedType::UTF8), move |v| convert(v.into()));
 	}
+	let schema = ParquetType::primitive_type_builder(name, basic::Type::BYTE_ARRAY)
+		.with_logical_type(Some(LogicalType::String))
+		.with_converted_type(ConvertedType::UTF8)
+		.build().unwrap();
+	let appender = GenericColumnAppender::<String, ByteArrayType, _>::new(c.definition_level + 1, c.repetition_level, convert);
+	let appender = TextNullHandlingAppender::new(appender, s.null_string.clone(), s.empty_string_as_null)
+		.preprocess(|v: Cow<T>| Cow::Owned(v.into_owned().into()));
+	(Box::new(wrap_pg_row_reader(c, appender)), schema)
+}
+
+/// Text, json or bytea column copied directly from the Postgres binary representation, which is the same as the Parquet value
+fn resolve_raw_bytes<TRow: PgAbstractRow + Clone + 'static>(name: &str, c: &ColumnInfo, logical_type: Option<LogicalType>, is_jsonb: bool) -> ResolvedColumn<TRow> {
+	let schema = ParquetType::primitive_type_builder(name, basic::Type::BYTE_ARRAY)
+		.with_logical_type(logical_type)
+		.build().unwrap();
+	let appender: DynColumnAppender<TRow> = if is_jsonb {
+		Box::new(byte_array::create_jsonb_appender(c.definition_level + 1, c.repetition_level, c.col_i, None))
+	} else {
+		Box::new(byte_array::create_pg_raw_appender(c.definition_level + 1, c.repetition_level, c.col_i))
+	};
+	(appender, schema)
+}
+
+/// Column of a type handled by a `CustomTypeMapper`
+fn resolve_custom<TRow: PgAbstractRow + Clone + 'static>(mapper: &Arc<dyn CustomTypeMapper>, c: &ColumnInfo) -> Result<ResolvedColumn<TRow>, String> {
+	let schema = ParquetType::primitive_type_builder(c.col_name(), mapper.physical_type())
+		.with_logical_type(mapper.logical_type())
+		.with_length(mapper.type_length())
+		.build()
+		.map_err(|e| format!("Invalid Parquet type of the custom mapper of {}: {}", mapper.type_name(), e))?;
+	let (dl, rl, name, mapper) = (c.definition_level + 1, c.repetition_level, c.full_name(), mapper.clone());
+	let appender: DynColumnAppender<TRow> = match mapper.physical_type() {
+		basic::Type::BOOLEAN => Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(new_autoconv_generic_appender::<bool, BoolType>(dl, rl), mapper, name,
+			|v| match v { CustomValue::Bool(v) => Ok(v), v => Err(v) }))),
+		basic::Type::INT32 => Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(new_autoconv_generic_appender::<i32, Int32Type>(dl, rl), mapper, name,
+			|v| match v { CustomValue::Int32(v) => Ok(v), v => Err(v) }))),
+		basic::Type::INT64 => Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(new_autoconv_generic_appender::<i64, Int64Type>(dl, rl), mapper, name,
+			|v| match v { CustomValue::Int64(v) => Ok(v), v => Err(v) }))),
+		basic::Type::FLOAT => Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(new_autoconv_generic_appender::<f32, FloatType>(dl, rl), mapper, name,
+			|v| match v { CustomValue::Float(v) => Ok(v), v => Err(v) }))),
+		basic::Type::DOUBLE => Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(new_autoconv_generic_appender::<f64, DoubleType>(dl, rl), mapper, name,
+			|v| match v { CustomValue::Double(v) => Ok(v), v => Err(v) }))),
+		basic::Type::BYTE_ARRAY => Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(new_autoconv_generic_appender::<Vec<u8>, ByteArrayType>(dl, rl), mapper, name,
+			|v| match v { CustomValue::Bytes(v) => Ok(v), v => Err(v) }))),
+		basic::Type::FIXED_LEN_BYTE_ARRAY => {
+			let appender = GenericColumnAppender::<Vec<u8>, FixedLenByteArrayType, _>::new(dl, rl, FixedLenByteArray::from);
+			Box::new(wrap_pg_row_reader(c, CustomTypeAppender::new(appender, mapper, name,
+				|v| match v { CustomValue::Bytes(v) => Ok(v), v => Err(v) })))
+		},
+		t => return Err(format!("Physical type {} of the custom mapper of {} is not supported", t, mapper.type_name())),
+	};
+	Ok((appender, schema))
+}
+
+/// Floating point column with NaN and Infinity handled according to --float-special-values
+fn resolve_float<T: for<'a> FromSql<'a> + SpecialFloatValue + Clone + 'static, TDataType, TRow: PgAbstractRow + Clone + 'static>(s: &SchemaSettings, name: &str, c: &ColumnInfo) -> ResolvedColumn<TRow>
+	where TDataType: DataType, TDataType::T : RealMemorySize + MyFrom<T> {
+	let schema = ParquetType::primitive_type_builder(name, TDataType::get_physical_type()).build().unwrap();
+	let appender = GenericColumnAppender::<T, TDataType, _>::new(c.definition_level + 1, c.repetition_level, |v| MyFrom::my_from(v));
+	(Box::new(wrap_pg_row_reader(c, FloatSpecialValuesAppender::new(appender, s.float_special_values, c.full_name(), s.value_counters.clone()))), schema)
 }
 
 fn resolve_primitive<T: for<'a> FromSql<'a> + Clone + 'static, TDataType, TRow: PgAbstractRow + Clone + 'static>(
@@ -631,7 +3030,7 @@ fn resolve_primitive<T: for<'a> FromSql<'a> + Clone + 'static, TDataType, TRow:
 	resolve_primitive_conv::<T, TDataType, _, TRow>(name, c, None, logical_type, conv_type, |v| MyFrom::my_from(v))
 }
 
-fn resolve_primitive_conv<T: for<'a> FromSql<'a> + Clone + 'static, TDataType, FConversion: Fn(T) -> TDataType::T + 'static, TRow: PgAbstractRow + Clone + 'static>(
+fn resolve_primitive_conv<T: for<'a> FromSql<'a> + Clone + 'static, TDataType, FConversion: Fn(T) -> TDataType::T + Send + 'static, TRow: PgAbstractRow + Clone + 'static>(
 	name: &str,
 	c: &ColumnInfo,
 	length: Option<i32>,
@@ -646,19 +3045,13 @@ fn resolve_primitive_conv<T: for<'a> FromSql<'a> + Clone + 'static, TDataType, F
 		ParquetType::primitive_type_builder(name, TDataType::get_physical_type())
 		.with_converted_type(conv_type.unwrap_or(ConvertedType::NONE));
 
-	match length {
-		Some(l) => {
-			t = t.with_length(l);
-		},
-		_ => {}
-	};
+	if let Some(l) = length {
+		t = t.with_length(l);
+	}
 
-	match &logical_type {
-		Some(LogicalType::Decimal { scale, precision }) => {
-			t = t.with_precision(*precision).with_scale(*scale);
-		},
-		_ => {}
-	};
+	if let Some(LogicalType::Decimal { scale, precision }) = &logical_type {
+		t = t.with_precision(*precision).with_scale(*scale);
+	}
 	
 	let t = t.with_logical_type(logical_type).build().unwrap();
 
@@ -676,7 +3069,7 @@ fn create_primitive_appender_simple<T: for <'a> FromSql<'a> + Clone + 'static, T
 	Box::new(create_primitive_appender::<T, TDataType, _, TRow>(&c, |x| TDataType::T::my_from(x)))
 }
 
-fn create_primitive_appender<T: for <'a> FromSql<'a> + Clone + 'static, TDataType, FConversion: Fn(T) -> TDataType::T + 'static, TRow: PgAbstractRow + Clone>(
+fn create_primitive_appender<T: for <'a> FromSql<'a> + Clone + 'static, TDataType, FConversion: Fn(T) -> TDataType::T + Send + 'static, TRow: PgAbstractRow + Clone>(
 	c: &ColumnInfo,
 	convert: FConversion
 ) -> impl ColumnAppender<TRow>
@@ -685,27 +3078,29 @@ fn create_primitive_appender<T: for <'a> FromSql<'a> + Clone + 'static, TDataTyp
 	wrap_pg_row_reader(c, basic_appender)
 }
 
-fn create_complex_appender<T: for <'a> FromSql<'a> + Clone + 'static, TRow: PgAbstractRow + Clone>(c: &ColumnInfo, columns: Vec<DynColumnAppender<Arc<T>>>) -> impl ColumnAppender<TRow> {
+/// The composite value is read once and passed by reference to the appenders of its fields
+fn create_complex_appender<T: for <'a> FromSql<'a> + Clone + 'static, TRow: PgAbstractRow + Clone>(c: &ColumnInfo, columns: Vec<DynColumnAppender<T>>) -> impl ColumnAppender<TRow> {
 	let main_cp = DynamicMergedAppender::new(columns, c.definition_level + 1, c.repetition_level);
-	wrap_pg_row_reader(c, RcWrapperAppender::new(main_cp))
+	wrap_pg_row_reader(c, main_cp)
 }
 
-fn create_array_appender<TRow: PgAbstractRow + Clone>(inner: DynColumnAppender<PgAny>, c: &ColumnInfo, warn_on_multidim: bool) -> impl ColumnAppender<TRow> {
+fn create_array_appender<TRow: PgAbstractRow + Clone>(inner: DynColumnAppender<PgAny>, c: &ColumnInfo, warn_on_multidim: bool, allow_element_null: bool) -> impl ColumnAppender<TRow> {
 	let outer_dl = c.definition_level + 1;
-	debug_assert_eq!(outer_dl + 2, inner.max_dl());
-	let array_appender = ArrayColumnAppender::new(inner, true, true, outer_dl, c.repetition_level);
+	debug_assert_eq!(outer_dl + 1 + allow_element_null as i16, inner.max_dl());
+	let array_appender = ArrayColumnAppender::new(inner, true, allow_element_null, outer_dl, c.repetition_level);
 	let warned = AtomicBool::new(false);
 	let col_clone = c.clone();
 	let multidim_appender = array_appender.preprocess(move |x: Cow<PgMultidimArray<Option<PgAny>>>| {
-		if warn_on_multidim && x.dims.is_some() && !warned.load(Ordering::Relaxed) {
-			if !warned.fetch_or(true, Ordering::SeqCst) {
-				eprintln!("Warning: Column {} contains a {}-dimensional array which will be flattened in Parquet (i.e. {} -> {}). Use --array-handling=dimensions, include another column with the PostgreSQL array dimensions.",
+		match &x.dims {
+			Some(dims) if warn_on_multidim && !warned.load(Ordering::Relaxed) && !warned.fetch_or(true, Ordering::SeqCst) => {
+				warn(Some(col_clone.full_name()), format!("Column {} contains a {}-dimensional array which will be flattened in Parquet (i.e. {} -> {}). Use --array-handling=dimensions, include another column with the PostgreSQL array dimensions.",
 					col_clone.full_name(),
-					x.dims.as_ref().unwrap().len(),
-					x.dims.as_ref().unwrap().iter().map(|x| x.to_string()).collect::<Vec<_>>().join("x"),
+					dims.len(),
+					dims.iter().map(|x| x.to_string()).collect::<Vec<_>>().join("x"),
 					x.data.len()
-				)
-			}
+				))
+			},
+			_ => {},
 		}
 		match x {
 			Cow::Owned(x) => Cow::Owned(x.data),
@@ -722,7 +3117,7 @@ fn create_array_dim_appender<T: Clone + for <'a> FromSql<'a> + 'static, TRow: Pg
 			.preprocess(|x: Cow<PgMultidimArray<Option<T>>>| Cow::<Vec<Option<i32>>>::Owned(
 				x.dims.as_ref()
 					.map(|x| x.iter().map(|c| Some(*c)).collect())
-					.unwrap_or_else(|| if x.data.len() == 0 { Vec::new() } else { vec![Some(x.data.len() as i32)] })
+					.unwrap_or_else(|| if x.data.is_empty() { Vec::new() } else { vec![Some(x.data.len() as i32)] })
 			));
 	wrap_pg_row_reader::<TRow, PgMultidimArray<Option<T>>>(c, dim_appender)
 }
@@ -734,7 +3129,7 @@ fn create_array_lower_bound_appender<T: Clone + for <'a> FromSql<'a> + 'static,
 		ArrayColumnAppender::new(int_appender, false, false, c.definition_level + 1, c.repetition_level)
 			.preprocess(|x: Cow<PgMultidimArray<Option<T>>>| Cow::<Vec<Option<i32>>>::Owned(
 				match &x.lower_bounds {
-					_ if x.data.len() == 0 => Vec::new(),
+					_ if x.data.is_empty() => Vec::new(),
 					PgMultidimArrayLowerBounds::Const(c) => vec![Some(*c); x.dims.as_ref().map(|x| x.len()).unwrap_or(1)],
 					PgMultidimArrayLowerBounds::PerDim(v) => v.iter().map(|x| Some(*x)).collect()
 				}
@@ -753,6 +3148,8 @@ struct ColumnInfo {
 	pub is_array: bool,
 	pub definition_level: i16,
 	pub repetition_level: i16,
+	/// atttypmod of the column (e.g. the n in bit(n)), -1 if unknown
+	pub type_modifier: i32,
 }
 impl ColumnInfo {
 	pub fn root(col_i: usize, name: String) -> ColumnInfo {
@@ -762,9 +3159,14 @@ impl ColumnInfo {
 			is_array: false,
 			definition_level: 0,
 			repetition_level: 0,
+			type_modifier: -1,
 		}
 	}
 
+	fn with_type_modifier(&self, type_modifier: i32) -> ColumnInfo {
+		ColumnInfo { type_modifier, ..self.clone() }
+	}
+
 	fn nest<TString: Into<String>>(&self, name: TString, col_i: usize) -> ColumnInfo {
 		ColumnInfo {
 			names: Arc::new({
@@ -776,17 +3178,19 @@ impl ColumnInfo {
 			is_array: false,
 			definition_level: self.definition_level + 1,
 			repetition_level: self.repetition_level,
+			type_modifier: -1,
 		}
 	}
 
 	fn as_array(&self) -> ColumnInfo {
-		assert!(self.is_array == false, "Parquet does not support nested arrays");
+		assert!(!self.is_array, "Parquet does not support nested arrays");
 		ColumnInfo {
 			names: self.names.clone(),
 			col_i: self.col_i,
 			is_array: true,
 			definition_level: self.definition_level,
 			repetition_level: self.repetition_level + 1,
+			type_modifier: self.type_modifier,
 		}
 	}
 
@@ -1,51 +1,109 @@
 use postgres::{self, Row, types::Kind};
 use uuid::Uuid;
 
-pub fn identify_row(row: &Row) -> String {
+use crate::pg_custom_types::PgAnyRef;
+
+/// Short description of the row for error messages: values of the `key` columns (i.e. the primary key),
+/// or the first non-null text, integer or uuid column if no key is specified
+pub fn identify_row(row: &Row, key: &[String]) -> String {
+	if !key.is_empty() {
+		return key.iter().map(|name| {
+			let value = match row.columns().iter().position(|c| c.name() == name) {
+				Some(i) if matches!(row.try_get::<usize, Option<PgAnyRef>>(i), Ok(None)) => "NULL".to_string(),
+				Some(i) => format_value(row, i).unwrap_or_else(|| "?".to_string()),
+				None => "?".to_string(),
+			};
+			format!("{}={}", name, value)
+		}).collect::<Vec<_>>().join(", ");
+	}
 
 	// first row with simple data type
 	for (i, column) in row.columns().iter().enumerate() {
-		let t = column.type_();
-		if t.kind().clone() == Kind::Simple {
-			match t.name() {
-				"text" => {
-					match row.get::<usize, Option<String>>(i) {
-						Some(v) =>
-							return format!("{}={:?}", column.name(), v),
-						None => ()
-					}
-				},
-				"oid" => {
-					match row.get::<usize, Option<u32>>(i) {
-						Some(v) =>
-							return format!("{}={:?}", column.name(), v),
-						None => ()
-					}
-				},
-				"int4" => {
-					match row.get::<usize, Option<i32>>(i) {
-						Some(v) =>
-							return format!("{}={:?}", column.name(), v),
-						None => ()
-					}
-				},
-				"int8" => {
-					match row.get::<usize, Option<i64>>(i) {
-						Some(v) =>
-							return format!("{}={:?}", column.name(), v),
-						None => ()
-					}
-				},
-				"uuid" => {
-					match row.get::<usize, Option<Uuid>>(i) {
-						Some(v) =>
-							return format!("{}={:?}", column.name(), v),
-						None => ()
-					}
-				},
-				_ => ()
-			}
+		if let Some(v) = format_value(row, i) {
+			return format!("{}={}", column.name(), v);
 		}
 	}
 	"Row ¯\\_(ツ)_/¯".to_owned()
 }
+
+/// Formats a text, integer or uuid value, None if it is NULL or of another type
+fn format_value(row: &Row, i: usize) -> Option<String> {
+	let t = row.columns()[i].type_();
+	if t.kind().clone() != Kind::Simple {
+		return None;
+	}
+	match t.name() {
+		"text" | "varchar" => row.try_get::<usize, Option<String>>(i).ok().flatten().map(|v| format!("{:?}", v)),
+		"oid" => row.try_get::<usize, Option<u32>>(i).ok().flatten().map(|v| v.to_string()),
+		"int2" => row.try_get::<usize, Option<i16>>(i).ok().flatten().map(|v| v.to_string()),
+		"int4" => row.try_get::<usize, Option<i32>>(i).ok().flatten().map(|v| v.to_string()),
+		"int8" => row.try_get::<usize, Option<i64>>(i).ok().flatten().map(|v| v.to_string()),
+		"uuid" => row.try_get::<usize, Option<Uuid>>(i).ok().flatten().map(|v| v.to_string()),
+		_ => None,
+	}
+}
+
+/// Quotes the name as PostgreSQL identifier, so it can be safely inserted into generated SQL
+pub fn quote_identifier(name: &str) -> String {
+	format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes the string as PostgreSQL literal, for the statements which do not support parameters (such as COPY options)
+pub fn quote_literal(value: &str) -> String {
+	format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Looks up the password in the libpq password file (PGPASSFILE, or ~/.pgpass)
+/// https://www.postgresql.org/docs/current/libpq-pgpass.html
+pub fn find_pgpass_password(host: &str, port: u16, dbname: &str, user: &str) -> Option<String> {
+	let path = match std::env::var_os("PGPASSFILE") {
+		Some(p) => std::path::PathBuf::from(p),
+		None if cfg!(target_family = "windows") => std::path::PathBuf::from(std::env::var_os("APPDATA")?).join("postgresql").join("pgpass.conf"),
+		None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".pgpass"),
+	};
+	let content = std::fs::read_to_string(&path).ok()?;
+	// libpq uses localhost for matching connections over unix socket
+	let host = if host.starts_with('/') { "localhost" } else { host };
+	let port = port.to_string();
+
+	for line in content.lines() {
+		if line.trim().is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let fields = split_pgpass_line(line);
+		if fields.len() < 5 {
+			continue;
+		}
+		let matches = |pattern: &str, value: &str| pattern == "*" || pattern == value;
+		if matches(&fields[0], host) && matches(&fields[1], &port) && matches(&fields[2], dbname) && matches(&fields[3], user) {
+			return Some(fields[4..].join(":"));
+		}
+	}
+	None
+}
+
+fn split_pgpass_line(line: &str) -> Vec<String> {
+	let mut fields = vec![String::new()];
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => {
+				if let Some(escaped) = chars.next() {
+					fields.last_mut().unwrap().push(escaped);
+				}
+			},
+			':' => fields.push(String::new()),
+			c => fields.last_mut().unwrap().push(c),
+		}
+	}
+	fields
+}
+
+/// Error message including the details which are not part of postgres::Error Display
+pub fn format_pg_error(e: &postgres::Error) -> String {
+	match (e.as_db_error(), std::error::Error::source(e)) {
+		(Some(db_error), _) => db_error.to_string(),
+		(None, Some(source)) => format!("{}: {}", e, source),
+		(None, None) => e.to_string(),
+	}
+}
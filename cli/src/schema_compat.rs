@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use parquet::basic::{ConvertedType, LogicalType, Repetition};
+use parquet::file::metadata::ParquetMetaDataReader;
+use parquet::schema::types::Type as ParquetType;
+
+use crate::postgres_cloner::{resolve_schema, warn, SchemaSettings};
+use crate::PostgresConnArgs;
+
+/// What to do when the schema of the export differs from the `--schema-compat` reference file
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnSchemaChange {
+	/// Fail before anything is exported
+	Error,
+	/// Print the differences as warnings (also included in the --report) and export the data
+	Warn,
+	/// Export the data without warnings, the differences are only recorded in the pg2parquet.schema_changes file metadata
+	WriteAnyway,
+}
+
+/// Reads the schema of an existing Parquet file
+pub fn read_reference_schema(path: &PathBuf) -> Result<ParquetType, String> {
+	let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+	let metadata = ParquetMetaDataReader::new().parse_and_finish(&file).map_err(|e| format!("Failed to read parquet file {:?}: {}", path, e))?;
+	Ok(metadata.file_metadata().schema().clone())
+}
+
+/// Compares the schema of the query result with the reference file before the export. Returns the differences,
+/// which should be recorded in the file metadata, or an error if they are not allowed by `on_change`
+pub fn check_schema_compat(pg_args: &PostgresConnArgs, query: &str, settings: &SchemaSettings, reference_file: &PathBuf, on_change: OnSchemaChange) -> Result<Vec<String>, String> {
+	let reference = read_reference_schema(reference_file)?;
+	let schema = resolve_schema(pg_args, query, settings)?;
+	let differences = schema_differences(&reference, &schema);
+	match on_change {
+		OnSchemaChange::Error if !differences.is_empty() =>
+			return Err(format!("The schema of the export is not compatible with {:?}:\n  {}", reference_file, differences.join("\n  "))),
+		OnSchemaChange::Warn => for d in &differences {
+			warn(None, format!("The schema differs from {:?}: {}", reference_file, d));
+		},
+		_ => {},
+	}
+	Ok(differences)
+}
+
+/// Lists the differences between the schema of the reference file and the new schema: removed and added columns,
+/// changed types and nullability. Columns are matched by name (nested fields by their path), the order of the columns is ignored
+pub fn schema_differences(reference: &ParquetType, new: &ParquetType) -> Vec<String> {
+	let mut result = Vec::new();
+	compare_fields(reference.get_fields(), new.get_fields(), "", &mut result);
+	result
+}
+
+fn compare_fields(reference: &[parquet::schema::types::TypePtr], new: &[parquet::schema::types::TypePtr], prefix: &str, result: &mut Vec<String>) {
+	for r in reference {
+		let path = format!("{}{}", prefix, r.name());
+		match new.iter().find(|n| n.name() == r.name()) {
+			None => result.push(format!("column {} was removed", path)),
+			Some(n) => compare_field(r, n, &path, result),
+		}
+	}
+	for n in new {
+		if !reference.iter().any(|r| r.name() == n.name()) {
+			result.push(format!("column {}{} was added", prefix, n.name()));
+		}
+	}
+}
+
+fn compare_field(reference: &ParquetType, new: &ParquetType, path: &str, result: &mut Vec<String>) {
+	let (r_rep, n_rep) = (reference.get_basic_info().repetition(), new.get_basic_info().repetition());
+	if r_rep != n_rep {
+		let describe = |r: Repetition| match r {
+			Repetition::REQUIRED => "not nullable",
+			Repetition::OPTIONAL => "nullable",
+			Repetition::REPEATED => "repeated",
+		};
+		result.push(format!("column {} changed from {} to {}", path, describe(r_rep), describe(n_rep)));
+	}
+	let (r_type, n_type) = (type_description(reference), type_description(new));
+	if r_type != n_type {
+		result.push(format!("column {} changed type from {} to {}", path, r_type, n_type));
+	} else if reference.is_group() {
+		compare_fields(reference.get_fields(), new.get_fields(), &format!("{}.", path), result);
+	}
+}
+
+/// Physical and logical type of a primitive field, or the logical type of a group (such as List or Map)
+fn type_description(t: &ParquetType) -> String {
+	let info = t.get_basic_info();
+	let logical = match (info.logical_type(), info.converted_type()) {
+		(Some(LogicalType::Decimal { scale, precision }), _) => Some(format!("Decimal({}, {})", precision, scale)),
+		(Some(lt), _) => Some(format!("{:?}", lt)),
+		(None, ConvertedType::NONE) => None,
+		(None, c) => Some(c.to_string()),
+	};
+	let base = match t {
+		ParquetType::PrimitiveType { physical_type, type_length, .. } if *type_length > 0 => format!("{}[{}]", physical_type, type_length),
+		ParquetType::PrimitiveType { physical_type, .. } => physical_type.to_string(),
+		ParquetType::GroupType { .. } => "group".to_string(),
+	};
+	match logical {
+		Some(logical) => format!("{} {}", base, logical),
+		None => base,
+	}
+}
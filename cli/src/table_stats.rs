@@ -0,0 +1,63 @@
+use serde_json::json;
+
+use crate::postgres_cloner::pg_connect;
+use crate::postgresutils::format_pg_error;
+use crate::PostgresConnArgs;
+
+/// Cumulative statistics of the table from `pg_stat_user_tables`, summed over all partitions
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStatCounters {
+	pub seq_scan: i64,
+	pub seq_tup_read: i64,
+	pub idx_scan: i64,
+	pub idx_tup_fetch: i64,
+	pub n_live_tup: i64,
+	/// Time of the last manual or automatic ANALYZE of any of the partitions
+	pub last_analyze: Option<String>,
+}
+
+/// How long to wait for the server to publish the statistics of the export connection, which are only flushed when the backend exits
+const FLUSH_WAIT: std::time::Duration = std::time::Duration::from_secs(2);
+
+pub fn read_table_stats(pg_args: &PostgresConnArgs, table: &str) -> Result<TableStatCounters, String> {
+	let mut client = pg_connect(pg_args)?;
+	read_counters(&mut client, table)
+}
+
+fn read_counters(client: &mut postgres::Client, table: &str) -> Result<TableStatCounters, String> {
+	let row = client.query_one(
+		"SELECT coalesce(sum(seq_scan), 0)::int8, coalesce(sum(seq_tup_read), 0)::int8, coalesce(sum(idx_scan), 0)::int8, coalesce(sum(idx_tup_fetch), 0)::int8,
+			coalesce(sum(n_live_tup), 0)::int8, max(greatest(last_analyze, last_autoanalyze))::text
+		FROM pg_stat_user_tables WHERE relid = $1::text::regclass OR relid IN (SELECT relid FROM pg_partition_tree($1::text::regclass))",
+		&[&table]
+	).map_err(|e| format!("Failed to read pg_stat_user_tables of {}: {}", table, format_pg_error(&e)))?;
+	Ok(TableStatCounters {
+		seq_scan: row.get(0),
+		seq_tup_read: row.get(1),
+		idx_scan: row.get(2),
+		idx_tup_fetch: row.get(3),
+		n_live_tup: row.get(4),
+		last_analyze: row.get(5),
+	})
+}
+
+/// Differences of the scan counters since `before` and the current live tuple estimate and ANALYZE time, for the --report.
+/// The counters include the activity of other sessions during the export
+pub fn table_stats_report(pg_args: &PostgresConnArgs, table: &str, before: &TableStatCounters) -> Result<serde_json::Value, String> {
+	let mut client = pg_connect(pg_args)?;
+	let start = std::time::Instant::now();
+	let mut after = read_counters(&mut client, table)?;
+	while after.seq_scan == before.seq_scan && after.idx_scan == before.idx_scan && start.elapsed() < FLUSH_WAIT {
+		std::thread::sleep(std::time::Duration::from_millis(100));
+		after = read_counters(&mut client, table)?;
+	}
+	Ok(json!({
+		"table": table,
+		"seq_scan": after.seq_scan - before.seq_scan,
+		"seq_tup_read": after.seq_tup_read - before.seq_tup_read,
+		"idx_scan": after.idx_scan - before.idx_scan,
+		"idx_tup_fetch": after.idx_tup_fetch - before.idx_tup_fetch,
+		"n_live_tup": after.n_live_tup,
+		"last_analyze": after.last_analyze,
+	}))
+}
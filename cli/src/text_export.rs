@@ -0,0 +1,191 @@
+//! CSV and JSONL export. The rows do not go through the appenders, PostgreSQL formats the values, so the CLI rejects the schema settings which are implemented by the appenders. Only `--mask`, `--rename-column` and `--column-case` are applied in the query instead
+use std::io::{BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use postgres::fallible_iterator::FallibleIterator;
+use postgres::types::{FromSql, Kind, Type};
+
+use crate::cancellation;
+use crate::checksum::{Checksum, ChecksumWriter};
+use crate::parquet_writer::WriterStats;
+use crate::ddl;
+use crate::masking::ColumnMask;
+use crate::postgres_cloner::{pg_connect, CopyOptions, SchemaSettings, SchemaSettingsColumnCase};
+use crate::postgresutils::{format_pg_error, quote_identifier, quote_literal};
+use crate::PostgresConnArgs;
+
+/// Writes the query result as CSV with a header line. The values are formatted by PostgreSQL (COPY ... CSV)
+pub fn export_csv(pg_args: &PostgresConnArgs, query: &str, output_file: &PathBuf, schema_settings: &SchemaSettings, options: &CopyOptions, checksum: Option<&Checksum>) -> Result<WriterStats, String> {
+	let mut client = pg_connect(pg_args)?;
+	options.hooks.run_pre_sql(&mut client)?;
+	let query = prepare_query(&mut client, query, schema_settings, options)?;
+	let null_option = match &schema_settings.null_string {
+		Some(null_string) => format!(", NULL {}", quote_literal(null_string)),
+		None => String::new(),
+	};
+	let mut reader = client.copy_out(&format!("COPY ({}) TO STDOUT (FORMAT csv, HEADER true{})", query, null_option))
+		.map_err(|e| format!("Failed to execute query: {}", format_pg_error(&e)))?;
+	let mut writer = create_file(output_file, checksum)?;
+
+	let mut buffer = vec![0u8; 64 * 1024];
+	let mut stats = WriterStats::default();
+	let mut lines = 0;
+	let mut in_quotes = false;
+	// only complete records are written, so that the file is valid when the export is cancelled
+	let mut incomplete_record = Vec::new();
+	loop {
+		cancellation::check_aborted()?;
+		let n = match reader.read(&mut buffer) {
+			Ok(n) => n,
+			// the query was cancelled by the signal handler
+			Err(_) if cancellation::should_stop()? => break,
+			Err(e) => return Err(format!("Failed to read query results: {}", e)),
+		};
+		if n == 0 {
+			break;
+		}
+		// newlines in quoted values do not end the record, escaped quotes ("") toggle the state twice
+		let mut record_end = None;
+		for (i, &b) in buffer[..n].iter().enumerate() {
+			match b {
+				b'"' => in_quotes = !in_quotes,
+				b'\n' if !in_quotes => {
+					lines += 1;
+					record_end = Some(i + 1);
+				},
+				_ => {},
+			}
+		}
+		if let Some(record_end) = record_end {
+			writer.write_all(&incomplete_record)
+				.and_then(|_| writer.write_all(&buffer[..record_end]))
+				.map_err(|e| format!("Failed to write {:?}: {}", output_file, e))?;
+			stats.bytes += incomplete_record.len() + record_end;
+			incomplete_record.clear();
+			incomplete_record.extend_from_slice(&buffer[record_end..n]);
+		} else {
+			incomplete_record.extend_from_slice(&buffer[..n]);
+		}
+	}
+	writer.flush().map_err(|e| format!("Failed to write {:?}: {}", output_file, e))?;
+	stats.rows = lines.max(1) - 1;
+	stats.bytes_out = stats.bytes;
+	print_summary(&stats, options.quiet);
+	drop(reader);
+	options.hooks.run_post_sql(&mut client, &stats)?;
+	Ok(stats)
+}
+
+/// Writes each row of the query result as a JSON object on a separate line. The values are formatted by PostgreSQL (row_to_json)
+pub fn export_jsonl(pg_args: &PostgresConnArgs, query: &str, output_file: &PathBuf, schema_settings: &SchemaSettings, options: &CopyOptions, checksum: Option<&Checksum>) -> Result<WriterStats, String> {
+	let mut client = pg_connect(pg_args)?;
+	options.hooks.run_pre_sql(&mut client)?;
+	let query = prepare_query(&mut client, query, schema_settings, options)?;
+	let json_query = format!("SELECT row_to_json(q)::text FROM ({}) q", query);
+	let mut rows = client.query_raw(&json_query, std::iter::empty::<i32>())
+		.map_err(|e| format!("Failed to execute query: {}", format_pg_error(&e)))?;
+	let mut writer = create_file(output_file, checksum)?;
+
+	let mut stats = WriterStats::default();
+	loop {
+		let row = match rows.next() {
+			Ok(Some(row)) => row,
+			Ok(None) => break,
+			// the query was cancelled by the signal handler
+			Err(_) if cancellation::should_stop()? => break,
+			Err(e) => return Err(format!("Failed to read query results: {}", format_pg_error(&e))),
+		};
+		cancellation::check_aborted()?;
+		let line: &str = row.get(0);
+		writer.write_all(line.as_bytes())
+			.and_then(|_| writer.write_all(b"\n"))
+			.map_err(|e| format!("Failed to write {:?}: {}", output_file, e))?;
+		stats.rows += 1;
+		stats.bytes += line.len() + 1;
+	}
+	writer.flush().map_err(|e| format!("Failed to write {:?}: {}", output_file, e))?;
+	stats.bytes_out = stats.bytes;
+	print_summary(&stats, options.quiet);
+	drop(rows);
+	options.hooks.run_post_sql(&mut client, &stats)?;
+	Ok(stats)
+}
+
+/// Applies --columns, --mask, --rename-column and --column-case to the query after --pre-sql, and writes the --emit-ddl file from the columns of the resulting query
+fn prepare_query(client: &mut postgres::Client, query: &str, schema_settings: &SchemaSettings, options: &CopyOptions) -> Result<String, String> {
+	let query = match &schema_settings.column_definitions {
+		Some(columns) => columns.apply(client, query)?,
+		None => query.to_string(),
+	};
+	let query = rename_and_mask_columns(client, &query, schema_settings)?;
+	if let Some(ddl) = &options.ddl {
+		let statement = client.prepare(&query).map_err(|e| format!("Failed to prepare query: {}", format_pg_error(&e)))?;
+		ddl::write_ddl(client, statement.columns(), ddl)?;
+	}
+	Ok(query)
+}
+
+/// The Parquet and Arrow exports rename and mask the columns in the appenders, the text formats do it in the query with the same results
+fn rename_and_mask_columns(client: &mut postgres::Client, query: &str, s: &SchemaSettings) -> Result<String, String> {
+	if s.column_masks.is_empty() && s.column_renames.is_empty() && s.column_case == SchemaSettingsColumnCase::Preserve {
+		return Ok(query.to_string());
+	}
+	let statement = client.prepare(query).map_err(|e| format!("Failed to prepare query: {}", format_pg_error(&e)))?;
+	let columns = statement.columns();
+	if let Some((name, _)) = s.column_masks.iter().find(|(name, _)| !columns.iter().any(|c| c.name() == name)) {
+		return Err(format!("Column {} specified in --mask is not in the result of the query", name));
+	}
+	if let Some((name, _)) = s.column_renames.iter().find(|(name, _)| !columns.iter().any(|c| c.name() == name)) {
+		return Err(format!("Column {} specified in --rename-column is not in the result of the query", name));
+	}
+	let mut select = Vec::new();
+	for (i, c) in columns.iter().enumerate() {
+		let output_name = s.output_column_name(c.name());
+		if let Some(b) = columns[..i].iter().find(|b| b.name() != c.name() && s.output_column_name(b.name()) == output_name) {
+			return Err(format!("Columns {} and {} would both be named {} in the output file", b.name(), c.name(), output_name));
+		}
+		let column = format!("q.{}", quote_identifier(c.name()));
+		let value = match s.column_masks.iter().find(|(name, _)| name == c.name()) {
+			Some((_, mask)) => mask_expression(client, &column, c.name(), c.type_(), mask)?,
+			None => column,
+		};
+		select.push(format!("{} AS {}", value, quote_identifier(&output_name)));
+	}
+	Ok(format!("SELECT {} FROM ({}) q", select.join(", "), query))
+}
+
+/// SQL expression computing the same value as the Parquet export writes for the masked column, see `map_masked_column`
+fn mask_expression(client: &mut postgres::Client, column: &str, name: &str, t: &Type, mask: &ColumnMask) -> Result<String, String> {
+	if matches!(t.kind(), Kind::Array(_)) && *mask != ColumnMask::Null {
+		return Err(format!("Column {} is an array, only the null mask is supported for arrays", name));
+	}
+	Ok(match mask {
+		ColumnMask::Null => "NULL".to_string(),
+		// the hash of the binary representation, which the Parquet export reads from the server
+		ColumnMask::Sha256 => {
+			let send_function: String = client.query_one("SELECT typsend::text FROM pg_type WHERE oid = $1", &[&t.oid()])
+				.map_err(|e| format!("Failed to query the send function of type {}: {}", t, format_pg_error(&e)))?
+				.get(0);
+			format!("encode(sha256({}({})), 'hex')", send_function, column)
+		},
+		ColumnMask::Constant(value) => format!("CASE WHEN {} IS DISTINCT FROM NULL THEN {} END", column, quote_literal(value)),
+		ColumnMask::RedactEmail => {
+			if !<String as FromSql>::accepts(t) {
+				return Err(format!("Column {} has type {}, redact-email mask requires a text column", name, t));
+			}
+			format!("CASE WHEN strpos({0}, '@') = 0 THEN '***' ELSE regexp_replace({0}, '^(.?).*@([^@]*)$', '\\1***@\\2') END", column)
+		},
+	})
+}
+
+fn create_file(output_file: &PathBuf, checksum: Option<&Checksum>) -> Result<BufWriter<ChecksumWriter<std::fs::File>>, String> {
+	std::fs::File::create(output_file)
+		.map(|f| BufWriter::new(ChecksumWriter::new(f, checksum)))
+		.map_err(|e| format!("Failed to create {:?}: {}", output_file, e))
+}
+
+fn print_summary(stats: &WriterStats, quiet: bool) {
+	if !quiet {
+		eprintln!("Wrote {} rows, {} bytes", stats.rows, stats.bytes_out);
+	}
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use postgres::types::{Kind, Type as PgType};
+use postgres::{Client, Column};
+
+use crate::postgresutils::format_pg_error;
+
+/// Catalog information about a type which is not built into PostgreSQL, from pg_type and pg_namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgTypeInfo {
+	pub oid: u32,
+	pub name: String,
+	pub schema: String,
+	/// pg_type.typcategory, for example 'S' for string types or 'U' for user-defined types
+	pub category: char,
+	/// Name of the extension which created the type, None for types created by CREATE TYPE
+	pub extension: Option<String>,
+	/// pg_type.typnotnull, whether the type is a domain with a NOT NULL constraint
+	pub not_null: bool,
+}
+
+/// Catalog information about the extension and user-defined types used in a query result.
+/// The OIDs of these types differ between databases, so they are identified by the extension which created them instead.
+/// Types built into PostgreSQL are not included, they are recognized by postgres-types
+#[derive(Debug, Clone, Default)]
+pub struct TypeCatalog {
+	types: HashMap<u32, PgTypeInfo>,
+}
+
+impl TypeCatalog {
+	/// Loads the information about all non-builtin types used by the columns (including array elements, domains and composite fields).
+	/// Types which are already in the catalog are not queried again
+	pub fn load(&self, client: &mut Client, columns: &[Column]) -> Result<TypeCatalog, String> {
+		let mut oids = Vec::new();
+		for c in columns {
+			collect_types(c.type_(), &mut oids);
+		}
+		oids.retain(|oid| !self.types.contains_key(oid));
+		let mut catalog = self.clone();
+		if oids.is_empty() {
+			return Ok(catalog);
+		}
+		let rows = client.query("
+			SELECT t.oid, t.typname::text, n.nspname::text, t.typcategory::text, e.extname::text, t.typnotnull
+			FROM pg_catalog.pg_type t
+			JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace
+			LEFT JOIN pg_catalog.pg_depend d ON d.classid = 'pg_catalog.pg_type'::regclass AND d.objid = t.oid AND d.deptype = 'e'
+			LEFT JOIN pg_catalog.pg_extension e ON e.oid = d.refobjid
+			WHERE t.oid = ANY($1)", &[&oids])
+			.map_err(|e| format!("Failed to load the type information: {}", format_pg_error(&e)))?;
+		for row in rows {
+			let info = PgTypeInfo {
+				oid: row.get(0),
+				name: row.get(1),
+				schema: row.get(2),
+				category: row.get::<_, String>(3).chars().next().unwrap_or('X'),
+				extension: row.get(4),
+				not_null: row.get(5),
+			};
+			catalog.types.insert(info.oid, info);
+		}
+		Ok(catalog)
+	}
+
+	pub fn get(&self, oid: u32) -> Option<&PgTypeInfo> {
+		self.types.get(&oid)
+	}
+
+	/// Whether the type belongs to the string category (typcategory 'S'), such as citext
+	pub fn is_string_category(&self, t: &PgType) -> bool {
+		self.get(t.oid()).is_some_and(|info| info.category == 'S')
+	}
+
+	/// Whether the type is a domain with a NOT NULL constraint, possibly over another domain
+	pub fn is_not_null_domain(&self, t: &PgType) -> bool {
+		match t.kind() {
+			Kind::Domain(base) => self.get(t.oid()).is_some_and(|info| info.not_null) || self.is_not_null_domain(base),
+			_ => false,
+		}
+	}
+
+	/// Whether the type is `type_name` created by the extension `extension`. Without the catalog information
+	/// (when it was not loaded for the type), only the type name is compared
+	pub fn is_extension_type(&self, t: &PgType, extension: &str, type_name: &str) -> bool {
+		match self.get(t.oid()) {
+			Some(info) => info.extension.as_deref() == Some(extension) && info.name == type_name,
+			None => t.name() == type_name,
+		}
+	}
+}
+
+fn collect_types(t: &PgType, result: &mut Vec<u32>) {
+	match t.kind() {
+		Kind::Array(element) | Kind::Domain(element) | Kind::Range(element) => collect_types(element, result),
+		Kind::Composite(fields) => fields.iter().for_each(|f| collect_types(f.type_(), result)),
+		_ => {},
+	}
+	if PgType::from_oid(t.oid()).is_none() && !result.contains(&t.oid()) {
+		result.push(t.oid());
+	}
+}
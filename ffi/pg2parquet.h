@@ -0,0 +1,42 @@
+#ifndef PG2PARQUET_H
+#define PG2PARQUET_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Exports a PostgreSQL table or query into a Parquet file.
+ *
+ * config_json is a JSON object with the connection parameters (host, which may be a comma-separated list, port, user,
+ * dbname, password, sslmode, snapshot, read_only, keepalive_interval_seconds), output_file, either query or table, and optionally compression (e.g. "zstd(3)" or "snappy"), retries,
+ * retry_backoff_seconds, max_buffer_memory (in bytes), flush_interval_seconds, engine (parquet or arrow), page_checksums (boolean), on_row_error (fail, skip or null-column),
+ * row_identifier_columns (comma separated column names shown in the row errors), chunk_size (rows per query of a table export),
+ * as_map (key and value column separated by a comma, aggregated into a MAP column), watermark_column (its minimum and maximum is written into the file metadata),
+ * pre_sql and post_sql (SQL executed on the export connection before and after the export),
+ * column_stats (boolean, adds the null count and approximate distinct count of each column to the report),
+ * job_id (written into the pg2parquet.job_id file metadata), created_by (replaces the writer identity in the Parquet footer),
+ * columns (the column definition list of the query, e.g. "id int, name text"), masks (object of column name to mask, e.g. {"email": "redact-email"}),
+ * rename_columns (object of column name to its new name)
+ * and the schema options of the command line tool
+ * (macaddr_handling, json_handling, enum_handling, interval_handling, numeric_handling, decimal_scale,
+ * decimal_precision, decimal_rounding, array_handling, array_element_nullability, list_encoding, lo_handling, column_case, flatten_composites,
+ * column_order, field_ids, bpchar_handling, uuid_handling, inet_handling, money_handling, money_scale, reg_handling, bit_handling, float_special_values,
+ * vector_element_type, vector_i8_scale, vector_fixed_size_list, sparsevec_handling, cube_handling, isn_handling, null_string, empty_string_as_null, invalid_utf8, meta_columns and text_types as a comma separated list).
+ *
+ * Unknown keys are rejected.
+ * The password is never asked for, without the password key it is taken from the PGPASSWORD environment variable or the ~/.pgpass file, otherwise the export fails.
+ *
+ * Returns a JSON report in the same format as `pg2parquet export --report json`, with "status" either "ok" or "error".
+ * The warnings in the report are only the ones of this call, concurrent calls from multiple threads may however see each other's warnings.
+ * The returned string must be released using pg2parquet_free_string.
+ */
+char *pg2parquet_export(const char *config_json);
+
+void pg2parquet_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
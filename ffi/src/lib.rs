@@ -0,0 +1,331 @@
+//! C ABI of the pg2parquet export, see `pg2parquet.h`
+use std::ffi::{c_char, CStr, CString};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use pg2parquet::column_definitions::ColumnDefinitions;
+use pg2parquet::masking::ColumnMask;
+use pg2parquet::{postgres_cloner, ExportJob, PostgresConnArgs, RetrySettings, SchemaSettings, WriterStats};
+use serde_json::{json, Value};
+
+/// Runs the export described by the JSON config and returns the JSON report, in the same format as `pg2parquet export --report json`.
+/// The returned string must be released using `pg2parquet_free_string`
+///
+/// # Safety
+/// `config_json` must be NULL or a pointer to a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn pg2parquet_export(config_json: *const c_char) -> *mut c_char {
+	let start_time = std::time::Instant::now();
+	let result = std::panic::catch_unwind(|| {
+		if config_json.is_null() {
+			return Err("config_json is NULL".to_string());
+		}
+		let config = unsafe { CStr::from_ptr(config_json) }.to_str().map_err(|e| format!("config_json is not valid UTF-8: {}", e))?;
+		let config: Value = serde_json::from_str(config).map_err(|e| format!("config_json is not valid JSON: {}", e))?;
+		export_job(&config)?.run()
+	}).unwrap_or_else(|_| Err("pg2parquet has crashed".to_string()));
+	let report = report_json(&result, start_time.elapsed());
+	CString::new(report.to_string()).unwrap().into_raw()
+}
+
+/// Releases a string returned by `pg2parquet_export`
+///
+/// # Safety
+/// `s` must be NULL or a string returned by `pg2parquet_export`, which was not released yet
+#[no_mangle]
+pub unsafe extern "C" fn pg2parquet_free_string(s: *mut c_char) {
+	if !s.is_null() {
+		drop(unsafe { CString::from_raw(s) });
+	}
+}
+
+fn report_json(result: &Result<WriterStats, String>, duration: std::time::Duration) -> Value {
+	let warnings: Vec<_> = postgres_cloner::take_warnings().into_iter()
+		.map(|w| json!({ "column": w.column, "message": w.message }))
+		.collect();
+	match result {
+		Ok(stats) => json!({
+			"status": "ok",
+			"rows": stats.rows,
+			"raw_bytes": stats.bytes,
+			"output_bytes": stats.bytes_out,
+			"row_groups": stats.groups,
+			"special_float_values": stats.special_float_values,
+			"rounded_decimal_values": stats.rounded_decimal_values,
+			"columns": stats.columns.iter().map(|c| json!({
+				"name": c.name,
+				"null_count": c.null_count,
+				"approx_distinct_count": c.approx_distinct_count,
+			})).collect::<Value>(),
+			"column_sizes": stats.column_sizes.iter().map(|c| json!({
+				"column": c.column,
+				"compressed_bytes": c.compressed_bytes,
+				"uncompressed_bytes": c.uncompressed_bytes,
+			})).collect::<Value>(),
+			"failed_rows": stats.failed_rows,
+			"row_errors": stats.row_errors.iter().map(|e| json!({ "row": e.row, "column": e.column, "error": e.message })).collect::<Value>(),
+			"duration_seconds": duration.as_secs_f64(),
+			"warnings": warnings,
+		}),
+		Err(e) => json!({
+			"status": "error",
+			"error": e,
+			"duration_seconds": duration.as_secs_f64(),
+			"warnings": warnings,
+		}),
+	}
+}
+
+/// All keys documented in `pg2parquet.h`, anything else is most likely a typo which would be silently ignored
+const CONFIG_KEYS: &[&str] = &[
+	"host", "port", "user", "dbname", "password", "sslmode", "snapshot", "read_only", "keepalive_interval_seconds",
+	"output_file", "query", "table", "compression", "created_by", "retries", "retry_backoff_seconds", "max_buffer_memory",
+	"flush_interval_seconds", "engine", "page_checksums", "pre_sql", "post_sql", "on_row_error", "chunk_size", "as_map",
+	"row_identifier_columns", "watermark_column", "column_stats", "job_id", "columns", "masks", "rename_columns",
+	"macaddr_handling", "json_handling", "enum_handling", "interval_handling", "numeric_handling", "decimal_scale",
+	"decimal_precision", "decimal_rounding", "array_handling", "array_element_nullability", "list_encoding", "lo_handling",
+	"column_case", "flatten_composites", "column_order", "field_ids", "meta_columns", "bpchar_handling", "uuid_handling",
+	"inet_handling", "money_handling", "money_scale", "reg_handling", "bit_handling", "float_special_values",
+	"vector_element_type", "vector_i8_scale", "vector_fixed_size_list", "sparsevec_handling", "cube_handling", "text_types",
+	"isn_handling", "null_string", "empty_string_as_null", "invalid_utf8",
+];
+
+fn export_job(config: &Value) -> Result<ExportJob, String> {
+	let Some(keys) = config.as_object() else {
+		return Err(format!("config_json must be an object, got {}", config));
+	};
+	if let Some(key) = keys.keys().find(|k| !CONFIG_KEYS.contains(&k.as_str())) {
+		return Err(format!("Unknown config key {}", key));
+	}
+	let string = |key: &str| -> Result<Option<String>, String> {
+		match config.get(key) {
+			None | Some(Value::Null) => Ok(None),
+			Some(Value::String(s)) => Ok(Some(s.clone())),
+			Some(v) => Err(format!("{} must be a string, got {}", key, v)),
+		}
+	};
+	let number = |key: &str| -> Result<Option<u64>, String> {
+		match config.get(key) {
+			None | Some(Value::Null) => Ok(None),
+			Some(v) => v.as_u64().map(Some).ok_or_else(|| format!("{} must be a non-negative integer, got {}", key, v)),
+		}
+	};
+	// the JSON numbers are u64, the settings narrower integers which must not be silently truncated
+	fn narrow<T: TryFrom<u64>>(key: &str, value: Option<u64>) -> Result<Option<T>, String> {
+		value.map(|v| T::try_from(v).map_err(|_| format!("{} is out of range, got {}", key, v))).transpose()
+	}
+	let float = |key: &str| -> Result<Option<f64>, String> {
+		match config.get(key) {
+			None | Some(Value::Null) => Ok(None),
+			Some(v) => v.as_f64().map(Some).ok_or_else(|| format!("{} must be a number, got {}", key, v)),
+		}
+	};
+	let boolean = |key: &str| -> Result<Option<bool>, String> {
+		match config.get(key) {
+			None | Some(Value::Null) => Ok(None),
+			Some(v) => v.as_bool().map(Some).ok_or_else(|| format!("{} must be a boolean, got {}", key, v)),
+		}
+	};
+	// column name -> string value, e.g. the masks
+	let object = |key: &str| -> Result<Vec<(String, String)>, String> {
+		match config.get(key) {
+			None | Some(Value::Null) => Ok(Vec::new()),
+			Some(Value::Object(o)) => o.iter()
+				.map(|(column, v)| v.as_str().map(|v| (column.clone(), v.to_string())).ok_or_else(|| format!("{}.{} must be a string, got {}", key, column, v)))
+				.collect(),
+			Some(v) => Err(format!("{} must be an object, got {}", key, v)),
+		}
+	};
+	fn value_enum<T: ValueEnum>(key: &str, value: Option<String>) -> Result<Option<T>, String> {
+		value.map(|v| T::from_str(&v, true).map_err(|e| format!("Invalid {}: {}", key, e))).transpose()
+	}
+
+	let connection = PostgresConnArgs {
+		host: string("host")?.map(|h| h.split(',').map(|h| h.trim().to_string()).collect()).unwrap_or_default(),
+		user: string("user")?,
+		dbname: string("dbname")?,
+		port: narrow("port", number("port")?)?,
+		password: string("password")?,
+		// there is no terminal to ask on
+		no_password: true,
+		sslmode: value_enum("sslmode", string("sslmode")?)?,
+		ssl_root_cert: None,
+		pg_settings: Vec::new(),
+		snapshot: string("snapshot")?,
+		read_only: boolean("read_only")?.unwrap_or(false),
+		keepalive_interval: number("keepalive_interval_seconds")?.map(std::time::Duration::from_secs),
+	};
+	let output_file = string("output_file")?.ok_or_else(|| "output_file is required".to_string())?;
+	let mut job = ExportJob::new(connection, output_file);
+	match (string("query")?, string("table")?) {
+		(Some(query), None) => job = job.query(query),
+		(None, Some(table)) => job = job.table(table),
+		_ => return Err("Either query or table must be specified".to_string()),
+	}
+	if config.get("columns").is_some_and(|c| !c.is_null()) && string("query")?.is_none() {
+		return Err("columns can only be used with query".to_string());
+	}
+
+	if let Some(compression) = string("compression")? {
+		let compression = Compression::from_str(&compression).map_err(|e| format!("Invalid compression: {}", e))?;
+		job = job.writer_properties(WriterProperties::builder()
+			.set_compression(compression)
+			.set_created_by(format!("pg2parquet version {}, using {}", env!("CARGO_PKG_VERSION"), parquet::file::properties::DEFAULT_CREATED_BY)));
+	}
+	if let Some(created_by) = string("created_by")? {
+		job = job.created_by(created_by);
+	}
+
+	let defaults = SchemaSettings::default();
+	job = job.schema_settings(SchemaSettings {
+		macaddr_handling: value_enum("macaddr_handling", string("macaddr_handling")?)?.unwrap_or(defaults.macaddr_handling),
+		json_handling: value_enum("json_handling", string("json_handling")?)?.unwrap_or(defaults.json_handling),
+		enum_handling: value_enum("enum_handling", string("enum_handling")?)?.unwrap_or(defaults.enum_handling),
+		interval_handling: value_enum("interval_handling", string("interval_handling")?)?.unwrap_or(defaults.interval_handling),
+		numeric_handling: value_enum("numeric_handling", string("numeric_handling")?)?.unwrap_or(defaults.numeric_handling),
+		decimal_scale: narrow("decimal_scale", number("decimal_scale")?)?.unwrap_or(defaults.decimal_scale),
+		decimal_precision: narrow("decimal_precision", number("decimal_precision")?)?.unwrap_or(defaults.decimal_precision),
+		decimal_rounding: value_enum("decimal_rounding", string("decimal_rounding")?)?.unwrap_or(defaults.decimal_rounding),
+		array_handling: value_enum("array_handling", string("array_handling")?)?.unwrap_or(defaults.array_handling),
+		array_element_nullability: value_enum("array_element_nullability", string("array_element_nullability")?)?.unwrap_or(defaults.array_element_nullability),
+		list_encoding: value_enum("list_encoding", string("list_encoding")?)?.unwrap_or(defaults.list_encoding),
+		lo_handling: value_enum("lo_handling", string("lo_handling")?)?.unwrap_or(defaults.lo_handling),
+		as_map: defaults.as_map,
+		column_definitions: string("columns")?.map(|c| ColumnDefinitions::parse(&c)).transpose()?,
+		column_masks: object("masks")?.into_iter()
+			.map(|(column, mask)| {
+				let mask = ColumnMask::from_str(&mask).map_err(|e| format!("Invalid masks.{}: {}", column, e))?;
+				Ok((column, mask))
+			})
+			.collect::<Result<_, String>>()?,
+		column_renames: object("rename_columns")?,
+		column_case: value_enum("column_case", string("column_case")?)?.unwrap_or(defaults.column_case),
+		flatten_composites: boolean("flatten_composites")?.unwrap_or(defaults.flatten_composites),
+		column_order: string("column_order")?.map(|s| s.parse()).transpose()?.unwrap_or(defaults.column_order),
+		field_ids: value_enum("field_ids", string("field_ids")?)?.unwrap_or(defaults.field_ids),
+		field_id_mapping: defaults.field_id_mapping,
+		meta_columns: match string("meta_columns")? {
+			Some(columns) => columns.split(',').map(|c| value_enum("meta_columns", Some(c.trim().to_string())).map(Option::unwrap)).collect::<Result<_, _>>()?,
+			None => defaults.meta_columns,
+		},
+		exported_at: defaults.exported_at,
+		source_table: string("table")?,
+		job_id: string("job_id")?,
+		bpchar_handling: value_enum("bpchar_handling", string("bpchar_handling")?)?.unwrap_or(defaults.bpchar_handling),
+		uuid_handling: value_enum("uuid_handling", string("uuid_handling")?)?.unwrap_or(defaults.uuid_handling),
+		inet_handling: value_enum("inet_handling", string("inet_handling")?)?.unwrap_or(defaults.inet_handling),
+		money_handling: value_enum("money_handling", string("money_handling")?)?.unwrap_or(defaults.money_handling),
+		money_scale: narrow("money_scale", number("money_scale")?)?.unwrap_or(defaults.money_scale),
+		reg_handling: value_enum("reg_handling", string("reg_handling")?)?.unwrap_or(defaults.reg_handling),
+		bit_handling: value_enum("bit_handling", string("bit_handling")?)?.unwrap_or(defaults.bit_handling),
+		float_special_values: value_enum("float_special_values", string("float_special_values")?)?.unwrap_or(defaults.float_special_values),
+		vector_element_type: value_enum("vector_element_type", string("vector_element_type")?)?.unwrap_or(defaults.vector_element_type),
+		vector_i8_scale: float("vector_i8_scale")?.map_or(defaults.vector_i8_scale, |v| v as f32),
+		vector_fixed_size_list: boolean("vector_fixed_size_list")?.unwrap_or(defaults.vector_fixed_size_list),
+		sparsevec_handling: value_enum("sparsevec_handling", string("sparsevec_handling")?)?.unwrap_or(defaults.sparsevec_handling),
+		cube_handling: value_enum("cube_handling", string("cube_handling")?)?.unwrap_or(defaults.cube_handling),
+		text_types: match string("text_types")? {
+			Some(types) => types.split(',').map(|t| t.trim().to_string()).collect(),
+			None => defaults.text_types,
+		},
+		isn_handling: value_enum("isn_handling", string("isn_handling")?)?.unwrap_or(defaults.isn_handling),
+		null_string: string("null_string")?,
+		empty_string_as_null: boolean("empty_string_as_null")?.unwrap_or(defaults.empty_string_as_null),
+		invalid_utf8: value_enum("invalid_utf8", string("invalid_utf8")?)?.unwrap_or(defaults.invalid_utf8),
+		reg_names: defaults.reg_names,
+		custom_type_mappers: defaults.custom_type_mappers,
+		type_catalog: defaults.type_catalog,
+		value_counters: defaults.value_counters,
+	});
+	if let Some(retries) = narrow("retries", number("retries")?)? {
+		job = job.retry(RetrySettings { retries, backoff: std::time::Duration::from_secs(number("retry_backoff_seconds")?.unwrap_or(30)) });
+	}
+	if let Some(bytes) = narrow("max_buffer_memory", number("max_buffer_memory")?)? {
+		job = job.max_buffer_memory(bytes);
+	}
+	if let Some(seconds) = number("flush_interval_seconds")? {
+		job = job.flush_interval(Some(std::time::Duration::from_secs(seconds)));
+	}
+	if let Some(engine) = value_enum("engine", string("engine")?)? {
+		job = job.engine(engine);
+	}
+	if let Some(page_checksums) = boolean("page_checksums")? {
+		job = job.page_checksums(page_checksums);
+	}
+	if let Some(sql) = string("pre_sql")? {
+		job = job.pre_sql(sql);
+	}
+	if let Some(sql) = string("post_sql")? {
+		job = job.post_sql(sql);
+	}
+	if let Some(on_row_error) = value_enum("on_row_error", string("on_row_error")?)? {
+		job = job.on_row_error(on_row_error);
+	}
+	if let Some(rows) = narrow("chunk_size", number("chunk_size")?)? {
+		job = job.chunk_size(Some(rows));
+	}
+	if let Some(columns) = string("as_map")? {
+		let Some((key, value)) = columns.split_once(',') else {
+			return Err(format!("as_map must be the key and value column separated by a comma, got '{}'", columns));
+		};
+		job = job.as_map(key.trim(), value.trim());
+	}
+	if let Some(columns) = string("row_identifier_columns")? {
+		job = job.row_identifier_columns(columns.split(',').map(|c| c.trim().to_owned()).collect());
+	}
+	if let Some(column) = string("watermark_column")? {
+		job = job.watermark_column(column);
+	}
+	if let Some(column_stats) = boolean("column_stats")? {
+		job = job.column_stats(column_stats);
+	}
+	Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn export(config: Option<&str>) -> Value {
+		let config = config.map(|c| CString::new(c).unwrap());
+		unsafe {
+			let report = pg2parquet_export(config.as_ref().map_or(std::ptr::null(), |c| c.as_ptr()));
+			let json = serde_json::from_str(CStr::from_ptr(report).to_str().unwrap()).unwrap();
+			pg2parquet_free_string(report);
+			json
+		}
+	}
+
+	fn error(config: Option<&str>) -> String {
+		let report = export(config);
+		assert_eq!(report["status"], "error", "{}", report);
+		report["error"].as_str().unwrap().to_string()
+	}
+
+	#[test]
+	fn invalid_config() {
+		assert_eq!(error(None), "config_json is NULL");
+		assert!(error(Some("{")).starts_with("config_json is not valid JSON"));
+		assert_eq!(error(Some(r#"{"table": "t"}"#)), "output_file is required");
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet"}"#)), "Either query or table must be specified");
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "port": 65536}"#)), "port is out of range, got 65536");
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "decimal_scale": 4294967296}"#)), "decimal_scale is out of range, got 4294967296");
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "retries": 4294967296}"#)), "retries is out of range, got 4294967296");
+		assert_eq!(error(Some("[]")), "config_json must be an object, got []");
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "compresion": "zstd"}"#)), "Unknown config key compresion");
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "masks": ["email"]}"#)), r#"masks must be an object, got ["email"]"#);
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "rename_columns": {"a": 1}}"#)), "rename_columns.a must be a string, got 1");
+		assert!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "masks": {"email": "md5"}}"#)).starts_with("Invalid masks.email: "));
+		assert_eq!(error(Some(r#"{"output_file": "t.parquet", "table": "t", "columns": "id int"}"#)), "columns can only be used with query");
+	}
+
+	#[test]
+	fn connection_error() {
+		// without a password, the export fails instead of waiting for it on the terminal
+		let report = export(Some(r#"{"output_file": "t.parquet", "table": "t", "host": "127.0.0.1", "port": 1, "user": "u", "dbname": "db", "sslmode": "disable"}"#));
+		assert_eq!(report["status"], "error", "{}", report);
+		assert!(report["warnings"].as_array().unwrap().is_empty());
+	}
+}
@@ -7,6 +7,7 @@ import unittest
 import duckdb
 import polars as pl
 import pandas as pd
+import pyarrow.parquet as pq
 
 
 class TestBasic(unittest.TestCase):
@@ -130,3 +131,80 @@ class TestBasic(unittest.TestCase):
         self.assertEqual(dims_lb_df["a"].struct.field("lower_bound").to_list(), [ [1, 1], None, [], [1, 1, 1, 1], [-2] ])
         self.assertEqual(dims_lb_df["b"].struct.field("lower_bound").to_list(), [ [1, 1], None, [], [1, 1, 1], [-1, 4] ])
 
+
+    def test_struct_elements(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS arrays_struct_elements",
+            "DROP DOMAIN IF EXISTS interval_list_domain",
+            "DROP DOMAIN IF EXISTS tstzrange_domain",
+            "CREATE DOMAIN interval_list_domain AS interval[]",
+            "CREATE DOMAIN tstzrange_domain AS tstzrange",
+        )
+        file = wrappers.create_and_export(
+            "arrays_struct_elements", "id",
+            "id int, iv interval[], rr tstzrange[], nested interval_list_domain[], dr tstzrange_domain[]",
+            """
+                (1, ARRAY['1 day'::interval, NULL], ARRAY['[2020-01-01 00:00:00+00,2020-01-02 00:00:00+00)'::tstzrange, NULL], ARRAY[ARRAY['1 second'::interval]::interval_list_domain, NULL::interval_list_domain], ARRAY['empty'::tstzrange_domain]),
+                (2, NULL, NULL, NULL, NULL)
+            """,
+            options=["--interval-handling=struct"]
+        )
+        def iv(months=0, days=0, microseconds=0):
+            return {'months': months, 'days': days, 'microseconds': microseconds}
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table[0][1], [iv(days=1), None])
+        self.assertEqual(duckdb_table[0][2][0]['lower'], datetime.datetime(2020, 1, 1, tzinfo=datetime.timezone.utc))
+        self.assertEqual(duckdb_table[0][2][1], None)
+        self.assertEqual(duckdb_table[0][3], [[iv(microseconds=1000000)], None])
+        self.assertEqual(duckdb_table[0][4], [{'lower': None, 'upper': None, 'lower_inclusive': False, 'upper_inclusive': False, 'is_empty': True}])
+        self.assertEqual(duckdb_table[1], (2, None, None, None, None))
+
+    def test_element_nullability(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS arrays_element_nullability",
+            "DROP DOMAIN IF EXISTS not_null_int",
+            "CREATE DOMAIN not_null_int AS int NOT NULL",
+        )
+        file = wrappers.create_and_export(
+            "arrays_element_nullability", "id",
+            "id int, a not_null_int[], b int[]",
+            "(1, '{1,2}', '{3}'), (2, NULL, '{}'), (3, '{}', NULL)"
+        )
+        schema = pq.read_schema(file)
+        self.assertEqual(str(schema.field("a").type), "list<element: int32 not null>")
+        self.assertEqual(str(schema.field("b").type), "list<element: int32>")
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1, [1, 2], [3]), (2, None, []), (3, [], None)])
+
+        optional_file = wrappers.run_export_table("arrays_element_nullability_optional", "arrays_element_nullability", "id", options=["--array-element-nullability=optional"])
+        self.assertEqual(str(pq.read_schema(optional_file).field("a").type), "list<element: int32>")
+
+        required_file = wrappers.run_export_table("arrays_element_nullability_required", "arrays_element_nullability", "id", options=["--array-element-nullability=required"])
+        self.assertEqual(str(pq.read_schema(required_file).field("b").type), "list<element: int32 not null>")
+        self.assertEqual(pl.read_parquet(required_file)["b"].to_list(), [[3], [], None])
+
+        with self.assertRaises(Exception):
+            wrappers.run_export("arrays_element_nullability_null", "SELECT ARRAY[1, NULL] AS a", options=["--array-element-nullability=required"])
+
+    def test_unsupported_element_type(self):
+        file = wrappers.run_export(
+            "arrays_unsupported_element",
+            "SELECT 1 AS id, ARRAY[point(1, 2), NULL] AS p, ARRAY['b a'::tsvector] AS t, ARRAY[1] AS i",
+        )
+        self.assertEqual(str(pq.read_schema(file).field("p").type), "list<element: string>")
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1, ["(1,2)", None], ["'a' 'b'"], [1])])
+
+    def test_two_level_lists(self):
+        file = wrappers.run_export(
+            "arrays_two_level",
+            "SELECT 1 AS id, ARRAY[1, 2] AS a, ARRAY['[1,2)'::int4range] AS r, ARRAY[]::text[] AS e, NULL::int[] AS n",
+            options=["--list-encoding=two-level"]
+        )
+        schema = pq.read_schema(file)
+        self.assertEqual(str(schema.field("a").type), "list<array: int32 not null>")
+        self.assertEqual(pq.ParquetFile(file).schema.column(1).path, "a.array")
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, [1, 2], [{'lower': 1, 'upper': 2, 'lower_inclusive': True, 'upper_inclusive': False, 'is_empty': False}], [], None)
+        ])
+
+        with self.assertRaises(Exception):
+            wrappers.run_export("arrays_two_level_null", "SELECT ARRAY[1, NULL] AS a", options=["--list-encoding=two-level"])
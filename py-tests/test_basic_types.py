@@ -1,12 +1,15 @@
 import datetime
+import json
 from decimal import Decimal
 import math
+import os
 import uuid
 import wrappers
 import unittest
 import duckdb
 import polars as pl
 import pandas as pd
+import pyarrow.parquet as pq
 
 
 class TestBasic(unittest.TestCase):
@@ -55,6 +58,108 @@ class TestBasic(unittest.TestCase):
             ("id2", None, None, None, None, None, None)
         ])
 
+    def test_xml_and_text_arrays(self):
+        file = wrappers.create_and_export(
+            "xml_text_arrays", "id",
+            "id int, x xml, t text[], j jsonb[], b bytea[]",
+            "(1, '<a>ž</a>', ARRAY['a', NULL, ''], ARRAY['{\"a\": 1}'::jsonb, NULL], ARRAY['\\x00ff'::bytea]), (2, NULL, NULL, NULL, NULL)"
+        )
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table, [
+            {"id": 1, "x": "<a>ž</a>", "t": ["a", None, ""], "j": ["{\"a\": 1}", None], "b": [b"\x00\xff"]},
+            {"id": 2, "x": None, "t": None, "j": None, "b": None},
+        ])
+
+    def test_bpchar_trim_and_citext_metadata(self):
+        file = wrappers.create_and_export(
+            "bpchar_citext", "id",
+            "id int, char char(10), chars char(3)[], citext citext",
+            "(1, 'char', ARRAY['a', NULL], 'CiTeXt'), (2, NULL, NULL, NULL)",
+            options=["--bpchar-handling", "trim"]
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, "char", ["a", None], "CiTeXt"),
+            (2, None, None, None)
+        ])
+        metadata = pq.read_metadata(file).metadata
+        self.assertEqual(json.loads(metadata[b"pg2parquet.case_insensitive_columns"]), ["citext"])
+
+    def test_extension_text_types(self):
+        wrappers.run_sql("DROP DOMAIN IF EXISTS short_citext CASCADE", "CREATE DOMAIN short_citext AS citext CHECK (length(VALUE) < 10)")
+        file = wrappers.create_and_export(
+            "extension_text_types", "id",
+            "id int, tags citext[], short short_citext",
+            "(1, ARRAY['A', NULL, 'b'], 'Short'), (2, NULL, NULL)"
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, ["A", None, "b"], "Short"),
+            (2, None, None)
+        ])
+        self.assertEqual(str(pq.read_schema(file).field("short").type), "string")
+
+    def test_type_catalog(self):
+        # the extension types are recognized by the extension which created them, not by the type name
+        wrappers.run_sql(
+            "DROP SCHEMA IF EXISTS lookalike CASCADE",
+            "CREATE SCHEMA lookalike",
+            "CREATE DOMAIN lookalike.citext AS text",
+            "DROP DOMAIN IF EXISTS positive_not_null_int, not_null_int CASCADE",
+            "CREATE DOMAIN not_null_int AS int NOT NULL",
+            "CREATE DOMAIN positive_not_null_int AS not_null_int CHECK (VALUE > 0)",
+            "DROP TYPE IF EXISTS labeled_value CASCADE",
+            "CREATE TYPE labeled_value AS (label citext, value int)",
+        )
+        file = wrappers.create_and_export(
+            "type_catalog", "id",
+            "id int, real_citext citext, fake_citext lookalike.citext, not_null not_null_int[], positive positive_not_null_int[], labeled labeled_value",
+            "(1, 'A', 'B', ARRAY[1, 2], ARRAY[3], ROW('X', 1)), (2, NULL, NULL, NULL, NULL, NULL)"
+        )
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, "A", "B", [1, 2], [3], {"label": "X", "value": 1}),
+            (2, None, None, None, None, None)
+        ])
+        metadata = pq.read_metadata(file).metadata
+        self.assertEqual(json.loads(metadata[b"pg2parquet.case_insensitive_columns"]), ["real_citext", "labeled.label"])
+        # the elements of NOT NULL domains (also through another domain) are required
+        schema = pq.read_schema(file)
+        self.assertFalse(schema.field("not_null").type.value_field.nullable)
+        self.assertFalse(schema.field("positive").type.value_field.nullable)
+
+    def test_invalid_utf8(self):
+        # only a SQL_ASCII database can contain text which is not valid UTF-8
+        dbname = wrappers.pg2parquet_dbname + "_sql_ascii"
+        with wrappers.pg_connect("postgres") as conn:
+            conn.autocommit = True
+            conn.execute(f"DROP DATABASE IF EXISTS {dbname}")
+            conn.execute(f"CREATE DATABASE {dbname} TEMPLATE template0 ENCODING 'SQL_ASCII' LC_COLLATE 'C' LC_CTYPE 'C'")
+        with wrappers.pg_connect(dbname) as conn:
+            conn.execute("CREATE TYPE invalid_utf8_enum AS ENUM ('a', 'b')")
+            conn.execute("CREATE TABLE invalid_utf8 (id int, t text, a varchar[], j json, jb jsonb, e invalid_utf8_enum)")
+            conn.execute("INSERT INTO invalid_utf8 VALUES (1, E'ab\\xffcd', ARRAY[E'\\xe9', 'ok'], E'\"\\xff\"', E'[\"\\xff\"]', 'a'), (2, 'valid', NULL, '1', '2', 'b')")
+        def export(mode):
+            outfile = os.path.join(wrappers.output_directory, f"invalid_utf8_{mode}.parquet")
+            wrappers.run_pg2parquet([
+                "export", "--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", dbname,
+                "--query", "SELECT * FROM invalid_utf8 ORDER BY id", "--output-file", outfile,
+                "--pg-setting", "client_encoding=SQL_ASCII", "--invalid-utf8", mode
+            ])
+            return outfile
+        with self.assertRaises(Exception):
+            export("error")
+        file = export("replace")
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, "ab\ufffdcd", ["\ufffd", "ok"], "\"\ufffd\"", "[\"\ufffd\"]", "a"),
+            (2, "valid", None, "1", "2", "b")
+        ])
+        file = export("bytes")
+        self.assertEqual(str(pq.read_schema(file).field("t").type), "binary")
+        self.assertEqual(pq.read_table(file).to_pylist(), [
+            {"id": 1, "t": b"ab\xffcd", "a": [b"\xe9", b"ok"], "j": b"\"\xff\"", "jb": b"[\"\xff\"]", "e": b"a"},
+            {"id": 2, "t": b"valid", "a": None, "j": b"1", "jb": b"2", "e": b"b"}
+        ])
+
     def test_integer_types(self):
         file = wrappers.create_and_export(
             "integer_types", "id",
@@ -102,6 +207,18 @@ class TestBasic(unittest.TestCase):
         self.assertEqual(list(polars_df["f32"].cast(str)), ['1.1', 'NaN', 'inf', '-inf', '-0.0'])
         self.assertEqual(list(polars_df["f64"].cast(str)), ['2.2', 'NaN', 'inf', '-inf', '-0.0'])
 
+    def test_float_special_values(self):
+        schema = "id int, f32 real, f64 double precision, n numeric, arr double precision[]"
+        inserts = "(1, 'NaN', 'inf', 'NaN', ARRAY[1, '-inf']::double precision[]), (2, 1.5, 2.5, 3.5, NULL)"
+        file = wrappers.create_and_export("float_special_values", "id", schema, inserts, options=["--float-special-values=null"])
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, None, None, None, [1.0, None]),
+            (2, 1.5, 2.5, 3.5, None)
+        ])
+        with self.assertRaises(Exception):
+            wrappers.create_and_export("float_special_values", "id", schema, inserts, options=["--float-special-values=error"])
+
     def test_decimal(self):
         file = wrappers.create_and_export(
             "numeric_types", "id",
@@ -154,6 +271,41 @@ class TestBasic(unittest.TestCase):
             (1, Decimal('1000.000100000000000000'), Decimal('1.000000000000000000')),
             (2,  None, None )
         ])
+
+    def test_decimal_rounding(self):
+        wrappers.create_and_export(
+            "numeric_rounding", "id",
+            "id int, n numeric",
+            "(1, 0.25), (2, 0.35), (3, -0.25), (4, 1.05), (5, 2.5), (6, NULL)",
+            options=["--numeric-handling=decimal", "--decimal-precision=9", "--decimal-scale=1"]
+        )
+        expected = {
+            "half-even": [Decimal("0.2"), Decimal("0.4"), Decimal("-0.2"), Decimal("1.0"), Decimal("2.5"), None],
+            "half-up": [Decimal("0.3"), Decimal("0.4"), Decimal("-0.3"), Decimal("1.1"), Decimal("2.5"), None],
+            "truncate": [Decimal("0.2"), Decimal("0.3"), Decimal("-0.2"), Decimal("1.0"), Decimal("2.5"), None],
+        }
+        for rounding, values in expected.items():
+            for precision in ["9", "38"]:
+                file = wrappers.run_export(f"numeric_rounding_{rounding}_{precision}", "select * from numeric_rounding order by id", options=["--numeric-handling=decimal", f"--decimal-precision={precision}", "--decimal-scale=1", f"--decimal-rounding={rounding}"])
+                self.assertEqual(list(pd.read_parquet(file, engine="pyarrow")["n"]), values, f"{rounding} {precision}")
+
+        with self.assertRaises(Exception):
+            wrappers.run_export("numeric_rounding_error", "select * from numeric_rounding order by id", options=["--numeric-handling=decimal", "--decimal-scale=1", "--decimal-rounding=error"])
+
+    def test_numeric_struct(self):
+        values = ["0.25", "-1", "1e30", "-12345678901234567890123456789012345678901234567890.000000000000000000001"]
+        file = wrappers.create_and_export(
+            "numeric_struct", "id",
+            "id int, n numeric",
+            ", ".join(f"({i}, {v})" for i, v in enumerate(values)) + f", ({len(values)}, 'NaN'), ({len(values) + 1}, NULL)",
+            options=["--numeric-handling=struct"]
+        )
+        rows = pq.read_table(file).to_pylist()
+        self.assertEqual(rows[len(values):], [{"id": len(values), "n": None}, {"id": len(values) + 1, "n": None}])
+        for value, row in zip(values, rows):
+            n = row["n"]
+            self.assertEqual(Decimal(int.from_bytes(n["unscaled"], "big", signed=True)).scaleb(-n["scale"]), Decimal(value))
+
     def test_numeric_f64(self):
         file = wrappers.create_and_export(
             "numeric_types", "id",
@@ -214,6 +366,73 @@ class TestBasic(unittest.TestCase):
             "bytes": pl.Binary,
             "onebyte": pl.UInt8
         })
+    def test_large_objects(self):
+        values = "(1, lo_from_bytea(0, 'foo'::bytea)), (2, NULL)"
+        file = wrappers.create_and_export("large_objects", "id", "id int, doc oid", values)
+        [(_, oid), (_, null)] = duckdb.read_parquet(file).fetchall()
+        self.assertIsInstance(oid, int)
+        self.assertIsNone(null)
+
+        file = wrappers.create_and_export("large_objects", "id", "id int, doc oid", values, options=["--lo-handling=inline-bytes"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1, b'foo'), (2, None)])
+
+        file = wrappers.create_and_export("large_objects", "id", "id int, doc oid", values, options=["--lo-handling=skip"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1,), (2,)])
+
+    def create_cubes(self, name, options=[]):
+        return wrappers.create_and_export(
+            name, "id",
+            "id int, c cube, s seg, a cube[]",
+            """(1, '(1, 2),(3, 4)', '1.5 .. 2.5', ARRAY['(1)'::cube]),
+               (2, '(0.1, 1e20)', '<0.1', NULL),
+               (3, NULL, NULL, NULL)
+            """,
+            options=options
+        )
+
+    def test_cube_bounds(self):
+        table = pq.read_table(self.create_cubes("cube_bounds")).to_pylist()
+        self.assertEqual(table[0]["c"], {"lower": [1.0, 2.0], "upper": [3.0, 4.0]})
+        self.assertEqual(table[0]["s"], [1.5, 2.5])
+        self.assertEqual(table[0]["a"], [{"lower": [1.0], "upper": [1.0]}])
+        self.assertEqual(table[1]["c"], {"lower": [0.1, 1e20], "upper": [0.1, 1e20]})
+        self.assertEqual(table[1]["s"], [0.1, 0.1])
+        self.assertEqual(table[2]["c"], None)
+        self.assertEqual(table[2]["s"], None)
+
+    def test_cube_text(self):
+        table = pq.read_table(self.create_cubes("cube_text", options=["--cube-handling=text"])).to_pylist()
+        self.assertEqual([(r["c"], r["s"], r["a"]) for r in table], [
+            ("(1, 2),(3, 4)", "1.5 .. 2.5", ["(1)"]),
+            ("(0.1, 1e+20)", "<0.1", None),
+            (None, None, None),
+        ])
+
+    def test_text_types(self):
+        schema = "id int, v tsvector, a tsvector[], s seg"
+        values = "(1, 'b a', ARRAY['c'::tsvector], '1 .. 2'), (2, NULL, NULL, NULL)"
+        with self.assertRaises(Exception):
+            wrappers.create_and_export("text_types", "id", schema, values)
+        file = wrappers.create_and_export("text_types", "id", schema, values, options=["--text-types", "tsvector,seg"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, "'a' 'b'", ["'c'"], "1 .. 2"),
+            (2, None, None, None),
+        ])
+
+    def test_isn(self):
+        schema = "id int, e ean13, i isbn, s issn, a isbn[]"
+        values = "(1, '9780316148412', '978-0-316-14841-2', '1234-5679', ARRAY['9780316148412'::isbn, NULL]), (2, NULL, NULL, NULL, NULL)"
+        file = wrappers.create_and_export("isn_types", "id", schema, values)
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, "978-0-316-14841-2", "0-316-14841-5", "1234-5679", ["0-316-14841-5", None]),
+            (2, None, None, None, None),
+        ])
+        file = wrappers.create_and_export("isn_types", "id", schema, values, options=["--isn-handling=int64"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, 9780316148412, 9780316148412, 9771234567003, [9780316148412, None]),
+            (2, None, None, None, None),
+        ])
+
     def test_uuid(self):
         file = wrappers.create_and_export(
             "uuid_types", "id",
@@ -226,6 +445,89 @@ class TestBasic(unittest.TestCase):
             (2, None)
         ])
 
+    def test_uuid_text(self):
+        file = wrappers.create_and_export(
+            "uuid_text_types", "id",
+            "id int, uuid uuid",
+            "(1, '0000000A-000B-000C-000D-E00000000001'::uuid), (2, NULL)",
+            options=["--uuid-handling", "text"]
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, '0000000a-000b-000c-000d-e00000000001'),
+            (2, None)
+        ])
+
+    def test_inet(self):
+        file = wrappers.create_and_export(
+            "inet_types", "id",
+            "id int, addr inet",
+            "(1, '10.1.2.3/8'), (2, '::1'), (3, NULL)",
+            options=["--inet-handling", "struct"]
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, {"family": 4, "prefix": 8, "addr": bytes([10, 1, 2, 3])}),
+            (2, {"family": 6, "prefix": 128, "addr": bytes(15) + b"\x01"}),
+            (3, None)
+        ])
+        file = wrappers.create_and_export(
+            "inet_types", "id",
+            "id int, addr inet",
+            "(1, '10.1.2.3/8'), (2, '::1'), (3, NULL)",
+            options=["--inet-handling", "binary"]
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, bytes(10) + b"\xff\xff" + bytes([10, 1, 2, 3, 104])),
+            (2, bytes(15) + b"\x01" + bytes([128])),
+            (3, None)
+        ])
+
+    def test_money_handling(self):
+        for handling, expected in [("decimal", Decimal("-1234.56")), ("int64", -123456), ("string", "-1234.56")]:
+            file = wrappers.create_and_export(
+                "money_handling", "id",
+                "id int, m money",
+                "(1, -1234.56), (2, NULL)",
+                options=["--money-handling", handling]
+            )
+            duckdb_table = duckdb.read_parquet(file).fetchall()
+            self.assertEqual(duckdb_table, [(1, expected), (2, None)])
+
+    def test_reg_types(self):
+        file = wrappers.run_export("reg_types", query="SELECT 'pg_class'::regclass c, 'int4'::regtype t, 0::regclass z")
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1259, 23, 0)])
+        file = wrappers.run_export("reg_types", query="SELECT 'pg_class'::regclass c, 'int4'::regtype t, 0::regclass z", options=["--reg-handling", "text"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [("pg_class", "integer", "-")])
+
+    def test_snapshot_types(self):
+        file = wrappers.create_and_export(
+            "snapshot_types", "id",
+            "id int, s txid_snapshot, p pg_snapshot",
+            "(1, '10:20:10,14,15', '5:5:'), (2, NULL, NULL)"
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, {"xmin": 10, "xmax": 20, "xip": [10, 14, 15]}, {"xmin": 5, "xmax": 5, "xip": []}),
+            (2, None, None)
+        ])
+
+    def test_bit_binary(self):
+        file = wrappers.create_and_export(
+            "bit_binary", "id",
+            "id int, b bit(12), ba bit(3)[], vb varbit",
+            "(1, B'101000000001', ARRAY[B'110', NULL], B'1011'), (2, NULL, NULL, NULL)",
+            options=["--bit-handling=binary"]
+        )
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, b"\xa0\x10", [b"\xc0", None], "1011"),
+            (2, None, None, None)
+        ])
+        metadata = pq.read_metadata(file).metadata
+        self.assertEqual(json.loads(metadata[b"pg2parquet.bit_lengths"]), {"b": 12, "ba": 3})
+
     def test_dates(self):
         file = wrappers.create_and_export(
             "date_types", "id",
@@ -1,5 +1,12 @@
+import hashlib
+import json
+import os
+import shutil
+import subprocess
 import wrappers
 import unittest
+import duckdb
+import pyarrow.parquet as pq
 
 class TestCLI(unittest.TestCase):
     def test_prints_version(self) -> None:
@@ -27,3 +34,261 @@ class TestCLI(unittest.TestCase):
         self.assertIn("--output-file", out)
         self.assertIn("--table", out)
         self.assertLess(len(out.splitlines()), 40) # short help would better fit on a screen
+
+    def test_dry_run(self) -> None:
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS dry_run_table",
+            "CREATE TABLE dry_run_table (id int, label text)"
+        )
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        p = wrappers.run_pg2parquet(["export", *connection, "--table", "dry_run_table", "--dry-run"])
+        out = p.stdout.decode("utf-8")
+        self.assertIn("optional id: INT32", out)
+        self.assertIn("optional label: String (BYTE_ARRAY)", out)
+
+        p = wrappers.run_pg2parquet(["export", *connection, "--table", "dry_run_table", "--dry-run=json"])
+        schema = json.loads(p.stdout)
+        self.assertEqual([ f["name"] for f in schema["fields"] ], ["id", "label"])
+        self.assertEqual(schema["fields"][1]["logical_type"], "String")
+
+    def test_sslmode(self) -> None:
+        with wrappers.pg_connect() as conn:
+            server_ssl = conn.execute("SHOW ssl").fetchone()[0] == "on"
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        outfile = os.path.join(wrappers.output_directory, "sslmode.csv")
+        def connection_ssl(options, env = {}):
+            args = ["export", *connection, "--query", "SELECT ssl FROM pg_stat_ssl WHERE pid = pg_backend_pid()", "--output-file", outfile, "--format", "csv", "--quiet", *options]
+            p = subprocess.run([ wrappers.pg2parquet_binary, *args ], env={ "PGPASSWORD": wrappers.pg2parquet_password, **env }, capture_output=True)
+            if p.returncode != 0:
+                return None
+            with open(outfile) as f:
+                return f.read().splitlines()[1] == "t"
+
+        self.assertEqual(connection_ssl(["--sslmode", "disable"]), False)
+        self.assertEqual(connection_ssl([], { "PGSSLMODE": "DISABLE" }), False)
+        # the server certificate is not verified, the test server uses a self-signed one
+        self.assertEqual(connection_ssl(["--sslmode", "require"]), True if server_ssl else None)
+        self.assertEqual(connection_ssl(["--tls-mode", "require"]), True if server_ssl else None)
+        self.assertEqual(connection_ssl([], { "PGSSLMODE": "require" }), True if server_ssl else None)
+        self.assertEqual(connection_ssl(["--sslmode", "prefer"]), server_ssl)
+        self.assertIsNone(connection_ssl(["--sslmode", "verify-full"]))
+        self.assertIsNone(connection_ssl(["--sslmode", "verify-ca"]))
+        # the option overrides the environment variable
+        self.assertEqual(connection_ssl(["--sslmode", "disable"], { "PGSSLMODE": "verify-full" }), False)
+        self.assertIsNone(connection_ssl(["--sslmode", "unknown"]))
+        self.assertIsNone(connection_ssl([], { "PGSSLMODE": "unknown" }))
+
+    def test_tls_connection(self) -> None:
+        # the binaries of the musl targets use rustls, the glibc ones the system TLS library
+        with wrappers.pg_connect() as conn:
+            if conn.execute("SHOW ssl").fetchone()[0] != "on":
+                self.skipTest("the server does not support TLS")
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        query = "SELECT ssl, version, i, md5(i::text) AS hash FROM pg_stat_ssl, generate_series(1, 100000) i WHERE pid = pg_backend_pid()"
+        file = wrappers.run_export("tls_connection", query=query, options=["--sslmode", "require"])
+        rows = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(len(rows), 100000)
+        self.assertTrue(all(r[0] for r in rows))
+        self.assertIn(rows[0][1], ["TLSv1.2", "TLSv1.3"])
+        self.assertEqual(rows[-1][2:], (100000, hashlib.md5(b"100000").hexdigest()))
+
+        outfile = os.path.join(wrappers.output_directory, "tls_connection_error.parquet")
+        invalid_cert = os.path.join(wrappers.output_directory, "invalid_cert.pem")
+        with open(invalid_cert, "w") as f:
+            f.write("not a certificate\n")
+        for root_cert, error in [(invalid_cert, "Failed to load certificate"), (os.path.join(wrappers.output_directory, "missing.pem"), "Failed to read certificate file")]:
+            args = ["export", *connection, "--query", "SELECT 1", "--output-file", outfile, "--sslmode", "verify-ca", "--ssl-root-cert", root_cert]
+            p = subprocess.run([ wrappers.pg2parquet_binary, *args ], env={ "PGPASSWORD": wrappers.pg2parquet_password }, capture_output=True)
+            self.assertNotEqual(p.returncode, 0)
+            self.assertIn(error, p.stderr.decode("utf-8"))
+        self.assertFalse(os.path.exists(outfile))
+
+    def test_libpq_environment(self) -> None:
+        outfile = os.path.join(wrappers.output_directory, "libpq_environment.csv")
+        home = os.path.join(wrappers.output_directory, "libpq_environment_home")
+        os.makedirs(home, exist_ok=True)
+        def connect(env):
+            args = ["export", "--query", "SELECT current_database(), current_user", "--output-file", outfile, "--format", "csv", "--quiet", "--no-password"]
+            p = subprocess.run([ wrappers.pg2parquet_binary, *args ], env={ "HOME": home, **env }, capture_output=True)
+            if p.returncode != 0:
+                return None
+            with open(outfile) as f:
+                return f.read().splitlines()[1]
+
+        # no connection options, everything is taken from the environment
+        env = { "PGHOST": wrappers.pg2parquet_host, "PGPORT": wrappers.pg2parquet_port, "PGUSER": wrappers.pg2parquet_user, "PGDATABASE": wrappers.pg2parquet_dbname }
+        expected = f"{wrappers.pg2parquet_dbname},{wrappers.pg2parquet_user}"
+        self.assertEqual(connect({ **env, "PGPASSWORD": wrappers.pg2parquet_password }), expected)
+        # the server may not require a password for local connections
+        requires_password = connect({ **env, "PGPASSWORD": "wrong" }) is None
+
+        # the first matching line of the password file is used, colons and backslashes in the password are escaped
+        escaped_password = wrappers.pg2parquet_password.replace("\\", "\\\\").replace(":", "\\:")
+        pgpass = os.path.join(wrappers.output_directory, "pgpass")
+        with open(pgpass, "w") as f:
+            f.write(f"# comment\n*:*:other_database:*:wrong\n{wrappers.pg2parquet_host}:{wrappers.pg2parquet_port}:*:{wrappers.pg2parquet_user}:{escaped_password}\n*:*:*:*:wrong\n")
+        os.chmod(pgpass, 0o600)
+        self.assertEqual(connect({ **env, "PGPASSFILE": pgpass }), expected)
+        # ~/.pgpass is used without PGPASSFILE
+        shutil.copy(pgpass, os.path.join(home, ".pgpass"))
+        self.assertEqual(connect(env), expected)
+        os.remove(os.path.join(home, ".pgpass"))
+        # with --no-password, the export fails instead of asking for the password
+        self.assertIsNone(connect(env))
+        if requires_password:
+            with open(pgpass, "w") as f:
+                f.write("*:*:*:*:wrong\n")
+            self.assertIsNone(connect({ **env, "PGPASSFILE": pgpass }))
+
+    def test_json_report(self) -> None:
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS report_table",
+            "CREATE TABLE report_table (id int)",
+            "INSERT INTO report_table SELECT generate_series(1, 1000)"
+        )
+        outfile = os.path.join(wrappers.output_directory, "report_table.parquet")
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        p = wrappers.run_pg2parquet(["export", *connection, "--table", "report_table", "--output-file", outfile, "--report", "json", "--quiet"])
+        report = json.loads(p.stdout)
+        self.assertEqual(report["status"], "ok")
+        self.assertEqual(report["rows"], 1000)
+        self.assertEqual(report["row_groups"], 1)
+        self.assertEqual(report["warnings"], [])
+        self.assertEqual(report["output_files"], [{ "path": outfile, "size": os.path.getsize(outfile), "sha256": None }])
+        [column] = report["columns"]
+        self.assertEqual((column["name"], column["null_count"]), ("id", 0))
+        self.assertAlmostEqual(column["approx_distinct_count"], 1000, delta=50)
+        [size] = report["column_sizes"]
+        self.assertEqual(size["column"], "id")
+        self.assertEqual(size["compressed_bytes"], pq.ParquetFile(outfile).metadata.row_group(0).column(0).total_compressed_size)
+        self.assertNotIn("table_stats", report)
+
+    def test_report_table_stats(self) -> None:
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS report_stats_table",
+            "CREATE TABLE report_stats_table (id int)",
+            "INSERT INTO report_stats_table SELECT generate_series(1, 100)",
+            "ANALYZE report_stats_table"
+        )
+        outfile = os.path.join(wrappers.output_directory, "report_stats_table.parquet")
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        p = wrappers.run_pg2parquet(["export", *connection, "--table", "report_stats_table", "--output-file", outfile, "--report", "json", "--report-table-stats", "--quiet"])
+        stats = json.loads(p.stdout)["table_stats"]
+        self.assertEqual(stats["table"], "report_stats_table")
+        self.assertGreaterEqual(stats["seq_scan"], 1)
+        self.assertGreaterEqual(stats["seq_tup_read"], 100)
+        self.assertIsNotNone(stats["last_analyze"])
+
+    def test_inspect(self) -> None:
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS inspect_table",
+            "CREATE TABLE inspect_table (id int, label text)",
+            "INSERT INTO inspect_table VALUES (1, 'a'), (2, NULL)"
+        )
+        file = wrappers.run_export("inspect_table")
+        p = wrappers.run_pg2parquet(["inspect", file, "--json", "--sample-rows", "1"])
+        info = json.loads(p.stdout.decode("utf-8"))
+        self.assertEqual(info["rows"], 2)
+        self.assertEqual([ c["path"] for c in info["columns"] ], ["id", "label"])
+        self.assertEqual([ c["null_count"] for c in info["columns"] ], [0, 1])
+        self.assertEqual(info["sample_rows"], [{"id": 1, "label": "a"}])
+        self.assertEqual(info["metadata"]["pg2parquet.source_table"], "inspect_table")
+
+    def test_diff(self) -> None:
+        old = wrappers.run_export("diff_old", query="SELECT * FROM (VALUES (1, 'a', 1.5), (2, 'b', 2.0), (3, 'c', NULL)) t(id, label, x)")
+        new = wrappers.run_export("diff_new", query="SELECT * FROM (VALUES (1, 'a', true), (2, 'B', false), (4, 'd', NULL)) t(id, label, flag)")
+        p = subprocess.run([wrappers.pg2parquet_binary, "diff", old, new, "--key", "id", "--json"], capture_output=True)
+        self.assertEqual(p.returncode, 2)
+        diff = json.loads(p.stdout.decode("utf-8"))
+        self.assertEqual(diff["schema_changes"], ["column x was removed", "column flag was added"])
+        self.assertEqual((diff["added"], diff["removed"], diff["changed"]), (1, 1, 1))
+        self.assertEqual(diff["added_rows"], [{"id": 4, "label": "d", "flag": None}])
+        self.assertEqual(diff["removed_rows"], [{"id": 3, "label": "c", "x": None}])
+        self.assertEqual(diff["changed_rows"], [{"key": {"id": 2}, "columns": {"label": {"old": "b", "new": "B"}}}])
+
+        p = wrappers.run_pg2parquet(["diff", old, old, "--key", "id"])
+        self.assertIn("Added: 0, removed: 0, changed: 0", p.stdout.decode("utf-8"))
+
+    def test_compact(self) -> None:
+        outdir = os.path.join(wrappers.output_directory, "compact")
+        shutil.rmtree(outdir, ignore_errors=True)
+        os.makedirs(outdir)
+        for i in range(3):
+            file = wrappers.run_export(f"compact_{i}", query=f"SELECT g AS id, 'row ' || g AS label, CASE WHEN g % 4 = 0 THEN NULL ELSE ARRAY[g, -g] END AS arr FROM generate_series({i * 10}, {i * 10 + 9}) g ORDER BY g DESC")
+            shutil.move(file, os.path.join(outdir, f"part_{i}.parquet"))
+        expected = duckdb.sql(f"SELECT * FROM read_parquet('{outdir}/*.parquet') ORDER BY id").fetchall()
+
+        output = os.path.join(outdir, "all.parquet")
+        wrappers.run_pg2parquet(["compact", outdir, "-o", output, "--quiet"])
+        self.assertEqual(pq.ParquetFile(output).metadata.num_row_groups, 3)
+        self.assertEqual(duckdb.read_parquet(output).order("id").fetchall(), expected)
+
+        # the output is not read as an input when compacting the directory again
+        wrappers.run_pg2parquet(["compact", outdir, "-o", output, "--compression", "snappy", "--sort-by", "id", "--quiet"])
+        metadata = pq.ParquetFile(output).metadata
+        self.assertEqual(metadata.num_row_groups, 1)
+        self.assertEqual(metadata.row_group(0).column(0).compression, "SNAPPY")
+        self.assertEqual(duckdb.read_parquet(output).fetchall(), expected)
+
+        other = wrappers.run_export("compact_other", query="SELECT 1 AS id")
+        p = subprocess.run([wrappers.pg2parquet_binary, "compact", output, other, "-o", os.path.join(outdir, "fail.parquet")], capture_output=True)
+        self.assertNotEqual(p.returncode, 0)
+        self.assertIn("column label was removed", p.stderr.decode("utf-8"))
+
+    def test_stream(self) -> None:
+        wrappers.run_sql("SELECT pg_drop_replication_slot(slot_name) FROM pg_replication_slots WHERE slot_name = 'stream_slot'")
+        wrappers.run_sql(
+            "DROP PUBLICATION IF EXISTS stream_publication",
+            "DROP TABLE IF EXISTS stream_table",
+            "CREATE TABLE stream_table (id int PRIMARY KEY, label text)",
+            "CREATE PUBLICATION stream_publication FOR TABLE stream_table"
+        )
+        outdir = os.path.join(wrappers.output_directory, "stream")
+        shutil.rmtree(outdir, ignore_errors=True)
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        stream = ["stream", *connection, "--slot", "stream_slot", "--publication", "stream_publication", "--output-dir", outdir, "--exit-when-idle", "--quiet"]
+        wrappers.run_pg2parquet([*stream, "--create-slot"])
+        wrappers.run_sql("INSERT INTO stream_table VALUES (1, 'a'), (2, 'b')")
+        wrappers.run_sql("UPDATE stream_table SET label = 'c' WHERE id = 1", "DELETE FROM stream_table WHERE id = 2")
+        wrappers.run_pg2parquet(stream)
+        # the slot was advanced, so the changes are not exported again
+        wrappers.run_pg2parquet(stream)
+        [file] = os.listdir(outdir)
+        self.assertEqual(duckdb.read_parquet(os.path.join(outdir, file)).project('op, "table", before, after').fetchall(), [
+            ("insert", "stream_table", None, '{"id":"1","label":"a"}'),
+            ("insert", "stream_table", None, '{"id":"2","label":"b"}'),
+            ("update", "stream_table", None, '{"id":"1","label":"c"}'),
+            ("delete", "stream_table", '{"id":"2"}', None),
+        ])
+        wrappers.run_sql("SELECT pg_drop_replication_slot('stream_slot')")
+
+    def test_estimate(self) -> None:
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS estimate_table",
+            "CREATE TABLE estimate_table AS SELECT g AS id, md5(g::text) AS label FROM generate_series(1, 20000) g",
+            "ANALYZE estimate_table"
+        )
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        p = wrappers.run_pg2parquet(["estimate", *connection, "--table", "estimate_table", "--sample-rows", "1000", "--compression", "zstd,none", "--json"])
+        estimate = json.loads(p.stdout)
+        self.assertEqual(estimate["table_rows"], 20000)
+        self.assertEqual(estimate["sampled_rows"], 1000)
+        [zstd, none] = estimate["compression"]
+        self.assertEqual((zstd["compression"], none["compression"]), ("zstd(3)", "none"))
+        self.assertEqual(zstd["estimated_bytes"], round(zstd["sampled_bytes"] * 20))
+        self.assertLess(zstd["estimated_bytes"], none["estimated_bytes"])
+
+    def test_bench(self) -> None:
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS bench_table",
+            "CREATE TABLE bench_table AS SELECT g AS id, md5(g::text) AS label FROM generate_series(1, 5000) g",
+            "ANALYZE bench_table"
+        )
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        p = wrappers.run_pg2parquet(["bench", *connection, "--table", "bench_table", "--rows", "1e3", "--compression", "zstd,gzip,none", "--compression-level", "1,15", "--json"])
+        bench = json.loads(p.stdout)
+        self.assertEqual(bench["sampled_rows"], 1000)
+        # gzip only supports levels up to 9
+        self.assertEqual([r["compression"] for r in bench["results"]], ["zstd(1)", "zstd(15)", "gzip(1)", "none"])
+        self.assertLess(bench["results"][0]["bytes"], bench["results"][-1]["bytes"])
+        self.assertTrue(all(r["seconds"] > 0 for r in bench["results"]))
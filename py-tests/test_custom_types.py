@@ -1,4 +1,5 @@
 import datetime
+import json
 from decimal import Decimal
 import math
 import uuid
@@ -9,11 +10,13 @@ import unittest
 import duckdb
 import polars as pl
 import pandas as pd
+import pyarrow.parquet as pq
 
 wrappers.run_sql(
     """CREATE TYPE weekday AS ENUM ('monday', 'tuesday', 'wednesday', 'thursday', 'friday', 'saturday', 'sunday');""",
     """CREATE TYPE weekday_range AS RANGE (subtype = weekday);""",
     """CREATE TYPE chain_id AS (pdbid char(4), model int, chain char(1));""",
+    """CREATE TYPE chain_residues AS (chain chain_id, residues int4range, tags text[]);""",
 )
 
 class TestBasic(unittest.TestCase):
@@ -91,6 +94,11 @@ class TestBasic(unittest.TestCase):
         self.assertEqual(polars_df["a"].to_list(), [1, None, 7])
         self.assertEqual(polars_df["b"].to_list(), [[1, 2], None, [7, None, 1]])
 
+        weekdays = {"1": "monday", "2": "tuesday", "3": "wednesday", "4": "thursday", "5": "friday", "6": "saturday", "7": "sunday"}
+        with open(file + ".enums.json") as f:
+            self.assertEqual(json.load(f), { "a": weekdays, "b": weekdays })
+        self.assertEqual(json.loads(pq.read_metadata(file).metadata[b"pg2parquet.enums"]), { "a": weekdays, "b": weekdays })
+
     def test_enum_ranges(self):
         self.maxDiff = None
         file = wrappers.create_and_export(
@@ -180,4 +188,51 @@ class TestBasic(unittest.TestCase):
         self.assertEqual(tuple(pd_rows[2])[:-1], (2, 3, {'pdbid': None, 'model': None, 'chain': None}))
         self.assertEqual(list(tuple(pd_rows[2])[-1]), [None, {'pdbid': '1ehz', 'model': 1.0, 'chain': 'A'}, {'pdbid': None, 'model': None, 'chain': 'A'}])
 
+    def test_composite_type_nested(self):
+        # the nested composites, ranges and arrays are passed to the field appenders by reference
+        self.maxDiff = None
+        rows = """(1, ROW(ROW('1ehz', 1, 'A'), '[1,10)', ARRAY['x', 'y']), ARRAY[ROW(ROW('1ehz', 1, 'B'), '[5,6)', '{}')::chain_residues, NULL]),
+               (2, NULL, NULL),
+               (3, ROW(NULL, 'empty', ARRAY[NULL]), ARRAY[ROW(ROW(NULL, NULL, 'C'), NULL, NULL)::chain_residues])
+            """
+        def r(low, up, low_inc=True, up_inc=False, is_empty=False):
+            return {'lower': low, 'upper': up, 'lower_inclusive': low_inc, 'upper_inclusive': up_inc, 'is_empty': is_empty}
+        b1 = [{'chain': {'pdbid': '1ehz', 'model': 1, 'chain': 'B'}, 'residues': r(5, 6), 'tags': []}, None]
+        b3 = [{'chain': {'pdbid': None, 'model': None, 'chain': 'C'}, 'residues': None, 'tags': None}]
+        expected = [
+            (1, {'chain': {'pdbid': '1ehz', 'model': 1, 'chain': 'A'}, 'residues': r(1, 10), 'tags': ['x', 'y']}, b1),
+            (2, None, None),
+            (3, {'chain': None, 'residues': r(None, None, low_inc=False, is_empty=True), 'tags': [None]}, b3),
+        ]
+        for engine in ["parquet", "arrow"]:
+            file = wrappers.create_and_export(f"custom_composite_type_nested_{engine}", "id", "id int, a chain_residues, b chain_residues[]", rows, options=["--engine", engine])
+            self.assertEqual(duckdb.read_parquet(file).fetchall(), expected)
+
+        file = wrappers.create_and_export("custom_composite_type_nested_flattened", "id", "id int, a chain_residues, b chain_residues[]", rows, options=["--flatten-composites"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, '1ehz', 1, 'A', r(1, 10), ['x', 'y'], b1),
+            (2, None, None, None, None, None, None),
+            (3, None, None, None, r(None, None, low_inc=False, is_empty=True), [None], b3),
+        ])
+
+    def test_composite_type_flattened(self):
+        file = wrappers.create_and_export(
+            "custom_composite_type_flattened", "id",
+            "id int, a chain_id",
+            """(1, ROW('1ehz', 1, 'A')),
+               (2, NULL),
+               (3, '(,,B)')
+            """, options=["--flatten-composites"])
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (1, '1ehz', 1, 'A'),
+            (2, None, None, None),
+            (3, None, None, 'B'),
+        ])
+        self.assertEqual(pl.read_parquet(file).columns, ["id", "a_pdbid", "a_model", "a_chain"])
+
+        # a_chain would be written twice
+        with self.assertRaises(Exception):
+            wrappers.create_and_export("custom_composite_type_flattened_conflict", "id", "id int, a chain_id, a_chain text", "(1, NULL, 'x')", options=["--flatten-composites"])
+
 
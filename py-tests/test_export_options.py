@@ -0,0 +1,854 @@
+import datetime
+import hashlib
+import json
+import os
+import shutil
+import signal
+import subprocess
+import time
+import wrappers
+import unittest
+import duckdb
+import pyarrow as pa
+import pyarrow.parquet as pq
+
+
+class TestExportOptions(unittest.TestCase):
+    def test_sort_by(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS sorted_table",
+            "CREATE TABLE sorted_table (id int, category text)",
+            "INSERT INTO sorted_table VALUES (3, 'a'), (1, 'b'), (2, 'a'), (4, NULL)"
+        )
+        file = wrappers.run_export("sorted_table", options=["--sort-by", "category DESC,id"])
+        duckdb_table = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(duckdb_table, [
+            (4, None),
+            (1, "b"),
+            (2, "a"),
+            (3, "a"),
+        ])
+
+        sorting = pq.ParquetFile(file).metadata.row_group(0).sorting_columns
+        self.assertEqual(len(sorting), 2)
+        self.assertEqual((sorting[0].column_index, sorting[0].descending, sorting[0].nulls_first), (1, True, True))
+        self.assertEqual((sorting[1].column_index, sorting[1].descending, sorting[1].nulls_first), (0, False, False))
+
+    def test_row_group_per_partition_value(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS partitioned_table",
+            "CREATE TABLE partitioned_table (day date, id int)",
+            "INSERT INTO partitioned_table SELECT date '2024-01-01' + i % 3, i FROM generate_series(1, 300) i"
+        )
+        file = wrappers.run_export("partitioned_table", options=["--sort-by", "day", "--row-group-per-partition-value"])
+        metadata = pq.read_metadata(file)
+        self.assertEqual(metadata.num_row_groups, 3)
+        for i in range(3):
+            statistics = metadata.row_group(i).column(0).statistics
+            self.assertEqual(metadata.row_group(i).num_rows, 100)
+            self.assertEqual(statistics.min, statistics.max)
+
+    def test_background_row_groups(self):
+        # the row groups are compressed on a background thread while the next one is filled, the reused appenders must not leak values between the groups
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS background_groups_table",
+            "CREATE TABLE background_groups_table AS SELECT i AS id, md5(i::text) AS name, CASE WHEN i % 7 = 0 THEN NULL ELSE array_fill(i, ARRAY[i % 5]) END AS arr FROM generate_series(1, 20000) i"
+        )
+        outfile = os.path.join(wrappers.output_directory, "background_groups_table.parquet")
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        result = wrappers.run_pg2parquet(["export", *connection, "--table", "background_groups_table", "--output-file", outfile,
+            "--sort-by", "id", "--max-buffer-memory", "64KiB", "--compression", "zstd", "--compression-level", "19", "--report", "json", "--quiet"])
+        report = json.loads(result.stdout)
+        metadata = pq.read_metadata(outfile)
+        self.assertGreater(metadata.num_row_groups, 5)
+        self.assertEqual(report["row_groups"], metadata.num_row_groups)
+        self.assertEqual(report["rows"], 20000)
+        self.assertEqual(report["output_bytes"], sum(
+            metadata.row_group(i).column(c).total_compressed_size for i in range(metadata.num_row_groups) for c in range(metadata.num_columns)))
+
+        expected = [ (i, hashlib.md5(str(i).encode()).hexdigest(), None if i % 7 == 0 else [i] * (i % 5)) for i in range(1, 20001) ]
+        self.assertEqual(duckdb.read_parquet(outfile).fetchall(), expected)
+
+    def test_on_row_error(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS row_error_table",
+            "CREATE TABLE row_error_table (id int, d date, arr date[])",
+            # infinite dates cannot be read into chrono dates
+            "INSERT INTO row_error_table VALUES (1, '2020-01-01', NULL), (2, 'infinity', ARRAY['2020-01-02'::date, 'infinity']), (3, '2020-01-03', NULL)"
+        )
+        with self.assertRaises(Exception):
+            wrappers.run_export("row_error_table", options=["--sort-by", "id"])
+        file = wrappers.run_export("row_error_table", options=["--sort-by", "id", "--on-row-error", "skip"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, datetime.date(2020, 1, 1), None),
+            (3, datetime.date(2020, 1, 3), None),
+        ])
+        file = wrappers.run_export("row_error_table", options=["--sort-by", "id", "--on-row-error", "null-column"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [
+            (1, datetime.date(2020, 1, 1), None),
+            (2, None, None),
+            (3, datetime.date(2020, 1, 3), None),
+        ])
+
+    def test_row_identifier_column(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS row_identifier_table",
+            "CREATE TABLE row_identifier_table (label text, code int PRIMARY KEY, d date)",
+            "INSERT INTO row_identifier_table VALUES ('a', 10, '2020-01-01'), ('b', 20, 'infinity')"
+        )
+        outfile = os.path.join(wrappers.output_directory, "row_identifier_table.parquet")
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        export = ["export", *connection, "--table", "row_identifier_table", "--output-file", outfile, "--on-row-error", "skip", "--report", "json", "--quiet"]
+        # the primary key is used by default
+        result = wrappers.run_pg2parquet(export)
+        report = json.loads(result.stdout)
+        self.assertEqual([ e["row"] for e in report["row_errors"] ], ["code=20"])
+        # the row errors are reported as warnings, which --quiet does not print
+        self.assertEqual(result.stderr, b"")
+        self.assertEqual([ w["message"].split(":")[0] for w in report["warnings"] ], ["Skipping Row[code=20]"])
+        report = json.loads(wrappers.run_pg2parquet([*export, "--row-identifier-column", "label,code"]).stdout)
+        self.assertEqual([ e["row"] for e in report["row_errors"] ], ['label="b", code=20'])
+        with self.assertRaises(Exception):
+            wrappers.run_pg2parquet([*export, "--row-identifier-column", "missing"])
+
+    def test_per_partition_files(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS partitioned_table",
+            "CREATE TABLE partitioned_table (id int, year int, kind text) PARTITION BY RANGE (year)",
+            "CREATE TABLE partitioned_table_2023 PARTITION OF partitioned_table FOR VALUES FROM (2023) TO (2024)",
+            "CREATE TABLE partitioned_table_2024 PARTITION OF partitioned_table FOR VALUES FROM (2024) TO (2025) PARTITION BY LIST (kind)",
+            "CREATE TABLE partitioned_table_2024_a PARTITION OF partitioned_table_2024 FOR VALUES IN ('a')",
+            "CREATE TABLE partitioned_table_2024_other PARTITION OF partitioned_table_2024 DEFAULT",
+            "INSERT INTO partitioned_table VALUES (1, 2023, 'a'), (2, 2024, 'a'), (3, 2024, 'b'), (4, 2024, 'c')"
+        )
+        shutil.rmtree(os.path.join(wrappers.output_directory, "partitioned_table.parquet"), ignore_errors=True)
+        outdir = wrappers.run_export("partitioned_table", options=["--per-partition-files", "--jobs", "2"])
+        self.assertEqual(duckdb.read_parquet(os.path.join(outdir, "partitioned_table_2023.parquet")).fetchall(), [ (1, 2023, "a") ])
+        self.assertEqual(duckdb.read_parquet(os.path.join(outdir, "partitioned_table_2024", "partitioned_table_2024_a.parquet")).fetchall(), [ (2, 2024, "a") ])
+        file = os.path.join(outdir, "partitioned_table_2024", "partitioned_table_2024_other.parquet")
+        self.assertEqual(duckdb.read_parquet(file).order("id").fetchall(), [ (3, 2024, "b"), (4, 2024, "c") ])
+        metadata = pq.ParquetFile(file).metadata.metadata
+        self.assertEqual(metadata[b"pg2parquet.partition_bound"], b"DEFAULT")
+        self.assertEqual(metadata[b"pg2parquet.partition_of"], b"partitioned_table")
+
+    def test_per_partition_files_counters(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS special_floats_partitioned",
+            "CREATE TABLE special_floats_partitioned (id int, f float8) PARTITION BY RANGE (id)",
+            "CREATE TABLE special_floats_partitioned_a PARTITION OF special_floats_partitioned FOR VALUES FROM (0) TO (10)",
+            "CREATE TABLE special_floats_partitioned_b PARTITION OF special_floats_partitioned FOR VALUES FROM (10) TO (20)",
+            "INSERT INTO special_floats_partitioned VALUES (1, 'NaN'), (2, 'Infinity'), (11, 'NaN'), (12, 1)"
+        )
+        outdir = os.path.join(wrappers.output_directory, "special_floats_partitioned")
+        shutil.rmtree(outdir, ignore_errors=True)
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        # the partitions exported in parallel count their values separately
+        report = json.loads(wrappers.run_pg2parquet(["export", *connection, "--table", "special_floats_partitioned", "--output-file", outdir, "--per-partition-files", "--jobs", "2", "--report", "json", "--quiet"]).stdout)
+        self.assertEqual(report["special_float_values"], {"f": 3})
+        self.assertEqual(sorted(w["message"] for w in report["warnings"]), ["Column f contains 1 NaN or Infinity values", "Column f contains 2 NaN or Infinity values"])
+
+    def test_per_partition_files_dotted_names(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS sales",
+            "CREATE TABLE sales (id int, year int) PARTITION BY LIST (year)",
+            'CREATE TABLE "sales.2023" PARTITION OF sales FOR VALUES IN (2023)',
+            'CREATE TABLE "sales.2024" PARTITION OF sales FOR VALUES IN (2024)',
+            "INSERT INTO sales VALUES (1, 2023), (2, 2024)"
+        )
+        shutil.rmtree(os.path.join(wrappers.output_directory, "sales.parquet"), ignore_errors=True)
+        outdir = wrappers.run_export("sales", options=["--per-partition-files"])
+        self.assertEqual(sorted(os.listdir(outdir)), ["_pg2parquet_manifest.json", "sales.2023.parquet", "sales.2024.parquet"])
+        self.assertEqual(duckdb.read_parquet(os.path.join(outdir, "sales.2024.parquet")).fetchall(), [ (2, 2024) ])
+
+    def test_resume_per_partition_files(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS resumed_table",
+            "CREATE TABLE resumed_table (id int) PARTITION BY RANGE (id)",
+            "CREATE TABLE resumed_table_a PARTITION OF resumed_table FOR VALUES FROM (0) TO (10)",
+            "CREATE TABLE resumed_table_b PARTITION OF resumed_table FOR VALUES FROM (10) TO (20)",
+            "INSERT INTO resumed_table VALUES (1), (11)"
+        )
+        shutil.rmtree(os.path.join(wrappers.output_directory, "resumed_table.parquet"), ignore_errors=True)
+        outdir = wrappers.run_export("resumed_table", options=["--per-partition-files"])
+        with open(os.path.join(outdir, "_pg2parquet_manifest.json")) as f:
+            manifest = json.load(f)
+        self.assertEqual(manifest["table"], "resumed_table")
+        self.assertEqual(sorted((e["partition"], e["bound"], e["rows"]) for e in manifest["files"]), [
+            ("resumed_table_a", "FOR VALUES FROM (0) TO (10)", 1),
+            ("resumed_table_b", "FOR VALUES FROM (10) TO (20)", 1),
+        ])
+        self.assertEqual(sorted(e["path"] for e in manifest["files"]), ["resumed_table_a.parquet", "resumed_table_b.parquet"])
+
+        # the export cannot be resumed with a different query
+        with self.assertRaises(Exception):
+            wrappers.run_export("resumed_table", options=["--per-partition-files", "--resume", "--where", "id > 1"])
+
+        # the completed partition is skipped even though it has new rows, the removed file is exported again
+        wrappers.run_sql("INSERT INTO resumed_table VALUES (2), (12)")
+        os.remove(os.path.join(outdir, "resumed_table_b.parquet"))
+        wrappers.run_export("resumed_table", options=["--per-partition-files", "--resume"])
+        self.assertEqual(pq.read_table(os.path.join(outdir, "resumed_table_a.parquet")).to_pylist(), [{"id": 1}])
+        self.assertEqual(sorted(pq.read_table(os.path.join(outdir, "resumed_table_b.parquet")).column("id").to_pylist()), [11, 12])
+
+        # without --resume, all partitions are exported
+        wrappers.run_export("resumed_table", options=["--per-partition-files"])
+        self.assertEqual(sorted(pq.read_table(os.path.join(outdir, "resumed_table_a.parquet")).column("id").to_pylist()), [1, 2])
+
+    def test_output_template(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS template_table",
+            "CREATE TABLE template_table (id int) PARTITION BY RANGE (id)",
+            "CREATE TABLE template_table_a PARTITION OF template_table FOR VALUES FROM (0) TO (10)",
+            "CREATE TABLE template_table_b PARTITION OF template_table FOR VALUES FROM (10) TO (20)",
+            "INSERT INTO template_table VALUES (1), (11)"
+        )
+        today = datetime.datetime.now(datetime.timezone.utc).strftime("%Y-%m-%d")
+        outdir = os.path.join(wrappers.output_directory, "output_template")
+        shutil.rmtree(outdir, ignore_errors=True)
+        export = ["export", "--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        wrappers.run_pg2parquet([*export, "--query", "SELECT 1 AS x", "--output-template", os.path.join(outdir, "{table}", "{date}", "part-{seq:05}.parquet")])
+        self.assertEqual(pq.read_table(os.path.join(outdir, "query", today, "part-00000.parquet")).to_pylist(), [{"x": 1}])
+
+        wrappers.run_pg2parquet([*export, "--table", "template_table", "--per-partition-files", "--output-template", os.path.join(outdir, "{table}-{seq:02}.parquet")])
+        self.assertEqual(pq.read_table(os.path.join(outdir, "template_table_a-00.parquet")).to_pylist(), [{"id": 1}])
+        self.assertEqual(pq.read_table(os.path.join(outdir, "template_table_b-01.parquet")).to_pylist(), [{"id": 11}])
+
+        with self.assertRaises(Exception):
+            wrappers.run_pg2parquet([*export, "--query", "SELECT 1", "--output-template", os.path.join(outdir, "{unknown}.parquet")])
+        # all partitions would be written into the same file
+        with self.assertRaises(Exception):
+            wrappers.run_pg2parquet([*export, "--table", "template_table", "--per-partition-files", "--output-template", os.path.join(outdir, "{date}.parquet")])
+
+    def test_chunk_size(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS chunked_table",
+            "CREATE TABLE chunked_table (a int, b text, PRIMARY KEY (a, b))",
+            "INSERT INTO chunked_table SELECT i % 7, 'x' || (i / 7) FROM generate_series(1, 1000) i"
+        )
+        file = wrappers.run_export("chunked_table", options=["--chunk-size", "1_00"])
+        rows = duckdb.read_parquet(file).fetchall()
+        self.assertEqual(len(rows), 1000)
+        self.assertEqual(sorted(rows), sorted((i % 7, f"x{i // 7}") for i in range(1, 1001)))
+        with self.assertRaises(Exception):
+            wrappers.run_export("chunked_table", query="SELECT * FROM chunked_table", options=["--chunk-size", "100"])
+
+    def test_chunk_size_duplicate_sort_key(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS chunked_duplicates",
+            "CREATE TABLE chunked_duplicates (id int PRIMARY KEY, g int)",
+            "INSERT INTO chunked_duplicates SELECT i, i / 3 FROM generate_series(1, 10) i"
+        )
+        # the rows with the same g are not skipped at the chunk boundaries, the primary key is used as the tiebreaker
+        file = wrappers.run_export("chunked_duplicates", options=["--sort-by", "g", "--chunk-size", "2"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [ (i, i // 3) for i in range(1, 11) ])
+        self.assertIn(b'ORDER BY "g" ASC NULLS LAST, "id" ASC NULLS LAST', pq.read_metadata(file).metadata[b"pg2parquet.query"])
+
+        wrappers.run_sql("ALTER TABLE chunked_duplicates DROP CONSTRAINT chunked_duplicates_pkey")
+        with self.assertRaises(Exception):
+            wrappers.run_export("chunked_duplicates", options=["--sort-by", "g", "--chunk-size", "2"])
+        wrappers.run_sql("CREATE UNIQUE INDEX ON chunked_duplicates (id)")
+        with self.assertRaises(Exception):
+            wrappers.run_export("chunked_duplicates", options=["--sort-by", "g", "--chunk-size", "2"])
+        wrappers.run_sql("ALTER TABLE chunked_duplicates ALTER COLUMN id SET NOT NULL")
+        file = wrappers.run_export("chunked_duplicates", options=["--sort-by", "g", "--chunk-size", "2"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [ (i, i // 3) for i in range(1, 11) ])
+
+    def test_file_metadata(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS metadata_table",
+            "CREATE TABLE metadata_table (id int)",
+            "INSERT INTO metadata_table VALUES (1)"
+        )
+        file = wrappers.run_export("metadata_table", options=["--file-metadata", "job=nightly", "--file-metadata", "owner=data=team"])
+        metadata = pq.ParquetFile(file).metadata.metadata
+        self.assertEqual(metadata[b"job"], b"nightly")
+        self.assertEqual(metadata[b"owner"], b"data=team")
+        self.assertEqual(metadata[b"pg2parquet.source_table"], b"metadata_table")
+        self.assertIn(b"metadata_table", metadata[b"pg2parquet.query"])
+        self.assertIn(b"pg2parquet.exported_at", metadata)
+        self.assertIn(b"pg2parquet.server_version", metadata)
+        self.assertNotIn(b"pg2parquet.job_id", metadata)
+
+    def test_job_id_and_created_by(self):
+        file = wrappers.run_export("job_id", query="SELECT 1 AS id", options=["--job-id", "run-1234", "--created-by", "nightly-pipeline 2.1"])
+        metadata = pq.ParquetFile(file).metadata
+        self.assertEqual(metadata.metadata[b"pg2parquet.job_id"], b"run-1234")
+        self.assertEqual(metadata.created_by, "nightly-pipeline 2.1")
+
+    def test_generated_columns(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS generated_table",
+            "CREATE TABLE generated_table (id int, doubled int GENERATED ALWAYS AS (id * 2) STORED)",
+            "INSERT INTO generated_table (id) VALUES (1), (2)"
+        )
+        file = wrappers.run_export("generated_table")
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [ (1, 2), (2, 4) ])
+        metadata = pq.ParquetFile(file).metadata.metadata
+        self.assertIn(b'SELECT "id", "doubled" FROM generated_table', metadata[b"pg2parquet.query"])
+        schema = pq.read_schema(file)
+        self.assertEqual(schema.field("doubled").metadata, { b"pg2parquet.generation_expression": b"(id * 2)" })
+        self.assertIsNone(schema.field("id").metadata)
+        file = wrappers.run_export("generated_table", options=["--engine", "arrow"])
+        self.assertEqual(pq.read_schema(file).field("doubled").metadata, { b"pg2parquet.generation_expression": b"(id * 2)" })
+
+    def test_retry_resume(self):
+        # the view kills its own connection once, in the middle of the export
+        wrappers.run_sql(
+            "DROP VIEW IF EXISTS retry_view",
+            "DROP TABLE IF EXISTS retry_table",
+            "DROP SEQUENCE IF EXISTS retry_kill_seq",
+            "CREATE TABLE retry_table AS SELECT g AS id, 'row ' || g AS label FROM generate_series(1, 10000) g",
+            "CREATE SEQUENCE retry_kill_seq",
+            "CREATE VIEW retry_view AS SELECT * FROM retry_table WHERE CASE WHEN id = 5000 AND nextval('retry_kill_seq') = 1 THEN pg_terminate_backend(pg_backend_pid()) ELSE true END"
+        )
+        file = wrappers.run_export("retry_view", options=["--sort-by", "id", "--retries", "1", "--retry-backoff", "10ms"])
+        ids = [ id for (id,) in duckdb.read_parquet(file).project("id").fetchall() ]
+        self.assertEqual(ids, list(range(1, 10001)))
+
+    def test_retry_duplicate_sort_key(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS retry_duplicates",
+            "CREATE TABLE retry_duplicates (id int, g int)",
+            "INSERT INTO retry_duplicates SELECT i, i / 3 FROM generate_series(1, 10) i"
+        )
+        outfile = os.path.join(wrappers.output_directory, "retry_duplicates.parquet")
+        connection = ["--host", wrappers.pg2parquet_host, "--port", wrappers.pg2parquet_port, "--user", wrappers.pg2parquet_user, "--dbname", wrappers.pg2parquet_dbname]
+        export = ["export", *connection, "--table", "retry_duplicates", "--output-file", outfile, "--sort-by", "g", "--retries", "1", "--report", "json", "--quiet"]
+        # resuming after the last g would skip the rows with the same g, the export is restarted instead
+        report = json.loads(wrappers.run_pg2parquet(export).stdout)
+        self.assertEqual([ w["message"].split(",")[0] for w in report["warnings"] ], ["The export cannot be resumed"])
+
+        wrappers.run_sql("ALTER TABLE retry_duplicates ADD PRIMARY KEY (id)")
+        report = json.loads(wrappers.run_pg2parquet(export).stdout)
+        self.assertEqual(report["warnings"], [])
+        self.assertIn(b'ORDER BY "g" ASC NULLS LAST, "id" ASC NULLS LAST', pq.read_metadata(outfile).metadata[b"pg2parquet.query"])
+        self.assertEqual(duckdb.read_parquet(outfile).fetchall(), [ (i, i // 3) for i in range(1, 11) ])
+
+    def test_distinct(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS distinct_table",
+            "CREATE TABLE distinct_table AS SELECT g / 3 AS a, g % 2 AS b FROM generate_series(1, 12) g"
+        )
+        file = wrappers.run_export("distinct_table", options=["--distinct", "--sort-by", "a,b"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(a, b) for a in range(4) for b in range(2)] + [(4, 0)])
+        file = wrappers.run_export("distinct_table", options=["--distinct-on", "a", "--sort-by", "a,b"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(a, 0) for a in range(5)])
+
+    def test_pg_setting(self):
+        file = wrappers.run_export("pg_setting", query="SELECT current_setting('work_mem') AS work_mem, current_setting('application_name') AS app", options=["--pg-setting", "work_mem=123MB", "--pg-setting", "application_name=nightly_job"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [("123MB", "nightly_job")])
+
+    def test_snapshot(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS snapshot_table",
+            "CREATE TABLE snapshot_table (id int)",
+            "INSERT INTO snapshot_table VALUES (1), (2)"
+        )
+        with wrappers.pg_connect() as conn:
+            with conn.cursor() as cur:
+                cur.execute("BEGIN ISOLATION LEVEL REPEATABLE READ")
+                cur.execute("SELECT pg_export_snapshot()")
+                (snapshot,) = cur.fetchone()
+                wrappers.run_sql("INSERT INTO snapshot_table VALUES (3)")
+                file = wrappers.run_export("snapshot_table", options=["--snapshot", snapshot])
+                conn.rollback()
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1,), (2,)])
+
+    def test_read_only(self):
+        file = wrappers.run_export("read_only", query="SELECT current_setting('default_transaction_read_only') AS ro", options=["--read-only"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [("on",)])
+        # no standby is available, so the primary is used
+        file = wrappers.run_export("read_only_hosts", query="SELECT 1 AS x", options=["--read-only", "--host", wrappers.pg2parquet_host])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1,)])
+        file = wrappers.run_export("read_only_host_list", query="SELECT 1 AS x", options=["--read-only", "--host", f"{wrappers.pg2parquet_host},{wrappers.pg2parquet_host}"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1,)])
+        wrappers.run_sql("DROP TABLE IF EXISTS read_only_table", "CREATE TABLE read_only_table (id int)")
+        with self.assertRaises(Exception):
+            wrappers.run_export("read_only_insert", query="WITH x AS (INSERT INTO read_only_table VALUES (1) RETURNING id) SELECT * FROM x", options=["--read-only"])
+
+    def test_import_roundtrip(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS import_source",
+            "DROP TABLE IF EXISTS import_target",
+            "CREATE TABLE import_source (id int, label text, amount numeric(10, 2), created timestamptz, day date, at time, tags text[], ids int[])",
+            "INSERT INTO import_source VALUES (1, 'a', 1.5, '2024-01-02 03:04:05.123456+00', '2024-02-29', '12:34:56.789', '{x,y}', '{1,NULL}'), (2, NULL, NULL, NULL, NULL, NULL, NULL, '{}')"
+        )
+        file = wrappers.run_export("import_source", options=["--numeric-handling", "decimal", "--decimal-precision", "10", "--decimal-scale", "2"])
+        wrappers.run_pg2parquet([
+            "import",
+            "--host", wrappers.pg2parquet_host,
+            "--port", wrappers.pg2parquet_port,
+            "--user", wrappers.pg2parquet_user,
+            "--dbname", wrappers.pg2parquet_dbname,
+            "--input-file", file,
+            "--table", "import_target",
+        ])
+        with wrappers.pg_connect() as conn:
+            with conn.cursor() as cur:
+                cur.execute("SELECT count(*) FROM import_target")
+                self.assertEqual(cur.fetchone(), (2,))
+                cur.execute("SELECT * FROM import_source EXCEPT SELECT * FROM import_target")
+                self.assertEqual(cur.fetchall(), [])
+
+    def test_verify(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS verify_table",
+            "CREATE TABLE verify_table (id int, label text, day date)",
+            "INSERT INTO verify_table VALUES (1, 'a', '2024-01-01'), (2, NULL, '2024-02-29'), (3, 'c', NULL)"
+        )
+        file = wrappers.run_export("verify_table")
+        verify_args = [
+            "verify",
+            "--host", wrappers.pg2parquet_host,
+            "--port", wrappers.pg2parquet_port,
+            "--user", wrappers.pg2parquet_user,
+            "--dbname", wrappers.pg2parquet_dbname,
+            "--input-file", file,
+            "--min-max",
+        ]
+        r = wrappers.run_pg2parquet(verify_args)
+        self.assertIn("Verification passed", r.stdout.decode("utf-8"))
+
+        wrappers.run_sql("UPDATE verify_table SET label = NULL WHERE id = 1")
+        with self.assertRaises(Exception):
+            wrappers.run_pg2parquet(verify_args)
+
+    def test_emit_ddl(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS ddl_table",
+            "DROP TYPE IF EXISTS ddl_enum",
+            "CREATE TYPE ddl_enum AS ENUM ('b', 'a')",
+            "CREATE TABLE ddl_table (id int NOT NULL, label varchar(10), e ddl_enum)",
+        )
+        ddl_file = os.path.join(wrappers.output_directory, "ddl_table.sql")
+        wrappers.run_export("ddl_table", options=["--emit-ddl", ddl_file])
+        with open(ddl_file) as f:
+            ddl = f.read()
+        self.assertIn("CREATE TYPE ddl_enum AS ENUM ('b', 'a');", ddl)
+        self.assertIn('"id" integer NOT NULL', ddl)
+        self.assertIn('"label" character varying(10)', ddl)
+        self.assertIn('"e" ddl_enum', ddl)
+
+    def test_text_formats(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS text_format_table",
+            "CREATE TABLE text_format_table (id int, label text, tags text[])",
+            "INSERT INTO text_format_table VALUES (1, 'a,b', '{x}'), (2, NULL, NULL)"
+        )
+        # the file name is decided by run_export, the content is not Parquet
+        csv_file = wrappers.run_export("text_format_table", options=["--format", "csv"])
+        with open(csv_file) as f:
+            self.assertEqual(f.read(), 'id,label,tags\n1,"a,b",{x}\n2,,\n')
+
+        jsonl_file = wrappers.run_export("text_format_table", options=["--format", "jsonl"])
+        with open(jsonl_file) as f:
+            rows = [ json.loads(line) for line in f ]
+        self.assertEqual(rows, [
+            {"id": 1, "label": "a,b", "tags": ["x"]},
+            {"id": 2, "label": None, "tags": None},
+        ])
+
+        # the values are not converted by pg2parquet, the type handling options are rejected instead of ignored
+        for options in [["--decimal-scale", "4"], ["--invalid-utf8", "replace"], ["--add-meta-columns", "row_number"], ["--json-handling", "text-marked-as-json"]]:
+            with self.assertRaises(Exception):
+                wrappers.run_export("text_format_table", options=["--format", "csv", *options])
+
+    def test_null_string(self):
+        query = "SELECT * FROM (VALUES (1, 'a'::text, ARRAY['', NULL]::text[]), (2, '', NULL), (3, NULL, '{}')) v(id, label, tags)"
+        file = wrappers.run_export("null_string", query=query, options=["--null-string", "\\N"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1, "a", ["", "\\N"]), (2, "", None), (3, "\\N", [])])
+        file = wrappers.run_export("empty_string_as_null", query=query, options=["--empty-string-as-null"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1, "a", [None, None]), (2, None, None), (3, None, [])])
+        csv_file = wrappers.run_export("null_string_csv", query=query, options=["--format", "csv", "--null-string", "NULL"])
+        with open(csv_file) as f:
+            self.assertEqual(f.read(), 'id,label,tags\n1,a,"{"""",NULL}"\n2,,NULL\n3,NULL,{}\n')
+
+    def test_arrow_format(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS arrow_format_table",
+            "CREATE TABLE arrow_format_table (id int, label text, tags text[])",
+            "INSERT INTO arrow_format_table VALUES (1, 'a', '{x,y}'), (2, NULL, NULL)"
+        )
+        file = wrappers.run_export("arrow_format_table", options=["--format", "arrow"])
+        table = pa.ipc.open_file(file).read_all()
+        self.assertEqual(table.to_pylist(), [
+            {"id": 1, "label": "a", "tags": ["x", "y"]},
+            {"id": 2, "label": None, "tags": None},
+        ])
+
+    def test_delta_log(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS delta_table",
+            "CREATE TABLE delta_table (id int, label text, tags int[])",
+            "INSERT INTO delta_table VALUES (1, 'a', '{1,2}'), (2, NULL, NULL)"
+        )
+        table_dir = os.path.join(wrappers.output_directory, "delta_table")
+        shutil.rmtree(table_dir, ignore_errors=True)
+        os.makedirs(table_dir)
+        wrappers.run_export("delta_table/part1", query="SELECT * FROM delta_table", options=["--delta-log"])
+        wrappers.run_export("delta_table/part2", query="SELECT * FROM delta_table WHERE id = 1", options=["--delta-log"])
+
+        commits = sorted(os.listdir(os.path.join(table_dir, "_delta_log")))
+        self.assertEqual(commits, ["00000000000000000000.json", "00000000000000000001.json"])
+        with open(os.path.join(table_dir, "_delta_log", commits[1])) as f:
+            actions = [ json.loads(line) for line in f ]
+        add = next(a["add"] for a in actions if "add" in a)
+        self.assertEqual(add["path"], "part2.parquet")
+        self.assertEqual(json.loads(add["stats"])["numRecords"], 1)
+        with open(os.path.join(table_dir, "_delta_log", "00000000000000000000.json")) as f:
+            metadata = next(json.loads(line)["metaData"] for line in f if "metaData" in line)
+        schema = json.loads(metadata["schemaString"])
+        self.assertEqual([ (f["name"], f["type"]) for f in schema["fields"] ], [
+            ("id", "integer"),
+            ("label", "string"),
+            ("tags", {"type": "array", "elementType": "integer", "containsNull": True}),
+        ])
+
+    def test_checksum(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS checksum_table",
+            "CREATE TABLE checksum_table (id int, label text)",
+            "INSERT INTO checksum_table VALUES (1, 'a'), (2, NULL)"
+        )
+        file = wrappers.run_export("checksum_table", options=["--checksum", "sha256"])
+        with open(file, "rb") as f:
+            expected = hashlib.sha256(f.read()).hexdigest()
+        with open(file + ".sha256") as f:
+            self.assertEqual(f.read(), f"{expected}  checksum_table.parquet\n")
+
+    def test_watermark_column(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS watermark_table",
+            "CREATE TABLE watermark_table (id int, updated_at timestamptz)",
+            "INSERT INTO watermark_table VALUES (1, '2024-03-01 12:00:00+00'), (2, NULL), (3, '2024-01-15 08:30:00.25+00')"
+        )
+        file = wrappers.run_export("watermark_table", options=["--watermark-column", "updated_at"])
+        metadata = pq.read_metadata(file).metadata
+        self.assertEqual(metadata[b"pg2parquet.watermark.column"], b"updated_at")
+        self.assertEqual(metadata[b"pg2parquet.watermark.min"], b"2024-01-15T08:30:00.250Z")
+        self.assertEqual(metadata[b"pg2parquet.watermark.max"], b"2024-03-01T12:00:00Z")
+
+        file = wrappers.run_export("watermark_table", options=["--watermark-column", "id", "--where", "updated_at > '2024-03-01T12:00:00Z'"])
+        metadata = pq.read_metadata(file).metadata
+        self.assertEqual(metadata[b"pg2parquet.watermark.column"], b"id")
+        self.assertNotIn(b"pg2parquet.watermark.min", metadata)
+
+    def test_max_buffer_memory(self):
+        file = wrappers.run_export("max_buffer_memory", query="SELECT i, repeat('x', 1000) AS s FROM generate_series(1, 5000) i", options=["--max-buffer-memory", "1MiB"])
+        metadata = pq.read_metadata(file)
+        self.assertEqual(metadata.num_rows, 5000)
+        self.assertGreaterEqual(metadata.num_row_groups, 4)
+
+    def test_flush_interval(self):
+        # the rows are large enough to be sent by the server one by one
+        query = "SELECT i, repeat('x', 20000) || pg_sleep(0.5) AS s FROM generate_series(1, 6) i"
+        metadata = pq.read_metadata(wrappers.run_export("flush_interval_off", query=query))
+        self.assertEqual(metadata.num_row_groups, 1)
+        metadata = pq.read_metadata(wrappers.run_export("flush_interval", query=query, options=["--flush-interval", "1s"]))
+        self.assertEqual(metadata.num_rows, 6)
+        self.assertGreaterEqual(metadata.num_row_groups, 2)
+
+    def test_flush_interval_without_new_rows(self):
+        # the first row is received immediately, the second one after a long pause,
+        # the row group is written out while waiting for it
+        query = "SELECT i, repeat('x', 100000) AS s FROM generate_series(1, 3) i, LATERAL (SELECT pg_sleep(CASE WHEN i = 3 THEN 2 ELSE 0 END)) x"
+        metadata = pq.read_metadata(wrappers.run_export("flush_interval_idle", query=query, options=["--flush-interval", "500ms"]))
+        self.assertEqual([metadata.row_group(i).num_rows for i in range(metadata.num_row_groups)], [1, 2])
+
+    def test_arrow_engine(self):
+        query = "SELECT i, i::int2 AS s, CASE WHEN i % 3 = 0 THEN NULL ELSE 'x' || i END AS t, i % 2 = 0 AS b, '2020-01-01'::date + i AS d, decode(md5(i::text), 'hex') AS h FROM generate_series(1, 1000) i"
+        parquet_file = pq.read_table(wrappers.run_export("engine_parquet", query=query))
+        arrow_file = wrappers.run_export("engine_arrow", query=query, options=["--engine", "arrow"])
+        self.assertIn(b"ARROW:schema", pq.read_metadata(arrow_file).metadata)
+        self.assertEqual(pq.read_table(arrow_file).to_pylist(), parquet_file.to_pylist())
+
+        # nested columns are written by the parquet engine
+        file = wrappers.run_export("engine_arrow_nested", query="SELECT 1 AS i, ARRAY[1, 2] AS a", options=["--engine", "arrow"])
+        self.assertNotIn(b"ARROW:schema", pq.read_metadata(file).metadata)
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"i": 1, "a": [1, 2]}])
+
+    def test_page_checksums(self):
+        query = "SELECT i, 'value ' || i AS t FROM generate_series(1, 1000) i"
+        file = wrappers.run_export("page_checksums", query=query, options=["--page-checksums", "--compression", "none"])
+        self.assertEqual(pq.read_table(file, page_checksum_verification=True).num_rows, 1000)
+
+        data = bytearray(open(file, "rb").read())
+        position = data.find(b"value 500")
+        data[position] ^= 1
+        with open(file, "wb") as f:
+            f.write(data)
+        with self.assertRaises(OSError):
+            pq.read_table(file, page_checksum_verification=True)
+
+    def test_auto_compression(self):
+        query = "SELECT i, (ARRAY['red', 'green', 'blue'])[1 + i % 3] AS color, md5(i::text) AS hash, decode(md5(random()::text) || md5(random()::text), 'hex') AS blob FROM generate_series(1, 50000) i"
+        file = wrappers.run_export("auto_compression", query=query, options=["--compression", "auto", "--max-buffer-memory", "1MiB"])
+        table = pq.read_table(file)
+        self.assertEqual(table.column("i").to_pylist(), list(range(1, 50001)))
+        metadata = pq.read_metadata(file)
+        self.assertGreaterEqual(metadata.num_row_groups, 2)
+        # the first row group is compressed with the default zstd, the other ones with the compression chosen for each column
+        self.assertEqual(metadata.row_group(0).column(3).compression, "ZSTD")
+        self.assertEqual(metadata.row_group(1).column(3).compression, "UNCOMPRESSED")
+        self.assertIn("RLE_DICTIONARY", metadata.row_group(1).column(1).encodings)
+        self.assertNotIn("RLE_DICTIONARY", metadata.row_group(1).column(2).encodings)
+
+    def test_lz4_raw_compression(self):
+        query = "SELECT i, md5(i::text) AS hash FROM generate_series(1, 1000) i"
+        for compression in ["lz4-raw", "lz4_raw"]:
+            file = wrappers.run_export("lz4_raw_compression", query=query, options=["--compression", compression])
+            info = json.loads(wrappers.run_pg2parquet(["inspect", file, "--json"]).stdout)
+            self.assertEqual([ c["compression"] for c in info["columns"] ], ["LZ4_RAW", "LZ4_RAW"])
+            self.assertEqual(pq.read_table(file).column("i").to_pylist(), list(range(1, 1001)))
+            self.assertEqual(duckdb.read_parquet(file).fetchall()[0], (1, hashlib.md5(b"1").hexdigest()))
+
+        # lz4 has no compression levels
+        with self.assertRaises(Exception):
+            wrappers.run_export("lz4_raw_compression_level", query=query, options=["--compression", "lz4-raw", "--compression-level", "3"])
+
+    def test_pre_post_sql(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS export_audit",
+            "CREATE TABLE export_audit (name text, rows int8)",
+        )
+        query = "SELECT i FROM generate_series(1, 100) i WHERE i <= (SELECT max_rows FROM export_limit)"
+        wrappers.run_export("pre_post_sql", query=query, options=[
+            "--pre-sql", "CREATE TEMP TABLE export_limit AS SELECT 42 AS max_rows",
+            "--post-sql", "INSERT INTO export_audit VALUES ('pre_post_sql', current_setting('pg2parquet.rows')::int8)",
+        ])
+        file = wrappers.run_export("export_audit")
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"name": "pre_post_sql", "rows": 42}])
+
+        # a failing --pre-sql stops the export before anything is written
+        with self.assertRaises(Exception):
+            wrappers.run_export("pre_sql_failed", query="SELECT 1 AS x", options=["--pre-sql", "DO $$ BEGIN RAISE EXCEPTION 'validation failed'; END $$"])
+        self.assertFalse(os.path.exists(os.path.join(wrappers.output_directory, "pre_sql_failed.parquet")))
+
+    def test_columns_definition_list(self):
+        wrappers.run_sql(
+            "CREATE OR REPLACE FUNCTION report_fn(n int) RETURNS SETOF record LANGUAGE sql AS $$ SELECT i, 'row ' || i FROM generate_series(1, n) i $$",
+        )
+        file = wrappers.run_export("columns_definition_list", query="SELECT * FROM report_fn(3)", options=["--columns", "id int, name text"])
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"id": 1, "name": "row 1"}, {"id": 2, "name": "row 2"}, {"id": 3, "name": "row 3"}])
+
+        file = wrappers.run_export("columns_cast", query="SELECT '42' AS x", options=["--columns", "answer int8"])
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"answer": 42}])
+
+    def test_lock_key(self):
+        with wrappers.pg_connect() as conn:
+            with conn.cursor() as cur:
+                cur.execute("SELECT pg_advisory_lock(42)")
+                with self.assertRaises(Exception):
+                    wrappers.run_export("lock_key", query="SELECT 1 AS x", options=["--lock-key=42", "--pg-setting", "lock_timeout=100ms"])
+                cur.execute("SELECT pg_advisory_unlock(42)")
+        file = wrappers.run_export("lock_key", query="SELECT 1 AS x", options=["--lock-key=42", "--pg-setting", "lock_timeout=100ms"])
+        self.assertEqual(pq.read_table(file).num_rows, 1)
+
+    def test_where_limit_sample(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS filtered_table",
+            "CREATE TABLE filtered_table (id int PRIMARY KEY)",
+            "INSERT INTO filtered_table SELECT generate_series(1, 10000)"
+        )
+        file = wrappers.run_export("filtered_table", options=["--where", "id % 2 = 0", "--limit", "100", "--sort-by", "id"])
+        self.assertEqual(pq.read_table(file).column("id").to_pylist(), list(range(2, 202, 2)))
+        file = wrappers.run_export("filtered_table", options=["--sample", "0.1"])
+        self.assertLess(pq.read_metadata(file).num_rows, 5000)
+        file = wrappers.run_export("filtered_query", query="SELECT id FROM filtered_table", options=["--where", "id <= 10", "--limit", "5"])
+        self.assertEqual(pq.read_metadata(file).num_rows, 5)
+
+    def test_add_column(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS people_table",
+            "CREATE TABLE people_table (id int PRIMARY KEY, first_name text, last_name text)",
+            "INSERT INTO people_table VALUES (1, 'Ada', 'Lovelace'), (2, 'Alan', 'Turing')"
+        )
+        file = wrappers.run_export("people_table", options=["--add-column", "full_name=first_name || ' ' || last_name", "--add-column", "id2=id * 2", "--sort-by", "id"])
+        self.assertEqual(pq.read_table(file).to_pylist(), [
+            {"id": 1, "first_name": "Ada", "last_name": "Lovelace", "full_name": "Ada Lovelace", "id2": 2},
+            {"id": 2, "first_name": "Alan", "last_name": "Turing", "full_name": "Alan Turing", "id2": 4},
+        ])
+
+    def test_rename_column(self):
+        file = wrappers.run_export("rename_query",
+            query='SELECT 1 "UserID", 2 "HTTPServer", 3 "firstName", 4 x',
+            options=["--column-case", "snake", "--rename-column", "x=renamed"])
+        self.assertEqual(pq.read_table(file).column_names, ["user_id", "http_server", "first_name", "renamed"])
+        with self.assertRaises(Exception):
+            wrappers.run_export("rename_query", query='SELECT 1 "UserID", 2 user_id', options=["--column-case", "snake"])
+
+        csv_file = wrappers.run_export("rename_csv", query='SELECT 1 "UserID", 2 "HTTPServer", 3 "firstName", 4 x', options=["--format", "csv", "--column-case", "snake", "--rename-column", "x=renamed"])
+        with open(csv_file) as f:
+            self.assertEqual(f.read(), "user_id,http_server,first_name,renamed\n1,2,3,4\n")
+        jsonl_file = wrappers.run_export("rename_jsonl", query='SELECT 1 "UserID", 2 x', options=["--format", "jsonl", "--column-case", "lower", "--rename-column", "x=renamed"])
+        with open(jsonl_file) as f:
+            self.assertEqual(json.loads(f.read()), {"userid": 1, "renamed": 2})
+        with self.assertRaises(Exception):
+            wrappers.run_export("rename_csv", query='SELECT 1 "UserID", 2 user_id', options=["--format", "csv", "--column-case", "snake"])
+
+    def test_column_order(self):
+        query = 'SELECT 1 b, 2 "C", 3 a'
+        file = wrappers.run_export("column_order_name", query=query, options=["--column-order", "name-asc", "--column-case", "lower"])
+        self.assertEqual(pq.read_table(file).column_names, ["a", "b", "c"])
+        file = wrappers.run_export("column_order_custom", query=query, options=["--column-order", "custom:a,C"])
+        self.assertEqual(pq.read_table(file).column_names, ["a", "C", "b"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(3, 2, 1)])
+        with self.assertRaises(Exception):
+            wrappers.run_export("column_order_custom", query=query, options=["--column-order", "custom:missing"])
+
+    def test_meta_columns(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS meta_columns_table",
+            "CREATE TABLE meta_columns_table AS SELECT g AS id FROM generate_series(1, 5000) g"
+        )
+        file = wrappers.run_export("meta_columns_table", options=["--add-meta-columns", "row_number,exported_at,source_table", "--sort-by", "id", "--max-buffer-memory", "10kB"])
+        table = pq.read_table(file)
+        self.assertEqual(table.column_names, ["id", "_row_number", "_exported_at", "_source_table"])
+        self.assertEqual(table.column("_row_number").to_pylist(), list(range(1, 5001)))
+        self.assertEqual(set(table.column("_source_table").to_pylist()), {"meta_columns_table"})
+        self.assertEqual(len(set(table.column("_exported_at").to_pylist())), 1)
+        file = wrappers.run_export("meta_columns_query", query="SELECT 1 AS x", options=["--add-meta-columns", "source_table"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1, None)])
+
+    def test_field_ids(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS field_ids_table",
+            "CREATE TABLE field_ids_table (a int, dropped int, b text)",
+            "ALTER TABLE field_ids_table DROP COLUMN dropped",
+            "INSERT INTO field_ids_table VALUES (1, 'x')"
+        )
+        file = wrappers.run_export("field_ids_table", options=["--field-ids", "attnum", "--rename-column", "b=renamed", "--add-meta-columns", "row_number", "--field-id", "_row_number=100"])
+        schema = pq.read_schema(file)
+        field_ids = { name: schema.field(name).metadata[b"PARQUET:field_id"] for name in schema.names }
+        self.assertEqual(field_ids, { "a": b"1", "renamed": b"3", "_row_number": b"100" })
+
+        with self.assertRaises(Exception):
+            wrappers.run_export("field_ids_query", query="SELECT a, a AS a2 FROM field_ids_table", options=["--field-ids", "attnum"])
+
+    def test_materialized_view(self):
+        wrappers.run_sql(
+            "DROP MATERIALIZED VIEW IF EXISTS matview_export",
+            "CREATE MATERIALIZED VIEW matview_export AS SELECT 1 AS id, 'a'::text AS label WITH NO DATA"
+        )
+        with self.assertRaises(Exception):
+            wrappers.run_export("matview_export")
+        file = wrappers.run_export("matview_export", options=["--refresh-matview"])
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"id": 1, "label": "a"}])
+        self.assertEqual(pq.read_metadata(file).metadata[b"pg2parquet.query"], b'SELECT "id", "label" FROM matview_export')
+
+        wrappers.run_sql("DROP TABLE IF EXISTS matview_source", "CREATE TABLE matview_source (id int)")
+        with self.assertRaises(Exception):
+            wrappers.run_export("matview_source", options=["--refresh-matview"])
+
+    def test_mask(self):
+        file = wrappers.run_export("mask_query",
+            query="SELECT 'alice@example.com'::text email, 'secret'::text token, array[1, 2] ids, 42 n",
+            options=["--mask", "email=redact-email", "--mask", "token=sha256", "--mask", "ids=null", "--mask", "n=constant:x"])
+        self.assertEqual(pq.read_table(file).to_pylist(), [{
+            "email": "a***@example.com",
+            "token": hashlib.sha256(b"secret").hexdigest(),
+            "ids": None,
+            "n": "x",
+        }])
+        with self.assertRaises(Exception):
+            wrappers.run_export("mask_query", query="SELECT 1 n", options=["--mask", "missing=null"])
+
+    def test_mask_text_formats(self):
+        query = "SELECT 'alice@example.com'::text email, 'secret'::text token, array[1, 2] ids, 42 n, NULL::text missing"
+        options = ["--mask", "email=redact-email", "--mask", "token=sha256", "--mask", "ids=null", "--mask", "n=constant:x", "--mask", "missing=constant:x"]
+        token = hashlib.sha256(b"secret").hexdigest()
+        csv_file = wrappers.run_export("mask_csv", query=query, options=["--format", "csv", *options])
+        with open(csv_file) as f:
+            self.assertEqual(f.read(), f"email,token,ids,n,missing\na***@example.com,{token},,x,\n")
+        jsonl_file = wrappers.run_export("mask_jsonl", query=query, options=["--format", "jsonl", *options])
+        with open(jsonl_file) as f:
+            self.assertEqual(json.loads(f.read()), {"email": "a***@example.com", "token": token, "ids": None, "n": "x", "missing": None})
+        with self.assertRaises(Exception):
+            wrappers.run_export("mask_csv", query="SELECT 1 n", options=["--format", "csv", "--mask", "missing=null"])
+
+    def test_no_overwrite(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS no_overwrite_table",
+            "CREATE TABLE no_overwrite_table (id int)",
+            "INSERT INTO no_overwrite_table VALUES (1)"
+        )
+        file = wrappers.run_export("no_overwrite_table")
+        self.assertFalse(os.path.exists(file + ".tmp"))
+        with self.assertRaises(Exception):
+            wrappers.run_export("no_overwrite_table", options=["--no-overwrite"])
+
+        # failed export does not replace the existing file
+        with self.assertRaises(Exception):
+            wrappers.run_export("no_overwrite_table", query="SELECT 1 / (id - 1) FROM no_overwrite_table")
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"id": 1}])
+        self.assertFalse(os.path.exists(file + ".tmp"))
+
+    def test_schema_compat(self):
+        reference = wrappers.run_export("schema_compat_reference", query="SELECT 1 AS id, 'a'::text AS label")
+        file = wrappers.run_export("schema_compat", query="SELECT 2 AS id, 'b'::text AS label", options=["--schema-compat", reference])
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"id": 2, "label": "b"}])
+        self.assertNotIn(b"pg2parquet.schema_changes", pq.read_metadata(file).metadata)
+
+        changed_query = "SELECT 2::bigint AS id, 1 AS extra"
+        with self.assertRaises(Exception):
+            wrappers.run_export("schema_compat", query=changed_query, options=["--schema-compat", reference])
+        self.assertEqual(pq.read_table(file).to_pylist(), [{"id": 2, "label": "b"}])
+
+        file = wrappers.run_export("schema_compat", query=changed_query, options=["--schema-compat", reference, "--on-schema-change", "write-anyway"])
+        self.assertEqual(json.loads(pq.read_metadata(file).metadata[b"pg2parquet.schema_changes"]), [
+            "column id changed type from INT32 to INT64",
+            "column label was removed",
+            "column extra was added",
+        ])
+
+    def test_cancel_finalize(self):
+        outfile = os.path.join(wrappers.output_directory, "cancel_finalize.parquet")
+        p = subprocess.Popen([
+            wrappers.pg2parquet_binary, "export",
+            "--host", wrappers.pg2parquet_host,
+            "--port", wrappers.pg2parquet_port,
+            "--user", wrappers.pg2parquet_user,
+            "--dbname", wrappers.pg2parquet_dbname,
+            "--query", "SELECT i FROM generate_series(1, 10000) i WHERE pg_sleep(0.005 + i * 0)::text = ''",
+            "--output-file", outfile,
+            "--on-cancel", "finalize",
+        ], env={ "PGPASSWORD": wrappers.pg2parquet_password })
+        time.sleep(2)
+        p.send_signal(signal.SIGINT)
+        self.assertEqual(p.wait(timeout=20), 130)
+        ids = [ r["i"] for r in pq.read_table(outfile).to_pylist() ]
+        self.assertGreater(len(ids), 0)
+        self.assertLess(len(ids), 10000)
+        self.assertEqual(ids, list(range(1, len(ids) + 1)))
+
+    def test_as_map(self):
+        wrappers.run_sql(
+            "DROP TABLE IF EXISTS as_map_properties",
+            "CREATE TABLE as_map_properties (product_id int, property text, value numeric)",
+            "INSERT INTO as_map_properties VALUES (1, 'weight', 1.5), (1, 'height', NULL), (2, 'width', 3), (3, NULL, NULL)",
+        )
+        file = wrappers.run_export("as_map_properties", options=["--as-map", "property", "value"])
+        schema = pq.read_schema(file)
+        self.assertEqual(schema.names, ["product_id", "value"])
+        self.assertEqual(str(schema.field("value").type), "map<string, double>")
+        rows = sorted(pq.read_table(file).to_pylist(), key=lambda r: r["product_id"])
+        self.assertEqual(rows, [
+            {"product_id": 1, "value": [("height", None), ("weight", 1.5)]},
+            {"product_id": 2, "value": [("width", 3.0)]},
+            {"product_id": 3, "value": None},
+        ])
+
+        with self.assertRaises(Exception):
+            wrappers.run_export("as_map_missing", "SELECT 1 AS a, 2 AS b", options=["--as-map", "a", "c"])
+
+    def test_keepalive_interval(self):
+        query = "SELECT 1 AS id FROM pg_sleep(1)"
+        file = wrappers.run_export("keepalive_interval", query, options=["--keepalive-interval", "2s"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1,)])
+        file = wrappers.run_export("keepalive_disabled", query, options=["--keepalive-interval", "0"])
+        self.assertEqual(duckdb.read_parquet(file).fetchall(), [(1,)])
+
+        with self.assertRaises(Exception):
+            wrappers.run_export("keepalive_invalid", query, options=["--keepalive-interval", "soon"])
+        with self.assertRaises(Exception):
+            wrappers.run_export("keepalive_too_short", query, options=["--keepalive-interval", "500ms"])
@@ -0,0 +1,33 @@
+import os
+import wrappers
+import unittest
+import duckdb
+
+@unittest.skipUnless(wrappers.pg2parquet_examples, "PG2PARQUET_TEST_EXAMPLES_DIR is not set")
+class TestLibrary(unittest.TestCase):
+    def test_export_job(self):
+        query = "SELECT id, 'row ' || id AS name, ARRAY[id, NULL] AS arr FROM generate_series(1, 1000) id"
+        outfile = os.path.join(wrappers.output_directory, "library_export.parquet")
+        p = wrappers.run_example("library_export", [query, outfile])
+        self.assertEqual(p.stdout.decode("utf-8").strip(), "Exported 1000 rows, 3 columns")
+
+        cli_file = wrappers.run_export("library_export_cli", query=query)
+        self.assertEqual(duckdb.read_parquet(outfile).fetchall(), duckdb.read_parquet(cli_file).fetchall())
+        self.assertEqual(duckdb.read_parquet(outfile).fetchall()[0], (1, "row 1", [1, None]))
+
+    def test_custom_type_mapper(self):
+        # pg_lsn is not supported by pg2parquet, the example exports it using a CustomTypeMapper
+        query = "SELECT 1 AS id, '0/16B3748'::pg_lsn AS lsn, ARRAY['1/0'::pg_lsn, NULL] AS arr, NULL::pg_lsn AS n"
+        with self.assertRaises(Exception):
+            wrappers.run_export("custom_type_mapper_cli", query=query)
+
+        outfile = os.path.join(wrappers.output_directory, "custom_type_mapper.parquet")
+        wrappers.run_example("library_export", [query, outfile])
+        self.assertEqual(duckdb.read_parquet(outfile).fetchall(), [(1, 0x16B3748, [1 << 32, None], None)])
+        self.assertEqual(duckdb.sql(f"SELECT typeof(lsn) FROM '{outfile}'").fetchone(), ("BIGINT",))
+
+    def test_export_job_error(self):
+        outfile = os.path.join(wrappers.output_directory, "library_export_error.parquet")
+        with self.assertRaises(Exception):
+            wrappers.run_example("library_export", ["SELECT * FROM nonexistent_table", outfile])
+        self.assertFalse(os.path.exists(outfile))
@@ -0,0 +1,100 @@
+import base64
+import json
+import struct
+import wrappers
+import unittest
+import pyarrow as pa
+import pyarrow.parquet as pq
+
+class TestVector(unittest.TestCase):
+    def create_vectors(self, name, options=[]):
+        return wrappers.create_and_export(
+            name, "id",
+            "id int, v vector(3), h halfvec(3), a vector[]",
+            """(1, '[1, -0.5, 0.1]', '[1, -0.5, 0.1]', ARRAY['[1, 2]'::vector, '[3]']),
+               (2, NULL, NULL, NULL)
+            """,
+            options=options
+        )
+
+    def test_vector_f32(self):
+        file = self.create_vectors("vector_f32")
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table[1], {"id": 2, "v": None, "h": None, "a": None})
+        self.assertEqual(table[0]["v"], [1.0, -0.5, struct.unpack("f", struct.pack("f", 0.1))[0]])
+        self.assertEqual(table[0]["h"], [1.0, -0.5, 0.0999755859375])
+        self.assertEqual(table[0]["a"], [[1.0, 2.0], [3.0]])
+
+    def test_vector_f16(self):
+        file = self.create_vectors("vector_f16", options=["--vector-element-type=f16"])
+        schema = pq.read_schema(file)
+        self.assertEqual(str(schema.field("v").type), "list<element: halffloat not null>")
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual([float(x) for x in table[0]["v"]], [1.0, -0.5, 0.0999755859375])
+        self.assertEqual(table[0]["v"], table[0]["h"])
+
+    def test_vector_bf16(self):
+        file = self.create_vectors("vector_bf16", options=["--vector-element-type=bf16"])
+        table = pq.read_table(file).to_pylist()
+        # bfloat16 is the upper half of the f32 bits
+        self.assertEqual([struct.unpack("<f", b"\0\0" + x)[0] for x in table[0]["v"]], [1.0, -0.5, 0.10009765625])
+
+    def test_vector_i8(self):
+        file = self.create_vectors("vector_i8", options=["--vector-element-type=i8"])
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table[0]["v"], [127, -64, 13])
+        self.assertEqual(table[0]["a"], [[127, 127], [127]])
+
+        file = self.create_vectors("vector_i8_scaled", options=["--vector-element-type=i8", "--vector-i8-scale=10"])
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table[0]["v"], [10, -5, 1])
+        self.assertEqual(table[0]["a"], [[10, 20], [30]])
+
+    def test_vector_dims(self):
+        file = self.create_vectors("vector_dims")
+        metadata = pq.read_metadata(file).metadata
+        self.assertEqual(json.loads(metadata[b"pg2parquet.vector_dims"]), {"v": 3, "h": 3})
+        self.assertNotIn(b"ARROW:schema", metadata)
+
+        file = self.create_vectors("vector_fixed_size_list", options=["--vector-fixed-size-list"])
+        metadata = pq.read_metadata(file).metadata
+        arrow_schema = pa.ipc.read_schema(pa.py_buffer(base64.b64decode(metadata[b"ARROW:schema"])))
+        self.assertEqual(arrow_schema.field("v").type, pa.list_(pa.field("element", pa.float32(), nullable=False), 3))
+        self.assertEqual(arrow_schema.field("h").type, pa.list_(pa.field("element", pa.float32(), nullable=False), 3))
+        self.assertEqual(arrow_schema.field("a").type.id, pa.list_(pa.int32()).id)
+        self.assertEqual(pq.read_table(file).to_pylist()[0]["v"], pq.read_table(self.create_vectors("vector_list")).to_pylist()[0]["v"])
+
+    def create_sparse_vectors(self, name, options=[]):
+        return wrappers.create_and_export(
+            name, "id",
+            "id int, s sparsevec(5), a sparsevec[]",
+            """(1, '{1:1.5,4:-2}/5', ARRAY['{1:1.5,4:-2}/5'::sparsevec, '{}/3']),
+               (2, NULL, NULL),
+               (3, '{}/5', NULL)
+            """,
+            options=options
+        )
+
+    def test_sparsevec_map(self):
+        file = self.create_sparse_vectors("sparsevec_map")
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table[0]["s"], [(0, 1.5), (3, -2.0)])
+        self.assertEqual(table[0]["a"], [[(0, 1.5), (3, -2.0)], []])
+        self.assertEqual(table[1]["s"], None)
+        self.assertEqual(table[2]["s"], [])
+
+    def test_sparsevec_arrays(self):
+        file = self.create_sparse_vectors("sparsevec_arrays", options=["--sparsevec-handling=arrays"])
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table[0]["s"], {"indices": [0, 3], "values": [1.5, -2.0], "dim": 5})
+        self.assertEqual(table[0]["a"][1], {"indices": [], "values": [], "dim": 3})
+        self.assertEqual(table[1]["s"], None)
+        self.assertEqual(table[2]["s"], {"indices": [], "values": [], "dim": 5})
+
+    def test_sparsevec_dense(self):
+        file = self.create_sparse_vectors("sparsevec_dense", options=["--sparsevec-handling=dense"])
+        table = pq.read_table(file).to_pylist()
+        self.assertEqual(table[0]["s"], [1.5, 0, 0, -2.0, 0])
+        self.assertEqual(table[0]["a"], [[1.5, 0, 0, -2.0, 0], [0, 0, 0]])
+        self.assertEqual(table[2]["s"], [0, 0, 0, 0, 0])
+        self.assertEqual(json.loads(pq.read_metadata(file).metadata[b"pg2parquet.vector_dims"]), {"s": 5})
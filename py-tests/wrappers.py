@@ -11,6 +11,8 @@ import atexit
 env_prefix = "PG2PARQUET_TEST_"
 
 pg2parquet_binary = os.getenv(env_prefix + "BIN", None)
+# optional, directory with the binaries of the cli/examples programs using the library API
+pg2parquet_examples = os.getenv(env_prefix + "EXAMPLES_DIR", None)
 pg2parquet_host = os.getenv(env_prefix + "DB_HOST", "localhost")
 pg2parquet_port = os.getenv(env_prefix + "DB_PORT", "5432")
 pg2parquet_dbname = os.getenv(env_prefix + "DB_NAME", "pg2parquet_test")
@@ -60,7 +62,7 @@ def ensure_new_db(dbname):
     conn = pg_connect(dbname)
     try:
 
-        for ext in ["citext", "postgis", "vector"]:
+        for ext in ["citext", "postgis", "vector", "cube", "seg", "isn"]:
             conn.autocommit = True
             with conn.cursor() as cur:
                 cur.execute(sql.SQL("CREATE EXTENSION IF NOT EXISTS {}").format(sql.Identifier(ext)))
@@ -91,6 +93,23 @@ def run_pg2parquet(args: list[str]):
         raise Exception(f"pg2parquet exited with code {r.returncode}")
     return r
 
+def run_example(example: str, args: list[str]):
+    """Runs a program from cli/examples, connected to the test database through the libpq environment variables"""
+    r = subprocess.run([ os.path.join(pg2parquet_examples, example), *args ], env={
+        "PGHOST": pg2parquet_host,
+        "PGPORT": pg2parquet_port,
+        "PGUSER": pg2parquet_user,
+        "PGDATABASE": pg2parquet_dbname,
+        "PGPASSWORD": pg2parquet_password,
+    }, capture_output=True)
+    if r.returncode != 0:
+        print(f"{example} exited with code {r.returncode}. Stdout:")
+        print(r.stdout.decode("utf-8"))
+        print("Stderr:")
+        print(r.stderr.decode("utf-8"))
+        raise Exception(f"{example} exited with code {r.returncode}")
+    return r
+
 
 def run_export(name, query = None, options = []) -> str:
     outfile = os.path.join(output_directory, name + ".parquet")